@@ -0,0 +1,200 @@
+//! CBOR interop for AGON's JSON representation
+//!
+//! JSON has no byte-string type, so byte strings are escaped as a tagged
+//! object on the way into JSON: `{"__bytes__": {"base64": "..."}}`. On the
+//! way back out, a tagged object using that exact key is restored to a real
+//! CBOR byte string. Any other reserved-looking key (`__foo__`) is rejected
+//! with `AgonError::UnknownTaggedKey` rather than silently passed through as
+//! a regular map, since that would otherwise lose information round-tripping
+//! through CBOR.
+
+use ciborium::value::Value as CborValue;
+
+use crate::error::{AgonError, Result};
+use crate::types::{self, JsonValue};
+
+/// Convert a JSON value to CBOR bytes.
+pub fn json_to_cbor(value: &JsonValue) -> Result<Vec<u8>> {
+    let cbor_value = json_to_cbor_value(value)?;
+    let mut buf = Vec::new();
+    ciborium::into_writer(&cbor_value, &mut buf)
+        .map_err(|e| AgonError::CborError(e.to_string()))?;
+    Ok(buf)
+}
+
+/// Convert CBOR bytes to a JSON value.
+pub fn cbor_to_json(bytes: &[u8]) -> Result<JsonValue> {
+    let cbor_value: CborValue =
+        ciborium::from_reader(bytes).map_err(|e| AgonError::CborError(e.to_string()))?;
+    cbor_value_to_json(&cbor_value)
+}
+
+fn json_to_cbor_value(value: &JsonValue) -> Result<CborValue> {
+    match value {
+        JsonValue::Null => Ok(CborValue::Null),
+        JsonValue::Bool(b) => Ok(CborValue::Bool(*b)),
+        JsonValue::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Ok(CborValue::Integer(i.into()))
+            } else if let Some(u) = n.as_u64() {
+                Ok(CborValue::Integer(u.into()))
+            } else if let Some(f) = n.as_f64().filter(|f| f.is_finite()) {
+                Ok(CborValue::Float(f))
+            } else {
+                // Either `n` has no f64 representation at all, or it's a
+                // true bignum whose magnitude overflows f64 -- Rust's float
+                // parser silently saturates those to +/-infinity rather
+                // than returning `None`, so `as_f64` alone can't tell a
+                // bignum from a value that's genuinely representable. Error
+                // instead of letting a bignum silently round-trip through
+                // CBOR as `Infinity`, losing every digit.
+                Err(AgonError::CborError(format!(
+                    "Cannot represent number {} in CBOR",
+                    n
+                )))
+            }
+        }
+        JsonValue::String(s) => Ok(CborValue::Text(s.clone())),
+        JsonValue::Array(arr) => {
+            let items: Result<Vec<CborValue>> = arr.iter().map(json_to_cbor_value).collect();
+            Ok(CborValue::Array(items?))
+        }
+        JsonValue::Object(map) => {
+            if let Some(bytes) = types::decode_bytes_escape(map)? {
+                return Ok(CborValue::Bytes(bytes));
+            }
+            let entries: Result<Vec<(CborValue, CborValue)>> = map
+                .iter()
+                .map(|(k, v)| Ok((CborValue::Text(k.clone()), json_to_cbor_value(v)?)))
+                .collect();
+            Ok(CborValue::Map(entries?))
+        }
+    }
+}
+
+fn cbor_value_to_json(value: &CborValue) -> Result<JsonValue> {
+    match value {
+        CborValue::Null => Ok(JsonValue::Null),
+        CborValue::Bool(b) => Ok(JsonValue::Bool(*b)),
+        CborValue::Integer(i) => {
+            let n: i128 = (*i).into();
+            if let Ok(v) = i64::try_from(n) {
+                Ok(JsonValue::Number(v.into()))
+            } else if let Ok(v) = u64::try_from(n) {
+                Ok(JsonValue::Number(v.into()))
+            } else {
+                Ok(JsonValue::Number(serde_json::Number::from_string_unchecked(
+                    n.to_string(),
+                )))
+            }
+        }
+        CborValue::Float(f) => serde_json::Number::from_f64(*f)
+            .map(JsonValue::Number)
+            .ok_or_else(|| AgonError::CborError("CBOR float is NaN or infinite".to_string())),
+        CborValue::Text(s) => Ok(JsonValue::String(s.clone())),
+        CborValue::Bytes(b) => Ok(types::bytes_to_tagged_json(b)),
+        CborValue::Array(arr) => {
+            let items: Result<Vec<JsonValue>> = arr.iter().map(cbor_value_to_json).collect();
+            Ok(JsonValue::Array(items?))
+        }
+        CborValue::Map(entries) => {
+            let mut obj = serde_json::Map::new();
+            for (k, v) in entries {
+                let key = match k {
+                    CborValue::Text(s) => s.clone(),
+                    other => {
+                        return Err(AgonError::CborError(format!(
+                            "Non-string CBOR map key is not supported: {:?}",
+                            other
+                        )))
+                    }
+                };
+                obj.insert(key, cbor_value_to_json(v)?);
+            }
+            Ok(JsonValue::Object(obj))
+        }
+        other => Err(AgonError::CborError(format!(
+            "Unsupported CBOR value: {:?}",
+            other
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_roundtrip_primitives() {
+        let data = json!({"name": "Alice", "age": 30, "active": true, "score": 3.5, "note": null});
+        let bytes = json_to_cbor(&data).unwrap();
+        let back = cbor_to_json(&bytes).unwrap();
+        assert_eq!(back, data);
+    }
+
+    #[test]
+    fn test_roundtrip_array() {
+        let data = json!([1, 2, 3, "four"]);
+        let bytes = json_to_cbor(&data).unwrap();
+        let back = cbor_to_json(&bytes).unwrap();
+        assert_eq!(back, data);
+    }
+
+    #[test]
+    fn test_bytes_roundtrip_through_cbor() {
+        let bytes = vec![0u8, 1, 2, 255, 254];
+        let cbor_bytes = {
+            let mut buf = Vec::new();
+            ciborium::into_writer(&CborValue::Bytes(bytes.clone()), &mut buf).unwrap();
+            buf
+        };
+        let json_value = cbor_to_json(&cbor_bytes).unwrap();
+        let escape = json_value.as_object().unwrap();
+        assert!(escape.contains_key(types::BYTES_TAG));
+
+        let back_to_cbor = json_to_cbor(&json_value).unwrap();
+        assert_eq!(back_to_cbor, cbor_bytes);
+    }
+
+    #[test]
+    fn test_bytes_escape_decodes_to_base64() {
+        let data = json!({"__bytes__": {"base64": "AQID"}}); // [1, 2, 3]
+        let bytes = json_to_cbor(&data).unwrap();
+        let cbor_value: CborValue = ciborium::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(cbor_value, CborValue::Bytes(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn test_unknown_tagged_key_errors() {
+        let data = json!({"__weird__": {"base64": "AQID"}});
+        let err = json_to_cbor(&data).unwrap_err();
+        assert!(matches!(err, AgonError::UnknownTaggedKey(_)));
+    }
+
+    #[test]
+    fn test_malformed_bytes_escape_errors() {
+        let data = json!({"__bytes__": "not an object"});
+        let err = json_to_cbor(&data).unwrap_err();
+        assert!(matches!(err, AgonError::CborError(_)));
+    }
+
+    #[test]
+    fn test_nested_object_roundtrip() {
+        let data = json!({"user": {"name": "Bob", "tags": ["a", "b"]}});
+        let bytes = json_to_cbor(&data).unwrap();
+        let back = cbor_to_json(&bytes).unwrap();
+        assert_eq!(back, data);
+    }
+
+    #[test]
+    fn test_bignum_beyond_f64_range_errors_instead_of_becoming_infinity() {
+        // Overflows both i64/u64 and f64 -- `as_f64` would otherwise
+        // silently saturate this to `f64::INFINITY` rather than losing
+        // every digit without any signal.
+        let digits = "1".to_string() + &"0".repeat(400);
+        let data = JsonValue::Number(serde_json::Number::from_string_unchecked(digits));
+        let err = json_to_cbor(&data).unwrap_err();
+        assert!(matches!(err, AgonError::CborError(_)));
+    }
+}
@@ -0,0 +1,57 @@
+//! Deserialize an AGON struct payload straight into a Rust type.
+//!
+//! `decode` already has to walk the parsed `StructRegistry` to resolve every
+//! `StructName(v1, v2, ...)` instance back into a full object before it can
+//! return a value at all, so by the time a payload reaches here it's a
+//! plain `serde_json::Value` with every struct reference expanded. That
+//! `Value` already implements `serde::Deserializer`, so [`from_str`] simply
+//! hands it to `T`'s `Deserialize` impl rather than re-walking the text a
+//! second time.
+
+use serde::de::DeserializeOwned;
+
+use crate::error::{AgonError, Result};
+use crate::struct_fmt;
+
+/// Decode an AGON struct payload into `T`.
+pub fn from_str<T: DeserializeOwned>(s: &str) -> Result<T> {
+    let value = struct_fmt::decode(s)?;
+    serde_json::from_value(value).map_err(AgonError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Quote {
+        fmt: String,
+        raw: f64,
+    }
+
+    #[test]
+    fn test_from_str_round_trips_through_to_string() {
+        let quote = Quote {
+            fmt: "100.00".to_string(),
+            raw: 100.0,
+        };
+        let encoded = crate::ser::to_string(&quote).unwrap();
+        let decoded: Quote = from_str(&encoded).unwrap();
+        assert_eq!(decoded, quote);
+    }
+
+    #[test]
+    fn test_from_str_plain_struct_literal() {
+        let payload = "@AGON struct\n\n@Quote: fmt, raw\n\nfmt: \"100.00\"\nraw: 100.0";
+        let decoded: Quote = from_str(payload).unwrap();
+        assert_eq!(decoded.fmt, "100.00");
+        assert_eq!(decoded.raw, 100.0);
+    }
+
+    #[test]
+    fn test_from_str_invalid_payload_errors() {
+        let result: Result<Quote> = from_str("not an agon payload");
+        assert!(result.is_err());
+    }
+}
@@ -4,6 +4,101 @@ use pyo3::exceptions::PyValueError;
 use pyo3::PyErr;
 use thiserror::Error;
 
+/// Category of a positioned struct-decode failure, mirroring the location
+/// info serde_json and RON attach to their own parse errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StructParseErrorKind {
+    /// A line is indented in a way the surrounding grammar doesn't expect.
+    UnexpectedIndent,
+    /// A `Name(...)` call supplies fewer or more arguments than `Name`'s
+    /// registered field list allows.
+    BadStructArity,
+    /// A `Name(...)` call references a struct with no matching definition.
+    UnknownStruct,
+    /// A quoted string contains a malformed `\` escape: an unknown escape
+    /// letter, a truncated `\uXXXX`, or an unpaired UTF-16 surrogate.
+    InvalidEscape,
+}
+
+impl std::fmt::Display for StructParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            StructParseErrorKind::UnexpectedIndent => "unexpected indent",
+            StructParseErrorKind::BadStructArity => "bad struct arity",
+            StructParseErrorKind::UnknownStruct => "unknown struct",
+            StructParseErrorKind::InvalidEscape => "invalid escape",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Category of a positioned AGONRows decode failure, the tabular-format
+/// counterpart to [`StructParseErrorKind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowParseErrorKind {
+    /// The payload's first line isn't a recognized `@AGON rows` header.
+    MissingHeader,
+    /// A tabular row's field count doesn't match its header's declared
+    /// column count.
+    RowArityMismatch,
+    /// An `@D=` directive names an empty or otherwise unusable delimiter.
+    BadDelimiter,
+    /// A quoted cell opens a `"` that's never closed before the line ends.
+    UnterminatedQuote,
+    /// A line is indented in a way the surrounding grammar doesn't expect.
+    UnexpectedIndent,
+}
+
+impl std::fmt::Display for RowParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            RowParseErrorKind::MissingHeader => "missing header",
+            RowParseErrorKind::RowArityMismatch => "row arity mismatch",
+            RowParseErrorKind::BadDelimiter => "bad delimiter",
+            RowParseErrorKind::UnterminatedQuote => "unterminated quote",
+            RowParseErrorKind::UnexpectedIndent => "unexpected indent",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A 1-based line and char-column position in decoded source text, matching
+/// what an editor would show (including header lines in the count).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.col)
+    }
+}
+
+/// One recovered problem from a lenient decode (e.g.
+/// [`crate::formats::columns::decode_lenient`]): unlike [`AgonError`], a
+/// `Diagnostic` doesn't abort decoding -- it's collected alongside a
+/// best-effort `Value` so tooling can surface every issue from one pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub reason: String,
+    /// How the decoder recovered at this point, e.g. "padded with 2 missing
+    /// cells" or "treated as plain key: value".
+    pub recovered_as: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at {} (recovered as: {})",
+            self.reason, self.span, self.recovered_as
+        )
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum AgonError {
     #[error("Invalid AGON format: {0}")]
@@ -21,11 +116,59 @@ pub enum AgonError {
     #[error("Invalid data structure: {0}")]
     InvalidData(String),
 
-    #[error("Parse error at line {line}: {message}")]
-    ParseError { line: usize, message: String },
+    #[error("Parse error at line {line}, column {column}: {message}")]
+    ParseError {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    #[error("{kind} at line {line}, column {column}: {message}")]
+    StructDecodeError {
+        line: usize,
+        column: usize,
+        kind: StructParseErrorKind,
+        message: String,
+    },
+
+    /// A tabular value violated a declared [`crate::formats::rows::RowSchema`]
+    /// column's type, nullability, or the header's declared column set.
+    #[error("schema mismatch at line {line}, column {column}: expected {expected}, found {found}")]
+    SchemaError {
+        line: usize,
+        column: usize,
+        expected: String,
+        found: String,
+    },
+
+    /// A structured, positioned AGONRows decode failure: `line` is 1-based,
+    /// `column` is a 0-based byte offset into that line.
+    #[error("{kind} at line {line}, column {column}: {message}")]
+    RowDecodeError {
+        line: usize,
+        column: usize,
+        kind: RowParseErrorKind,
+        message: String,
+    },
+
+    /// A structured, positioned AGONColumns decode failure, pairing a
+    /// [`Span`] with the offending line's own text so a malformed column
+    /// line in a large table gives the user something to act on.
+    #[error("{reason} at {span}: {line_text}")]
+    ColumnsDecodeError {
+        span: Span,
+        line_text: String,
+        reason: String,
+    },
 
     #[error("Python error: {0}")]
     PyError(String),
+
+    #[error("CBOR error: {0}")]
+    CborError(String),
+
+    #[error("Unknown tagged key in CBOR escape: {0}")]
+    UnknownTaggedKey(String),
 }
 
 impl From<AgonError> for PyErr {
@@ -40,6 +183,18 @@ impl From<PyErr> for AgonError {
     }
 }
 
+impl serde::ser::Error for AgonError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        AgonError::EncodingError(msg.to_string())
+    }
+}
+
+impl serde::de::Error for AgonError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        AgonError::DecodingError(msg.to_string())
+    }
+}
+
 pub type Result<T> = std::result::Result<T, AgonError>;
 
 #[cfg(test)]
@@ -74,9 +229,13 @@ mod tests {
     fn test_parse_error() {
         let err = AgonError::ParseError {
             line: 42,
+            column: 7,
             message: "unexpected token".to_string(),
         };
-        assert_eq!(err.to_string(), "Parse error at line 42: unexpected token");
+        assert_eq!(
+            err.to_string(),
+            "Parse error at line 42, column 7: unexpected token"
+        );
     }
 
     #[test]
@@ -85,6 +244,21 @@ mod tests {
         assert_eq!(err.to_string(), "Python error: Python exception");
     }
 
+    #[test]
+    fn test_cbor_error() {
+        let err = AgonError::CborError("truncated stream".to_string());
+        assert_eq!(err.to_string(), "CBOR error: truncated stream");
+    }
+
+    #[test]
+    fn test_unknown_tagged_key_error() {
+        let err = AgonError::UnknownTaggedKey("__foo__".to_string());
+        assert_eq!(
+            err.to_string(),
+            "Unknown tagged key in CBOR escape: __foo__"
+        );
+    }
+
     #[test]
     fn test_json_error_from() {
         // Create a JSON parse error
@@ -99,4 +273,87 @@ mod tests {
         let debug_str = format!("{:?}", err);
         assert!(debug_str.contains("InvalidFormat"));
     }
+
+    #[test]
+    fn test_struct_decode_error_formats_line_and_column() {
+        let err = AgonError::StructDecodeError {
+            line: 5,
+            column: 3,
+            kind: StructParseErrorKind::BadStructArity,
+            message: "Quote expects 2 arguments, got 1".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "bad struct arity at line 5, column 3: Quote expects 2 arguments, got 1"
+        );
+    }
+
+    #[test]
+    fn test_schema_error_formats_line_column_expected_found() {
+        let err = AgonError::SchemaError {
+            line: 4,
+            column: 9,
+            expected: "int".to_string(),
+            found: "string".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "schema mismatch at line 4, column 9: expected int, found string"
+        );
+    }
+
+    #[test]
+    fn test_row_decode_error_formats_kind_line_and_column() {
+        let err = AgonError::RowDecodeError {
+            line: 5,
+            column: 1,
+            kind: RowParseErrorKind::RowArityMismatch,
+            message: "expected 2 columns, found 3".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "row arity mismatch at line 5, column 1: expected 2 columns, found 3"
+        );
+    }
+
+    #[test]
+    fn test_row_parse_error_kind_display() {
+        assert_eq!(RowParseErrorKind::MissingHeader.to_string(), "missing header");
+        assert_eq!(RowParseErrorKind::BadDelimiter.to_string(), "bad delimiter");
+        assert_eq!(
+            RowParseErrorKind::UnterminatedQuote.to_string(),
+            "unterminated quote"
+        );
+    }
+
+    #[test]
+    fn test_span_display() {
+        let span = Span { line: 5, col: 2 };
+        assert_eq!(span.to_string(), "line 5, column 2");
+    }
+
+    #[test]
+    fn test_columns_decode_error_formats_span_and_line_text() {
+        let err = AgonError::ColumnsDecodeError {
+            span: Span { line: 3, col: 4 },
+            line_text: "├ id: 1\t\"unterminated".to_string(),
+            reason: "unterminated quote in column cell".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "unterminated quote in column cell at line 3, column 4: ├ id: 1\t\"unterminated"
+        );
+    }
+
+    #[test]
+    fn test_serde_ser_error_custom_is_encoding_error() {
+        let err = <AgonError as serde::ser::Error>::custom("bad value");
+        assert!(matches!(err, AgonError::EncodingError(_)));
+    }
+
+    #[test]
+    fn test_serde_de_error_custom_is_decoding_error() {
+        let err = <AgonError as serde::de::Error>::custom("bad value");
+        assert!(matches!(err, AgonError::DecodingError(_)));
+    }
 }
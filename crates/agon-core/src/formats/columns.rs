@@ -8,17 +8,106 @@
 //!     ├ field1: val1<delim>val2<delim>...
 //!     ├ field2: val1<delim>val2<delim>...
 //!     └ fieldN: val1<delim>val2<delim>...
-
+//!
+//! ## Column type tags
+//!
+//! A column whose values are all the same JSON type (as determined by
+//! [`is_uniform_array`]) gets an inline `field:type` tag instead of a bare
+//! `field`, e.g. `├ id:str: 007\t042`. This is chosen over a separate
+//! schema block (the format's other option) since it keeps each column
+//! self-describing on its own line, matching how the rest of this format
+//! already puts everything about a column on one `├`/`└` line.
+//!
+//! The only type that changes decoding is `str`: a declared-`str` column
+//! skips [`parse_primitive`]'s number/bool/null coercion entirely, so a
+//! numeric-looking id like `"00123"` round-trips as a string without ever
+//! needing to be quoted. Declared `int`/`float`/`bool`/`null` columns are
+//! already unambiguous under the heuristic, so they parse the same way
+//! tagged or not. A column with no tag falls back to the untyped heuristic
+//! path, so older payloads keep decoding exactly as before.
+//!
+//! ## Nested-object columns
+//!
+//! A row whose values are themselves objects (as long as every leaf is a
+//! primitive, not an array) still takes the columnar layout: [`flatten_row`]
+//! walks each row depth-first and turns a nested `address: {city, zip}`
+//! into dotted columns `address.city`/`address.zip`, so the table stays
+//! columnar instead of falling back to the verbose list-item format. A key
+//! that itself contains a literal `.` is escaped (`\.`) so it isn't mistaken
+//! for a nesting separator when [`split_dotted_path`] splits the column
+//! name back apart on decode. A row missing a nested field entirely still
+//! produces an empty cell for that column, the same present-vs-missing
+//! semantics [`parse_columnar_cell`] already gives flat fields.
+//!
+//! ## Lenient decoding
+//!
+//! [`decode`] and [`decode_projected`] abort on the first malformed line,
+//! which is painful when one bad row in a large dump otherwise decodes
+//! fine. [`decode_lenient`] instead parses as much as it can and collects a
+//! [`Diagnostic`] at each recovery point: a column line whose cell count
+//! doesn't match its declared `[N]` is padded with missing cells or
+//! truncated, and an unparseable `[N]` bracket falls back to treating the
+//! line as a plain `key: value`. It never returns `Err` -- the invariant is
+//! that it always yields a structurally valid `Value`, with every problem
+//! it recovered from alongside it.
+//!
+//! ## Streaming rows
+//!
+//! [`RowReader`] reads a single top-level `[N]`/`name[N]` columnar array off
+//! a [`BufRead`] one reconstructed row at a time, the columnar-table
+//! counterpart to [`crate::formats::struct_fmt::StreamDecoder`] streaming
+//! whole documents. A row can't be reconstructed until every column's `├`/
+//! `└` line has been read -- cell `i` of the last column is as much a part
+//! of row `i` as cell `i` of the first -- so a columnar block is still
+//! buffered in full (its raw lines and every parsed cell) before the first
+//! row comes out; only the row objects are built one at a time instead of
+//! being collected into the `Vec<Value>` [`decode`] would otherwise build.
+//! Anything else at the top level (a plain object, a
+//! list-item array, or a named array sharing the top level with other
+//! fields) falls back to one full [`decode`] call, yielded as a single item.
+//!
+//! ## Batched writes
+//!
+//! Unlike [`crate::formats::rows`]/[`crate::formats::struct_fmt`], whose
+//! row-per-line/item-per-line shapes let a batched writer simply append,
+//! every AGONColumns field's values live on one logical `├`/`└` line, so
+//! [`encode_streaming`] splits a field across batches by following its
+//! opening segment with one `│`-prefixed continuation segment per later
+//! batch -- a marker distinct from `├`/`└` and carrying no `field:` label,
+//! since it's only ever read immediately after the segment it continues.
+//! [`decode_columnar_array`] stitches a field's continuation segments back
+//! into one value string before splitting cells, so `decode(encode_streaming
+//! (...))` round-trips the same as a plain, unbatched [`encode`].
+
+use std::cell::RefCell;
+use std::io::{BufRead, Write};
+
+use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::ser::{
+    Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+    SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+};
 use serde_json::{Map, Value};
 
-use crate::error::{AgonError, Result};
+use crate::error::{AgonError, Diagnostic, Result, Span};
+use crate::options::SerializeOptions;
+use crate::types::bytes_to_tagged_json;
 
 const HEADER: &str = "@AGON columns";
 const DEFAULT_DELIMITER: &str = "\t";
-const INDENT: &str = "  ";
 
 /// Encode data to AGONColumns format
 pub fn encode(data: &Value, include_header: bool) -> Result<String> {
+    encode_with_options(data, include_header, &SerializeOptions::default())
+}
+
+/// Encode data to AGONColumns format with explicit serialize options
+pub fn encode_with_options(
+    data: &Value,
+    include_header: bool,
+    options: &SerializeOptions,
+) -> Result<String> {
     let mut lines = Vec::new();
     let delimiter = DEFAULT_DELIMITER;
 
@@ -27,11 +116,120 @@ pub fn encode(data: &Value, include_header: bool) -> Result<String> {
         lines.push(String::new());
     }
 
-    encode_value(data, &mut lines, 0, delimiter, None);
+    encode_value(data, &mut lines, 0, delimiter, None, options);
 
     Ok(lines.join("\n"))
 }
 
+/// Encode a uniform top-level array to a [`Write`]r, splitting every field's
+/// values into fixed-size batches instead of formatting the whole column at
+/// once. The `[N]` header is written immediately (the row count is already
+/// known from `data` being a fully in-memory `&Value`), and then each field
+/// is written as a sequence of segments: an opening `├`/`└ field: ...`
+/// segment for its first batch, formatted with rayon's `par_iter`, followed
+/// by one `│ ...` continuation segment per later batch -- see the
+/// module-level "Batched writes" docs for why this format needs a
+/// continuation marker where [`crate::formats::rows::encode_streaming`]/
+/// [`crate::formats::struct_fmt::encode_streaming`] don't.
+/// [`decode_columnar_array`] stitches the continuation segments back
+/// together, so `decode(encode_streaming(data, ..))` round-trips like
+/// [`encode`]. Any shape [`is_uniform_array`] doesn't recognize (a non-array
+/// `data`, an empty array, or a non-uniform array) falls back to one
+/// [`encode`] call written in a single `write_all`.
+pub fn encode_streaming<W: Write>(
+    writer: W,
+    data: &Value,
+    include_header: bool,
+    batch_size: usize,
+) -> Result<W> {
+    let mut writer = writer;
+
+    let Value::Array(arr) = data else {
+        let encoded = encode(data, include_header)?;
+        writer
+            .write_all(encoded.as_bytes())
+            .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+        return Ok(writer);
+    };
+
+    let (is_uniform, fields, field_types) = if arr.is_empty() {
+        (false, Vec::new(), Vec::new())
+    } else {
+        is_uniform_array(arr)
+    };
+
+    if !is_uniform || fields.is_empty() {
+        let encoded = encode(data, include_header)?;
+        writer
+            .write_all(encoded.as_bytes())
+            .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+        return Ok(writer);
+    }
+
+    let flattened: Vec<Map<String, Value>> = arr
+        .iter()
+        .map(|v| {
+            flatten_row(v.as_object().expect("checked uniform: all rows are objects"))
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let mut header_lines = Vec::new();
+    if include_header {
+        header_lines.push(HEADER.to_string());
+        header_lines.push(String::new());
+    }
+    header_lines.push(format!("[{}]", arr.len()));
+    writer
+        .write_all(header_lines.join("\n").as_bytes())
+        .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+
+    let batch_size = batch_size.max(1);
+    let batches: Vec<&[Map<String, Value>]> = flattened.chunks(batch_size).collect();
+    let total_batches = batches.len();
+    let total_fields = fields.len();
+
+    for (field_idx, (field, tag)) in fields.iter().zip(field_types.iter()).enumerate() {
+        let is_last_field = field_idx == total_fields - 1;
+        let label = match tag {
+            Some(t) => format!("{}:{}", field, t),
+            None => field.clone(),
+        };
+
+        for (batch_idx, batch) in batches.iter().enumerate() {
+            let values: Vec<String> = batch
+                .par_iter()
+                .map(|flat| {
+                    flat.get(field)
+                        .map(|v| format_primitive_for_column(v, *tag))
+                        .unwrap_or_default()
+                })
+                .collect();
+
+            let is_last_batch = batch_idx == total_batches - 1;
+            let line = if batch_idx == 0 {
+                let prefix = if is_last_batch && is_last_field {
+                    "└"
+                } else {
+                    "├"
+                };
+                format!("{} {}: {}", prefix, label, values.join(DEFAULT_DELIMITER))
+            } else {
+                format!("│ {}", values.join(DEFAULT_DELIMITER))
+            };
+
+            writer
+                .write_all(b"\n")
+                .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+            writer
+                .write_all(line.as_bytes())
+                .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+        }
+    }
+
+    Ok(writer)
+}
+
 /// Decode AGONColumns payload
 pub fn decode(payload: &str) -> Result<Value> {
     let lines: Vec<&str> = payload.lines().collect();
@@ -44,10 +242,12 @@ pub fn decode(payload: &str) -> Result<Value> {
     // Parse header
     let header_line = lines[idx].trim();
     if !header_line.starts_with("@AGON columns") {
-        return Err(AgonError::DecodingError(format!(
-            "Invalid header: {}",
-            header_line
-        )));
+        return Err(columns_decode_error(
+            idx,
+            0,
+            lines[idx],
+            "expected `@AGON columns` header",
+        ));
     }
     idx += 1;
 
@@ -60,10 +260,656 @@ pub fn decode(payload: &str) -> Result<Value> {
         return Ok(Value::Null);
     }
 
-    let (result, _) = decode_value(&lines, idx, 0, DEFAULT_DELIMITER)?;
+    let (result, _) = decode_value(&lines, idx, 0, DEFAULT_DELIMITER, None, None)?;
+    Ok(result)
+}
+
+/// Decode a payload, keeping only the named fields of any columnar table it
+/// contains. Because each field already lives on its own `├`/`└` line, a
+/// reader that only wants `fields.len()` columns out of a much wider table
+/// never has to split or parse the cells of the columns it skips -- the
+/// whole point of the columnar layout. An empty `fields` means "all
+/// columns", matching [`decode`].
+pub fn decode_projected(payload: &str, fields: &[&str]) -> Result<Value> {
+    let lines: Vec<&str> = payload.lines().collect();
+    if lines.is_empty() {
+        return Err(AgonError::DecodingError("Empty payload".to_string()));
+    }
+
+    let mut idx = 0;
+
+    let header_line = lines[idx].trim();
+    if !header_line.starts_with("@AGON columns") {
+        return Err(columns_decode_error(
+            idx,
+            0,
+            lines[idx],
+            "expected `@AGON columns` header",
+        ));
+    }
+    idx += 1;
+
+    while idx < lines.len() && lines[idx].trim().is_empty() {
+        idx += 1;
+    }
+
+    if idx >= lines.len() {
+        return Ok(Value::Null);
+    }
+
+    let projection = if fields.is_empty() { None } else { Some(fields) };
+    let (result, _) = decode_value(&lines, idx, 0, DEFAULT_DELIMITER, projection, None)?;
     Ok(result)
 }
 
+/// Decode a payload the same way [`decode`] does, except never abort: every
+/// point that would otherwise return `Err` instead records a [`Diagnostic`]
+/// and keeps going with a best-effort recovery, so one bad row in a large
+/// dump doesn't take down the whole decode. Returns the best-effort `Value`
+/// alongside every diagnostic collected along the way, in the order they
+/// were found.
+pub fn decode_lenient(payload: &str) -> (Value, Vec<Diagnostic>) {
+    let diagnostics = RefCell::new(Vec::new());
+    let lines: Vec<&str> = payload.lines().collect();
+
+    if lines.is_empty() {
+        diagnostics.borrow_mut().push(Diagnostic {
+            span: Span { line: 1, col: 0 },
+            reason: "empty payload".to_string(),
+            recovered_as: "null".to_string(),
+        });
+        return (Value::Null, diagnostics.into_inner());
+    }
+
+    let mut idx = 0;
+    let header_line = lines[idx].trim();
+    if header_line.starts_with("@AGON columns") {
+        idx += 1;
+    } else {
+        diagnostics.borrow_mut().push(Diagnostic {
+            span: Span { line: 1, col: 0 },
+            reason: "expected `@AGON columns` header".to_string(),
+            recovered_as: "treated the whole payload as headerless body".to_string(),
+        });
+    }
+
+    while idx < lines.len() && lines[idx].trim().is_empty() {
+        idx += 1;
+    }
+
+    if idx >= lines.len() {
+        return (Value::Null, diagnostics.into_inner());
+    }
+
+    let result = decode_value(&lines, idx, 0, DEFAULT_DELIMITER, None, Some(&diagnostics));
+    let value = match result {
+        Ok((value, _)) => value,
+        Err(err) => {
+            diagnostics.borrow_mut().push(Diagnostic {
+                span: Span { line: idx + 1, col: 0 },
+                reason: err.to_string(),
+                recovered_as: "null".to_string(),
+            });
+            Value::Null
+        }
+    };
+
+    (value, diagnostics.into_inner())
+}
+
+/// Iterator over the rows of a top-level AGONColumns array read from a
+/// [`BufRead`], produced by [`RowReader::new`]. See the module-level
+/// "Streaming rows" docs for the buffering guarantee and the fallback this
+/// takes for non-columnar roots.
+pub struct RowReader<R> {
+    lines: std::io::Lines<R>,
+    state: RowReaderState,
+}
+
+enum RowReaderState {
+    Columnar {
+        fields: Vec<String>,
+        columns: Vec<Vec<Option<Value>>>,
+        count: usize,
+        index: usize,
+    },
+    Fallback(Option<Result<Value>>),
+    Done,
+}
+
+impl<R: BufRead> RowReader<R> {
+    pub fn new(reader: R) -> Self {
+        let mut lines = reader.lines();
+        let state = Self::start(&mut lines);
+        RowReader { lines, state }
+    }
+
+    /// Read lines up to and including the header, then the top-level
+    /// value's opening line, buffering every raw line seen along the way so
+    /// a fallback decode can still see them. Returns the columnar cursor to
+    /// stream from, or a one-shot fallback state if the top level isn't a
+    /// bare `[N]`/`name[N]` columnar array immediately followed by `├`/`└`
+    /// lines.
+    fn start(lines: &mut std::io::Lines<R>) -> RowReaderState {
+        let mut buffered = Vec::new();
+
+        let header = match Self::read_line(lines, &mut buffered) {
+            Some(Ok(line)) => line,
+            Some(Err(err)) => return RowReaderState::Fallback(Some(Err(err))),
+            None => return RowReaderState::Fallback(Some(Ok(Value::Null))),
+        };
+        if !header.trim().starts_with(HEADER) {
+            return Self::fallback(buffered, lines);
+        }
+
+        let content = loop {
+            match Self::read_line(lines, &mut buffered) {
+                Some(Ok(line)) if line.trim().is_empty() => continue,
+                Some(Ok(line)) => break line,
+                Some(Err(err)) => return RowReaderState::Fallback(Some(Err(err))),
+                None => return RowReaderState::Fallback(Some(Ok(Value::Null))),
+            }
+        };
+
+        match Self::start_columnar(&content, lines, &mut buffered) {
+            Some(state) => state,
+            None => Self::fallback(buffered, lines),
+        }
+    }
+
+    /// Read one line, recording its raw text in `buffered` so it can still
+    /// feed a fallback decode if streaming turns out not to apply.
+    fn read_line(
+        lines: &mut std::io::Lines<R>,
+        buffered: &mut Vec<String>,
+    ) -> Option<std::io::Result<String>> {
+        let item = lines.next();
+        if let Some(Ok(line)) = &item {
+            buffered.push(line.clone());
+        }
+        item
+    }
+
+    /// Drain the rest of `lines` onto `buffered` and decode the joined
+    /// result as a single whole-document fallback.
+    fn fallback(mut buffered: Vec<String>, lines: &mut std::io::Lines<R>) -> RowReaderState {
+        for line in lines {
+            match line {
+                Ok(line) => buffered.push(line),
+                Err(err) => {
+                    return RowReaderState::Fallback(Some(Err(AgonError::DecodingError(
+                        err.to_string(),
+                    ))));
+                }
+            }
+        }
+        RowReaderState::Fallback(Some(decode(&buffered.join("\n"))))
+    }
+
+    /// `content` is the top-level value's line with the header and any
+    /// leading blank lines already consumed. Returns `None` (meaning "fall
+    /// back to a full decode") unless it is a bare `[N]`/`name[N]` whose
+    /// very next line opens a columnar block, and that block is the only
+    /// remaining top-level content.
+    fn start_columnar(
+        content: &str,
+        lines: &mut std::io::Lines<R>,
+        buffered: &mut Vec<String>,
+    ) -> Option<RowReaderState> {
+        let trimmed = content.trim();
+        let bracket_pos = trimmed.find('[')?;
+        let end_pos = trimmed[bracket_pos..].find(']').map(|p| bracket_pos + p)?;
+        let count: usize = trimmed[bracket_pos + 1..end_pos].parse().ok()?;
+
+        let mut fields: Vec<String> = Vec::new();
+        let mut columns: Vec<Vec<Option<Value>>> = Vec::new();
+        let mut saw_column_line = false;
+
+        loop {
+            let line = match Self::read_line(lines, buffered) {
+                Some(Ok(line)) => line,
+                Some(Err(err)) => {
+                    return Some(RowReaderState::Fallback(Some(Err(err))));
+                }
+                None => break,
+            };
+            let trimmed_line = line.trim();
+            let field_line = trimmed_line
+                .strip_prefix('├')
+                .or_else(|| trimmed_line.strip_prefix('└'))
+                .map(str::trim);
+            // A non-`├`/`└` line before any column line was seen means this
+            // wasn't a columnar block after all (e.g. an inline or list
+            // array); once a column line has been seen, only the `└` line
+            // (handled below) is allowed to end the block.
+            let Some(field_content) = field_line else {
+                return None;
+            };
+            let (field, declared, values_str) = parse_column_header(field_content)?;
+            saw_column_line = true;
+            fields.push(field.to_string());
+            let mut values: Vec<Option<Value>> = if values_str.is_empty() {
+                vec![]
+            } else {
+                split_column_values(values_str, DEFAULT_DELIMITER)
+                    .iter()
+                    .map(|s| parse_columnar_cell_typed(s, declared))
+                    .collect()
+            };
+            values.resize_with(count, || None);
+            columns.push(values);
+
+            if trimmed_line.starts_with('└') {
+                break;
+            }
+        }
+
+        // A bare `[0]` has no column lines to read; it still streams as a
+        // columnar array with no rows.
+        if !saw_column_line && count > 0 {
+            return None;
+        }
+
+        // Only trailing blank lines may follow the block for it to be the
+        // document's sole top-level value.
+        loop {
+            match Self::read_line(lines, buffered) {
+                Some(Ok(line)) => {
+                    if !line.trim().is_empty() {
+                        return None;
+                    }
+                }
+                Some(Err(err)) => {
+                    return Some(RowReaderState::Fallback(Some(Err(err))));
+                }
+                None => break,
+            }
+        }
+
+        Some(RowReaderState::Columnar {
+            fields,
+            columns,
+            count,
+            index: 0,
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for RowReader<R> {
+    type Item = Result<Value>;
+
+    fn next(&mut self) -> Option<Result<Value>> {
+        match &mut self.state {
+            RowReaderState::Columnar {
+                fields,
+                columns,
+                count,
+                index,
+            } => {
+                if *index >= *count {
+                    self.state = RowReaderState::Done;
+                    return None;
+                }
+                let i = *index;
+                *index += 1;
+                let mut obj = Map::new();
+                for (field, column) in fields.iter().zip(columns.iter()) {
+                    if let Some(Some(val)) = column.get(i) {
+                        insert_dotted(&mut obj, field, val.clone());
+                    }
+                }
+                Some(Ok(Value::Object(obj)))
+            }
+            RowReaderState::Fallback(item) => {
+                let result = item.take();
+                self.state = RowReaderState::Done;
+                result
+            }
+            RowReaderState::Done => None,
+        }
+    }
+}
+
+/// Serialize `value` to AGON columns text via a [`serde::Serializer`] that
+/// builds the same `serde_json::Value` [`encode`] already renders, the way
+/// [`crate::ser::to_string`] drives AGONStruct text straight from a
+/// `Serialize` implementor instead of going through `serde_json::to_value`
+/// first. Unlike that struct serializer, this one needs no schema registry
+/// -- a uniform `Vec<Struct>` takes the columnar layout the same way a
+/// uniform `Vec<serde_json::Value>` already does, purely from
+/// [`is_uniform_array`] inspecting the `Value` [`encode`] is handed.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    let json = value.serialize(ValueSerializer)?;
+    encode(&json, true)
+}
+
+/// Deserialize an AGON columns payload into `T`. [`decode`] already resolves
+/// the payload down to a `serde_json::Value`, which implements
+/// `serde::Deserializer` itself, so this hands that off to `T`'s
+/// `Deserialize` impl rather than re-walking the text a second time.
+pub fn from_str<T: DeserializeOwned>(s: &str) -> Result<T> {
+    let value = decode(s)?;
+    serde_json::from_value(value).map_err(AgonError::from)
+}
+
+// ============================================================================
+// Serde `Serializer` front-end
+// ============================================================================
+
+struct ValueSerializer;
+
+impl serde::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = AgonError;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = TupleVariantSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = StructSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i16(self, v: i16) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i32(self, v: i32) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i64(self, v: i64) -> Result<Value> {
+        Ok(Value::Number(v.into()))
+    }
+    fn serialize_i128(self, v: i128) -> Result<Value> {
+        serde_json::Number::from_i128(v)
+            .map(Value::Number)
+            .ok_or_else(|| AgonError::EncodingError(format!("i128 out of range: {}", v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value> {
+        self.serialize_u64(v as u64)
+    }
+    fn serialize_u16(self, v: u16) -> Result<Value> {
+        self.serialize_u64(v as u64)
+    }
+    fn serialize_u32(self, v: u32) -> Result<Value> {
+        self.serialize_u64(v as u64)
+    }
+    fn serialize_u64(self, v: u64) -> Result<Value> {
+        Ok(Value::Number(v.into()))
+    }
+    fn serialize_u128(self, v: u128) -> Result<Value> {
+        serde_json::Number::from_u128(v)
+            .map(Value::Number)
+            .ok_or_else(|| AgonError::EncodingError(format!("u128 out of range: {}", v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value> {
+        self.serialize_f64(v as f64)
+    }
+    fn serialize_f64(self, v: f64) -> Result<Value> {
+        serde_json::Number::from_f64(v)
+            .map(Value::Number)
+            .ok_or_else(|| AgonError::EncodingError(format!("non-finite float: {}", v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value> {
+        Ok(Value::String(v.to_string()))
+    }
+    fn serialize_str(self, v: &str) -> Result<Value> {
+        Ok(Value::String(v.to_string()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value> {
+        Ok(bytes_to_tagged_json(v))
+    }
+
+    fn serialize_none(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
+        Ok(Value::Null)
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        Ok(Value::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        let mut obj = Map::new();
+        obj.insert(variant.to_string(), value.serialize(ValueSerializer)?);
+        Ok(Value::Object(obj))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<TupleVariantSerializer> {
+        Ok(TupleVariantSerializer {
+            variant,
+            items: SeqSerializer {
+                items: Vec::with_capacity(len),
+            },
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer> {
+        Ok(MapSerializer {
+            map: Map::new(),
+            pending_key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<StructSerializer> {
+        Ok(StructSerializer {
+            variant: None,
+            map: Map::with_capacity(len),
+        })
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<StructSerializer> {
+        Ok(StructSerializer {
+            variant: Some(variant),
+            map: Map::with_capacity(len),
+        })
+    }
+}
+
+struct SeqSerializer {
+    items: Vec<Value>,
+}
+
+impl SerializeSeq for SeqSerializer {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value> {
+        Ok(Value::Array(self.items))
+    }
+}
+
+impl SerializeTuple for SeqSerializer {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Value> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl SerializeTupleStruct for SeqSerializer {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Value> {
+        SerializeSeq::end(self)
+    }
+}
+
+struct TupleVariantSerializer {
+    variant: &'static str,
+    items: SeqSerializer,
+}
+
+impl SerializeTupleVariant for TupleVariantSerializer {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        SerializeSeq::serialize_element(&mut self.items, value)
+    }
+    fn end(self) -> Result<Value> {
+        let mut obj = Map::new();
+        obj.insert(self.variant.to_string(), SerializeSeq::end(self.items)?);
+        Ok(Value::Object(obj))
+    }
+}
+
+struct MapSerializer {
+    map: Map<String, Value>,
+    pending_key: Option<String>,
+}
+
+impl SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        let key = key.serialize(ValueSerializer)?;
+        self.pending_key = Some(value_to_map_key(key)?);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .pending_key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        let value = value.serialize(ValueSerializer)?;
+        self.map.insert(key, value);
+        Ok(())
+    }
+    fn end(self) -> Result<Value> {
+        Ok(Value::Object(self.map))
+    }
+}
+
+/// Converts a serialized map key into the `String` AGON object keys require.
+fn value_to_map_key(value: Value) -> Result<String> {
+    match value {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        other => Err(AgonError::EncodingError(format!(
+            "map keys must serialize to a string, number, or bool, got {}",
+            other
+        ))),
+    }
+}
+
+struct StructSerializer {
+    variant: Option<&'static str>,
+    map: Map<String, Value>,
+}
+
+impl SerializeStruct for StructSerializer {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.map.insert(key.to_string(), value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value> {
+        let obj = Value::Object(self.map);
+        match self.variant {
+            Some(variant) => {
+                let mut wrapper = Map::new();
+                wrapper.insert(variant.to_string(), obj);
+                Ok(Value::Object(wrapper))
+            }
+            None => Ok(obj),
+        }
+    }
+}
+
+impl SerializeStructVariant for StructSerializer {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        SerializeStruct::serialize_field(self, key, value)
+    }
+    fn end(self) -> Result<Value> {
+        SerializeStruct::end(self)
+    }
+}
+
 // ============================================================================
 // Encoding helpers
 // ============================================================================
@@ -76,13 +922,7 @@ fn format_primitive(val: &Value) -> String {
         Value::String(s) => {
             // Quote if contains delimiter, special chars, or could be parsed as another type
             if needs_quote(s) {
-                format!(
-                    "\"{}\"",
-                    s.replace('\\', "\\\\")
-                        .replace('"', "\\\"")
-                        .replace('\n', "\\n")
-                        .replace('\t', "\\t")
-                )
+                quote_str(s)
             } else {
                 s.clone()
             }
@@ -91,20 +931,48 @@ fn format_primitive(val: &Value) -> String {
     }
 }
 
-/// Check if a string needs quoting to preserve its type
-fn needs_quote(s: &str) -> bool {
+/// Format a single columnar cell, given the column's declared type (if any).
+/// A declared `str` column skips the "looks like a number/bool/null"
+/// ambiguity checks entirely -- the type tag already disambiguates it for
+/// the decoder -- so only the structural escaping `needs_structural_quote`
+/// checks still apply.
+fn format_primitive_for_column(val: &Value, declared: Option<&str>) -> String {
+    if declared == Some("str")
+        && let Value::String(s) = val
+    {
+        return if needs_structural_quote(s) {
+            quote_str(s)
+        } else {
+            s.clone()
+        };
+    }
+    format_primitive(val)
+}
+
+fn quote_str(s: &str) -> String {
+    format!(
+        "\"{}\"",
+        s.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+            .replace('\t', "\\t")
+    )
+}
+
+/// Whether `s` needs quoting purely to survive this format's own grammar:
+/// an empty cell (which would otherwise read as "missing"), leading/
+/// trailing whitespace, the delimiter/newline/backslash/quote characters,
+/// or a leading tree-drawing/directive character.
+fn needs_structural_quote(s: &str) -> bool {
     if s.is_empty() {
         return true;
     }
-    // Strings with leading/trailing whitespace need quoting
     if s != s.trim() {
         return true;
     }
-    // Delimiter and special chars
     if s.contains('\t') || s.contains('\n') || s.contains('\\') || s.contains('"') {
         return true;
     }
-    // Tree drawing chars at start
     if s.starts_with('├')
         || s.starts_with('└')
         || s.starts_with('|')
@@ -114,6 +982,15 @@ fn needs_quote(s: &str) -> bool {
     {
         return true;
     }
+    false
+}
+
+/// Check if a string needs quoting to preserve its type, when no column
+/// type tag is declared for it.
+fn needs_quote(s: &str) -> bool {
+    if needs_structural_quote(s) {
+        return true;
+    }
     // Boolean/null keywords
     let lower = s.to_lowercase();
     if lower == "true" || lower == "false" || lower == "null" {
@@ -126,6 +1003,60 @@ fn needs_quote(s: &str) -> bool {
     false
 }
 
+/// Whether `s` is a complete JSON-grammar number lexeme: an optional
+/// leading `-`, an integer part with no extraneous leading zeros, an
+/// optional `.` fraction requiring at least one digit, and an optional
+/// `e`/`E` exponent with an optional sign, also requiring at least one
+/// digit. `str::parse::<f64>` is too loose to use as this check directly --
+/// it also accepts `1.`, `inf`, and `nan` -- so [`parse_primitive`]
+/// validates the lexeme itself before trusting Rust's float parser with it.
+fn is_json_number(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    if bytes.first() == Some(&b'-') {
+        i += 1;
+    }
+    let int_start = i;
+    if bytes.get(i) == Some(&b'0') {
+        i += 1;
+    } else if matches!(bytes.get(i), Some(b'1'..=b'9')) {
+        i += 1;
+        while matches!(bytes.get(i), Some(b'0'..=b'9')) {
+            i += 1;
+        }
+    }
+    if i == int_start {
+        return false;
+    }
+
+    if bytes.get(i) == Some(&b'.') {
+        i += 1;
+        let frac_start = i;
+        while matches!(bytes.get(i), Some(b'0'..=b'9')) {
+            i += 1;
+        }
+        if i == frac_start {
+            return false;
+        }
+    }
+
+    if matches!(bytes.get(i), Some(b'e') | Some(b'E')) {
+        i += 1;
+        if matches!(bytes.get(i), Some(b'+') | Some(b'-')) {
+            i += 1;
+        }
+        let exp_start = i;
+        while matches!(bytes.get(i), Some(b'0'..=b'9')) {
+            i += 1;
+        }
+        if i == exp_start {
+            return false;
+        }
+    }
+
+    i == bytes.len()
+}
+
 fn parse_primitive(s: &str) -> Value {
     let s = s.trim();
     if s.is_empty() {
@@ -133,7 +1064,7 @@ fn parse_primitive(s: &str) -> Value {
     }
 
     // Quoted string
-    if s.starts_with('"') && s.ends_with('"') {
+    if s.starts_with('"') && s.ends_with('"') && s.len() >= 2 {
         let inner = &s[1..s.len() - 1];
         return Value::String(
             inner
@@ -153,13 +1084,39 @@ fn parse_primitive(s: &str) -> Value {
     }
 
     // Number
-    if let Ok(i) = s.parse::<i64>() {
-        return Value::Number(i.into());
-    }
-    if let Ok(f) = s.parse::<f64>()
-        && let Some(n) = serde_json::Number::from_f64(f)
-    {
-        return Value::Number(n);
+    if is_json_number(s) {
+        let has_fraction_or_exponent = s.contains('.') || s.contains('e') || s.contains('E');
+        if !has_fraction_or_exponent {
+            if s == "-0" {
+                // `-0` has no distinct i64 representation of its own sign --
+                // keep it as the float `-0.0` instead of silently becoming
+                // integer `0`.
+                return Value::Number(serde_json::Number::from_f64(-0.0).unwrap());
+            }
+            if let Ok(i) = s.parse::<i64>() {
+                return Value::Number(i.into());
+            }
+            if let Ok(u) = s.parse::<u64>() {
+                return Value::Number(u.into());
+            }
+            // Wider than u64: keep every digit via the arbitrary-precision
+            // Number rather than demoting to a string, the same trick
+            // `types::py_to_json` uses for Python bignums.
+            return Value::Number(serde_json::Number::from_string_unchecked(s.to_string()));
+        }
+
+        if let Ok(f) = s.parse::<f64>()
+            && let Some(n) = serde_json::Number::from_f64(f)
+        {
+            return Value::Number(n);
+        }
+        // `f` is non-finite (the lexeme's magnitude is beyond f64 range,
+        // e.g. `1e999999`): preserve the exact digits via the
+        // arbitrary-precision Number instead of losing them to a plain
+        // string. A later `.as_f64()` read naturally saturates to
+        // `±f64::INFINITY`, or rounds a below-range exponent toward `0.0`,
+        // matching Rust's own float parser.
+        return Value::Number(serde_json::Number::from_string_unchecked(s.to_string()));
     }
 
     Value::String(s.to_string())
@@ -168,48 +1125,199 @@ fn parse_primitive(s: &str) -> Value {
 /// Parse a columnar cell value
 /// Returns None for empty/missing cells, Some(value) for present values (including explicit null)
 fn parse_columnar_cell(s: &str) -> Option<Value> {
+    parse_columnar_cell_typed(s, None)
+}
+
+/// Parse a columnar cell, honoring the column's declared type tag (if any).
+/// A declared `str` column is read verbatim, with no number/bool/null
+/// coercion, so a value like `007` stays the string `"007"`.
+fn parse_columnar_cell_typed(s: &str, declared: Option<&str>) -> Option<Value> {
     let trimmed = s.trim();
     if trimmed.is_empty() {
         // Empty cell means field is missing (absent from object)
         return None;
     }
     // Non-empty cell means field is present (could be explicit "null")
-    Some(parse_primitive(s))
+    Some(if declared == Some("str") {
+        parse_string_cell(s)
+    } else {
+        parse_primitive(s)
+    })
 }
 
-fn is_uniform_array(arr: &[Value]) -> (bool, Vec<String>) {
-    if arr.is_empty() {
-        return (false, vec![]);
-    }
-
-    if !arr.iter().all(|v| v.is_object()) {
-        return (false, vec![]);
-    }
+/// Parse a declared-`str` column's cell: unquote it if quoted, otherwise
+/// take the trimmed text as-is, skipping [`parse_primitive`]'s type
+/// coercion entirely.
+fn parse_string_cell(s: &str) -> Value {
+    let s = s.trim();
+    if s.starts_with('"') && s.ends_with('"') && s.len() >= 2 {
+        let inner = &s[1..s.len() - 1];
+        Value::String(
+            inner
+                .replace("\\n", "\n")
+                .replace("\\t", "\t")
+                .replace("\\\"", "\"")
+                .replace("\\\\", "\\"),
+        )
+    } else {
+        Value::String(s.to_string())
+    }
+}
 
-    // Check all values are primitives
-    for obj in arr {
-        if let Some(map) = obj.as_object() {
-            for v in map.values() {
-                if v.is_object() || v.is_array() {
-                    return (false, vec![]);
+/// The AGON-columns type tag a value would round-trip under, or `None` if
+/// it's a type that isn't given a tag of its own (currently always `Some`,
+/// since all [`Value`] variants that can appear in a uniform array map to
+/// one of [`COLUMN_TYPE_TAGS`]).
+fn value_type_tag(v: &Value) -> Option<&'static str> {
+    match v {
+        Value::String(_) => Some("str"),
+        Value::Bool(_) => Some("bool"),
+        Value::Null => Some("null"),
+        Value::Number(n) => Some(if n.is_f64() { "float" } else { "int" }),
+        _ => None,
+    }
+}
+
+/// Escape a key for use as one segment of a dotted column path: a literal
+/// `.` or `\` in the key itself must not be mistaken for the nesting
+/// separator or an escape introducer when [`split_dotted_path`] later
+/// splits the path back apart.
+fn escape_dotted_key(key: &str) -> String {
+    key.replace('\\', "\\\\").replace('.', "\\.")
+}
+
+/// Split a dotted column path produced by [`flatten_row`] back into its
+/// original key segments, un-escaping `\.` and `\\` within each one. A path
+/// with no unescaped `.` is a single segment equal to the field name.
+fn split_dotted_path(path: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut chars = path.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.peek() {
+                Some('.') | Some('\\') => current.push(chars.next().unwrap()),
+                _ => current.push(c),
+            }
+        } else if c == '.' {
+            segments.push(std::mem::take(&mut current));
+        } else {
+            current.push(c);
+        }
+    }
+    segments.push(current);
+    segments
+}
+
+/// Recursively flatten a row object's nested-object fields into dotted-path
+/// scalar columns (`{"address": {"city": "NYC"}}` -> `{"address.city":
+/// "NYC"}`), so a record with a nested object can still take the compact
+/// columnar layout instead of falling back to the verbose list-item format.
+/// Returns `None` if any leaf value is an array -- arrays can't be
+/// represented as a scalar column.
+fn flatten_row(obj: &Map<String, Value>) -> Option<Map<String, Value>> {
+    let mut out = Map::new();
+    flatten_into(obj, "", &mut out).then_some(out)
+}
+
+fn flatten_into(obj: &Map<String, Value>, prefix: &str, out: &mut Map<String, Value>) -> bool {
+    for (k, v) in obj {
+        let escaped_key = escape_dotted_key(k);
+        let path = if prefix.is_empty() {
+            escaped_key
+        } else {
+            format!("{}.{}", prefix, escaped_key)
+        };
+        match v {
+            Value::Array(_) => return false,
+            Value::Object(nested) => {
+                if !flatten_into(nested, &path, out) {
+                    return false;
                 }
             }
+            _ => {
+                out.insert(path, v.clone());
+            }
+        }
+    }
+    true
+}
+
+/// Insert `val` into `obj` at a possibly-dotted `path`, creating nested
+/// objects as needed and overwriting any non-object value already sitting
+/// where one is needed (e.g. a flat `user` column and a dotted
+/// `user.nested` column colliding in the same block). The inverse of the
+/// path side of [`flatten_row`].
+fn insert_dotted(obj: &mut Map<String, Value>, path: &str, val: Value) {
+    let segments = split_dotted_path(path);
+    let mut current = obj;
+    for seg in &segments[..segments.len() - 1] {
+        let entry = current
+            .entry(seg.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !entry.is_object() {
+            *entry = Value::Object(Map::new());
+        }
+        current = entry.as_object_mut().expect("just ensured this is an object");
+    }
+    current.insert(segments[segments.len() - 1].clone(), val);
+}
+
+/// Returns `(is_uniform, fields, field_types)`, where `fields` are dotted
+/// column paths from flattening each row with [`flatten_row`], and
+/// `field_types[i]` is `Some(tag)` when every present value in `fields[i]`
+/// shares the same [`value_type_tag`], or `None` when the column is
+/// mixed-type or never populated (in which case it's encoded/decoded via
+/// the untyped heuristic).
+fn is_uniform_array(arr: &[Value]) -> (bool, Vec<String>, Vec<Option<&'static str>>) {
+    if arr.is_empty() {
+        return (false, vec![], vec![]);
+    }
+
+    if !arr.iter().all(|v| v.is_object()) {
+        return (false, vec![], vec![]);
+    }
+
+    let mut flattened: Vec<Map<String, Value>> = Vec::with_capacity(arr.len());
+    for obj in arr {
+        match flatten_row(obj.as_object().expect("checked above: all rows are objects")) {
+            Some(flat) => flattened.push(flat),
+            None => return (false, vec![], vec![]),
         }
     }
 
     // Collect keys in order
     let mut key_order = Vec::new();
-    for obj in arr {
-        if let Some(map) = obj.as_object() {
-            for k in map.keys() {
-                if !key_order.contains(k) {
-                    key_order.push(k.clone());
-                }
+    for flat in &flattened {
+        for k in flat.keys() {
+            if !key_order.contains(k) {
+                key_order.push(k.clone());
             }
         }
     }
 
-    (true, key_order)
+    let field_types = key_order
+        .iter()
+        .map(|field| {
+            let mut tag: Option<&'static str> = None;
+            let mut seen_any = false;
+            for flat in &flattened {
+                let Some(v) = flat.get(field) else {
+                    continue;
+                };
+                let this_tag = value_type_tag(v);
+                if !seen_any {
+                    tag = this_tag;
+                    seen_any = true;
+                } else if tag != this_tag {
+                    return None;
+                }
+            }
+            tag
+        })
+        .collect();
+
+    (true, key_order, field_types)
 }
 
 fn encode_value(
@@ -218,8 +1326,9 @@ fn encode_value(
     depth: usize,
     delimiter: &str,
     name: Option<&str>,
+    options: &SerializeOptions,
 ) {
-    let indent = INDENT.repeat(depth);
+    let indent = options.indent_unit().repeat(depth);
 
     match val {
         Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {
@@ -231,10 +1340,10 @@ fn encode_value(
             }
         }
         Value::Array(arr) => {
-            encode_array(arr, lines, depth, delimiter, name);
+            encode_array(arr, lines, depth, delimiter, name, options);
         }
         Value::Object(obj) => {
-            encode_object(obj, lines, depth, delimiter, name);
+            encode_object(obj, lines, depth, delimiter, name, options);
         }
     }
 }
@@ -245,8 +1354,9 @@ fn encode_array(
     depth: usize,
     delimiter: &str,
     name: Option<&str>,
+    options: &SerializeOptions,
 ) {
-    let indent = INDENT.repeat(depth);
+    let indent = options.indent_unit().repeat(depth);
 
     if arr.is_empty() {
         if let Some(n) = name {
@@ -258,8 +1368,13 @@ fn encode_array(
     }
 
     // Check for uniform objects (columnar format)
-    let (is_uniform, fields) = is_uniform_array(arr);
-    if is_uniform && !fields.is_empty() {
+    let (is_uniform, fields, field_types) = is_uniform_array(arr);
+    let mut columns: Vec<(String, Option<&'static str>)> =
+        fields.into_iter().zip(field_types).collect();
+    if options.sort_keys {
+        columns.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+    if is_uniform && !columns.is_empty() {
         // Columnar header
         if let Some(n) = name {
             lines.push(format!("{}{}[{}]", indent, n, arr.len()));
@@ -267,25 +1382,38 @@ fn encode_array(
             lines.push(format!("{}[{}]", indent, arr.len()));
         }
 
+        // Flatten each row once (nested objects become dotted paths) rather
+        // than re-walking every row's nesting once per column below.
+        let flattened: Vec<Map<String, Value>> = arr
+            .iter()
+            .map(|v| {
+                flatten_row(v.as_object().expect("checked uniform: all rows are objects"))
+                    .unwrap_or_default()
+            })
+            .collect();
+
         // Output each field as a column
-        let total_fields = fields.len();
-        for (i, field) in fields.iter().enumerate() {
-            let values: Vec<String> = arr
+        let total_fields = columns.len();
+        for (i, (field, tag)) in columns.iter().enumerate() {
+            let values: Vec<String> = flattened
                 .iter()
-                .map(|obj| {
-                    obj.as_object()
-                        .and_then(|m| m.get(field))
-                        .map(format_primitive)
+                .map(|flat| {
+                    flat.get(field)
+                        .map(|v| format_primitive_for_column(v, *tag))
                         .unwrap_or_default()
                 })
                 .collect();
 
+            let label = match tag {
+                Some(t) => format!("{}:{}", field, t),
+                None => field.clone(),
+            };
             let prefix = if i == total_fields - 1 { "└" } else { "├" };
             lines.push(format!(
                 "{}{} {}: {}",
                 indent,
                 prefix,
-                field,
+                label,
                 values.join(delimiter)
             ));
         }
@@ -323,7 +1451,7 @@ fn encode_array(
     for item in arr {
         match item {
             Value::Object(obj) => {
-                encode_list_item_object(obj, lines, depth + 1, delimiter);
+                encode_list_item_object(obj, lines, depth + 1, delimiter, options);
             }
             _ => {
                 lines.push(format!("{}  - {}", indent, format_primitive(item)));
@@ -338,11 +1466,12 @@ fn encode_list_item_object(
     lines: &mut Vec<String>,
     depth: usize,
     delimiter: &str,
+    options: &SerializeOptions,
 ) {
-    let indent = INDENT.repeat(depth);
+    let indent = options.indent_unit().repeat(depth);
     let mut first = true;
 
-    for (k, v) in obj {
+    for (k, v) in crate::options::ordered_entries(obj, options.sort_keys) {
         let prefix = if first {
             format!("{}- ", indent)
         } else {
@@ -353,10 +1482,10 @@ fn encode_list_item_object(
         match v {
             Value::Object(nested) => {
                 lines.push(format!("{}{}:", prefix, k));
-                for (nk, nv) in nested {
+                for (nk, nv) in crate::options::ordered_entries(nested, options.sort_keys) {
                     match nv {
                         Value::Object(_) | Value::Array(_) => {
-                            encode_value(nv, lines, depth + 2, delimiter, Some(nk));
+                            encode_value(nv, lines, depth + 2, delimiter, Some(nk), options);
                         }
                         _ => {
                             lines.push(format!("{}    {}: {}", indent, nk, format_primitive(nv)));
@@ -366,7 +1495,7 @@ fn encode_list_item_object(
             }
             Value::Array(arr) => {
                 lines.push(format!("{}{}:", prefix, k));
-                encode_array(arr, lines, depth + 2, delimiter, None);
+                encode_array(arr, lines, depth + 2, delimiter, None, options);
             }
             _ => {
                 lines.push(format!("{}{}: {}", prefix, k, format_primitive(v)));
@@ -381,8 +1510,9 @@ fn encode_object(
     depth: usize,
     delimiter: &str,
     name: Option<&str>,
+    options: &SerializeOptions,
 ) {
-    let indent = INDENT.repeat(depth);
+    let indent = options.indent_unit().repeat(depth);
     let mut actual_depth = depth;
 
     if let Some(n) = name {
@@ -390,12 +1520,12 @@ fn encode_object(
         actual_depth += 1;
     }
 
-    let actual_indent = INDENT.repeat(actual_depth);
+    let actual_indent = options.indent_unit().repeat(actual_depth);
 
-    for (k, v) in obj {
+    for (k, v) in crate::options::ordered_entries(obj, options.sort_keys) {
         match v {
             Value::Object(_) | Value::Array(_) => {
-                encode_value(v, lines, actual_depth, delimiter, Some(k));
+                encode_value(v, lines, actual_depth, delimiter, Some(k), options);
             }
             _ => {
                 lines.push(format!("{}{}: {}", actual_indent, k, format_primitive(v)));
@@ -414,11 +1544,88 @@ fn get_indent_depth(line: &str) -> usize {
     spaces / 2
 }
 
+/// The char column of `needle` within `haystack`, given `needle` is a
+/// sub-slice of `haystack` produced by `.trim()`/`.strip_prefix()`/etc.
+/// (always true for the slices this module passes through, since none of
+/// those operations copy the underlying bytes).
+fn char_col(haystack: &str, needle: &str) -> usize {
+    let byte_offset = needle.as_ptr() as usize - haystack.as_ptr() as usize;
+    haystack[..byte_offset].chars().count()
+}
+
+/// Build a positioned [`AgonError::ColumnsDecodeError`] for a failure while
+/// decoding `lines[idx]`. `line` is 1-based (matching an editor), `col` is a
+/// char offset into the raw (untrimmed) line text.
+fn columns_decode_error(
+    idx: usize,
+    col: usize,
+    raw_line: &str,
+    reason: impl Into<String>,
+) -> AgonError {
+    AgonError::ColumnsDecodeError {
+        span: Span { line: idx + 1, col },
+        line_text: raw_line.to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// Locate an opening `"` in `s` that's never closed, respecting `\`-escapes
+/// inside the quote. Returns the char column (within `s`) where the
+/// unterminated quote opened.
+fn find_unterminated_quote_column(s: &str) -> Option<usize> {
+    let mut in_quote = false;
+    let mut escape_next = false;
+    let mut opened_at = 0usize;
+    for (char_idx, c) in s.chars().enumerate() {
+        if escape_next {
+            escape_next = false;
+            continue;
+        }
+        if c == '\\' && in_quote {
+            escape_next = true;
+            continue;
+        }
+        if c == '"' {
+            if !in_quote {
+                opened_at = char_idx;
+            }
+            in_quote = !in_quote;
+        }
+    }
+    in_quote.then_some(opened_at)
+}
+
+/// Record a "malformed `[N]` bracket" [`Diagnostic`] and report whether the
+/// caller should recover by falling back to a plain `key: value` read
+/// (`diagnostics` is `Some`) instead of returning `Err` (`diagnostics` is
+/// `None`, the strict-decode case).
+fn recover_from_bad_bracket(
+    idx: usize,
+    raw_line: &str,
+    trimmed: &str,
+    diagnostics: Option<&RefCell<Vec<Diagnostic>>>,
+) -> bool {
+    let Some(diagnostics) = diagnostics else {
+        return false;
+    };
+    diagnostics.borrow_mut().push(Diagnostic {
+        span: Span {
+            line: idx + 1,
+            col: char_col(raw_line, trimmed),
+        },
+        reason: "expected `]` after array count".to_string(),
+        recovered_as: "treated as plain key: value".to_string(),
+    });
+    true
+}
+
 fn decode_value(
     lines: &[&str],
     idx: usize,
     _depth: usize,
     delimiter: &str,
+    fields: Option<&[&str]>,
+    diagnostics: Option<&RefCell<Vec<Diagnostic>>>,
 ) -> Result<(Value, usize)> {
     if idx >= lines.len() {
         return Ok((Value::Null, idx));
@@ -428,70 +1635,132 @@ fn decode_value(
     let base_depth = get_indent_depth(lines[idx]);
 
     // Check for array patterns: [N], [N]:, name[N], name[N]:
-    if let Some(bracket_pos) = line.find('[')
-        && let Some(end_pos) = line.find(']')
-        && end_pos > bracket_pos
-    {
-        let name = &line[..bracket_pos];
-        let count_str = &line[bracket_pos + 1..end_pos];
-        if let Ok(count) = count_str.parse::<usize>() {
-            // If this is a named array (name[N]), it's part of an object
-            // Delegate to decode_object to parse the full object
-            if !name.is_empty() {
-                return decode_object(lines, idx, delimiter);
-            }
+    if let Some(bracket_pos) = line.find('[') {
+        // An array count always starts with a digit right after `[`; a bare
+        // `[` elsewhere (e.g. inside an unquoted string value) shouldn't be
+        // treated as a malformed array opener.
+        let looks_like_array_count = line[bracket_pos + 1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_digit());
+
+        let end_pos = line[bracket_pos..].find(']').map(|p| bracket_pos + p);
+        if let Some(end_pos) = end_pos {
+            let name = &line[..bracket_pos];
+            let count_str = &line[bracket_pos + 1..end_pos];
+            if let Ok(count) = count_str.parse::<usize>() {
+                // If this is a named array (name[N]), it's part of an object
+                // Delegate to decode_object to parse the full object
+                if !name.is_empty() {
+                    return decode_object(lines, idx, delimiter, fields, diagnostics);
+                }
 
-            // Unnamed array: [N]
-            // Check if next line has ├ or └ (columnar format)
-            if idx + 1 < lines.len() {
-                let next = lines[idx + 1].trim();
-                if next.starts_with('├') || next.starts_with('└') {
-                    return decode_columnar_array(lines, idx, "", count, delimiter);
+                // Unnamed array: [N]
+                // Check if next line has ├ or └ (columnar format)
+                if idx + 1 < lines.len() {
+                    let next = lines[idx + 1].trim();
+                    if next.starts_with('├') || next.starts_with('└') {
+                        return decode_columnar_array(
+                            lines,
+                            idx,
+                            "",
+                            count,
+                            delimiter,
+                            fields,
+                            diagnostics,
+                        );
+                    }
                 }
-            }
 
-            // Check for inline primitive array: [N]: val1\tval2
-            if let Some(colon_pos) = line.find("]:") {
-                let values_str = line[colon_pos + 2..].trim();
-                if !values_str.is_empty() {
-                    let values: Vec<Value> =
-                        values_str.split(delimiter).map(parse_primitive).collect();
-                    return Ok((Value::Array(values), idx + 1));
+                // Check for inline primitive array: [N]: val1\tval2
+                if let Some(colon_pos) = line.find("]:") {
+                    let values_str = line[colon_pos + 2..].trim();
+                    if !values_str.is_empty() {
+                        let values: Vec<Value> =
+                            values_str.split(delimiter).map(parse_primitive).collect();
+                        return Ok((Value::Array(values), idx + 1));
+                    }
+                    // Empty values after colon means list array: [N]:
+                    return decode_list_array(lines, idx, base_depth, count, delimiter, diagnostics);
                 }
-                // Empty values after colon means list array: [N]:
-                return decode_list_array(lines, idx, base_depth, count, delimiter);
-            }
 
-            // Bare [N] with no colon - could be empty array or non-columnar array
-            if count == 0 {
+                // Bare [N] with no colon - could be empty array or non-columnar array
+                if count == 0 {
+                    return Ok((Value::Array(vec![]), idx + 1));
+                }
+                // Check if next line is a list item
+                if idx + 1 < lines.len() {
+                    let next = lines[idx + 1].trim();
+                    if next.starts_with("- ") {
+                        return decode_list_array(lines, idx, base_depth, count, delimiter, diagnostics);
+                    }
+                }
+                // No colon, no columnar, no list - it's an empty array
                 return Ok((Value::Array(vec![]), idx + 1));
-            }
-            // Check if next line is a list item
-            if idx + 1 < lines.len() {
-                let next = lines[idx + 1].trim();
-                if next.starts_with("- ") {
-                    return decode_list_array(lines, idx, base_depth, count, delimiter);
+            } else if looks_like_array_count {
+                if !recover_from_bad_bracket(idx, lines[idx], line, diagnostics) {
+                    return Err(columns_decode_error(
+                        idx,
+                        char_col(lines[idx], line),
+                        lines[idx],
+                        "expected `]` after array count",
+                    ));
                 }
+                // else: recovered -- fall through to the key/value check below.
+            }
+            // else: not actually an array pattern (e.g. a bracket inside a
+            // plain string value) -- fall through to the key/value check.
+        } else if looks_like_array_count {
+            if !recover_from_bad_bracket(idx, lines[idx], line, diagnostics) {
+                return Err(columns_decode_error(
+                    idx,
+                    char_col(lines[idx], line),
+                    lines[idx],
+                    "expected `]` after array count",
+                ));
             }
-            // No colon, no columnar, no list - it's an empty array
-            return Ok((Value::Array(vec![]), idx + 1));
         }
     }
 
     // Check for key: value
     if line.contains(':') {
-        return decode_object(lines, idx, delimiter);
+        return decode_object(lines, idx, delimiter, fields, diagnostics);
     }
 
     Ok((Value::Null, idx + 1))
 }
 
+const COLUMN_TYPE_TAGS: &[&str] = &["str", "int", "float", "bool", "null"];
+
+/// Split a `├`/`└` line's content into `(field, declared_type, values_str)`.
+/// Accepts both the tagged `field:type: values` form this format now emits
+/// and the legacy untagged `field: values` form, telling them apart by
+/// whether the text between the first two colons is one of
+/// [`COLUMN_TYPE_TAGS`].
+fn parse_column_header(content: &str) -> Option<(&str, Option<&str>, &str)> {
+    let first_colon = content.find(':')?;
+    let field = content[..first_colon].trim();
+    let after_first = &content[first_colon + 1..];
+
+    if let Some(second_colon) = after_first.find(':') {
+        let candidate = after_first[..second_colon].trim();
+        if COLUMN_TYPE_TAGS.contains(&candidate) {
+            let values_str = after_first[second_colon + 1..].trim_start();
+            return Some((field, Some(candidate), values_str));
+        }
+    }
+
+    Some((field, None, after_first.trim_start()))
+}
+
 fn decode_columnar_array(
     lines: &[&str],
     idx: usize,
     name: &str,
     count: usize,
     delimiter: &str,
+    projection: Option<&[&str]>,
+    diagnostics: Option<&RefCell<Vec<Diagnostic>>>,
 ) -> Result<(Value, usize)> {
     let mut fields: Vec<String> = Vec::new();
     // Each column stores Option<Value>: None = missing, Some(v) = present (including explicit null)
@@ -499,7 +1768,11 @@ fn decode_columnar_array(
 
     let mut idx = idx + 1;
 
-    // Parse columnar lines (├ field: val1\tval2... or └ field: val1\tval2...)
+    // Parse columnar lines (├ field: val1\tval2... or └ field: val1\tval2...).
+    // A field's segment can be followed by one or more `│` continuation
+    // lines (written by encode_streaming's batched columnar writer for a
+    // field split across batches); those carry no `field:` label and are
+    // stitched onto the segment they follow before any cell parsing happens.
     while idx < lines.len() {
         let line = lines[idx].trim();
 
@@ -510,26 +1783,121 @@ fn decode_columnar_array(
         };
 
         if let Some(content) = field_line {
-            if let Some(colon_pos) = content.find(':') {
-                let field = content[..colon_pos].trim();
-                // Don't strip trailing whitespace - it's part of delimiter for empty cells
-                let values_str = content[colon_pos + 1..].trim_start();
-
-                fields.push(field.to_string());
+            let is_last_field_line = line.starts_with('└');
+
+            if let Some((field, declared, first_values_str)) = parse_column_header(content) {
+                // Segments making up this field's value, one per physical
+                // line (the `├`/`└` line itself, plus any `│` continuation
+                // lines), each paired with its char offset into
+                // `full_values_str` -- so a column offset into the merged
+                // string can be mapped back to the physical line and
+                // in-line column it actually came from.
+                let mut segments: Vec<(usize, &str, usize)> = vec![(idx, first_values_str, 0)];
+                let mut segment_end_idx = idx;
+                let mut full_values_str = first_values_str.to_string();
+                while segment_end_idx + 1 < lines.len() {
+                    let Some(cont) = lines[segment_end_idx + 1].trim().strip_prefix('│') else {
+                        break;
+                    };
+                    let cont = cont.trim_start();
+                    segment_end_idx += 1;
+                    if full_values_str.is_empty() {
+                        full_values_str = cont.to_string();
+                        segments.push((segment_end_idx, cont, 0));
+                    } else {
+                        let start = full_values_str.chars().count() + delimiter.chars().count();
+                        full_values_str.push_str(delimiter);
+                        full_values_str.push_str(cont);
+                        segments.push((segment_end_idx, cont, start));
+                    }
+                }
 
-                let values: Vec<Option<Value>> = if values_str.is_empty() {
-                    vec![]
-                } else {
-                    split_column_values(values_str, delimiter)
-                        .iter()
-                        .map(|s| parse_columnar_cell(s))
-                        .collect()
-                };
-                columns.push(values);
+                // A column not in the projection is skipped entirely: no
+                // split_column_values/parse_columnar_cell_typed work, which
+                // is the whole performance point of picking N of M columns
+                // out of a wide table. `idx` still advances below so the
+                // `└` terminator is still honored.
+                let wanted = projection.is_none_or(|want| want.contains(&field));
+                if wanted {
+                    // Don't strip trailing whitespace - it's part of delimiter for empty cells
+                    fields.push(field.to_string());
+                    let values_str = full_values_str.as_str();
+
+                    let mut values: Vec<Option<Value>> = if values_str.is_empty() {
+                        vec![]
+                    } else {
+                        let unterminated_at = find_unterminated_quote_column(values_str);
+                        if let Some(quote_col) = unterminated_at {
+                            // Map the offset into the merged string back to
+                            // whichever physical segment (line) actually
+                            // contains it -- the last segment starting at or
+                            // before `quote_col`.
+                            let (line_idx, segment_text, segment_start) = segments
+                                .iter()
+                                .rev()
+                                .find(|(_, _, start)| *start <= quote_col)
+                                .copied()
+                                .unwrap_or(segments[0]);
+                            let col = char_col(lines[line_idx], segment_text)
+                                + (quote_col - segment_start);
+
+                            if let Some(diagnostics) = diagnostics {
+                                diagnostics.borrow_mut().push(Diagnostic {
+                                    span: Span {
+                                        line: line_idx + 1,
+                                        col,
+                                    },
+                                    reason: "unterminated quote in column cell".to_string(),
+                                    recovered_as: format!(
+                                        "treated the rest of field `{}`'s line as one cell",
+                                        field
+                                    ),
+                                });
+                            } else {
+                                return Err(columns_decode_error(
+                                    line_idx,
+                                    col,
+                                    lines[line_idx],
+                                    "unterminated quote in column cell",
+                                ));
+                            }
+                        }
+                        split_column_values(values_str, delimiter)
+                            .iter()
+                            .map(|s| parse_columnar_cell_typed(s, declared))
+                            .collect()
+                    };
+
+                    if let Some(diagnostics) = diagnostics
+                        && values.len() != count
+                    {
+                        diagnostics.borrow_mut().push(Diagnostic {
+                            span: Span {
+                                line: idx + 1,
+                                col: char_col(lines[idx], content),
+                            },
+                            reason: format!(
+                                "column `{}` has {} cell(s), expected {}",
+                                field,
+                                values.len(),
+                                count
+                            ),
+                            recovered_as: if values.len() < count {
+                                format!("padded {} missing cell(s)", count - values.len())
+                            } else {
+                                format!("truncated {} extra cell(s)", values.len() - count)
+                            },
+                        });
+                    }
+                    values.resize_with(count, || None);
+                    columns.push(values);
+                }
+                idx = segment_end_idx + 1;
+            } else {
+                idx += 1;
             }
-            idx += 1;
 
-            if line.starts_with('└') {
+            if is_last_field_line {
                 break;
             }
         } else {
@@ -537,7 +1905,9 @@ fn decode_columnar_array(
         }
     }
 
-    // Transpose columns to rows, preserving field order
+    // Transpose columns to rows, preserving field order. A dotted field
+    // path (from a flattened nested object) rebuilds its nested `Map`s as
+    // it's inserted, mirroring flatten_row on the encode side.
     let mut result: Vec<Value> = Vec::with_capacity(count);
     for i in 0..count {
         let mut obj = Map::new();
@@ -545,7 +1915,7 @@ fn decode_columnar_array(
             if let Some(col) = columns.get(j) {
                 // Only insert if value is present (Some(Some(val))), skip if missing
                 if let Some(Some(val)) = col.get(i) {
-                    obj.insert(field.clone(), val.clone());
+                    insert_dotted(&mut obj, field, val.clone());
                 }
             }
         }
@@ -618,7 +1988,13 @@ fn split_column_values(values_str: &str, delimiter: &str) -> Vec<String> {
     result
 }
 
-fn decode_object(lines: &[&str], idx: usize, delimiter: &str) -> Result<(Value, usize)> {
+fn decode_object(
+    lines: &[&str],
+    idx: usize,
+    delimiter: &str,
+    fields: Option<&[&str]>,
+    diagnostics: Option<&RefCell<Vec<Diagnostic>>>,
+) -> Result<(Value, usize)> {
     let mut result = Map::new();
     let base_depth = get_indent_depth(lines[idx]);
     let mut idx = idx;
@@ -638,18 +2014,42 @@ fn decode_object(lines: &[&str], idx: usize, delimiter: &str) -> Result<(Value,
         let stripped = line.trim();
 
         // Check for array patterns: name[N] or name[N]: values
-        if let Some(bracket_pos) = stripped.find('[')
-            && let Some(end_pos) = stripped.find(']')
-            && end_pos > bracket_pos
-        {
-            let name = &stripped[..bracket_pos];
-            let count_str = &stripped[bracket_pos + 1..end_pos];
-            if let Ok(count) = count_str.parse::<usize>() {
-                // This is an array pattern - decode it via decode_value
-                let (arr, new_idx) = decode_array_in_object(lines, idx, name, count, delimiter)?;
-                result.insert(name.to_string(), arr);
-                idx = new_idx;
-                continue;
+        if let Some(bracket_pos) = stripped.find('[') {
+            let looks_like_array_count = stripped[bracket_pos + 1..]
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_digit());
+            let end_pos = stripped[bracket_pos..].find(']').map(|p| bracket_pos + p);
+            if let Some(end_pos) = end_pos {
+                let name = &stripped[..bracket_pos];
+                let count_str = &stripped[bracket_pos + 1..end_pos];
+                if let Ok(count) = count_str.parse::<usize>() {
+                    // This is an array pattern - decode it via decode_value
+                    let (arr, new_idx) = decode_array_in_object(
+                        lines, idx, name, count, delimiter, fields, diagnostics,
+                    )?;
+                    result.insert(name.to_string(), arr);
+                    idx = new_idx;
+                    continue;
+                } else if looks_like_array_count {
+                    if !recover_from_bad_bracket(idx, line, stripped, diagnostics) {
+                        return Err(columns_decode_error(
+                            idx,
+                            char_col(line, stripped),
+                            line,
+                            "expected `]` after array count",
+                        ));
+                    }
+                }
+            } else if looks_like_array_count {
+                if !recover_from_bad_bracket(idx, line, stripped, diagnostics) {
+                    return Err(columns_decode_error(
+                        idx,
+                        char_col(line, stripped),
+                        line,
+                        "expected `]` after array count",
+                    ));
+                }
             }
         }
 
@@ -666,7 +2066,8 @@ fn decode_object(lines: &[&str], idx: usize, delimiter: &str) -> Result<(Value,
                 if idx < lines.len() {
                     let next_depth = get_indent_depth(lines[idx]);
                     if next_depth > line_depth {
-                        let (nested, new_idx) = decode_value(lines, idx, next_depth, delimiter)?;
+                        let (nested, new_idx) =
+                            decode_value(lines, idx, next_depth, delimiter, fields, diagnostics)?;
                         result.insert(key.to_string(), nested);
                         idx = new_idx;
                     } else {
@@ -692,6 +2093,8 @@ fn decode_array_in_object(
     _name: &str,
     count: usize,
     delimiter: &str,
+    fields: Option<&[&str]>,
+    diagnostics: Option<&RefCell<Vec<Diagnostic>>>,
 ) -> Result<(Value, usize)> {
     let line = lines[idx].trim();
     let base_depth = get_indent_depth(lines[idx]);
@@ -709,14 +2112,15 @@ fn decode_array_in_object(
     if idx + 1 < lines.len() {
         let next = lines[idx + 1].trim();
         if next.starts_with('├') || next.starts_with('└') {
-            let (arr, new_idx) = decode_columnar_array(lines, idx, "", count, delimiter)?;
+            let (arr, new_idx) =
+                decode_columnar_array(lines, idx, "", count, delimiter, fields, diagnostics)?;
             return Ok((arr, new_idx));
         }
     }
 
     // Check for list array: name[N]: followed by - items
     if line.ends_with(':') {
-        return decode_list_array(lines, idx, base_depth, count, delimiter);
+        return decode_list_array(lines, idx, base_depth, count, delimiter, diagnostics);
     }
 
     // Empty array
@@ -730,6 +2134,7 @@ fn decode_list_array(
     base_depth: usize,
     count: usize,
     delimiter: &str,
+    diagnostics: Option<&RefCell<Vec<Diagnostic>>>,
 ) -> Result<(Value, usize)> {
     let mut result: Vec<Value> = Vec::new();
     let mut idx = idx + 1;
@@ -751,7 +2156,8 @@ fn decode_list_array(
         if let Some(item_str) = stripped.strip_prefix("- ") {
             // Check if it's key: value (object) or primitive
             if item_str.contains(':') {
-                let (obj, new_idx) = decode_list_item_object(lines, idx, item_depth, delimiter)?;
+                let (obj, new_idx) =
+                    decode_list_item_object(lines, idx, item_depth, delimiter, diagnostics)?;
                 result.push(obj);
                 idx = new_idx;
             } else {
@@ -772,6 +2178,7 @@ fn decode_list_item_object(
     idx: usize,
     item_depth: usize,
     delimiter: &str,
+    diagnostics: Option<&RefCell<Vec<Diagnostic>>>,
 ) -> Result<(Value, usize)> {
     let mut obj = Map::new();
 
@@ -794,7 +2201,8 @@ fn decode_list_item_object(
             if idx < lines.len() {
                 let next_depth = get_indent_depth(lines[idx]);
                 if next_depth > item_depth {
-                    let (nested, new_idx) = decode_value(lines, idx, next_depth, delimiter)?;
+                    let (nested, new_idx) =
+                        decode_value(lines, idx, next_depth, delimiter, None, diagnostics)?;
                     obj.insert(key.to_string(), nested);
                     idx = new_idx;
                 } else {
@@ -837,8 +2245,9 @@ fn decode_list_item_object(
             let arr_name = &stripped[..bracket_pos];
             let count_str = &stripped[bracket_pos + 1..end_pos];
             if let Ok(count) = count_str.parse::<usize>() {
-                let (arr, new_idx) =
-                    decode_array_in_object(lines, idx, arr_name, count, delimiter)?;
+                let (arr, new_idx) = decode_array_in_object(
+                    lines, idx, arr_name, count, delimiter, None, diagnostics,
+                )?;
                 obj.insert(arr_name.to_string(), arr);
                 idx = new_idx;
                 continue;
@@ -858,7 +2267,8 @@ fn decode_list_item_object(
                 if idx < lines.len() {
                     let next_depth = get_indent_depth(lines[idx]);
                     if next_depth > line_depth {
-                        let (nested, new_idx) = decode_value(lines, idx, next_depth, delimiter)?;
+                        let (nested, new_idx) =
+                            decode_value(lines, idx, next_depth, delimiter, None, diagnostics)?;
                         obj.insert(key.to_string(), nested);
                         idx = new_idx;
                     } else {
@@ -1181,6 +2591,74 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_primitive_trailing_dot_stays_a_string() {
+        // `1.` has no digit after the `.`, so it fails the JSON number
+        // grammar and must not be coerced.
+        assert_eq!(parse_primitive("1."), Value::String("1.".to_string()));
+    }
+
+    #[test]
+    fn test_parse_primitive_rejects_float_keywords() {
+        // Rust's own `str::parse::<f64>` accepts "inf"/"nan" -- the JSON
+        // number grammar does not, so these must stay strings.
+        assert_eq!(parse_primitive("inf"), Value::String("inf".to_string()));
+        assert_eq!(parse_primitive("nan"), Value::String("nan".to_string()));
+        assert_eq!(
+            parse_primitive("infinity"),
+            Value::String("infinity".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_primitive_scientific_notation() {
+        assert_eq!(parse_primitive("1e10"), json!(1e10));
+        assert_eq!(parse_primitive("1.5e-3"), json!(1.5e-3));
+        assert_eq!(parse_primitive("-2E+5"), json!(-2e5));
+    }
+
+    #[test]
+    fn test_parse_primitive_u64_beyond_i64_range() {
+        let value = parse_primitive("18446744073709551615");
+        assert_eq!(value, json!(18446744073709551615u64));
+    }
+
+    #[test]
+    fn test_parse_primitive_bignum_beyond_u64_preserves_every_digit() {
+        let digits = "123456789012345678901234567890";
+        let value = parse_primitive(digits);
+        assert_eq!(value.to_string(), digits);
+    }
+
+    #[test]
+    fn test_parse_primitive_oversized_exponent_round_trips_exactly() {
+        // Beyond f64 range: the exact lexeme is preserved rather than
+        // collapsing to a string or erroring.
+        let value = parse_primitive("1e999999");
+        assert_eq!(value.to_string(), "1e999999");
+        assert_eq!(value.as_f64(), Some(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_parse_primitive_oversized_negative_exponent_round_trips_exactly() {
+        let value = parse_primitive("-1e999999");
+        assert_eq!(value.to_string(), "-1e999999");
+        assert_eq!(value.as_f64(), Some(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn test_parse_primitive_below_range_exponent_rounds_toward_zero() {
+        let value = parse_primitive("1e-999999");
+        assert_eq!(value.as_f64(), Some(0.0));
+    }
+
+    #[test]
+    fn test_parse_primitive_negative_zero_integer_stays_a_negative_zero_float() {
+        let value = parse_primitive("-0");
+        assert!(value.as_f64().unwrap().is_sign_negative());
+        assert_eq!(value.as_f64(), Some(-0.0));
+    }
+
     #[test]
     fn test_parse_columnar_cell_empty() {
         assert_eq!(parse_columnar_cell(""), None);
@@ -1196,21 +2674,21 @@ mod tests {
     #[test]
     fn test_is_uniform_array_empty() {
         let arr: Vec<Value> = vec![];
-        let (uniform, _) = is_uniform_array(&arr);
+        let (uniform, _, _) = is_uniform_array(&arr);
         assert!(!uniform);
     }
 
     #[test]
     fn test_is_uniform_array_primitives() {
         let arr = vec![json!(1), json!(2)];
-        let (uniform, _) = is_uniform_array(&arr);
+        let (uniform, _, _) = is_uniform_array(&arr);
         assert!(!uniform);
     }
 
     #[test]
     fn test_is_uniform_array_uniform_objects() {
         let arr = vec![json!({"id": 1, "name": "a"}), json!({"id": 2, "name": "b"})];
-        let (uniform, fields) = is_uniform_array(&arr);
+        let (uniform, fields, _) = is_uniform_array(&arr);
         assert!(uniform);
         assert!(fields.contains(&"id".to_string()));
         assert!(fields.contains(&"name".to_string()));
@@ -1218,9 +2696,20 @@ mod tests {
 
     #[test]
     fn test_is_uniform_array_nested() {
+        // A nested object's scalar leaves flatten into dotted columns
+        // (chunk7-4), so this is uniform, unlike a nested array.
         let arr = vec![json!({"nested": {"a": 1}})];
-        let (uniform, _) = is_uniform_array(&arr);
-        assert!(!uniform); // Contains nested object
+        let (uniform, fields, _) = is_uniform_array(&arr);
+        assert!(uniform);
+        assert_eq!(fields, vec!["nested.a".to_string()]);
+    }
+
+    #[test]
+    fn test_is_uniform_array_nested_array_is_not_uniform() {
+        // Arrays can't flatten into a scalar column, so this still bails.
+        let arr = vec![json!({"tags": ["a", "b"]})];
+        let (uniform, _, _) = is_uniform_array(&arr);
+        assert!(!uniform);
     }
 
     #[test]
@@ -1306,4 +2795,758 @@ mod tests {
         let decoded = decode(&encoded).unwrap();
         assert!(decoded["items"].is_array());
     }
+
+    // ========================================================================
+    // SerializeOptions tests
+    // ========================================================================
+
+    #[test]
+    fn test_encode_with_custom_indent_width() {
+        let data = json!({"outer": {"inner": "value"}});
+        let options = SerializeOptions {
+            indent: Some(4),
+            sort_keys: false,
+        };
+        let encoded = encode_with_options(&data, false, &options).unwrap();
+        assert!(encoded.contains("    inner: value"));
+    }
+
+    // ========================================================================
+    // Structured decode error tests
+    // ========================================================================
+
+    #[test]
+    fn test_decode_invalid_header_reports_span() {
+        let err = decode("not a header").unwrap_err();
+        match err {
+            AgonError::ColumnsDecodeError {
+                span, line_text, ..
+            } => {
+                assert_eq!(span, crate::error::Span { line: 1, col: 0 });
+                assert_eq!(line_text, "not a header");
+            }
+            other => panic!("expected ColumnsDecodeError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_malformed_array_bracket_reports_span() {
+        // "items[3" never closes its bracket.
+        let payload = "@AGON columns\n\nitems[3";
+        let err = decode(payload).unwrap_err();
+        match err {
+            AgonError::ColumnsDecodeError { span, reason, .. } => {
+                assert_eq!(span.line, 3);
+                assert_eq!(reason, "expected `]` after array count");
+            }
+            other => panic!("expected ColumnsDecodeError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_unterminated_quote_in_column_cell_reports_span() {
+        let payload = "@AGON columns\n\n[1]\n└ name: \"Alice";
+        let err = decode(payload).unwrap_err();
+        match err {
+            AgonError::ColumnsDecodeError {
+                span,
+                reason,
+                line_text,
+            } => {
+                assert_eq!(span.line, 4);
+                assert_eq!(reason, "unterminated quote in column cell");
+                assert_eq!(line_text, "└ name: \"Alice");
+            }
+            other => panic!("expected ColumnsDecodeError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_unterminated_quote_in_continuation_batch_reports_its_own_line() {
+        // A field split across `encode_streaming` batches: the unterminated
+        // quote sits in the *second* `│` continuation segment, not on the
+        // opening `└` line. The reported span must point at the physical
+        // line the quote actually opened on.
+        let payload = "@AGON columns\n\n[3]\n├ id: 1\t2\t3\n└ note: a\n│ b\n│ \"broken";
+        let err = decode(payload).unwrap_err();
+        match err {
+            AgonError::ColumnsDecodeError {
+                span, line_text, ..
+            } => {
+                assert_eq!(span.line, 7);
+                assert_eq!(line_text, "│ \"broken");
+                assert_eq!(span.col, 2);
+            }
+            other => panic!("expected ColumnsDecodeError, got {:?}", other),
+        }
+
+        let (_value, diagnostics) = decode_lenient(payload);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].span.line, 7);
+        assert_eq!(diagnostics[0].span.col, 2);
+    }
+
+    #[test]
+    fn test_decode_bracket_in_plain_string_value_still_decodes() {
+        // A literal, non-numeric bracket in a plain value shouldn't be
+        // mistaken for a malformed array opener.
+        let payload = "@AGON columns\n\nnote: see [source]";
+        let decoded = decode(payload).unwrap();
+        assert_eq!(decoded["note"], "see [source]");
+    }
+
+    #[test]
+    fn test_encode_with_sort_keys_orders_columns() {
+        let data = json!([
+            {"zeta": 1, "alpha": 2}
+        ]);
+        let options = SerializeOptions {
+            indent: None,
+            sort_keys: true,
+        };
+        let encoded = encode_with_options(&data, false, &options).unwrap();
+        let alpha_pos = encoded.find("alpha").unwrap();
+        let zeta_pos = encoded.find("zeta").unwrap();
+        assert!(alpha_pos < zeta_pos);
+    }
+
+    // ========================================================================
+    // chunk7-2: typed column schema
+    // ========================================================================
+
+    #[test]
+    fn test_encode_tags_uniform_str_column() {
+        let data = json!([{"id": "007"}, {"id": "042"}]);
+        let encoded = encode(&data, false).unwrap();
+        assert!(
+            encoded.contains("id:str:"),
+            "expected an inline str tag, got: {}",
+            encoded
+        );
+        // Unambiguous because of the tag, so no quotes are needed.
+        assert!(encoded.contains("007\t042"));
+    }
+
+    #[test]
+    fn test_roundtrip_str_tagged_numeric_looking_id() {
+        let data = json!([{"id": "007"}, {"id": "042"}]);
+        let encoded = encode(&data, false).unwrap();
+        let decoded = decode_columnar_payload(&encoded);
+        assert_eq!(decoded[0]["id"], "007");
+        assert_eq!(decoded[1]["id"], "042");
+    }
+
+    #[test]
+    fn test_encode_mixed_type_column_is_untagged() {
+        let data = json!([{"v": 1}, {"v": "two"}]);
+        let encoded = encode(&data, false).unwrap();
+        assert!(
+            encoded.contains("├ v: ") || encoded.contains("└ v: "),
+            "mixed-type column should not carry a type tag, got: {}",
+            encoded
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_explicit_type_tags() {
+        let data = json!([
+            {"n": 1, "f": 1.5, "b": true, "z": Value::Null},
+            {"n": 2, "f": 2.5, "b": false, "z": Value::Null}
+        ]);
+        let encoded = encode(&data, false).unwrap();
+        for tag in ["n:int", "f:float", "b:bool", "z:null"] {
+            assert!(encoded.contains(tag), "missing tag {} in: {}", tag, encoded);
+        }
+        let decoded = decode_columnar_payload(&encoded);
+        assert_eq!(decoded[0]["n"], 1);
+        assert_eq!(decoded[0]["f"], 1.5);
+        assert_eq!(decoded[0]["b"], true);
+        assert!(decoded[0]["z"].is_null());
+    }
+
+    #[test]
+    fn test_parse_column_header_legacy_untagged() {
+        assert_eq!(
+            parse_column_header("name: Alice\tBob"),
+            Some(("name", None, "Alice\tBob"))
+        );
+    }
+
+    #[test]
+    fn test_parse_column_header_with_type_tag() {
+        assert_eq!(
+            parse_column_header("id:str: 007\t042"),
+            Some(("id", Some("str"), "007\t042"))
+        );
+    }
+
+    #[test]
+    fn test_parse_string_cell_keeps_numeric_looking_value_unquoted() {
+        assert_eq!(parse_string_cell("007"), Value::String("007".to_string()));
+    }
+
+    #[test]
+    fn test_parse_string_cell_unquotes_when_quoted() {
+        assert_eq!(
+            parse_string_cell("\"has\\ttab\""),
+            Value::String("has\ttab".to_string())
+        );
+    }
+
+    /// Decode a columnar payload encoded without a header, for tests that
+    /// only care about the body.
+    fn decode_columnar_payload(encoded: &str) -> Value {
+        decode(&format!("@AGON columns\n\n{}", encoded)).unwrap()
+    }
+
+    // ========================================================================
+    // chunk7-3: column projection
+    // ========================================================================
+
+    #[test]
+    fn test_decode_projected_keeps_only_requested_fields() {
+        let payload = "@AGON columns\n\n[2]\n├ id: 1\t2\n├ name: Alice\tBob\n└ email: a@b.com\tc@d.com";
+        let decoded = decode_projected(payload, &["id", "name"]).unwrap();
+        let arr = decoded.as_array().unwrap();
+        assert_eq!(arr[0], json!({"id": 1, "name": "Alice"}));
+        assert_eq!(arr[1], json!({"id": 2, "name": "Bob"}));
+        assert!(arr[0].get("email").is_none());
+    }
+
+    #[test]
+    fn test_decode_projected_empty_fields_means_all_columns() {
+        let payload = "@AGON columns\n\n[2]\n├ id: 1\t2\n└ name: Alice\tBob";
+        let decoded = decode_projected(payload, &[]).unwrap();
+        assert_eq!(decoded, decode(payload).unwrap());
+    }
+
+    #[test]
+    fn test_decode_projected_skips_unterminated_quote_in_unwanted_column() {
+        // The "name" column has a malformed (unterminated) quote, but since
+        // it's not requested, it should never be parsed -- only "id" is.
+        let payload = "@AGON columns\n\n[1]\n├ id: 1\n└ name: \"Alice";
+        let decoded = decode_projected(payload, &["id"]).unwrap();
+        assert_eq!(decoded.as_array().unwrap()[0], json!({"id": 1}));
+    }
+
+    #[test]
+    fn test_decode_projected_named_array_in_object() {
+        let payload =
+            "@AGON columns\n\nusers[2]\n├ id: 1\t2\n└ name: Alice\tBob";
+        let decoded = decode_projected(payload, &["id"]).unwrap();
+        let users = decoded["users"].as_array().unwrap();
+        assert_eq!(users[0], json!({"id": 1}));
+        assert!(users[0].get("name").is_none());
+    }
+
+    #[test]
+    fn test_decode_projected_unrequested_field_is_absent_from_every_row() {
+        let payload = "@AGON columns\n\n[2]\n├ id: 1\t2\n└ name: Alice\tBob";
+        let decoded = decode_projected(payload, &["name"]).unwrap();
+        let arr = decoded.as_array().unwrap();
+        for row in arr {
+            assert!(row.get("id").is_none());
+        }
+    }
+
+    // ========================================================================
+    // chunk7-4: nested-object columns via dotted-path flattening
+    // ========================================================================
+
+    #[test]
+    fn test_encode_nested_object_array_uses_dotted_columns() {
+        let data = json!([
+            {"name": "Alice", "address": {"city": "NYC", "zip": "10001"}},
+            {"name": "Bob", "address": {"city": "LA", "zip": "90001"}}
+        ]);
+        let encoded = encode(&data, false).unwrap();
+        assert!(
+            encoded.contains("address.city") && encoded.contains("address.zip"),
+            "expected dotted address columns, got: {}",
+            encoded
+        );
+        // Nesting turned it columnar, not the verbose list-item format.
+        assert!(!encoded.contains("- name:"));
+    }
+
+    #[test]
+    fn test_roundtrip_nested_object_array() {
+        let data = json!([
+            {"name": "Alice", "address": {"city": "NYC", "zip": "10001"}},
+            {"name": "Bob", "address": {"city": "LA", "zip": "90001"}}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    // chunk8-4: nested dotted columns already cover mismatched element
+    // shapes the same way flat columns cover a field some rows omit -- a
+    // row missing part of a nested path just gets empty cells for those
+    // dotted columns, rather than the whole array falling back to the
+    // verbose list-item form.
+
+    #[test]
+    fn test_is_uniform_array_nested_mismatched_key_sets_still_uniform() {
+        let arr = vec![
+            json!({"user": {"id": 1, "name": "Alice"}, "score": 10}),
+            json!({"user": {"id": 2}, "score": 20}),
+        ];
+        let (uniform, fields, _) = is_uniform_array(&arr);
+        assert!(uniform);
+        assert!(fields.contains(&"user.id".to_string()));
+        assert!(fields.contains(&"user.name".to_string()));
+        assert!(fields.contains(&"score".to_string()));
+    }
+
+    #[test]
+    fn test_roundtrip_nested_object_array_with_missing_nested_field() {
+        let data = json!([
+            {"user": {"id": 1, "name": "Alice"}, "score": 10},
+            {"user": {"id": 2}, "score": 20}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+        assert!(encoded.contains("user.id") && encoded.contains("user.name"));
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_roundtrip_deeply_nested_object_array() {
+        let data = json!([
+            {"a": {"b": {"c": 1}}},
+            {"a": {"b": {"c": 2}}}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_roundtrip_ragged_nested_object_array() {
+        // "zip" is present for Alice but absent for Bob -- still honors
+        // present-vs-missing cell semantics under dotted flattening.
+        let data = json!([
+            {"name": "Alice", "address": {"city": "NYC", "zip": "10001"}},
+            {"name": "Bob", "address": {"city": "LA"}}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_nested_array_field_falls_back_to_list_item_format() {
+        // A leaf that's an array (not a scalar) can't flatten into a column.
+        let data = json!([
+            {"name": "Alice", "tags": ["a", "b"]},
+            {"name": "Bob", "tags": ["c"]}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_escape_dotted_key_and_split_dotted_path_roundtrip() {
+        let escaped = escape_dotted_key("a.b\\c");
+        assert_eq!(escaped, "a\\.b\\\\c");
+        assert_eq!(split_dotted_path(&escaped), vec!["a.b\\c".to_string()]);
+    }
+
+    #[test]
+    fn test_roundtrip_field_name_with_literal_dot() {
+        // A real key containing "." must survive flattening/rebuilding
+        // without being mistaken for a nesting separator.
+        let data = json!([
+            {"a.b": 1, "other": {"c": 2}},
+            {"a.b": 3, "other": {"c": 4}}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_insert_dotted_builds_nested_objects() {
+        let mut obj = Map::new();
+        insert_dotted(&mut obj, "address.city", json!("NYC"));
+        insert_dotted(&mut obj, "address.zip", json!("10001"));
+        assert_eq!(
+            Value::Object(obj),
+            json!({"address": {"city": "NYC", "zip": "10001"}})
+        );
+    }
+
+    #[test]
+    fn test_insert_dotted_overwrites_non_object_collision() {
+        // A flat "user" column inserted before a dotted "user.nested" column
+        // in the same block -- the flat value must be replaced with an
+        // object rather than panicking.
+        let mut obj = Map::new();
+        insert_dotted(&mut obj, "user", json!(1));
+        insert_dotted(&mut obj, "user.nested", json!(2));
+        assert_eq!(Value::Object(obj), json!({"user": {"nested": 2}}));
+    }
+
+    // ========================================================================
+    // chunk7-5: lenient decode mode
+    // ========================================================================
+
+    #[test]
+    fn test_decode_lenient_well_formed_payload_has_no_diagnostics() {
+        let payload = "@AGON columns\n\nname: Alice\nage: 30";
+        let (value, diagnostics) = decode_lenient(payload);
+        assert_eq!(value["name"], "Alice");
+        assert_eq!(value["age"], 30);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_decode_lenient_never_errors_on_empty_payload() {
+        let (value, diagnostics) = decode_lenient("");
+        assert!(value.is_null());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].reason, "empty payload");
+    }
+
+    #[test]
+    fn test_decode_lenient_missing_header_recovers() {
+        let (value, diagnostics) = decode_lenient("name: Alice");
+        assert_eq!(value["name"], "Alice");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].reason, "expected `@AGON columns` header");
+    }
+
+    #[test]
+    fn test_decode_lenient_malformed_bracket_falls_back_to_key_value() {
+        // "items[3abc]" never parses as a valid array count -- strict
+        // decode() errors here (see
+        // test_decode_malformed_array_bracket_reports_span for the "[3"
+        // unclosed-bracket variant of the same failure).
+        let payload = "@AGON columns\n\nitems[3abc]: foo";
+        let (value, diagnostics) = decode_lenient(payload);
+        assert_eq!(value["items[3abc]"], "foo");
+        assert!(!diagnostics.is_empty());
+        assert_eq!(diagnostics[0].reason, "expected `]` after array count");
+        assert_eq!(diagnostics[0].recovered_as, "treated as plain key: value");
+    }
+
+    #[test]
+    fn test_decode_lenient_pads_short_column() {
+        // "name" only has one cell for a declared count of 2.
+        let payload = "@AGON columns\n\n[2]\n├ id: 1\t2\n└ name: Alice";
+        let (value, diagnostics) = decode_lenient(payload);
+        let arr = value.as_array().unwrap();
+        assert_eq!(arr[0]["name"], "Alice");
+        assert!(arr[1].get("name").is_none());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].reason,
+            "column `name` has 1 cell(s), expected 2"
+        );
+        assert_eq!(diagnostics[0].recovered_as, "padded 1 missing cell(s)");
+    }
+
+    #[test]
+    fn test_decode_lenient_truncates_long_column() {
+        // "name" has three cells for a declared count of 2.
+        let payload = "@AGON columns\n\n[2]\n├ id: 1\t2\n└ name: Alice\tBob\tCarol";
+        let (value, diagnostics) = decode_lenient(payload);
+        let arr = value.as_array().unwrap();
+        assert_eq!(arr.len(), 2);
+        assert_eq!(arr[0]["name"], "Alice");
+        assert_eq!(arr[1]["name"], "Bob");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].reason,
+            "column `name` has 3 cell(s), expected 2"
+        );
+        assert_eq!(diagnostics[0].recovered_as, "truncated 1 extra cell(s)");
+    }
+
+    #[test]
+    fn test_decode_lenient_unterminated_quote_recovers() {
+        let payload = "@AGON columns\n\n[1]\n└ name: \"Alice";
+        let (value, diagnostics) = decode_lenient(payload);
+        // Strict decode() errors on this payload (see
+        // test_decode_unterminated_quote_in_column_cell_reports_span).
+        assert!(value.is_array());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].reason, "unterminated quote in column cell");
+    }
+
+    #[test]
+    fn test_decode_lenient_collects_multiple_diagnostics_in_one_pass() {
+        // A ragged column inside the named array, followed by a malformed
+        // bracket in the same object -- both problems are recorded, not
+        // just the first one encountered.
+        let payload = "@AGON columns\n\nusers[2]\n├ id: 1\n└ name: Alice\tBob\nbroken[5";
+        let (value, diagnostics) = decode_lenient(payload);
+        let users = value["users"].as_array().unwrap();
+        assert_eq!(users[0]["id"], 1);
+        assert!(users[1].get("id").is_none());
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.reason.contains("column `id`")));
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.reason == "expected `]` after array count"));
+    }
+
+    #[test]
+    fn test_decode_lenient_survives_prefix_colliding_dotted_columns() {
+        // A flat "user" column and a dotted "user.nested" column in the
+        // same block -- insert_dotted (see chunk7-4's fix) must overwrite
+        // rather than panic, so lenient mode's "never panics" invariant
+        // actually holds for this shape.
+        let payload = "@AGON columns\n\n[1]\n├ user: 1\n└ user.nested: 2";
+        let (value, _diagnostics) = decode_lenient(payload);
+        assert_eq!(value[0]["user"]["nested"], 2);
+    }
+
+    #[test]
+    fn test_decode_strict_survives_prefix_colliding_dotted_columns() {
+        let payload = "@AGON columns\n\n[1]\n├ user: 1\n└ user.nested: 2";
+        let value = decode(payload).unwrap();
+        assert_eq!(value[0]["user"]["nested"], 2);
+    }
+
+    #[test]
+    fn test_decode_lenient_matches_strict_decode_on_well_formed_input() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2, "name": "Bob"}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+        let (lenient_value, diagnostics) = decode_lenient(&encoded);
+        assert_eq!(lenient_value, decode(&encoded).unwrap());
+        assert!(diagnostics.is_empty());
+    }
+
+    // chunk8-1: serde Serializer/Deserializer front-end
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    enum Shape {
+        Unit,
+        Newtype(i32),
+        Tuple(i32, i32),
+        Struct { x: i32, y: i32 },
+    }
+
+    #[test]
+    fn test_to_string_primitive() {
+        assert_eq!(to_string(&42i32).unwrap(), "@AGON columns\n\n42");
+        assert_eq!(to_string(&"hello").unwrap(), "@AGON columns\n\nhello");
+        assert_eq!(to_string(&true).unwrap(), "@AGON columns\n\ntrue");
+    }
+
+    #[test]
+    fn test_to_string_uniform_vec_of_structs_is_columnar() {
+        let points = vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }];
+        let encoded = to_string(&points).unwrap();
+        assert!(encoded.contains("@AGON columns"));
+        assert!(encoded.contains("├ x:"));
+        assert!(encoded.contains("└ y:"));
+    }
+
+    #[test]
+    fn test_to_string_option_none_is_null() {
+        #[derive(serde::Serialize)]
+        struct Opt {
+            value: Option<i32>,
+        }
+        let encoded = to_string(&Opt { value: None }).unwrap();
+        assert!(encoded.contains("value: null"));
+    }
+
+    #[test]
+    fn test_to_string_unit_variant_is_string() {
+        let encoded = to_string(&Shape::Unit).unwrap();
+        assert_eq!(encoded, "@AGON columns\n\nUnit");
+    }
+
+    #[test]
+    fn test_to_string_newtype_variant() {
+        let encoded = to_string(&Shape::Newtype(7)).unwrap();
+        assert!(encoded.contains("Newtype: 7"));
+    }
+
+    #[test]
+    fn test_to_string_struct_variant() {
+        let encoded = to_string(&Shape::Struct { x: 1, y: 2 }).unwrap();
+        assert!(encoded.contains("Struct:"));
+        assert!(encoded.contains("x: 1"));
+        assert!(encoded.contains("y: 2"));
+    }
+
+    #[test]
+    fn test_from_str_struct_round_trip() {
+        let point = Point { x: 1, y: 2 };
+        let encoded = to_string(&point).unwrap();
+        let decoded: Point = from_str(&encoded).unwrap();
+        assert_eq!(point, decoded);
+    }
+
+    #[test]
+    fn test_from_str_vec_of_structs_round_trip() {
+        let points = vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }];
+        let encoded = to_string(&points).unwrap();
+        let decoded: Vec<Point> = from_str(&encoded).unwrap();
+        assert_eq!(points, decoded);
+    }
+
+    // ========================================================================
+    // RowReader (streaming rows) tests
+    // ========================================================================
+
+    #[test]
+    fn test_row_reader_streams_columnar_rows_one_at_a_time() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2, "name": "Bob"}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+        let reader = RowReader::new(encoded.as_bytes());
+        let rows: Vec<Value> = reader.map(|r| r.unwrap()).collect();
+        assert_eq!(Value::Array(rows), data);
+    }
+
+    #[test]
+    fn test_row_reader_streams_named_columnar_array() {
+        let data = json!({"users": [{"id": 1}, {"id": 2}, {"id": 3}]});
+        let encoded = encode(&data, true).unwrap();
+        let reader = RowReader::new(encoded.as_bytes());
+        let rows: Vec<Value> = reader.map(|r| r.unwrap()).collect();
+        assert_eq!(rows, vec![json!({"id": 1}), json!({"id": 2}), json!({"id": 3})]);
+    }
+
+    #[test]
+    fn test_row_reader_streams_nested_dotted_columns() {
+        let data = json!([
+            {"user": {"id": 1, "name": "Alice"}, "score": 10},
+            {"user": {"id": 2}, "score": 20}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+        let reader = RowReader::new(encoded.as_bytes());
+        let rows: Vec<Value> = reader.map(|r| r.unwrap()).collect();
+        assert_eq!(Value::Array(rows), data);
+    }
+
+    #[test]
+    fn test_row_reader_empty_columnar_array_yields_no_rows() {
+        let data = json!([]);
+        let encoded = encode(&data, true).unwrap();
+        let reader = RowReader::new(encoded.as_bytes());
+        let rows: Vec<Value> = reader.map(|r| r.unwrap()).collect();
+        assert!(rows.is_empty());
+    }
+
+    #[test]
+    fn test_row_reader_falls_back_to_full_decode_for_plain_object() {
+        let data = json!({"name": "test", "age": 30});
+        let encoded = encode(&data, true).unwrap();
+        let reader = RowReader::new(encoded.as_bytes());
+        let items: Vec<Value> = reader.map(|r| r.unwrap()).collect();
+        assert_eq!(items, vec![data]);
+    }
+
+    #[test]
+    fn test_row_reader_falls_back_when_named_array_shares_top_level() {
+        let data = json!({"users": [{"id": 1}, {"id": 2}], "total": 2});
+        let encoded = encode(&data, true).unwrap();
+        let reader = RowReader::new(encoded.as_bytes());
+        let items: Vec<Value> = reader.map(|r| r.unwrap()).collect();
+        assert_eq!(items, vec![data]);
+    }
+
+    #[test]
+    fn test_row_reader_falls_back_for_list_item_array() {
+        let data = json!([[1, 2], [3, 4]]);
+        let encoded = encode(&data, true).unwrap();
+        let reader = RowReader::new(encoded.as_bytes());
+        let items: Vec<Value> = reader.map(|r| r.unwrap()).collect();
+        assert_eq!(items, vec![data]);
+    }
+
+    // ========================================================================
+    // encode_streaming tests
+    // ========================================================================
+
+    #[test]
+    fn test_encode_streaming_round_trips_through_decode() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2, "name": "Bob"},
+            {"id": 3, "name": "Carol"},
+            {"id": 4, "name": "Dave"},
+            {"id": 5, "name": "Eve"}
+        ]);
+
+        for batch_size in [1, 2, 3, 100] {
+            let bytes = encode_streaming(Vec::new(), &data, true, batch_size).unwrap();
+            let text = String::from_utf8(bytes).unwrap();
+            let decoded = decode(&text).unwrap();
+            assert_eq!(decoded, data, "batch_size = {}", batch_size);
+        }
+    }
+
+    #[test]
+    fn test_encode_streaming_emits_continuation_markers_for_multi_batch_fields() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2, "name": "Bob"},
+            {"id": 3, "name": "Carol"}
+        ]);
+        let bytes = encode_streaming(Vec::new(), &data, true, 1).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.contains('│'), "expected a continuation segment:\n{}", text);
+    }
+
+    #[test]
+    fn test_encode_streaming_matches_encode_when_batch_size_covers_whole_array() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2, "name": "Bob"}
+        ]);
+        let bytes = encode_streaming(Vec::new(), &data, true, 100).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert_eq!(text, encode(&data, true).unwrap());
+    }
+
+    #[test]
+    fn test_encode_streaming_non_uniform_value_falls_back_to_encode() {
+        let data = json!({"name": "test"});
+        let bytes = encode_streaming(Vec::new(), &data, false, 10).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert_eq!(text, encode(&data, false).unwrap());
+    }
+
+    #[test]
+    fn test_encode_streaming_empty_array_falls_back_to_encode() {
+        let data = json!([]);
+        let bytes = encode_streaming(Vec::new(), &data, true, 10).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert_eq!(text, encode(&data, true).unwrap());
+    }
+
+    #[test]
+    fn test_encode_streaming_nested_dotted_columns_round_trip() {
+        let data = json!([
+            {"id": 1, "address": {"city": "NYC", "zip": "10001"}},
+            {"id": 2, "address": {"city": "LA", "zip": "90001"}},
+            {"id": 3, "address": {"city": "SF", "zip": "94101"}}
+        ]);
+        let bytes = encode_streaming(Vec::new(), &data, true, 2).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        let decoded = decode(&text).unwrap();
+        assert_eq!(decoded, data);
+    }
 }
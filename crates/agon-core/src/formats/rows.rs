@@ -6,16 +6,66 @@
 //!     @AGON rows
 //!     @D=<delimiter>  # optional, default: \t
 //!     <data>
+//!
+//! ## Column order
+//!
+//! [`is_uniform_array`] collects a tabular block's `{col1\tcol2}` header in
+//! first-seen order (a plain `Vec`, walked fresh for every call), and
+//! [`decode`] inserts row fields into a `serde_json::Map` in header order, so
+//! nothing in this module itself reorders columns or object keys. The one
+//! remaining dependency is `serde_json::Map`'s own representation: without
+//! its `preserve_order` Cargo feature enabled (which swaps the map's
+//! internals from a sorted `BTreeMap` to an insertion-ordered map), iterating
+//! a decoded object's keys yields them sorted, not in their original
+//! sequence. `agon-core`'s manifest needs to forward that feature (`serde_json
+//! = { version = "...", features = ["preserve_order"] }`, optionally behind
+//! this crate's own `preserve_order` feature) for `decode(encode(x))` to
+//! guarantee the original column order end to end.
+//!
+//! ## Arbitrary precision
+//!
+//! [`parse_primitive`] tries `i64` first, falls back to `u64` so unsigned
+//! IDs up to `u64::MAX` (e.g. `18446744073709551615`) round-trip exactly,
+//! and for an integer lexeme wider than that -- a true bignum, in either
+//! direction -- keeps every digit via `serde_json::Number::from_string_unchecked`
+//! instead of decaying into a plain string, the same way `columns.rs`'s
+//! `parse_primitive` and `types::py_to_json` already do.
+//!
+//! ## Structured errors
+//!
+//! [`decode`] reports a missing header, an empty `@D=` delimiter, a tabular
+//! row whose field count doesn't match its header, and an unterminated
+//! quoted cell as [`crate::error::AgonError::RowDecodeError`] -- a 1-based
+//! line, a byte column, a [`crate::error::RowParseErrorKind`], and a
+//! message -- instead of an opaque [`crate::error::AgonError::DecodingError`]
+//! string. [`decode_collecting`]'s own per-row recovery still reports
+//! through the older [`crate::error::AgonError::ParseError`] shape, since
+//! changing an already-collecting error's type isn't this change's concern.
+//!
+//! ## Batched writes
+//!
+//! [`encode_streaming`] writes a uniform array's `[N]{fields}` header
+//! immediately (the row count is already known from `data` being fully in
+//! memory) and then formats and appends fixed-size batches of rows one at a
+//! time via rayon, so peak memory is bounded by one batch's formatted text
+//! rather than [`to_writer`]/[`RowWriter`]'s whole-array buffer.
 
+use rayon::prelude::*;
 use regex::Regex;
+use serde::ser::{
+    Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant,
+};
 use serde_json::{Map, Value};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::sync::LazyLock;
 
-use crate::error::{AgonError, Result};
+use crate::error::{AgonError, Result, RowParseErrorKind};
+use crate::options::SerializeOptions;
+use crate::types::bytes_to_tagged_json;
 
 const HEADER: &str = "@AGON rows";
 const DEFAULT_DELIMITER: &str = "\t";
-const INDENT: &str = "  ";
 
 // Regex patterns for parsing
 static TABULAR_HEADER_RE: LazyLock<Regex> =
@@ -29,6 +79,15 @@ static NUMBER_RE: LazyLock<Regex> =
 
 /// Encode data to AGONRows format
 pub fn encode(data: &Value, include_header: bool) -> Result<String> {
+    encode_with_options(data, include_header, &SerializeOptions::default())
+}
+
+/// Encode data to AGONRows format with explicit serialize options
+pub fn encode_with_options(
+    data: &Value,
+    include_header: bool,
+    options: &SerializeOptions,
+) -> Result<String> {
     let mut lines = Vec::new();
     let delimiter = DEFAULT_DELIMITER;
 
@@ -37,11 +96,264 @@ pub fn encode(data: &Value, include_header: bool) -> Result<String> {
         lines.push(String::new());
     }
 
-    encode_value(data, &mut lines, 0, delimiter, None);
+    encode_value(data, &mut lines, 0, delimiter, None, options);
+
+    Ok(lines.join("\n"))
+}
+
+/// Delimiter candidates [`choose_delimiter`] tries in order, preferring
+/// [`DEFAULT_DELIMITER`] (a tab) so the common case still needs no `@D=`
+/// line at all.
+const DELIMITER_CANDIDATES: [&str; 4] = ["\t", ",", "|", ";"];
+
+/// Pick a delimiter for `data` that appears in none of its string values,
+/// so no cell needs quoting just to keep the delimiter out of its text.
+/// Tries [`DELIMITER_CANDIDATES`] in order and falls back to
+/// [`DEFAULT_DELIMITER`] if every candidate collides with some string --
+/// that delimiter's cells will just have to be quoted instead.
+fn choose_delimiter(data: &Value) -> &'static str {
+    let mut strings = Vec::new();
+    collect_strings(data, &mut strings);
+
+    for candidate in DELIMITER_CANDIDATES {
+        if strings.iter().all(|s| !s.contains(candidate)) {
+            return candidate;
+        }
+    }
+    DEFAULT_DELIMITER
+}
+
+/// Collect every string value reachable from `val`, the pool
+/// [`choose_delimiter`] scans for delimiter collisions.
+fn collect_strings<'a>(val: &'a Value, out: &mut Vec<&'a str>) {
+    match val {
+        Value::String(s) => out.push(s),
+        Value::Array(arr) => {
+            for item in arr {
+                collect_strings(item, out);
+            }
+        }
+        Value::Object(obj) => {
+            for v in obj.values() {
+                collect_strings(v, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Encode `data` to AGONRows the way [`encode`] does, except the delimiter
+/// is chosen automatically by [`choose_delimiter`] instead of always being
+/// [`DEFAULT_DELIMITER`]. The `@D=` line is only emitted when the chosen
+/// delimiter differs from the default, so data that's already tab-safe
+/// encodes identically to plain [`encode`].
+pub fn encode_auto_delimiter(data: &Value, include_header: bool) -> Result<String> {
+    encode_auto_delimiter_with_options(data, include_header, &SerializeOptions::default())
+}
+
+/// [`encode_auto_delimiter`] with explicit serialize options.
+pub fn encode_auto_delimiter_with_options(
+    data: &Value,
+    include_header: bool,
+    options: &SerializeOptions,
+) -> Result<String> {
+    let delimiter = choose_delimiter(data);
+    let mut lines = Vec::new();
+
+    if include_header {
+        lines.push(HEADER.to_string());
+        if delimiter != DEFAULT_DELIMITER {
+            lines.push(format!("@D={}", delimiter));
+        }
+        lines.push(String::new());
+    }
+
+    encode_value(data, &mut lines, 0, delimiter, None, options);
 
     Ok(lines.join("\n"))
 }
 
+/// Decode `payload` and re-encode it to a single canonical form: stable
+/// two-space indentation, the encoder's natural (first-seen) column
+/// ordering, and a delimiter chosen by [`choose_delimiter`] instead of
+/// whatever the original payload happened to use. Normalizing is
+/// idempotent -- re-normalizing already-canonical text reproduces it
+/// byte-for-byte, since both passes derive their formatting purely from
+/// the decoded [`Value`] -- the same property `dioxus-autofmt` holds for
+/// RSX, so a team can run this to keep AGONRows diffs clean regardless of
+/// how a file was originally hand-edited.
+pub fn normalize(payload: &str) -> Result<String> {
+    let data = decode(payload)?;
+    let options = SerializeOptions {
+        indent: Some(2),
+        sort_keys: false,
+    };
+    encode_auto_delimiter_with_options(&data, true, &options)
+}
+
+/// Encode `data` directly to a [`Write`]r instead of building up a
+/// [`String`] through [`encode`] first, the way `serde_json::to_writer` sits
+/// alongside `serde_json::to_string`. When `data` is a uniform array of
+/// objects, this drives a [`RowWriter`] row by row -- the same
+/// incremental-friendly path [`RowWriter`] already gives callers that build
+/// up `data` one row at a time. Any other shape (a bare object, a nested
+/// document) falls back to [`encode`]'s full in-memory string, written out
+/// in one `write_all`, since only the tabular shape has anywhere to stream
+/// to -- the same scope limit [`Parser`] and [`RowReader`] already draw.
+pub fn to_writer<W: Write>(writer: W, data: &Value, include_header: bool) -> Result<W> {
+    if let Value::Array(arr) = data {
+        let (is_uniform, fields) = is_uniform_array(arr);
+        if is_uniform {
+            let mut row_writer = RowWriter::new(writer, fields.clone(), include_header);
+            for row in arr {
+                let Value::Object(obj) = row else {
+                    unreachable!("is_uniform_array guarantees every element is an object");
+                };
+                let values: Vec<Value> = fields
+                    .iter()
+                    .map(|f| obj.get(f).cloned().unwrap_or(Value::Null))
+                    .collect();
+                row_writer.push_row(&values);
+            }
+            return row_writer.finish();
+        }
+    }
+
+    let mut writer = writer;
+    let encoded = encode(data, include_header)?;
+    writer
+        .write_all(encoded.as_bytes())
+        .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+    Ok(writer)
+}
+
+/// Encode a uniform array to a [`Write`]r in fixed-size batches instead of
+/// through one [`RowWriter`] pass, bounding peak memory to `batch_size` rows'
+/// formatted text rather than the whole array's. [`RowWriter`] still has to
+/// buffer every formatted row until [`RowWriter::finish`] because it's built
+/// to accept rows one at a time from a caller who may not know the final
+/// count up front; here `data` is already a fully in-memory `&Value`, so its
+/// row count is known immediately and the `[N]{fields}` header can be
+/// written before a single row is formatted. Each batch is then formatted in
+/// parallel with rayon's `par_iter` and appended -- a straightforward append,
+/// since a row-per-line format has nowhere that needs a continuation marker
+/// the way [`crate::formats::columns`]'s per-field lines do. Any shape
+/// [`is_uniform_array`] doesn't recognize falls back to one [`encode`] call
+/// written in a single `write_all`, the same fallback [`to_writer`] takes.
+pub fn encode_streaming<W: Write>(
+    writer: W,
+    data: &Value,
+    include_header: bool,
+    batch_size: usize,
+) -> Result<W> {
+    let mut writer = writer;
+
+    let Value::Array(arr) = data else {
+        let encoded = encode(data, include_header)?;
+        writer
+            .write_all(encoded.as_bytes())
+            .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+        return Ok(writer);
+    };
+
+    let (is_uniform, fields) = is_uniform_array(arr);
+    if !is_uniform {
+        let encoded = encode(data, include_header)?;
+        writer
+            .write_all(encoded.as_bytes())
+            .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+        return Ok(writer);
+    }
+
+    let mut header_lines = Vec::new();
+    if include_header {
+        header_lines.push(HEADER.to_string());
+    }
+    header_lines.push(format!(
+        "[{}]{{{}}}",
+        arr.len(),
+        fields.join(DEFAULT_DELIMITER)
+    ));
+    writer
+        .write_all(header_lines.join("\n").as_bytes())
+        .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+
+    let batch_size = batch_size.max(1);
+    for chunk in arr.chunks(batch_size) {
+        let formatted: Vec<String> = chunk
+            .par_iter()
+            .map(|row| {
+                let Value::Object(obj) = row else {
+                    unreachable!("is_uniform_array guarantees every element is an object");
+                };
+                fields
+                    .iter()
+                    .map(|f| match obj.get(f) {
+                        Some(v) => encode_primitive(v, DEFAULT_DELIMITER),
+                        None => "null".to_string(),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(DEFAULT_DELIMITER)
+            })
+            .collect();
+
+        writer
+            .write_all(b"\n")
+            .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+        writer
+            .write_all(formatted.join("\n").as_bytes())
+            .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+    }
+
+    Ok(writer)
+}
+
+/// Build an [`AgonError::RowDecodeError`] from a 0-based line index (as
+/// tracked internally while walking `lines`), converting it to the 1-based
+/// line number the error type documents.
+fn row_decode_error(
+    idx: usize,
+    column: usize,
+    kind: RowParseErrorKind,
+    message: impl Into<String>,
+) -> AgonError {
+    AgonError::RowDecodeError {
+        line: idx + 1,
+        column,
+        kind,
+        message: message.into(),
+    }
+}
+
+/// The byte offset of a `"` that's opened but never closed before `s` ends,
+/// if any -- `s` is a single logical line, so an unterminated quote here
+/// can't be closed by a continuation line the way a real multi-line string
+/// might be in a richer grammar.
+fn find_unterminated_quote(s: &str) -> Option<usize> {
+    let mut in_quote = false;
+    let mut escape_next = false;
+    let mut opened_at = 0;
+
+    for (i, c) in s.char_indices() {
+        if escape_next {
+            escape_next = false;
+            continue;
+        }
+        if c == '\\' && in_quote {
+            escape_next = true;
+            continue;
+        }
+        if c == '"' {
+            if !in_quote {
+                opened_at = i;
+            }
+            in_quote = !in_quote;
+        }
+    }
+
+    in_quote.then_some(opened_at)
+}
+
 /// Decode AGONRows payload
 pub fn decode(payload: &str) -> Result<Value> {
     let lines: Vec<&str> = payload.lines().collect();
@@ -54,16 +366,26 @@ pub fn decode(payload: &str) -> Result<Value> {
     // Parse header
     let header_line = lines[idx].trim();
     if !header_line.starts_with("@AGON rows") {
-        return Err(AgonError::DecodingError(format!(
-            "Invalid header: {}",
-            header_line
-        )));
+        return Err(row_decode_error(
+            idx,
+            0,
+            RowParseErrorKind::MissingHeader,
+            format!("Invalid header: {}", header_line),
+        ));
     }
     idx += 1;
 
     // Parse optional delimiter
     let delimiter = if idx < lines.len() && lines[idx].starts_with("@D=") {
         let d = parse_delimiter(&lines[idx][3..]);
+        if d.is_empty() {
+            return Err(row_decode_error(
+                idx,
+                3,
+                RowParseErrorKind::BadDelimiter,
+                format!("Empty delimiter in directive: {}", lines[idx]),
+            ));
+        }
         idx += 1;
         d
     } else {
@@ -83,1257 +405,3757 @@ pub fn decode(payload: &str) -> Result<Value> {
     Ok(result)
 }
 
-// ============================================================================
-// Encoding helpers
-// ============================================================================
-
-fn needs_quote(s: &str, delimiter: &str) -> bool {
-    if s.is_empty() {
-        return true;
+/// Decode an AGONRows payload the way a human fixing a malformed export
+/// would want: rather than stopping at the first bad row the way [`decode`]
+/// does, a malformed row in a top-level tabular array is skipped and its
+/// problem recorded as a [`AgonError::ParseError`] with a line and column,
+/// and decoding continues through the rest of the rows. All collected
+/// errors are returned alongside the partial [`Value`] so a caller can
+/// report every problem in one pass instead of bisecting by line number.
+///
+/// This only extends the collecting behavior to a top-level tabular array
+/// -- the one shape a human-edited row-at-a-time export is actually likely
+/// to have a typo in -- and falls back to [`decode`]'s single-error
+/// behavior for any other top-level shape (a bare object, a primitive or
+/// list array), since recovering mid-object or mid-list needs the same
+/// kind of structural redesign the grammar-documentation work in
+/// `struct_fmt` chose not to take on without a compiler to check it against.
+pub fn decode_collecting(payload: &str) -> (Value, Vec<AgonError>) {
+    let lines: Vec<&str> = payload.lines().collect();
+    if lines.is_empty() {
+        return (
+            Value::Null,
+            vec![AgonError::DecodingError("Empty payload".to_string())],
+        );
     }
-    if s.trim() != s {
-        return true;
+
+    let mut idx = 0;
+    let header_line = lines[idx].trim();
+    if !header_line.starts_with("@AGON rows") {
+        return (
+            Value::Null,
+            vec![AgonError::DecodingError(format!(
+                "Invalid header: {}",
+                header_line
+            ))],
+        );
     }
-    if s.contains(delimiter) {
-        return true;
+    idx += 1;
+
+    let delimiter = if idx < lines.len() && lines[idx].starts_with("@D=") {
+        let d = parse_delimiter(&lines[idx][3..]);
+        idx += 1;
+        d
+    } else {
+        DEFAULT_DELIMITER.to_string()
+    };
+
+    while idx < lines.len() && lines[idx].trim().is_empty() {
+        idx += 1;
     }
-    if s.contains('\n') || s.contains('\r') || s.contains('\\') || s.contains('"') {
-        return true;
+
+    if idx >= lines.len() {
+        return (Value::Null, Vec::new());
     }
-    let first = s.chars().next().unwrap();
-    if first == '@' || first == '#' || first == '-' {
-        return true;
+
+    let stripped = lines[idx].trim();
+    if let Some(caps) = TABULAR_HEADER_RE.captures(stripped) {
+        if caps.get(1).map(|m| m.as_str()).unwrap_or("").is_empty() {
+            let mut errors = Vec::new();
+            let value = decode_tabular_array_collecting(&lines, idx, &delimiter, &caps, &mut errors);
+            return (value, errors);
+        }
     }
-    let lower = s.to_lowercase();
-    if lower == "true" || lower == "false" || lower == "null" {
-        return true;
+
+    match decode_value(&lines, idx, 0, &delimiter) {
+        Ok((value, _)) => (value, Vec::new()),
+        Err(err) => (Value::Null, vec![err]),
     }
-    NUMBER_RE.is_match(s)
 }
 
-fn quote_string(s: &str) -> String {
-    let escaped = s
-        .replace('\\', "\\\\")
-        .replace('"', "\\\"")
-        .replace('\n', "\\n")
-        .replace('\r', "\\r")
-        .replace('\t', "\\t");
-    format!("\"{}\"", escaped)
-}
+/// The collecting counterpart to [`decode_tabular_array`]: instead of
+/// zipping each row against the header's field list and padding/truncating
+/// silently, a row whose field count doesn't match is skipped and recorded
+/// as a [`AgonError::ParseError`], with `column` pointing at the byte
+/// offset where the extra or missing field would have started.
+fn decode_tabular_array_collecting(
+    lines: &[&str],
+    idx: usize,
+    delimiter: &str,
+    caps: &regex::Captures,
+    errors: &mut Vec<AgonError>,
+) -> Value {
+    let count: usize = caps
+        .get(2)
+        .map(|m| m.as_str())
+        .unwrap_or("0")
+        .parse()
+        .unwrap_or(0);
+    let fields_str = caps.get(3).map(|m| m.as_str()).unwrap_or("");
+    let fields: Vec<&str> = fields_str.split(delimiter).map(|s| s.trim()).collect();
 
-fn unquote_string(s: &str) -> String {
-    if !(s.starts_with('"') && s.ends_with('"')) {
-        return s.to_string();
-    }
-    let inner = &s[1..s.len() - 1];
-    let mut result = String::new();
-    let mut chars = inner.chars().peekable();
+    let mut idx = idx + 1;
+    let mut result = Vec::new();
 
-    while let Some(c) = chars.next() {
-        if c == '\\' {
-            match chars.next() {
-                Some('n') => result.push('\n'),
-                Some('r') => result.push('\r'),
-                Some('t') => result.push('\t'),
-                Some('\\') => result.push('\\'),
-                Some('"') => result.push('"'),
-                Some(other) => result.push(other),
-                None => result.push('\\'),
-            }
-        } else {
-            result.push(c);
+    while idx < lines.len() && result.len() + errors.len() < count {
+        let row_line = lines[idx].trim();
+        if row_line.is_empty() || row_line.starts_with('#') {
+            idx += 1;
+            continue;
         }
-    }
-    result
-}
 
-fn encode_primitive(val: &Value, delimiter: &str) -> String {
-    match val {
-        Value::Null => "null".to_string(),
-        Value::Bool(b) => if *b { "true" } else { "false" }.to_string(),
-        Value::Number(n) => n.to_string(),
-        Value::String(s) => {
-            if needs_quote(s, delimiter) {
-                quote_string(s)
-            } else {
-                s.clone()
+        let values = split_row(row_line, delimiter);
+        if values.len() != fields.len() {
+            let matched = values.len().min(fields.len());
+            let column: usize = values[..matched]
+                .iter()
+                .map(|v| v.len() + delimiter.len())
+                .sum();
+            errors.push(AgonError::ParseError {
+                line: idx,
+                column,
+                message: format!(
+                    "expected {} fields, found {} at column {}",
+                    fields.len(),
+                    values.len(),
+                    column
+                ),
+            });
+            idx += 1;
+            continue;
+        }
+
+        let mut obj = Map::new();
+        for (field, raw) in fields.iter().zip(values.iter()) {
+            let val = parse_primitive(raw);
+            if !matches!(val, Value::Null) || !raw.trim().is_empty() {
+                insert_dotted_path(&mut obj, field, val);
             }
         }
-        _ => serde_json::to_string(val).unwrap_or_default(),
+        result.push(Value::Object(obj));
+        idx += 1;
     }
+
+    Value::Array(result)
 }
 
-fn parse_primitive(s: &str) -> Value {
-    let s = s.trim();
-    if s.is_empty() {
-        return Value::Null;
+/// Serialize `value` straight to AGONRows text through a dedicated
+/// [`Serializer`], the tabular counterpart to `struct_fmt`'s
+/// [`crate::ser::to_string`].
+///
+/// [`is_uniform_array`] already detects a `Vec<Struct>`'s tabular shape
+/// structurally from the `serde_json::Value` alone, so -- unlike
+/// `struct_fmt`, whose `@Name: fields` header needs a Rust type's own name,
+/// something only a custom `Serializer` can see -- nothing here depends on
+/// watching `serialize_struct` calls as they happen. The one place
+/// `serde_json::to_value` and [`Serializer`] actually disagree:
+/// `serialize_bytes` (what a `#[serde(with = "serde_bytes")]` field calls)
+/// gets AGON's own `{"__bytes__": {"base64": "..."}}` escape (see
+/// [`bytes_to_tagged_json`]) instead of decaying into a plain JSON array of
+/// byte values, the same convention `struct_fmt`'s `Serializer` already
+/// follows.
+pub fn to_string<T: serde::Serialize + ?Sized>(value: &T, include_header: bool) -> Result<String> {
+    let json = value.serialize(Serializer)?;
+    encode(&json, include_header)
+}
+
+/// Deserialize an AGONRows payload into `T`, the tabular counterpart to
+/// `struct_fmt`'s [`crate::de::from_str`]. `decode` already expands every
+/// row into a plain `serde_json::Value`, so `T::deserialize` is driven off
+/// that the same way `serde_json::from_value` always does -- a missing
+/// required column or a column that doesn't fit a field's type surfaces as
+/// the usual serde "missing field"/type-mismatch error, wrapped in
+/// [`AgonError::JsonError`].
+pub fn from_str<T: serde::de::DeserializeOwned>(s: &str) -> Result<T> {
+    let value = decode(s)?;
+    serde_json::from_value(value).map_err(AgonError::from)
+}
+
+// ============================================================================
+// Serde-native serializer
+// ============================================================================
+
+/// The `serde::Serializer` [`to_string`] drives a value through. See
+/// [`to_string`] for why this exists alongside plain `serde_json::to_value`.
+struct Serializer;
+
+impl serde::Serializer for Serializer {
+    type Ok = Value;
+    type Error = AgonError;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = TupleVariantSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = StructSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value> {
+        Ok(Value::Bool(v))
     }
 
-    // Quoted string
-    if s.starts_with('"') && s.ends_with('"') {
-        return Value::String(unquote_string(s));
+    fn serialize_i8(self, v: i8) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i16(self, v: i16) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i32(self, v: i32) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i64(self, v: i64) -> Result<Value> {
+        Ok(Value::Number(v.into()))
+    }
+    fn serialize_i128(self, v: i128) -> Result<Value> {
+        serde_json::Number::from_i128(v)
+            .map(Value::Number)
+            .ok_or_else(|| AgonError::EncodingError(format!("i128 out of range: {}", v)))
     }
 
-    // Boolean/null
-    let lower = s.to_lowercase();
-    if lower == "null" {
-        return Value::Null;
+    fn serialize_u8(self, v: u8) -> Result<Value> {
+        self.serialize_u64(v as u64)
     }
-    if lower == "true" {
-        return Value::Bool(true);
+    fn serialize_u16(self, v: u16) -> Result<Value> {
+        self.serialize_u64(v as u64)
     }
-    if lower == "false" {
-        return Value::Bool(false);
+    fn serialize_u32(self, v: u32) -> Result<Value> {
+        self.serialize_u64(v as u64)
+    }
+    fn serialize_u64(self, v: u64) -> Result<Value> {
+        Ok(Value::Number(v.into()))
+    }
+    fn serialize_u128(self, v: u128) -> Result<Value> {
+        serde_json::Number::from_u128(v)
+            .map(Value::Number)
+            .ok_or_else(|| AgonError::EncodingError(format!("u128 out of range: {}", v)))
     }
 
-    // Number
-    if NUMBER_RE.is_match(s) {
-        if s.contains('.') || s.to_lowercase().contains('e') {
-            if let Ok(f) = s.parse::<f64>()
-                && let Some(n) = serde_json::Number::from_f64(f)
-            {
-                return Value::Number(n);
-            }
-        } else if let Ok(i) = s.parse::<i64>() {
-            return Value::Number(i.into());
-        }
+    fn serialize_f32(self, v: f32) -> Result<Value> {
+        self.serialize_f64(v as f64)
+    }
+    fn serialize_f64(self, v: f64) -> Result<Value> {
+        serde_json::Number::from_f64(v)
+            .map(Value::Number)
+            .ok_or_else(|| AgonError::EncodingError(format!("non-finite float: {}", v)))
     }
 
-    Value::String(s.to_string())
-}
+    fn serialize_char(self, v: char) -> Result<Value> {
+        Ok(Value::String(v.to_string()))
+    }
+    fn serialize_str(self, v: &str) -> Result<Value> {
+        Ok(Value::String(v.to_string()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value> {
+        Ok(bytes_to_tagged_json(v))
+    }
 
-fn parse_delimiter(d: &str) -> String {
-    let d = d.trim();
-    match d {
-        "\\t" => "\t".to_string(),
-        "\\n" => "\n".to_string(),
-        _ => d.to_string(),
+    fn serialize_none(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value> {
+        value.serialize(self)
     }
-}
 
-fn is_uniform_array(arr: &[Value]) -> (bool, Vec<String>) {
-    if arr.is_empty() {
-        return (false, vec![]);
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
+        Ok(Value::Null)
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        Ok(Value::String(variant.to_string()))
     }
 
-    // Check all are objects
-    if !arr.iter().all(|v| v.is_object()) {
-        return (false, vec![]);
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        let mut obj = Map::new();
+        obj.insert(variant.to_string(), value.serialize(Serializer)?);
+        Ok(Value::Object(obj))
     }
 
-    // Check all values are primitives
-    for obj in arr {
-        if let Some(map) = obj.as_object() {
-            for v in map.values() {
-                if v.is_object() || v.is_array() {
-                    return (false, vec![]);
-                }
-            }
-        }
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<TupleVariantSerializer> {
+        Ok(TupleVariantSerializer {
+            variant,
+            items: SeqSerializer {
+                items: Vec::with_capacity(len),
+            },
+        })
     }
 
-    // Collect keys in order
-    let mut key_order = Vec::new();
-    for obj in arr {
-        if let Some(map) = obj.as_object() {
-            for k in map.keys() {
-                if !key_order.contains(k) {
-                    key_order.push(k.clone());
-                }
-            }
-        }
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer> {
+        Ok(MapSerializer {
+            map: Map::new(),
+            pending_key: None,
+        })
     }
 
-    (true, key_order)
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<StructSerializer> {
+        Ok(StructSerializer {
+            variant: None,
+            map: Map::new(),
+        })
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<StructSerializer> {
+        Ok(StructSerializer {
+            variant: Some(variant),
+            map: Map::new(),
+        })
+    }
 }
 
-fn is_primitive_array(arr: &[Value]) -> bool {
-    arr.iter().all(|v| !v.is_object() && !v.is_array())
+struct SeqSerializer {
+    items: Vec<Value>,
 }
 
-fn encode_value(
-    val: &Value,
-    lines: &mut Vec<String>,
-    depth: usize,
-    delimiter: &str,
-    name: Option<&str>,
-) {
-    let indent = INDENT.repeat(depth);
+impl SerializeSeq for SeqSerializer {
+    type Ok = Value;
+    type Error = AgonError;
 
-    match val {
-        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {
-            let encoded = encode_primitive(val, delimiter);
-            if let Some(n) = name {
-                lines.push(format!("{}{}: {}", indent, n, encoded));
-            } else {
-                lines.push(format!("{}{}", indent, encoded));
-            }
-        }
-        Value::Array(arr) => {
-            encode_array(arr, lines, depth, delimiter, name);
-        }
-        Value::Object(obj) => {
-            encode_object(obj, lines, depth, delimiter, name);
-        }
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.items.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value> {
+        Ok(Value::Array(self.items))
     }
 }
 
-fn encode_array(
-    arr: &[Value],
-    lines: &mut Vec<String>,
-    depth: usize,
-    delimiter: &str,
-    name: Option<&str>,
-) {
-    let indent = INDENT.repeat(depth);
+impl SerializeTuple for SeqSerializer {
+    type Ok = Value;
+    type Error = AgonError;
 
-    if arr.is_empty() {
-        if let Some(n) = name {
-            lines.push(format!("{}{}[0]:", indent, n));
-        } else {
-            lines.push(format!("{}[0]:", indent));
-        }
-        return;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Value> {
+        SerializeSeq::end(self)
     }
+}
 
-    // Check for uniform objects (tabular format)
-    let (is_uniform, fields) = is_uniform_array(arr);
-    if is_uniform && !fields.is_empty() {
-        let header = fields.join(delimiter);
-        if let Some(n) = name {
-            lines.push(format!("{}{}[{}]{{{}}}", indent, n, arr.len(), header));
-        } else {
-            lines.push(format!("{}[{}]{{{}}}", indent, arr.len(), header));
-        }
+impl SerializeTupleStruct for SeqSerializer {
+    type Ok = Value;
+    type Error = AgonError;
 
-        for obj in arr {
-            if let Some(map) = obj.as_object() {
-                let row: Vec<String> = fields
-                    .iter()
-                    .map(|f| {
-                        map.get(f)
-                            .map(|v| encode_primitive(v, delimiter))
-                            .unwrap_or_default()
-                    })
-                    .collect();
-                lines.push(format!("{}{}", indent, row.join(delimiter)));
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Value> {
+        SerializeSeq::end(self)
+    }
+}
+
+struct TupleVariantSerializer {
+    variant: &'static str,
+    items: SeqSerializer,
+}
+
+impl SerializeTupleVariant for TupleVariantSerializer {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        SerializeSeq::serialize_element(&mut self.items, value)
+    }
+    fn end(self) -> Result<Value> {
+        let mut obj = Map::new();
+        obj.insert(self.variant.to_string(), SerializeSeq::end(self.items)?);
+        Ok(Value::Object(obj))
+    }
+}
+
+struct MapSerializer {
+    map: Map<String, Value>,
+    pending_key: Option<String>,
+}
+
+impl SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        let key = key.serialize(Serializer)?;
+        self.pending_key = Some(value_to_map_key(key)?);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .pending_key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        let value = value.serialize(Serializer)?;
+        self.map.insert(key, value);
+        Ok(())
+    }
+    fn end(self) -> Result<Value> {
+        Ok(Value::Object(self.map))
+    }
+}
+
+/// Converts a serialized map key into the `String` AGON object keys require.
+fn value_to_map_key(value: Value) -> Result<String> {
+    match value {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        other => Err(AgonError::EncodingError(format!(
+            "map keys must serialize to a string, number, or bool, got {}",
+            other
+        ))),
+    }
+}
+
+struct StructSerializer {
+    variant: Option<&'static str>,
+    map: Map<String, Value>,
+}
+
+impl SerializeStruct for StructSerializer {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.map.insert(key.to_string(), value.serialize(Serializer)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value> {
+        let obj = Value::Object(self.map);
+        match self.variant {
+            Some(variant) => {
+                let mut wrapper = Map::new();
+                wrapper.insert(variant.to_string(), obj);
+                Ok(Value::Object(wrapper))
             }
+            None => Ok(obj),
         }
-        return;
     }
+}
 
-    // Primitive array (inline format)
-    if is_primitive_array(arr) {
-        let values: Vec<String> = arr.iter().map(|v| encode_primitive(v, delimiter)).collect();
-        if let Some(n) = name {
-            lines.push(format!(
-                "{}{}[{}]: {}",
-                indent,
-                n,
-                arr.len(),
-                values.join(delimiter)
-            ));
+impl SerializeStructVariant for StructSerializer {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        SerializeStruct::serialize_field(self, key, value)
+    }
+    fn end(self) -> Result<Value> {
+        SerializeStruct::end(self)
+    }
+}
+
+/// Open `reader` as a [`RowReader`], parsing the header and tabular array
+/// header up front. See [`RowReader`] for why this exists alongside
+/// [`decode`].
+pub fn row_reader<R: Read>(reader: R) -> Result<RowReader<R>> {
+    RowReader::new(reader)
+}
+
+/// Streams the data rows of a top-level `@AGON rows` tabular array one at a
+/// time from a [`Read`]er, so a multi-gigabyte export never needs its full
+/// `Value` in memory at once -- unlike [`decode`], which collects every line
+/// up front with `payload.lines().collect()`.
+///
+/// [`RowReader::new`] reads past the `@AGON rows` header, the optional
+/// `@D=<delimiter>` line, and the `[N]{f1,f2,...}` tabular header, then
+/// holds only the parsed field list; each call to
+/// [`next`](Iterator::next) reads and discards exactly one more line. Only
+/// an unnamed top-level tabular array is supported -- the same shape
+/// [`decode_tabular_array`] builds when its `caps` name group is empty --
+/// since that's the one shape a caller can stream row-by-row without
+/// first knowing the rest of the document's structure.
+pub struct RowReader<R> {
+    lines: std::io::Lines<BufReader<R>>,
+    delimiter: String,
+    fields: Vec<String>,
+    remaining: usize,
+}
+
+impl<R: Read> RowReader<R> {
+    fn new(reader: R) -> Result<Self> {
+        let mut lines = BufReader::new(reader).lines();
+
+        let header_line = Self::next_nonblank(&mut lines)?
+            .ok_or_else(|| AgonError::DecodingError("Empty payload".to_string()))?;
+        if !header_line.trim().starts_with(HEADER) {
+            return Err(AgonError::DecodingError(format!(
+                "Invalid header: {}",
+                header_line.trim()
+            )));
+        }
+
+        let mut line = Self::next_nonblank(&mut lines)?
+            .ok_or_else(|| AgonError::DecodingError("Missing tabular array header".to_string()))?;
+        let delimiter = if let Some(d) = line.strip_prefix("@D=") {
+            let d = parse_delimiter(d);
+            line = Self::next_nonblank(&mut lines)?.ok_or_else(|| {
+                AgonError::DecodingError("Missing tabular array header".to_string())
+            })?;
+            d
         } else {
-            lines.push(format!(
-                "{}[{}]: {}",
-                indent,
-                arr.len(),
-                values.join(delimiter)
+            DEFAULT_DELIMITER.to_string()
+        };
+
+        let caps = TABULAR_HEADER_RE.captures(line.trim()).ok_or_else(|| {
+            AgonError::DecodingError(format!(
+                "Expected an unnamed tabular array header, found: {}",
+                line.trim()
+            ))
+        })?;
+        if !caps.get(1).map(|m| m.as_str()).unwrap_or("").is_empty() {
+            return Err(AgonError::DecodingError(
+                "RowReader only supports an unnamed top-level tabular array".to_string(),
             ));
         }
-        return;
+        let remaining: usize = caps
+            .get(2)
+            .map(|m| m.as_str())
+            .unwrap_or("0")
+            .parse()
+            .unwrap_or(0);
+        let fields: Vec<String> = caps
+            .get(3)
+            .map(|m| m.as_str())
+            .unwrap_or("")
+            .split(&delimiter)
+            .map(|s| s.trim().to_string())
+            .collect();
+
+        Ok(RowReader {
+            lines,
+            delimiter,
+            fields,
+            remaining,
+        })
     }
 
-    // Mixed/nested array
-    if let Some(n) = name {
-        lines.push(format!("{}{}[{}]:", indent, n, arr.len()));
-    } else {
-        lines.push(format!("{}[{}]:", indent, arr.len()));
+    /// The column names declared by the tabular header, in row order.
+    pub fn fields(&self) -> &[String] {
+        &self.fields
     }
 
-    for item in arr {
-        if item.is_object() {
-            encode_list_item_object(item.as_object().unwrap(), lines, depth + 1, delimiter);
-        } else {
-            lines.push(format!(
-                "{}  - {}",
-                indent,
-                encode_primitive(item, delimiter)
-            ));
+    fn next_nonblank(lines: &mut std::io::Lines<BufReader<R>>) -> Result<Option<String>> {
+        loop {
+            match lines.next() {
+                Some(Ok(line)) if line.trim().is_empty() => continue,
+                Some(Ok(line)) => return Ok(Some(line)),
+                Some(Err(e)) => return Err(AgonError::DecodingError(e.to_string())),
+                None => return Ok(None),
+            }
         }
     }
 }
 
-fn encode_list_item_object(
-    obj: &Map<String, Value>,
-    lines: &mut Vec<String>,
-    depth: usize,
-    delimiter: &str,
-) {
-    let indent = INDENT.repeat(depth);
-    let mut first = true;
+impl<R: Read> Iterator for RowReader<R> {
+    type Item = Result<Value>;
 
-    for (k, v) in obj {
-        let prefix = if first {
-            format!("{}- ", indent)
-        } else {
-            format!("{}  ", indent)
-        };
-        first = false;
+    fn next(&mut self) -> Option<Result<Value>> {
+        if self.remaining == 0 {
+            return None;
+        }
 
-        match v {
-            Value::Object(nested) => {
-                lines.push(format!("{}{}:", prefix, k));
-                for (nk, nv) in nested {
-                    if nv.is_object() || nv.is_array() {
-                        encode_value(nv, lines, depth + 2, delimiter, Some(nk));
-                    } else {
-                        lines.push(format!(
-                            "{}    {}: {}",
-                            indent,
-                            nk,
-                            encode_primitive(nv, delimiter)
-                        ));
+        loop {
+            match self.lines.next() {
+                Some(Ok(line)) => {
+                    let row_line = line.trim();
+                    if row_line.is_empty() || row_line.starts_with('#') {
+                        continue;
+                    }
+                    self.remaining -= 1;
+
+                    let values = split_row(row_line, &self.delimiter);
+                    let mut obj = Map::new();
+                    for (i, field) in self.fields.iter().enumerate() {
+                        if let Some(raw) = values.get(i) {
+                            let val = parse_primitive(raw);
+                            if !matches!(val, Value::Null) || !raw.trim().is_empty() {
+                                obj.insert(field.clone(), val);
+                            }
+                        }
                     }
+                    return Some(Ok(Value::Object(obj)));
+                }
+                Some(Err(e)) => {
+                    self.remaining = 0;
+                    return Some(Err(AgonError::DecodingError(e.to_string())));
+                }
+                None => {
+                    self.remaining = 0;
+                    return None;
                 }
-            }
-            Value::Array(_) => {
-                lines.push(format!("{}{}:", prefix, k));
-                encode_value(v, lines, depth + 2, delimiter, None);
-            }
-            _ => {
-                lines.push(format!(
-                    "{}{}: {}",
-                    prefix,
-                    k,
-                    encode_primitive(v, delimiter)
-                ));
             }
         }
     }
 }
 
-fn encode_object(
-    obj: &Map<String, Value>,
-    lines: &mut Vec<String>,
-    depth: usize,
-    delimiter: &str,
-    name: Option<&str>,
-) {
-    let indent = INDENT.repeat(depth);
-    let mut actual_depth = depth;
+/// Open a [`RowWriter`] over `writer`, declaring `fields` as the tabular
+/// header's column list up front. See [`RowWriter`] for why row data still
+/// has to be buffered until [`RowWriter::finish`].
+pub fn row_writer<W: Write>(writer: W, fields: Vec<String>, include_header: bool) -> RowWriter<W> {
+    RowWriter::new(writer, fields, include_header)
+}
 
-    if let Some(n) = name {
-        lines.push(format!("{}{}:", indent, n));
-        actual_depth += 1;
+/// The encoding counterpart to [`RowReader`]: lets a caller push one row at
+/// a time as it's produced, rather than collecting a `Vec<Value>` first and
+/// handing the whole thing to [`encode`].
+///
+/// Rows are still buffered as already-formatted, delimiter-joined text
+/// lines rather than `Value`s -- the tabular header's `[N]` has to declare
+/// the final row count, which isn't known until every row has been pushed,
+/// so nothing reaches `writer` until [`finish`](Self::finish). That's
+/// strictly less to hold in memory than building the `Vec<Value>` of full
+/// row objects [`encode`] needs, even though it isn't a fully constant-memory
+/// stream -- the same header-carries-the-count constraint [`RowReader`]
+/// works around by trusting it rather than re-deriving it.
+pub struct RowWriter<W> {
+    writer: W,
+    delimiter: String,
+    fields: Vec<String>,
+    rows: Vec<String>,
+    include_header: bool,
+}
+
+impl<W: Write> RowWriter<W> {
+    fn new(writer: W, fields: Vec<String>, include_header: bool) -> Self {
+        RowWriter {
+            writer,
+            delimiter: DEFAULT_DELIMITER.to_string(),
+            fields,
+            rows: Vec::new(),
+            include_header,
+        }
     }
 
-    let actual_indent = INDENT.repeat(actual_depth);
+    /// Push one row's values, in the same order as [`RowWriter`]'s `fields`.
+    pub fn push_row(&mut self, values: &[Value]) {
+        let formatted: Vec<String> = values
+            .iter()
+            .map(|v| encode_primitive(v, &self.delimiter))
+            .collect();
+        self.rows.push(formatted.join(&self.delimiter));
+    }
 
-    for (k, v) in obj {
-        match v {
-            Value::Object(_) | Value::Array(_) => {
-                encode_value(v, lines, actual_depth, delimiter, Some(k));
-            }
-            _ => {
-                lines.push(format!(
-                    "{}{}: {}",
-                    actual_indent,
-                    k,
-                    encode_primitive(v, delimiter)
-                ));
-            }
+    /// Write the header and every pushed row to the underlying writer,
+    /// consuming `self` and returning it back.
+    pub fn finish(mut self) -> Result<W> {
+        let mut lines = Vec::with_capacity(self.rows.len() + 2);
+        if self.include_header {
+            lines.push(HEADER.to_string());
         }
+        lines.push(format!(
+            "[{}]{{{}}}",
+            self.rows.len(),
+            self.fields.join(&self.delimiter)
+        ));
+        lines.append(&mut self.rows);
+
+        self.writer
+            .write_all(lines.join("\n").as_bytes())
+            .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+        Ok(self.writer)
     }
 }
 
 // ============================================================================
-// Decoding helpers
+// Streaming event parser
 // ============================================================================
 
-fn get_indent_depth(line: &str) -> usize {
-    let stripped = line.trim_start_matches(' ');
-    let spaces = line.len() - stripped.len();
-    spaces / 2
+/// One SAX-style event a [`Parser`] yields as it walks a top-level tabular
+/// array, modeled on the token-event parser in rustc's `serialize` crate.
+/// Where [`RowReader`] reconstructs one row [`Value`] per line, `Parser`
+/// goes a level lower and yields the individual events that make up that
+/// row, so a caller building a running aggregate (a sum, a count, a
+/// filter) never needs even one row's `Value` materialized.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgonEvent {
+    ObjectStart,
+    ObjectEnd,
+    ArrayStart(usize),
+    ArrayEnd,
+    Key(String),
+    Primitive(Value),
 }
 
-fn split_row(values_str: &str, delimiter: &str) -> Vec<String> {
-    if delimiter.len() == 1 {
-        // Fast path for single-char delimiter (common case: tab)
-        let delim_char = delimiter.chars().next().unwrap();
-        let mut result = Vec::new();
-        let mut current = String::new();
-        let mut in_quote = false;
-        let mut escape_next = false;
+/// One frame of a [`Parser`]'s position trail (see [`Parser::stack`]): the
+/// array index of the row currently being read, or the field key within
+/// that row the most recently yielded `Key`/`Primitive` event belongs to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackElement {
+    Key(String),
+    Index(usize),
+}
 
-        for c in values_str.chars() {
-            if escape_next {
-                current.push(c);
-                escape_next = false;
-                continue;
-            }
+/// The states a [`Parser`] steps through to turn one row line into its
+/// `ObjectStart` / `Key` / `Primitive` / `ObjectEnd` events one at a time,
+/// without ever queuing more than a single pending event.
+enum ParserPhase {
+    Start,
+    RowBoundary,
+    RowKey,
+    RowValue,
+    RowEnd,
+    Finished,
+}
 
-            if c == '\\' && in_quote {
-                escape_next = true;
-                current.push(c);
-                continue;
-            }
+/// Streams [`AgonEvent`]s off a top-level unnamed tabular array from a
+/// [`Read`]er without ever materializing a row's [`Value`], the
+/// event-level counterpart to [`RowReader`]. [`Parser::new`] parses the
+/// header, optional `@D=<delimiter>` line, and `[N]{f1,f2,...}` tabular
+/// header up front exactly like [`RowReader::new`] -- see that type for why
+/// only the unnamed tabular shape is supported -- then [`next`](Iterator::next)
+/// yields `ArrayStart(N)`, followed by `ObjectStart` / `Key` / `Primitive` /
+/// `ObjectEnd` for each row in turn, and a final `ArrayEnd`.
+///
+/// A row's fields are yielded under their declared column name verbatim,
+/// even a dotted one -- unlike [`decode_tabular_array`], `Parser` doesn't
+/// reconstruct dotted columns into nested `Key`/`ObjectStart` events, since
+/// that would mean buffering a row's columns to detect collisions first,
+/// defeating the point of a one-event-at-a-time stream.
+pub struct Parser<R> {
+    lines: std::io::Lines<BufReader<R>>,
+    delimiter: String,
+    fields: Vec<String>,
+    remaining: usize,
+    row_index: usize,
+    current_values: Vec<String>,
+    current_field: usize,
+    stack: Vec<StackElement>,
+    phase: ParserPhase,
+}
 
-            if c == '"' {
-                in_quote = !in_quote;
-                current.push(c);
-            } else if c == delim_char && !in_quote {
-                result.push(current);
-                current = String::new();
-            } else {
-                current.push(c);
-            }
+impl<R: Read> Parser<R> {
+    fn new(reader: R) -> Result<Self> {
+        let mut lines = BufReader::new(reader).lines();
+
+        let header_line = RowReader::<R>::next_nonblank(&mut lines)?
+            .ok_or_else(|| AgonError::DecodingError("Empty payload".to_string()))?;
+        if !header_line.trim().starts_with(HEADER) {
+            return Err(AgonError::DecodingError(format!(
+                "Invalid header: {}",
+                header_line.trim()
+            )));
         }
 
-        result.push(current);
-        result
-    } else {
-        // Multi-char delimiter (less common)
-        let mut result = Vec::new();
-        let mut current = String::new();
-        let mut in_quote = false;
-        let mut i = 0;
-        let chars: Vec<char> = values_str.chars().collect();
-
-        while i < chars.len() {
-            let c = chars[i];
+        let mut line = RowReader::<R>::next_nonblank(&mut lines)?
+            .ok_or_else(|| AgonError::DecodingError("Missing tabular array header".to_string()))?;
+        let delimiter = if let Some(d) = line.strip_prefix("@D=") {
+            let d = parse_delimiter(d);
+            line = RowReader::<R>::next_nonblank(&mut lines)?.ok_or_else(|| {
+                AgonError::DecodingError("Missing tabular array header".to_string())
+            })?;
+            d
+        } else {
+            DEFAULT_DELIMITER.to_string()
+        };
 
-            if c == '"' {
-                in_quote = !in_quote;
-                current.push(c);
-                i += 1;
-            } else if !in_quote && values_str[i..].starts_with(delimiter) {
-                result.push(current);
-                current = String::new();
-                i += delimiter.len();
-            } else {
-                current.push(c);
-                i += 1;
-            }
+        let caps = TABULAR_HEADER_RE.captures(line.trim()).ok_or_else(|| {
+            AgonError::DecodingError(format!(
+                "Expected an unnamed tabular array header, found: {}",
+                line.trim()
+            ))
+        })?;
+        if !caps.get(1).map(|m| m.as_str()).unwrap_or("").is_empty() {
+            return Err(AgonError::DecodingError(
+                "Parser only supports an unnamed top-level tabular array".to_string(),
+            ));
         }
+        let remaining: usize = caps
+            .get(2)
+            .map(|m| m.as_str())
+            .unwrap_or("0")
+            .parse()
+            .unwrap_or(0);
+        let fields: Vec<String> = caps
+            .get(3)
+            .map(|m| m.as_str())
+            .unwrap_or("")
+            .split(&delimiter)
+            .map(|s| s.trim().to_string())
+            .collect();
+
+        Ok(Parser {
+            lines,
+            delimiter,
+            fields,
+            remaining,
+            row_index: 0,
+            current_values: Vec::new(),
+            current_field: 0,
+            stack: Vec::new(),
+            phase: ParserPhase::Start,
+        })
+    }
 
-        result.push(current);
-        result
+    /// The caller's current position: empty before the first event and
+    /// after the last, `[Index(i)]` while row `i`'s `ObjectStart` or
+    /// `ObjectEnd` is the most recent event, and `[Index(i), Key(field)]`
+    /// while a `Key`/`Primitive` pair for `field` is the most recent.
+    pub fn stack(&self) -> &[StackElement] {
+        &self.stack
     }
 }
 
-fn decode_value(
-    lines: &[&str],
-    idx: usize,
-    depth: usize,
-    delimiter: &str,
-) -> Result<(Value, usize)> {
-    if idx >= lines.len() {
-        return Ok((Value::Null, idx));
-    }
+impl<R: Read> Iterator for Parser<R> {
+    type Item = AgonEvent;
 
-    let line = lines[idx];
-    if get_indent_depth(line) < depth {
-        return Ok((Value::Null, idx));
+    fn next(&mut self) -> Option<AgonEvent> {
+        match self.phase {
+            ParserPhase::Start => {
+                self.phase = ParserPhase::RowBoundary;
+                Some(AgonEvent::ArrayStart(self.remaining))
+            }
+            ParserPhase::RowBoundary => {
+                if self.remaining == 0 {
+                    self.phase = ParserPhase::Finished;
+                    self.stack.clear();
+                    return Some(AgonEvent::ArrayEnd);
+                }
+                loop {
+                    match self.lines.next() {
+                        Some(Ok(line)) => {
+                            let row_line = line.trim();
+                            if row_line.is_empty() || row_line.starts_with('#') {
+                                continue;
+                            }
+                            self.current_values = split_row(row_line, &self.delimiter);
+                            self.current_field = 0;
+                            self.remaining -= 1;
+                            self.stack = vec![StackElement::Index(self.row_index)];
+                            self.row_index += 1;
+                            self.phase = if self.fields.is_empty() {
+                                ParserPhase::RowEnd
+                            } else {
+                                ParserPhase::RowKey
+                            };
+                            return Some(AgonEvent::ObjectStart);
+                        }
+                        Some(Err(_)) | None => {
+                            // A read error or a row count short of the header's
+                            // `[N]` just ends the stream early rather than
+                            // surfacing an error -- `Item = AgonEvent` has
+                            // nowhere to carry one.
+                            self.phase = ParserPhase::Finished;
+                            self.stack.clear();
+                            self.remaining = 0;
+                            return Some(AgonEvent::ArrayEnd);
+                        }
+                    }
+                }
+            }
+            ParserPhase::RowKey => {
+                let field = self.fields[self.current_field].clone();
+                if self.stack.len() == 1 {
+                    self.stack.push(StackElement::Key(field.clone()));
+                } else if let Some(StackElement::Key(k)) = self.stack.last_mut() {
+                    *k = field.clone();
+                }
+                self.phase = ParserPhase::RowValue;
+                Some(AgonEvent::Key(field))
+            }
+            ParserPhase::RowValue => {
+                let raw = self
+                    .current_values
+                    .get(self.current_field)
+                    .cloned()
+                    .unwrap_or_default();
+                let val = parse_primitive(&raw);
+                self.current_field += 1;
+                self.phase = if self.current_field < self.fields.len() {
+                    ParserPhase::RowKey
+                } else {
+                    ParserPhase::RowEnd
+                };
+                Some(AgonEvent::Primitive(val))
+            }
+            ParserPhase::RowEnd => {
+                self.stack.pop();
+                self.phase = ParserPhase::RowBoundary;
+                Some(AgonEvent::ObjectEnd)
+            }
+            ParserPhase::Finished => None,
+        }
     }
+}
 
-    let stripped = line.trim();
+/// Open `reader` as a [`Parser`], parsing the header and tabular array
+/// header up front. See [`Parser`] for why this exists alongside
+/// [`decode`] and [`row_reader`].
+pub fn parser<R: Read>(reader: R) -> Result<Parser<R>> {
+    Parser::new(reader)
+}
 
-    if stripped.is_empty() || stripped.starts_with('#') {
-        return decode_value(lines, idx + 1, depth, delimiter);
-    }
+/// Decode a `&str` payload by folding a [`Parser`]'s events into a
+/// [`Value`], the thin wrapper [`decode`] could become if it were rebuilt on
+/// top of the event stream. A thin wrapper itself over [`from_reader`] --
+/// kept as a separate function rather than replacing `decode`'s own
+/// recursive-descent body: `Parser` only understands the unnamed top-level
+/// tabular array shape, while `decode` has to handle every shape the format
+/// grammar allows.
+pub fn decode_events(payload: &str) -> Result<Value> {
+    from_reader(payload.as_bytes())
+}
 
-    // Check for tabular array
-    if let Some(caps) = TABULAR_HEADER_RE.captures(stripped) {
-        let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
-        if !name.is_empty() {
-            return decode_object(lines, idx, depth, delimiter);
+/// [`decode_events`]'s same fold, driven directly off a [`Read`]er instead
+/// of a buffered-up-front `&str` -- the incremental counterpart to
+/// [`decode`], the way `serde_json::from_reader` sits alongside
+/// `serde_json::from_str`. Like [`Parser`] itself, only the unnamed
+/// top-level tabular array shape streams; any other top-level shape needs
+/// `decode`'s full recursive-descent grammar instead.
+pub fn from_reader<R: Read>(reader: R) -> Result<Value> {
+    let parser = Parser::new(reader)?;
+    let mut containers: Vec<Value> = Vec::new();
+    let mut pending_key: Option<String> = None;
+    let mut root = Value::Null;
+
+    for event in parser {
+        match event {
+            AgonEvent::ObjectStart => containers.push(Value::Object(Map::new())),
+            AgonEvent::ArrayStart(_) => containers.push(Value::Array(Vec::new())),
+            AgonEvent::Key(key) => pending_key = Some(key),
+            AgonEvent::Primitive(val) => fold_into_top(&mut containers, &mut pending_key, val),
+            AgonEvent::ObjectEnd | AgonEvent::ArrayEnd => {
+                let finished = containers
+                    .pop()
+                    .expect("Parser emits a Start for every End it yields");
+                if containers.is_empty() {
+                    root = finished;
+                } else {
+                    fold_into_top(&mut containers, &mut pending_key, finished);
+                }
+            }
         }
-        return decode_tabular_array(lines, idx, depth, delimiter, &caps);
     }
 
-    // Check for primitive array
-    if let Some(caps) = PRIMITIVE_ARRAY_RE.captures(stripped) {
-        let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
-        let values_part = caps.get(3).map(|m| m.as_str()).unwrap_or("").trim();
-        if !values_part.is_empty() {
-            if !name.is_empty() {
-                return decode_object(lines, idx, depth, delimiter);
-            }
-            return decode_primitive_array(&caps, delimiter, idx);
+    Ok(root)
+}
+
+/// Insert `val` into the innermost container on `containers`: under
+/// `pending_key` (taking it) if the top is an object, or appended if it's
+/// an array. Shared by [`decode_events`] for both primitive values and
+/// just-closed nested containers.
+fn fold_into_top(containers: &mut [Value], pending_key: &mut Option<String>, val: Value) {
+    match containers.last_mut() {
+        Some(Value::Object(obj)) => {
+            let key = pending_key.take().unwrap_or_default();
+            obj.insert(key, val);
         }
+        Some(Value::Array(arr)) => arr.push(val),
+        _ => {}
     }
+}
 
-    // Check for list array
-    if let Some(caps) = LIST_ARRAY_RE.captures(stripped) {
-        let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
-        if !name.is_empty() {
-            return decode_object(lines, idx, depth, delimiter);
+// ============================================================================
+// Schema validation
+// ============================================================================
+
+/// The declared type of a [`RowSchema`] column, checked against each row's
+/// parsed [`Value`] the same way [`parse_primitive`] would have produced it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    String,
+    Int,
+    Float,
+    Bool,
+}
+
+impl ColumnType {
+    /// Whether `val` fits this column, allowing an integer value to satisfy
+    /// a `Float` column since `parse_primitive` only distinguishes ints from
+    /// floats by whether the source text had a `.` or exponent.
+    fn matches(&self, val: &Value) -> bool {
+        match self {
+            ColumnType::String => val.is_string(),
+            ColumnType::Int => val.is_i64() || val.is_u64(),
+            ColumnType::Float => val.is_number(),
+            ColumnType::Bool => val.is_boolean(),
         }
-        return decode_list_array(lines, idx, depth, delimiter, &caps);
     }
+}
 
-    // Check for key:value
-    if KEY_VALUE_RE.is_match(stripped) {
-        return decode_object(lines, idx, depth, delimiter);
+impl std::fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ColumnType::String => "string",
+            ColumnType::Int => "int",
+            ColumnType::Float => "float",
+            ColumnType::Bool => "bool",
+        };
+        write!(f, "{}", s)
     }
+}
 
-    Err(AgonError::ParseError {
-        line: idx,
-        message: format!("Cannot parse: {}", stripped),
-    })
+/// One column's contract within a [`RowSchema`]: its name, declared type,
+/// and whether a missing/null value is tolerated.
+#[derive(Debug, Clone)]
+pub struct ColumnDef {
+    name: String,
+    ty: ColumnType,
+    nullable: bool,
 }
 
-fn decode_tabular_array(
-    lines: &[&str],
-    idx: usize,
-    _depth: usize,
-    delimiter: &str,
-    caps: &regex::Captures,
-) -> Result<(Value, usize)> {
-    let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
-    let count: usize = caps
-        .get(2)
-        .map(|m| m.as_str())
-        .unwrap_or("0")
-        .parse()
-        .unwrap_or(0);
-    let fields_str = caps.get(3).map(|m| m.as_str()).unwrap_or("");
-    let fields: Vec<&str> = fields_str.split(delimiter).map(|s| s.trim()).collect();
+/// A machine-checkable contract for a top-level AGONRows tabular array,
+/// modeled on the way Preserves schemas attach a type/shape contract to a
+/// serialization format rather than leaving it purely structural. Unlike
+/// `struct_fmt`'s [`crate::formats::struct_fmt::Schema`] (which registers
+/// struct *shapes* by name for `@Name:` definitions), a `RowSchema` checks
+/// one tabular header's column set directly -- AGONRows has no named
+/// struct defs to register against.
+#[derive(Debug, Clone, Default)]
+pub struct RowSchema {
+    columns: Vec<ColumnDef>,
+}
 
-    let mut idx = idx + 1;
-    let mut result = Vec::new();
+impl RowSchema {
+    pub fn new() -> Self {
+        RowSchema { columns: Vec::new() }
+    }
 
-    while idx < lines.len() && result.len() < count {
-        let row_line = lines[idx].trim();
-        if row_line.is_empty() || row_line.starts_with('#') {
-            idx += 1;
-            continue;
-        }
+    /// Declare one column, in the order rows are expected to carry them.
+    pub fn column(mut self, name: impl Into<String>, ty: ColumnType, nullable: bool) -> Self {
+        self.columns.push(ColumnDef {
+            name: name.into(),
+            ty,
+            nullable,
+        });
+        self
+    }
+}
 
-        let values = split_row(row_line, delimiter);
-        let mut obj = Map::new();
+/// A human-readable name for `val`'s runtime type, for [`AgonError::SchemaError`]
+/// "found" messages.
+fn value_type_name(val: &Value) -> &'static str {
+    match val {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "int",
+        Value::Number(_) => "float",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
 
-        for (i, field) in fields.iter().enumerate() {
-            if i < values.len() {
-                let raw = &values[i];
-                let val = parse_primitive(raw);
-                if !matches!(val, Value::Null) || !raw.trim().is_empty() {
-                    obj.insert(field.to_string(), val);
+/// Validate that `data` (expected to be a JSON array of flat objects, the
+/// shape [`is_uniform_array`] renders as a tabular block) matches `schema`:
+/// every declared column is present and non-null unless it's nullable, and
+/// each present value fits its column's type. Keys a row carries beyond the
+/// declared columns are not checked. Runs before any text is rendered, so
+/// there's no source position to report: an error's `line` is the 0-based
+/// index of the offending row in `data` and its `column` the 0-based index
+/// of the offending column in `schema` (`0` when `data` or the row itself
+/// has the wrong shape).
+fn check_schema(data: &Value, schema: &RowSchema) -> Result<()> {
+    let rows = data.as_array().ok_or_else(|| AgonError::SchemaError {
+        line: 0,
+        column: 0,
+        expected: "a JSON array of objects".to_string(),
+        found: value_type_name(data).to_string(),
+    })?;
+
+    for (row_idx, row) in rows.iter().enumerate() {
+        let obj = row.as_object().ok_or_else(|| AgonError::SchemaError {
+            line: row_idx,
+            column: 0,
+            expected: "an object".to_string(),
+            found: value_type_name(row).to_string(),
+        })?;
+
+        for (col_idx, col) in schema.columns.iter().enumerate() {
+            let found = obj.get(&col.name);
+            match found {
+                None | Some(Value::Null) => {
+                    if !col.nullable {
+                        return Err(AgonError::SchemaError {
+                            line: row_idx,
+                            column: col_idx,
+                            expected: format!("non-null {}", col.ty),
+                            found: "null".to_string(),
+                        });
+                    }
                 }
+                Some(val) if !col.ty.matches(val) => {
+                    return Err(AgonError::SchemaError {
+                        line: row_idx,
+                        column: col_idx,
+                        expected: col.ty.to_string(),
+                        found: value_type_name(val).to_string(),
+                    });
+                }
+                Some(_) => {}
             }
         }
-
-        result.push(Value::Object(obj));
-        idx += 1;
     }
 
-    let arr = Value::Array(result);
-    if !name.is_empty() {
-        let mut wrapper = Map::new();
-        wrapper.insert(name.to_string(), arr);
-        Ok((Value::Object(wrapper), idx))
-    } else {
-        Ok((arr, idx))
-    }
+    Ok(())
 }
 
-fn decode_primitive_array(
-    caps: &regex::Captures,
-    delimiter: &str,
-    idx: usize,
-) -> Result<(Value, usize)> {
-    let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
-    let values_str = caps.get(3).map(|m| m.as_str()).unwrap_or("");
+/// Encode `data` to AGONRows, first checking it against `schema` so a
+/// shape violation surfaces as a positioned [`AgonError::SchemaError`]
+/// instead of silently rendering a row with missing or mistyped columns.
+pub fn encode_with_schema(data: &Value, schema: &RowSchema, include_header: bool) -> Result<String> {
+    check_schema(data, schema)?;
+    encode(data, include_header)
+}
 
-    let arr = if values_str.trim().is_empty() {
-        Value::Array(vec![])
+/// Decode an AGONRows payload expected to hold a single unnamed top-level
+/// tabular array, checking the header's column set and every row's parsed
+/// values against `schema` as it goes. Reuses [`TABULAR_HEADER_RE`] and
+/// [`split_row`] from [`decode`]'s own parsing, since a schema-checked
+/// decode is still decoding the same tabular block -- just with a contract
+/// to hold each field to.
+pub fn decode_with_schema(payload: &str, schema: &RowSchema) -> Result<Value> {
+    let lines: Vec<&str> = payload.lines().collect();
+    if lines.is_empty() {
+        return Err(AgonError::DecodingError("Empty payload".to_string()));
+    }
+
+    let mut idx = 0;
+    let header_line = lines[idx].trim();
+    if !header_line.starts_with(HEADER) {
+        return Err(AgonError::DecodingError(format!(
+            "Invalid header: {}",
+            header_line
+        )));
+    }
+    idx += 1;
+
+    let delimiter = if idx < lines.len() && lines[idx].starts_with("@D=") {
+        let d = parse_delimiter(&lines[idx][3..]);
+        idx += 1;
+        d
     } else {
-        let values = split_row(values_str, delimiter);
-        Value::Array(values.iter().map(|v| parse_primitive(v)).collect())
+        DEFAULT_DELIMITER.to_string()
     };
 
-    if !name.is_empty() {
-        let mut wrapper = Map::new();
-        wrapper.insert(name.to_string(), arr);
-        Ok((Value::Object(wrapper), idx + 1))
-    } else {
-        Ok((arr, idx + 1))
+    while idx < lines.len() && lines[idx].trim().is_empty() {
+        idx += 1;
     }
-}
 
-fn decode_list_array(
-    lines: &[&str],
-    idx: usize,
-    depth: usize,
-    delimiter: &str,
-    caps: &regex::Captures,
-) -> Result<(Value, usize)> {
-    let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
+    let header_line = lines.get(idx).map(|l| l.trim()).unwrap_or("");
+    let caps = TABULAR_HEADER_RE.captures(header_line).ok_or_else(|| {
+        AgonError::DecodingError(format!(
+            "Expected an unnamed tabular array header, found: {}",
+            header_line
+        ))
+    })?;
+    if !caps.get(1).map(|m| m.as_str()).unwrap_or("").is_empty() {
+        return Err(AgonError::DecodingError(
+            "decode_with_schema only supports an unnamed top-level tabular array".to_string(),
+        ));
+    }
     let count: usize = caps
         .get(2)
         .map(|m| m.as_str())
         .unwrap_or("0")
         .parse()
         .unwrap_or(0);
+    let header_fields: Vec<&str> = caps
+        .get(3)
+        .map(|m| m.as_str())
+        .unwrap_or("")
+        .split(&delimiter)
+        .map(|s| s.trim())
+        .collect();
+
+    let schema_fields: Vec<&str> = schema.columns.iter().map(|c| c.name.as_str()).collect();
+    let mut sorted_header = header_fields.clone();
+    sorted_header.sort_unstable();
+    let mut sorted_schema = schema_fields.clone();
+    sorted_schema.sort_unstable();
+    if sorted_header != sorted_schema {
+        return Err(AgonError::SchemaError {
+            line: idx,
+            column: 0,
+            expected: format!("columns {{{}}}", schema_fields.join(", ")),
+            found: format!("columns {{{}}}", header_fields.join(", ")),
+        });
+    }
 
-    let mut idx = idx + 1;
+    idx += 1;
     let mut result = Vec::new();
-    let base_depth = depth + 1;
 
     while idx < lines.len() && result.len() < count {
-        let line = lines[idx];
-        if line.trim().is_empty() || line.trim().starts_with('#') {
+        // Only spaces are stripped: a tab is the delimiter, so trimming it
+        // would drop a leading or trailing empty cell.
+        let row_line = lines[idx].trim_matches(' ');
+        if row_line.is_empty() || row_line.starts_with('#') {
             idx += 1;
             continue;
         }
 
-        let line_depth = get_indent_depth(line);
-        if line_depth < base_depth {
-            break;
+        let values = split_row(row_line, &delimiter);
+        if values.len() != header_fields.len() {
+            return Err(AgonError::SchemaError {
+                line: idx,
+                column: 0,
+                expected: format!("{} fields", header_fields.len()),
+                found: format!("{} fields", values.len()),
+            });
         }
 
-        let stripped = line.trim();
-        if let Some(item_str) = stripped.strip_prefix("- ") {
-            let item_str = item_str.trim();
-            if KEY_VALUE_RE.is_match(item_str) {
-                let (obj, new_idx) = decode_list_item_object(lines, idx, base_depth, delimiter)?;
-                result.push(obj);
-                idx = new_idx;
-            } else {
-                result.push(parse_primitive(item_str));
-                idx += 1;
+        let mut obj = Map::new();
+        let mut column = 0;
+        for (raw, header_field) in values.iter().zip(header_fields.iter()) {
+            let val = parse_primitive(raw);
+            if let Some(col) = schema.columns.iter().find(|c| c.name == *header_field) {
+                if matches!(val, Value::Null) {
+                    if !col.nullable {
+                        return Err(AgonError::SchemaError {
+                            line: idx,
+                            column,
+                            expected: format!("non-null {}", col.ty),
+                            found: "null".to_string(),
+                        });
+                    }
+                } else if !col.ty.matches(&val) {
+                    return Err(AgonError::SchemaError {
+                        line: idx,
+                        column,
+                        expected: col.ty.to_string(),
+                        found: value_type_name(&val).to_string(),
+                    });
+                }
             }
-        } else {
-            break;
+            if !matches!(val, Value::Null) || !raw.trim().is_empty() {
+                obj.insert(header_field.to_string(), val);
+            }
+            column += raw.len() + delimiter.len();
         }
-    }
 
-    let arr = Value::Array(result);
-    if !name.is_empty() {
-        let mut wrapper = Map::new();
-        wrapper.insert(name.to_string(), arr);
-        Ok((Value::Object(wrapper), idx))
-    } else {
-        Ok((arr, idx))
+        result.push(Value::Object(obj));
+        idx += 1;
     }
-}
 
-fn decode_list_item_object(
-    lines: &[&str],
-    idx: usize,
-    base_depth: usize,
-    delimiter: &str,
-) -> Result<(Value, usize)> {
-    let mut obj = Map::new();
-    let item_depth = base_depth;
+    Ok(Value::Array(result))
+}
 
-    // Parse first line (starts with -)
-    let first_line = lines[idx].trim();
-    let first_content = first_line.strip_prefix("- ").unwrap_or(first_line).trim();
+// ============================================================================
+// Encoding helpers
+// ============================================================================
 
-    let mut idx = idx;
+fn needs_quote(s: &str, delimiter: &str) -> bool {
+    if s.is_empty() {
+        return true;
+    }
+    if s.trim() != s {
+        return true;
+    }
+    if s.contains(delimiter) {
+        return true;
+    }
+    if s.contains('\n') || s.contains('\r') || s.contains('\\') || s.contains('"') {
+        return true;
+    }
+    let first = s.chars().next().unwrap();
+    if first == '@' || first == '#' || first == '-' {
+        return true;
+    }
+    let lower = s.to_lowercase();
+    if lower == "true" || lower == "false" || lower == "null" {
+        return true;
+    }
+    NUMBER_RE.is_match(s)
+}
 
-    if let Some(caps) = KEY_VALUE_RE.captures(first_content) {
-        let key = caps.get(1).map(|m| m.as_str()).unwrap_or("").trim();
-        let val_str = caps.get(2).map(|m| m.as_str()).unwrap_or("").trim();
+fn quote_string(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
 
-        if !val_str.is_empty() {
-            obj.insert(key.to_string(), parse_primitive(val_str));
-            idx += 1;
+fn unquote_string(s: &str) -> String {
+    if !(s.starts_with('"') && s.ends_with('"') && s.len() >= 2) {
+        return s.to_string();
+    }
+    let inner = &s[1..s.len() - 1];
+    let mut result = String::new();
+    let mut chars = inner.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('r') => result.push('\r'),
+                Some('t') => result.push('\t'),
+                Some('\\') => result.push('\\'),
+                Some('"') => result.push('"'),
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            }
         } else {
-            idx += 1;
-            if idx < lines.len() {
-                let next_depth = get_indent_depth(lines[idx]);
-                if next_depth > item_depth + 1 {
-                    let (nested, new_idx) = decode_value(lines, idx, next_depth, delimiter)?;
-                    obj.insert(key.to_string(), nested);
-                    idx = new_idx;
-                } else {
-                    // Empty object - no nested content at higher depth
-                    obj.insert(key.to_string(), Value::Object(Map::new()));
-                }
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn encode_primitive(val: &Value, delimiter: &str) -> String {
+    match val {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => if *b { "true" } else { "false" }.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => {
+            if needs_quote(s, delimiter) {
+                quote_string(s)
             } else {
-                obj.insert(key.to_string(), Value::Object(Map::new()));
+                s.clone()
+            }
+        }
+        _ => serde_json::to_string(val).unwrap_or_default(),
+    }
+}
+
+fn parse_primitive(s: &str) -> Value {
+    let s = s.trim();
+    if s.is_empty() {
+        return Value::Null;
+    }
+
+    // Quoted string (`s.len() >= 2` so a lone `"` -- which both starts and
+    // ends with itself -- doesn't fall in here and send `unquote_string` an
+    // empty inner slice to index).
+    if s.starts_with('"') && s.ends_with('"') && s.len() >= 2 {
+        return Value::String(unquote_string(s));
+    }
+
+    // Boolean/null
+    let lower = s.to_lowercase();
+    if lower == "null" {
+        return Value::Null;
+    }
+    if lower == "true" {
+        return Value::Bool(true);
+    }
+    if lower == "false" {
+        return Value::Bool(false);
+    }
+
+    // Number
+    if NUMBER_RE.is_match(s) {
+        if s.contains('.') || s.to_lowercase().contains('e') {
+            if let Ok(f) = s.parse::<f64>()
+                && let Some(n) = serde_json::Number::from_f64(f)
+            {
+                return Value::Number(n);
             }
+        } else if s == "-0" {
+            // `-0` has no distinct i64 representation of its own sign --
+            // keep it as the float `-0.0` instead of silently becoming
+            // integer `0`, the same as columns.rs's `parse_primitive`.
+            return Value::Number(serde_json::Number::from_f64(-0.0).unwrap());
+        } else if let Ok(i) = s.parse::<i64>() {
+            return Value::Number(i.into());
+        } else if let Ok(u) = s.parse::<u64>() {
+            // Falls here once the lexeme overflows i64 (e.g. an unsigned ID
+            // near u64::MAX).
+            return Value::Number(u.into());
+        } else {
+            // Wider than u64 (or a negative bignum too), i.e. a true
+            // bignum: keep every digit via the arbitrary-precision `Number`
+            // rather than demoting to a plain string, the same trick
+            // columns.rs's `parse_primitive` and `types::py_to_json` use.
+            return Value::Number(serde_json::Number::from_string_unchecked(s.to_string()));
         }
+    }
+
+    Value::String(s.to_string())
+}
+
+fn parse_delimiter(d: &str) -> String {
+    let d = d.trim();
+    match d {
+        "\\t" => "\t".to_string(),
+        "\\n" => "\n".to_string(),
+        _ => d.to_string(),
+    }
+}
+
+/// Wrap `segment` in `"..."` if it contains a literal `.`, so the dotted
+/// column name built from it doesn't let [`split_dotted_path`] mistake
+/// that `.` for a path separator on decode.
+fn escape_path_segment(segment: &str) -> String {
+    if segment.contains('.') {
+        format!("\"{}\"", segment.replace('\\', "\\\\").replace('"', "\\\""))
     } else {
-        idx += 1;
+        segment.to_string()
     }
+}
 
-    // Parse continuation lines
-    while idx < lines.len() {
-        let line = lines[idx];
-        if line.trim().is_empty() {
-            idx += 1;
-            continue;
+/// Depth-limited flattening of one record for [`flatten_uniform_array`]:
+/// an object is recursed into as long as `depth < max_depth`, each leaf
+/// (a scalar, or an object reached at `max_depth`) is inserted under its
+/// joined dotted path. Returns `None` -- disqualifying the whole array
+/// from flattening -- the moment an array turns up anywhere in the walk,
+/// since an array can't be rendered as a single tabular cell and has to
+/// stay in [`encode`]'s fallback list format instead.
+fn flatten_object(
+    obj: &Map<String, Value>,
+    path: &mut Vec<String>,
+    depth: usize,
+    max_depth: usize,
+    out: &mut Map<String, Value>,
+) -> Option<()> {
+    for (k, v) in obj {
+        path.push(escape_path_segment(k));
+        let ok = flatten_value(v, path, depth, max_depth, out);
+        path.pop();
+        ok?;
+    }
+    Some(())
+}
+
+fn flatten_value(
+    val: &Value,
+    path: &mut Vec<String>,
+    depth: usize,
+    max_depth: usize,
+    out: &mut Map<String, Value>,
+) -> Option<()> {
+    match val {
+        Value::Array(_) => None,
+        Value::Object(map) if depth < max_depth => flatten_object(map, path, depth + 1, max_depth, out),
+        _ => {
+            out.insert(path.join("."), val.clone());
+            Some(())
         }
+    }
+}
 
-        let line_depth = get_indent_depth(line);
-        if line_depth <= item_depth {
-            break;
+/// Whether `columns` contains a path that's both a leaf in its own right
+/// and a prefix of a deeper path -- the cross-record collision case where
+/// one record has a scalar at `user` and another has an object at `user`
+/// (so the union of columns holds both `user` and `user.name`). Such a
+/// column set can't be reassembled unambiguously on decode, so the whole
+/// array falls back to [`encode`]'s normal list format instead.
+fn has_leaf_prefix_collision(columns: &[String]) -> bool {
+    columns.iter().any(|a| {
+        columns
+            .iter()
+            .any(|b| a != b && b.starts_with(a.as_str()) && b.as_bytes().get(a.len()) == Some(&b'.'))
+    })
+}
+
+/// The flattening counterpart to [`is_uniform_array`]: also accepts an
+/// array of objects whose nested fields have a uniform shape, by
+/// depth-limited-flattening each record to a dotted-path leaf set first.
+/// Returns the union of leaf paths in first-seen order and each record's
+/// flattened row, or `None` if any record isn't an object, any leaf is an
+/// array, or the union of paths has a [`has_leaf_prefix_collision`].
+fn flatten_uniform_array(
+    arr: &[Value],
+    max_depth: usize,
+) -> Option<(Vec<String>, Vec<Map<String, Value>>)> {
+    if arr.is_empty() {
+        return None;
+    }
+
+    let mut column_order: Vec<String> = Vec::new();
+    let mut rows: Vec<Map<String, Value>> = Vec::with_capacity(arr.len());
+
+    for item in arr {
+        let obj = item.as_object()?;
+        let mut flat = Map::new();
+        flatten_object(obj, &mut Vec::new(), 0, max_depth, &mut flat)?;
+        for key in flat.keys() {
+            if !column_order.contains(key) {
+                column_order.push(key.clone());
+            }
         }
+        rows.push(flat);
+    }
 
-        let stripped = line.trim();
+    if has_leaf_prefix_collision(&column_order) {
+        return None;
+    }
 
-        if let Some(caps) = KEY_VALUE_RE.captures(stripped) {
-            let key = caps.get(1).map(|m| m.as_str()).unwrap_or("").trim();
-            let val_str = caps.get(2).map(|m| m.as_str()).unwrap_or("").trim();
+    Some((column_order, rows))
+}
 
-            if !val_str.is_empty() {
-                obj.insert(key.to_string(), parse_primitive(val_str));
-                idx += 1;
-            } else {
-                idx += 1;
-                if idx < lines.len() {
-                    let next_depth = get_indent_depth(lines[idx]);
-                    if next_depth > line_depth {
-                        let (nested, new_idx) = decode_value(lines, idx, next_depth, delimiter)?;
-                        obj.insert(key.to_string(), nested);
-                        idx = new_idx;
-                    } else {
-                        // Empty object - no nested content
-                        obj.insert(key.to_string(), Value::Object(Map::new()));
-                    }
-                } else {
-                    obj.insert(key.to_string(), Value::Object(Map::new()));
+/// Encode `data` to AGONRows, flattening a top-level array of
+/// uniformly-nested objects into dotted-path tabular columns (`user.name`,
+/// `user.addr.city`) instead of falling back to the verbose list format
+/// the moment a record's value is itself an object or array, the way plain
+/// [`encode`] does. `max_depth` bounds how many levels of nesting get
+/// flattened; a record that can't be flattened uniformly with the rest of
+/// the array -- an array at a leaf, or a path that's a scalar in one
+/// record and a nested object in another -- falls the *whole* array back
+/// to [`encode`]'s existing behavior rather than mixing the two formats.
+pub fn encode_flattened(data: &Value, include_header: bool, max_depth: usize) -> Result<String> {
+    let Value::Array(arr) = data else {
+        return encode(data, include_header);
+    };
+    let Some((columns, rows)) = flatten_uniform_array(arr, max_depth) else {
+        return encode(data, include_header);
+    };
+
+    let mut lines = Vec::new();
+    if include_header {
+        lines.push(HEADER.to_string());
+        lines.push(String::new());
+    }
+    lines.push(format!(
+        "[{}]{{{}}}",
+        rows.len(),
+        columns.join(DEFAULT_DELIMITER)
+    ));
+    for row in &rows {
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|c| {
+                row.get(c)
+                    .map(|v| encode_primitive(v, DEFAULT_DELIMITER))
+                    .unwrap_or_default()
+            })
+            .collect();
+        lines.push(cells.join(DEFAULT_DELIMITER));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+fn is_uniform_array(arr: &[Value]) -> (bool, Vec<String>) {
+    if arr.is_empty() {
+        return (false, vec![]);
+    }
+
+    // Check all are objects
+    if !arr.iter().all(|v| v.is_object()) {
+        return (false, vec![]);
+    }
+
+    // Check all values are primitives
+    for obj in arr {
+        if let Some(map) = obj.as_object() {
+            for v in map.values() {
+                if v.is_object() || v.is_array() {
+                    return (false, vec![]);
                 }
             }
-        } else {
-            idx += 1;
         }
     }
 
-    Ok((Value::Object(obj), idx))
+    // Collect keys in order
+    let mut key_order = Vec::new();
+    for obj in arr {
+        if let Some(map) = obj.as_object() {
+            for k in map.keys() {
+                if !key_order.contains(k) {
+                    key_order.push(k.clone());
+                }
+            }
+        }
+    }
+
+    (true, key_order)
 }
 
-fn decode_object(
-    lines: &[&str],
-    idx: usize,
-    _depth: usize,
+fn is_primitive_array(arr: &[Value]) -> bool {
+    arr.iter().all(|v| !v.is_object() && !v.is_array())
+}
+
+fn encode_value(
+    val: &Value,
+    lines: &mut Vec<String>,
+    depth: usize,
     delimiter: &str,
-) -> Result<(Value, usize)> {
-    let mut result = Map::new();
+    name: Option<&str>,
+    options: &SerializeOptions,
+) {
+    let indent = options.indent_unit().repeat(depth);
+
+    match val {
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {
+            let encoded = encode_primitive(val, delimiter);
+            if let Some(n) = name {
+                lines.push(format!("{}{}: {}", indent, n, encoded));
+            } else {
+                lines.push(format!("{}{}", indent, encoded));
+            }
+        }
+        Value::Array(arr) => {
+            encode_array(arr, lines, depth, delimiter, name, options);
+        }
+        Value::Object(obj) => {
+            encode_object(obj, lines, depth, delimiter, name, options);
+        }
+    }
+}
+
+fn encode_array(
+    arr: &[Value],
+    lines: &mut Vec<String>,
+    depth: usize,
+    delimiter: &str,
+    name: Option<&str>,
+    options: &SerializeOptions,
+) {
+    let indent = options.indent_unit().repeat(depth);
+
+    if arr.is_empty() {
+        if let Some(n) = name {
+            lines.push(format!("{}{}[0]:", indent, n));
+        } else {
+            lines.push(format!("{}[0]:", indent));
+        }
+        return;
+    }
+
+    // Check for uniform objects (tabular format)
+    let (is_uniform, fields) = is_uniform_array(arr);
+    if is_uniform && !fields.is_empty() {
+        let header = fields.join(delimiter);
+        if let Some(n) = name {
+            lines.push(format!("{}{}[{}]{{{}}}", indent, n, arr.len(), header));
+        } else {
+            lines.push(format!("{}[{}]{{{}}}", indent, arr.len(), header));
+        }
+
+        for obj in arr {
+            if let Some(map) = obj.as_object() {
+                let row: Vec<String> = fields
+                    .iter()
+                    .map(|f| {
+                        map.get(f)
+                            .map(|v| encode_primitive(v, delimiter))
+                            .unwrap_or_default()
+                    })
+                    .collect();
+                lines.push(format!("{}{}", indent, row.join(delimiter)));
+            }
+        }
+        return;
+    }
+
+    // Primitive array (inline format)
+    if is_primitive_array(arr) {
+        let values: Vec<String> = arr.iter().map(|v| encode_primitive(v, delimiter)).collect();
+        if let Some(n) = name {
+            lines.push(format!(
+                "{}{}[{}]: {}",
+                indent,
+                n,
+                arr.len(),
+                values.join(delimiter)
+            ));
+        } else {
+            lines.push(format!(
+                "{}[{}]: {}",
+                indent,
+                arr.len(),
+                values.join(delimiter)
+            ));
+        }
+        return;
+    }
+
+    // Mixed/nested array
+    if let Some(n) = name {
+        lines.push(format!("{}{}[{}]:", indent, n, arr.len()));
+    } else {
+        lines.push(format!("{}[{}]:", indent, arr.len()));
+    }
+
+    for item in arr {
+        if item.is_object() {
+            encode_list_item_object(item.as_object().unwrap(), lines, depth + 1, delimiter, options);
+        } else {
+            lines.push(format!(
+                "{}  - {}",
+                indent,
+                encode_primitive(item, delimiter)
+            ));
+        }
+    }
+}
+
+fn encode_list_item_object(
+    obj: &Map<String, Value>,
+    lines: &mut Vec<String>,
+    depth: usize,
+    delimiter: &str,
+    options: &SerializeOptions,
+) {
+    let indent = options.indent_unit().repeat(depth);
+    let mut first = true;
+
+    for (k, v) in crate::options::ordered_entries(obj, options.sort_keys) {
+        let prefix = if first {
+            format!("{}- ", indent)
+        } else {
+            format!("{}  ", indent)
+        };
+        first = false;
+
+        match v {
+            Value::Object(nested) => {
+                lines.push(format!("{}{}:", prefix, k));
+                for (nk, nv) in crate::options::ordered_entries(nested, options.sort_keys) {
+                    if nv.is_object() || nv.is_array() {
+                        encode_value(nv, lines, depth + 2, delimiter, Some(nk), options);
+                    } else {
+                        lines.push(format!(
+                            "{}    {}: {}",
+                            indent,
+                            nk,
+                            encode_primitive(nv, delimiter)
+                        ));
+                    }
+                }
+            }
+            Value::Array(_) => {
+                lines.push(format!("{}{}:", prefix, k));
+                encode_value(v, lines, depth + 2, delimiter, None, options);
+            }
+            _ => {
+                lines.push(format!(
+                    "{}{}: {}",
+                    prefix,
+                    k,
+                    encode_primitive(v, delimiter)
+                ));
+            }
+        }
+    }
+}
+
+fn encode_object(
+    obj: &Map<String, Value>,
+    lines: &mut Vec<String>,
+    depth: usize,
+    delimiter: &str,
+    name: Option<&str>,
+    options: &SerializeOptions,
+) {
+    let indent = options.indent_unit().repeat(depth);
+    let mut actual_depth = depth;
+
+    if let Some(n) = name {
+        lines.push(format!("{}{}:", indent, n));
+        actual_depth += 1;
+    }
+
+    let actual_indent = options.indent_unit().repeat(actual_depth);
+
+    for (k, v) in crate::options::ordered_entries(obj, options.sort_keys) {
+        match v {
+            Value::Object(_) | Value::Array(_) => {
+                encode_value(v, lines, actual_depth, delimiter, Some(k), options);
+            }
+            _ => {
+                lines.push(format!(
+                    "{}{}: {}",
+                    actual_indent,
+                    k,
+                    encode_primitive(v, delimiter)
+                ));
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Decoding helpers
+// ============================================================================
+
+fn get_indent_depth(line: &str) -> usize {
+    let stripped = line.trim_start_matches(' ');
+    let spaces = line.len() - stripped.len();
+    spaces / 2
+}
+
+fn split_row(values_str: &str, delimiter: &str) -> Vec<String> {
+    if delimiter.len() == 1 {
+        // Fast path for single-char delimiter (common case: tab)
+        let delim_char = delimiter.chars().next().unwrap();
+        let mut result = Vec::new();
+        let mut current = String::new();
+        let mut in_quote = false;
+        let mut escape_next = false;
+
+        for c in values_str.chars() {
+            if escape_next {
+                current.push(c);
+                escape_next = false;
+                continue;
+            }
+
+            if c == '\\' && in_quote {
+                escape_next = true;
+                current.push(c);
+                continue;
+            }
+
+            if c == '"' {
+                in_quote = !in_quote;
+                current.push(c);
+            } else if c == delim_char && !in_quote {
+                result.push(current);
+                current = String::new();
+            } else {
+                current.push(c);
+            }
+        }
+
+        result.push(current);
+        result
+    } else {
+        // Multi-char delimiter (less common)
+        let mut result = Vec::new();
+        let mut current = String::new();
+        let mut in_quote = false;
+        let mut i = 0;
+        let chars: Vec<char> = values_str.chars().collect();
+
+        while i < chars.len() {
+            let c = chars[i];
+
+            if c == '"' {
+                in_quote = !in_quote;
+                current.push(c);
+                i += 1;
+            } else if !in_quote && values_str[i..].starts_with(delimiter) {
+                result.push(current);
+                current = String::new();
+                i += delimiter.len();
+            } else {
+                current.push(c);
+                i += 1;
+            }
+        }
+
+        result.push(current);
+        result
+    }
+}
+
+fn decode_value(
+    lines: &[&str],
+    idx: usize,
+    depth: usize,
+    delimiter: &str,
+) -> Result<(Value, usize)> {
+    if idx >= lines.len() {
+        return Ok((Value::Null, idx));
+    }
+
+    let line = lines[idx];
+    if get_indent_depth(line) < depth {
+        return Ok((Value::Null, idx));
+    }
+
+    let stripped = line.trim();
+
+    if stripped.is_empty() || stripped.starts_with('#') {
+        return decode_value(lines, idx + 1, depth, delimiter);
+    }
+
+    // Check for tabular array
+    if let Some(caps) = TABULAR_HEADER_RE.captures(stripped) {
+        let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
+        if !name.is_empty() {
+            return decode_object(lines, idx, depth, delimiter);
+        }
+        return decode_tabular_array(lines, idx, depth, delimiter, &caps);
+    }
+
+    // Check for primitive array
+    if let Some(caps) = PRIMITIVE_ARRAY_RE.captures(stripped) {
+        let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
+        let values_part = caps.get(3).map(|m| m.as_str()).unwrap_or("").trim();
+        if !values_part.is_empty() {
+            if !name.is_empty() {
+                return decode_object(lines, idx, depth, delimiter);
+            }
+            return decode_primitive_array(&caps, delimiter, idx);
+        }
+    }
+
+    // Check for list array
+    if let Some(caps) = LIST_ARRAY_RE.captures(stripped) {
+        let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
+        if !name.is_empty() {
+            return decode_object(lines, idx, depth, delimiter);
+        }
+        return decode_list_array(lines, idx, depth, delimiter, &caps);
+    }
+
+    // Check for key:value
+    if KEY_VALUE_RE.is_match(stripped) {
+        return decode_object(lines, idx, depth, delimiter);
+    }
+
+    Err(AgonError::ParseError {
+        line: idx,
+        column: line.len() - line.trim_start().len(),
+        message: format!("Cannot parse: {}", stripped),
+    })
+}
+
+/// Split a column name on unquoted `.`s into its path segments, the decode
+/// counterpart to [`escape_path_segment`]: a segment that was wrapped in
+/// `"..."` (because the original key contained a literal `.`) comes back
+/// as one segment with the quotes and `\`-escapes removed. A plain
+/// undotted field name comes back as a single segment, same as before
+/// this existed.
+fn split_dotted_path(field: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = field.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '\\' if in_quotes => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            '.' if !in_quotes => segments.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    segments.push(current);
+    segments
+}
+
+/// Insert `val` into `obj` at the nested path `field` describes (see
+/// [`split_dotted_path`]), creating intermediate objects as needed and
+/// overwriting any non-object value already sitting where one is needed.
+/// A field name with no `.` is just a normal top-level insert -- this is
+/// what every tabular row insert goes through now, dotted or not.
+fn insert_dotted_path(obj: &mut Map<String, Value>, field: &str, val: Value) {
+    let mut segments = split_dotted_path(field);
+    let last = segments.pop().expect("split_dotted_path never empty");
+
+    let mut current = obj;
+    for segment in segments {
+        let entry = current
+            .entry(segment)
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !entry.is_object() {
+            *entry = Value::Object(Map::new());
+        }
+        current = entry.as_object_mut().expect("just ensured this is an object");
+    }
+    current.insert(last, val);
+}
+
+fn decode_tabular_array(
+    lines: &[&str],
+    idx: usize,
+    _depth: usize,
+    delimiter: &str,
+    caps: &regex::Captures,
+) -> Result<(Value, usize)> {
+    let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
+    let count: usize = caps
+        .get(2)
+        .map(|m| m.as_str())
+        .unwrap_or("0")
+        .parse()
+        .unwrap_or(0);
+    let fields_str = caps.get(3).map(|m| m.as_str()).unwrap_or("");
+    let fields: Vec<&str> = fields_str.split(delimiter).map(|s| s.trim()).collect();
+
+    let mut idx = idx + 1;
+    let mut result = Vec::new();
+
+    while idx < lines.len() && result.len() < count {
+        // Only spaces are stripped: a tab is the delimiter, so trimming it
+        // would drop a leading or trailing empty cell.
+        let row_line = lines[idx].trim_matches(' ');
+        if row_line.is_empty() || row_line.starts_with('#') {
+            idx += 1;
+            continue;
+        }
+
+        if let Some(column) = find_unterminated_quote(row_line) {
+            return Err(row_decode_error(
+                idx,
+                column,
+                RowParseErrorKind::UnterminatedQuote,
+                format!("Unterminated quote in row: {}", row_line),
+            ));
+        }
+
+        let values = split_row(row_line, delimiter);
+        if values.len() != fields.len() {
+            return Err(row_decode_error(
+                idx,
+                0,
+                RowParseErrorKind::RowArityMismatch,
+                format!(
+                    "expected {} columns, found {}",
+                    fields.len(),
+                    values.len()
+                ),
+            ));
+        }
+        let mut obj = Map::new();
+
+        for (field, raw) in fields.iter().zip(values.iter()) {
+            let val = parse_primitive(raw);
+            if !matches!(val, Value::Null) || !raw.trim().is_empty() {
+                insert_dotted_path(&mut obj, field, val);
+            }
+        }
+
+        result.push(Value::Object(obj));
+        idx += 1;
+    }
+
+    let arr = Value::Array(result);
+    if !name.is_empty() {
+        let mut wrapper = Map::new();
+        wrapper.insert(name.to_string(), arr);
+        Ok((Value::Object(wrapper), idx))
+    } else {
+        Ok((arr, idx))
+    }
+}
+
+fn decode_primitive_array(
+    caps: &regex::Captures,
+    delimiter: &str,
+    idx: usize,
+) -> Result<(Value, usize)> {
+    let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
+    let values_str = caps.get(3).map(|m| m.as_str()).unwrap_or("");
+
+    let arr = if values_str.trim().is_empty() {
+        Value::Array(vec![])
+    } else {
+        if let Some(column) = find_unterminated_quote(values_str) {
+            return Err(row_decode_error(
+                idx,
+                column,
+                RowParseErrorKind::UnterminatedQuote,
+                format!("Unterminated quote in: {}", values_str),
+            ));
+        }
+        let values = split_row(values_str, delimiter);
+        Value::Array(values.iter().map(|v| parse_primitive(v)).collect())
+    };
+
+    if !name.is_empty() {
+        let mut wrapper = Map::new();
+        wrapper.insert(name.to_string(), arr);
+        Ok((Value::Object(wrapper), idx + 1))
+    } else {
+        Ok((arr, idx + 1))
+    }
+}
+
+fn decode_list_array(
+    lines: &[&str],
+    idx: usize,
+    depth: usize,
+    delimiter: &str,
+    caps: &regex::Captures,
+) -> Result<(Value, usize)> {
+    let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
+    let count: usize = caps
+        .get(2)
+        .map(|m| m.as_str())
+        .unwrap_or("0")
+        .parse()
+        .unwrap_or(0);
+
+    let mut idx = idx + 1;
+    let mut result = Vec::new();
+    let base_depth = depth + 1;
+
+    while idx < lines.len() && result.len() < count {
+        let line = lines[idx];
+        if line.trim().is_empty() || line.trim().starts_with('#') {
+            idx += 1;
+            continue;
+        }
+
+        let line_depth = get_indent_depth(line);
+        if line_depth < base_depth {
+            break;
+        }
+
+        let stripped = line.trim();
+        if let Some(item_str) = stripped.strip_prefix("- ") {
+            let item_str = item_str.trim();
+            if KEY_VALUE_RE.is_match(item_str) {
+                let (obj, new_idx) = decode_list_item_object(lines, idx, base_depth, delimiter)?;
+                result.push(obj);
+                idx = new_idx;
+            } else {
+                result.push(parse_primitive(item_str));
+                idx += 1;
+            }
+        } else {
+            break;
+        }
+    }
+
+    let arr = Value::Array(result);
+    if !name.is_empty() {
+        let mut wrapper = Map::new();
+        wrapper.insert(name.to_string(), arr);
+        Ok((Value::Object(wrapper), idx))
+    } else {
+        Ok((arr, idx))
+    }
+}
+
+fn decode_list_item_object(
+    lines: &[&str],
+    idx: usize,
+    base_depth: usize,
+    delimiter: &str,
+) -> Result<(Value, usize)> {
+    let mut obj = Map::new();
+    let item_depth = base_depth;
+
+    // Parse first line (starts with -)
+    let first_line = lines[idx].trim();
+    let first_content = first_line.strip_prefix("- ").unwrap_or(first_line).trim();
+
+    let mut idx = idx;
+
+    if let Some(caps) = KEY_VALUE_RE.captures(first_content) {
+        let key = caps.get(1).map(|m| m.as_str()).unwrap_or("").trim();
+        let val_str = caps.get(2).map(|m| m.as_str()).unwrap_or("").trim();
+
+        if !val_str.is_empty() {
+            obj.insert(key.to_string(), parse_primitive(val_str));
+            idx += 1;
+        } else {
+            idx += 1;
+            if idx < lines.len() {
+                let next_depth = get_indent_depth(lines[idx]);
+                if next_depth > item_depth + 1 {
+                    let (nested, new_idx) = decode_value(lines, idx, next_depth, delimiter)?;
+                    obj.insert(key.to_string(), nested);
+                    idx = new_idx;
+                } else {
+                    // Empty object - no nested content at higher depth
+                    obj.insert(key.to_string(), Value::Object(Map::new()));
+                }
+            } else {
+                obj.insert(key.to_string(), Value::Object(Map::new()));
+            }
+        }
+    } else {
+        idx += 1;
+    }
+
+    // Parse continuation lines
+    while idx < lines.len() {
+        let line = lines[idx];
+        if line.trim().is_empty() {
+            idx += 1;
+            continue;
+        }
+
+        let line_depth = get_indent_depth(line);
+        if line_depth <= item_depth {
+            break;
+        }
+
+        let stripped = line.trim();
+
+        if let Some(caps) = KEY_VALUE_RE.captures(stripped) {
+            let key = caps.get(1).map(|m| m.as_str()).unwrap_or("").trim();
+            let val_str = caps.get(2).map(|m| m.as_str()).unwrap_or("").trim();
+
+            if !val_str.is_empty() {
+                obj.insert(key.to_string(), parse_primitive(val_str));
+                idx += 1;
+            } else {
+                idx += 1;
+                if idx < lines.len() {
+                    let next_depth = get_indent_depth(lines[idx]);
+                    if next_depth > line_depth {
+                        let (nested, new_idx) = decode_value(lines, idx, next_depth, delimiter)?;
+                        obj.insert(key.to_string(), nested);
+                        idx = new_idx;
+                    } else {
+                        // Empty object - no nested content
+                        obj.insert(key.to_string(), Value::Object(Map::new()));
+                    }
+                } else {
+                    obj.insert(key.to_string(), Value::Object(Map::new()));
+                }
+            }
+        } else {
+            idx += 1;
+        }
+    }
+
+    Ok((Value::Object(obj), idx))
+}
+
+fn decode_object(
+    lines: &[&str],
+    idx: usize,
+    _depth: usize,
+    delimiter: &str,
+) -> Result<(Value, usize)> {
+    let mut result = Map::new();
     if idx >= lines.len() {
         return Ok((Value::Object(result), idx));
     }
 
-    let base_depth = get_indent_depth(lines[idx]);
-    let mut idx = idx;
+    let base_depth = get_indent_depth(lines[idx]);
+    let mut idx = idx;
+
+    while idx < lines.len() {
+        let line = lines[idx];
+        if line.trim().is_empty() || line.trim().starts_with('#') {
+            idx += 1;
+            continue;
+        }
+
+        let line_depth = get_indent_depth(line);
+        if line_depth < base_depth {
+            break;
+        }
+
+        let stripped = line.trim();
+
+        // Check for array patterns first
+        if let Some(caps) = TABULAR_HEADER_RE.captures(stripped) {
+            let (nested, new_idx) = decode_tabular_array(lines, idx, line_depth, delimiter, &caps)?;
+            if let Value::Object(map) = nested {
+                for (k, v) in map {
+                    result.insert(k, v);
+                }
+            }
+            idx = new_idx;
+            continue;
+        }
+
+        if let Some(caps) = PRIMITIVE_ARRAY_RE.captures(stripped) {
+            let values_part = caps.get(3).map(|m| m.as_str()).unwrap_or("").trim();
+            if !values_part.is_empty() {
+                let (nested, new_idx) = decode_primitive_array(&caps, delimiter, idx)?;
+                if let Value::Object(map) = nested {
+                    for (k, v) in map {
+                        result.insert(k, v);
+                    }
+                }
+                idx = new_idx;
+                continue;
+            }
+        }
+
+        if let Some(caps) = LIST_ARRAY_RE.captures(stripped) {
+            let (nested, new_idx) = decode_list_array(lines, idx, line_depth, delimiter, &caps)?;
+            if let Value::Object(map) = nested {
+                for (k, v) in map {
+                    result.insert(k, v);
+                }
+            }
+            idx = new_idx;
+            continue;
+        }
+
+        if let Some(caps) = KEY_VALUE_RE.captures(stripped) {
+            let key = caps.get(1).map(|m| m.as_str()).unwrap_or("").trim();
+            let val_str = caps.get(2).map(|m| m.as_str()).unwrap_or("").trim();
+
+            if !val_str.is_empty() {
+                result.insert(key.to_string(), parse_primitive(val_str));
+                idx += 1;
+            } else {
+                idx += 1;
+                if idx < lines.len() {
+                    let next_depth = get_indent_depth(lines[idx]);
+                    if next_depth > line_depth {
+                        let (nested, new_idx) = decode_value(lines, idx, next_depth, delimiter)?;
+                        result.insert(key.to_string(), nested);
+                        idx = new_idx;
+                    } else {
+                        result.insert(key.to_string(), Value::Object(Map::new()));
+                    }
+                } else {
+                    result.insert(key.to_string(), Value::Object(Map::new()));
+                }
+            }
+        } else {
+            break;
+        }
+    }
+
+    Ok((Value::Object(result), idx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // ========================================================================
+    // Encoding tests
+    // ========================================================================
+
+    #[test]
+    fn test_encode_simple_array() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2, "name": "Bob"}
+        ]);
+        let encoded = encode(&data, false).unwrap();
+        assert!(encoded.contains("[2]{"));
+        assert!(encoded.contains("Alice"));
+    }
+
+    #[test]
+    fn test_encode_with_header() {
+        let data = json!({"name": "test"});
+        let encoded = encode(&data, true).unwrap();
+        assert!(encoded.starts_with("@AGON rows"));
+    }
+
+    #[test]
+    fn test_encode_without_header() {
+        let data = json!({"name": "test"});
+        let encoded = encode(&data, false).unwrap();
+        assert!(!encoded.contains("@AGON"));
+    }
+
+    #[test]
+    fn test_encode_primitives() {
+        let data = json!({
+            "string": "hello",
+            "number": 42,
+            "float": 3.15,
+            "bool_true": true,
+            "bool_false": false,
+            "null_val": null
+        });
+        let encoded = encode(&data, false).unwrap();
+        assert!(encoded.contains("string: hello"));
+        assert!(encoded.contains("number: 42"));
+        assert!(encoded.contains("float: 3.15"));
+        assert!(encoded.contains("bool_true: true"));
+        assert!(encoded.contains("bool_false: false"));
+        assert!(encoded.contains("null_val: null"));
+    }
+
+    #[test]
+    fn test_encode_empty_array() {
+        let data = json!({"items": []});
+        let encoded = encode(&data, false).unwrap();
+        assert!(encoded.contains("items[0]:"));
+    }
+
+    #[test]
+    fn test_encode_primitive_array() {
+        let data = json!({"nums": [1, 2, 3]});
+        let encoded = encode(&data, false).unwrap();
+        assert!(encoded.contains("nums[3]:"));
+    }
+
+    #[test]
+    fn test_encode_nested_object() {
+        let data = json!({
+            "outer": {
+                "inner": {
+                    "value": 42
+                }
+            }
+        });
+        let encoded = encode(&data, false).unwrap();
+        assert!(encoded.contains("outer:"));
+        assert!(encoded.contains("inner:"));
+        assert!(encoded.contains("value: 42"));
+    }
+
+    // ========================================================================
+    // Decoding tests
+    // ========================================================================
+
+    #[test]
+    fn test_decode_empty_payload() {
+        let result = decode("");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_decode_invalid_header() {
+        let result = decode("invalid header");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_decode_header_only() {
+        let result = decode("@AGON rows\n\n").unwrap();
+        assert!(result.is_null());
+    }
+
+    #[test]
+    fn test_decode_simple_object() {
+        let payload = "@AGON rows\n\nname: Alice\nage: 30";
+        let decoded = decode(payload).unwrap();
+        assert_eq!(decoded["name"], "Alice");
+        assert_eq!(decoded["age"], 30);
+    }
+
+    #[test]
+    fn test_decode_tabular_array() {
+        let payload = "@AGON rows\n\n[2]{id\tname}\n1\tAlice\n2\tBob";
+        let decoded = decode(payload).unwrap();
+        assert!(decoded.is_array());
+        let arr = decoded.as_array().unwrap();
+        assert_eq!(arr.len(), 2);
+        assert_eq!(arr[0]["id"], 1);
+        assert_eq!(arr[0]["name"], "Alice");
+    }
+
+    #[test]
+    fn test_decode_named_tabular_array() {
+        let payload = "@AGON rows\n\nusers[2]{id\tname}\n1\tAlice\n2\tBob";
+        let decoded = decode(payload).unwrap();
+        assert!(decoded.is_object());
+        let users = decoded["users"].as_array().unwrap();
+        assert_eq!(users.len(), 2);
+    }
+
+    #[test]
+    fn test_decode_primitive_array() {
+        let payload = "@AGON rows\n\nnums[3]: 1\t2\t3";
+        let decoded = decode(payload).unwrap();
+        let nums = decoded["nums"].as_array().unwrap();
+        assert_eq!(nums.len(), 3);
+        assert_eq!(nums[0], 1);
+        assert_eq!(nums[1], 2);
+        assert_eq!(nums[2], 3);
+    }
+
+    #[test]
+    fn test_decode_custom_delimiter() {
+        let payload = "@AGON rows\n@D=\\t\n\nname: test";
+        let decoded = decode(payload).unwrap();
+        assert_eq!(decoded["name"], "test");
+    }
+
+    #[test]
+    fn test_decode_invalid_header_reports_missing_header_kind() {
+        let err = decode("not an agon payload").unwrap_err();
+        match err {
+            AgonError::RowDecodeError { kind, line, .. } => {
+                assert_eq!(kind, RowParseErrorKind::MissingHeader);
+                assert_eq!(line, 1);
+            }
+            _ => panic!("expected RowDecodeError"),
+        }
+    }
+
+    #[test]
+    fn test_decode_empty_delimiter_reports_bad_delimiter_kind() {
+        let payload = "@AGON rows\n@D=\n\nname: test";
+        let err = decode(payload).unwrap_err();
+        assert!(matches!(
+            err,
+            AgonError::RowDecodeError {
+                kind: RowParseErrorKind::BadDelimiter,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_decode_row_arity_mismatch_reports_expected_and_found() {
+        let payload = "@AGON rows\n\n[1]{id\tname}\n1";
+        let err = decode(payload).unwrap_err();
+        match err {
+            AgonError::RowDecodeError {
+                kind, line, message, ..
+            } => {
+                assert_eq!(kind, RowParseErrorKind::RowArityMismatch);
+                assert_eq!(line, 4);
+                assert!(message.contains("expected 2 columns, found 1"));
+            }
+            _ => panic!("expected RowDecodeError"),
+        }
+    }
+
+    #[test]
+    fn test_decode_unterminated_quote_reports_unterminated_quote_kind() {
+        let payload = "@AGON rows\n\n[1]{id\tname}\n1\t\"Alice";
+        let err = decode(payload).unwrap_err();
+        assert!(matches!(
+            err,
+            AgonError::RowDecodeError {
+                kind: RowParseErrorKind::UnterminatedQuote,
+                ..
+            }
+        ));
+    }
+
+    // ========================================================================
+    // decode_collecting tests
+    // ========================================================================
+
+    #[test]
+    fn test_decode_collecting_returns_no_errors_for_valid_payload() {
+        let payload = "@AGON rows\n\n[2]{id\tname}\n1\tAlice\n2\tBob";
+        let (value, errors) = decode_collecting(payload);
+        assert!(errors.is_empty());
+        assert_eq!(value.as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_decode_collecting_skips_bad_row_and_keeps_good_ones() {
+        let payload = "@AGON rows\n\n[3]{id\tname}\n1\tAlice\n2\tBob\tExtra\n3\tCarol";
+        let (value, errors) = decode_collecting(payload);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], AgonError::ParseError { line: 4, .. }));
+        let rows = value.as_array().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["name"], "Alice");
+        assert_eq!(rows[1]["name"], "Carol");
+    }
+
+    #[test]
+    fn test_decode_collecting_reports_expected_and_found_counts() {
+        let payload = "@AGON rows\n\n[1]{id\tname}\n1";
+        let (_value, errors) = decode_collecting(payload);
+        assert_eq!(errors.len(), 1);
+        match &errors[0] {
+            AgonError::ParseError { message, .. } => {
+                assert!(message.contains("expected 2 fields, found 1"));
+            }
+            _ => panic!("expected ParseError"),
+        }
+    }
+
+    #[test]
+    fn test_decode_collecting_falls_back_to_single_error_for_non_tabular_payload() {
+        let payload = "@AGON rows\n\nnot valid @@@";
+        let (_value, errors) = decode_collecting(payload);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], AgonError::ParseError { .. }));
+    }
+
+    #[test]
+    fn test_decode_collecting_empty_payload_is_one_error() {
+        let (_value, errors) = decode_collecting("");
+        assert_eq!(errors.len(), 1);
+    }
+
+    // ========================================================================
+    // Roundtrip tests
+    // ========================================================================
+
+    #[test]
+    fn test_roundtrip() {
+        let data = json!({
+            "users": [
+                {"id": 1, "name": "Alice"},
+                {"id": 2, "name": "Bob"}
+            ]
+        });
+        let encoded = encode(&data, true).unwrap();
+        let decoded = decode(&encoded).unwrap();
+
+        assert!(decoded.is_object());
+        let users = decoded.get("users").unwrap();
+        assert!(users.is_array());
+        assert_eq!(users.as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_roundtrip_nested_object() {
+        let data = json!({
+            "company": {
+                "name": "ACME",
+                "address": {
+                    "city": "Seattle"
+                }
+            }
+        });
+        let encoded = encode(&data, true).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded["company"]["name"], "ACME");
+        assert_eq!(decoded["company"]["address"]["city"], "Seattle");
+    }
+
+    #[test]
+    fn test_roundtrip_empty_object() {
+        let data = json!({});
+        let encoded = encode(&data, true).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert!(
+            decoded.is_null() || (decoded.is_object() && decoded.as_object().unwrap().is_empty())
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_mixed_array() {
+        let data = json!({
+            "items": [1, "two", true, null]
+        });
+        let encoded = encode(&data, true).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        let items = decoded["items"].as_array().unwrap();
+        assert_eq!(items.len(), 4);
+    }
+
+    // ========================================================================
+    // Helper function tests
+    // ========================================================================
+
+    #[test]
+    fn test_needs_quote_empty() {
+        assert!(needs_quote("", "\t"));
+    }
+
+    #[test]
+    fn test_needs_quote_whitespace() {
+        assert!(needs_quote("  padded  ", "\t"));
+        assert!(needs_quote(" leading", "\t"));
+        assert!(needs_quote("trailing ", "\t"));
+    }
+
+    #[test]
+    fn test_needs_quote_delimiter() {
+        assert!(needs_quote("has\ttab", "\t"));
+        assert!(needs_quote("has,comma", ","));
+    }
+
+    #[test]
+    fn test_needs_quote_special_chars() {
+        assert!(needs_quote("has\nnewline", "\t"));
+        assert!(needs_quote("has\"quote", "\t"));
+        assert!(needs_quote("has\\backslash", "\t"));
+    }
+
+    #[test]
+    fn test_needs_quote_special_prefix() {
+        assert!(needs_quote("@mention", "\t"));
+        assert!(needs_quote("#comment", "\t"));
+        assert!(needs_quote("-item", "\t"));
+    }
+
+    #[test]
+    fn test_needs_quote_looks_like_primitive() {
+        assert!(needs_quote("true", "\t"));
+        assert!(needs_quote("false", "\t"));
+        assert!(needs_quote("null", "\t"));
+        assert!(needs_quote("42", "\t"));
+        assert!(needs_quote("3.14", "\t"));
+    }
+
+    #[test]
+    fn test_needs_quote_normal_string() {
+        assert!(!needs_quote("hello", "\t"));
+        assert!(!needs_quote("normal string", "\t"));
+    }
+
+    #[test]
+    fn test_quote_string() {
+        assert_eq!(quote_string("hello"), "\"hello\"");
+        assert_eq!(quote_string("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(quote_string("line\nbreak"), "\"line\\nbreak\"");
+        assert_eq!(quote_string("tab\there"), "\"tab\\there\"");
+    }
+
+    #[test]
+    fn test_unquote_string() {
+        assert_eq!(unquote_string("\"hello\""), "hello");
+        assert_eq!(unquote_string("\"say \\\"hi\\\"\""), "say \"hi\"");
+        assert_eq!(unquote_string("\"line\\nbreak\""), "line\nbreak");
+        assert_eq!(unquote_string("unquoted"), "unquoted");
+    }
+
+    #[test]
+    fn test_parse_primitive_null() {
+        assert_eq!(parse_primitive("null"), Value::Null);
+        assert_eq!(parse_primitive("NULL"), Value::Null);
+        assert_eq!(parse_primitive(""), Value::Null);
+    }
+
+    #[test]
+    fn test_parse_primitive_bool() {
+        assert_eq!(parse_primitive("true"), Value::Bool(true));
+        assert_eq!(parse_primitive("TRUE"), Value::Bool(true));
+        assert_eq!(parse_primitive("false"), Value::Bool(false));
+        assert_eq!(parse_primitive("FALSE"), Value::Bool(false));
+    }
+
+    #[test]
+    fn test_parse_primitive_number() {
+        assert_eq!(parse_primitive("42"), json!(42));
+        assert_eq!(parse_primitive("-17"), json!(-17));
+        assert_eq!(parse_primitive("3.15"), json!(3.15));
+        assert_eq!(parse_primitive("1e10"), json!(1e10));
+    }
+
+    #[test]
+    fn test_parse_primitive_large_u64_id_stays_a_number() {
+        // Exceeds i64::MAX, needs the u64 fallback to avoid decaying to a string.
+        let parsed = parse_primitive("18446744073709551615");
+        assert_eq!(parsed, json!(18446744073709551615u64));
+        assert!(parsed.is_u64());
+    }
+
+    #[test]
+    fn test_parse_primitive_negative_integer() {
+        assert_eq!(parse_primitive("-9223372036854775808"), json!(i64::MIN));
+    }
+
+    #[test]
+    fn test_parse_primitive_negative_zero_stays_a_float() {
+        // `-0` has no distinct i64 representation, so demoting it to the
+        // plain integer `0` would lose its sign -- matches columns.rs's
+        // `parse_primitive` for the same input.
+        let parsed = parse_primitive("-0");
+        assert!(parsed.is_f64());
+        assert_eq!(parsed.as_f64().unwrap().to_bits(), (-0.0f64).to_bits());
+    }
+
+    #[test]
+    fn test_parse_primitive_bignum_beyond_u64_preserves_every_digit() {
+        let digits = "123456789012345678901234567890";
+        let value = parse_primitive(digits);
+        assert_eq!(value.to_string(), digits);
+    }
+
+    #[test]
+    fn test_parse_primitive_negative_bignum_beyond_i64_preserves_every_digit() {
+        let digits = "-123456789012345678901234567890";
+        let value = parse_primitive(digits);
+        assert_eq!(value.to_string(), digits);
+    }
+
+    #[test]
+    fn test_parse_primitive_rejects_leading_zero_as_string() {
+        assert_eq!(parse_primitive("007"), Value::String("007".to_string()));
+    }
+
+    #[test]
+    fn test_parse_primitive_integral_float_stays_a_float() {
+        let parsed = parse_primitive("3.0");
+        assert!(parsed.is_f64());
+        assert_eq!(parsed, json!(3.0));
+    }
+
+    #[test]
+    fn test_parse_primitive_string() {
+        assert_eq!(parse_primitive("hello"), Value::String("hello".to_string()));
+        assert_eq!(
+            parse_primitive("\"quoted\""),
+            Value::String("quoted".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_primitive_lone_quote_char_does_not_panic() {
+        // A single `"` both starts and ends with itself, so the naive
+        // starts_with/ends_with check alone would misfire as a quoted empty
+        // string and slice out of bounds computing its interior.
+        assert_eq!(parse_primitive("\""), Value::String("\"".to_string()));
+    }
+
+    #[test]
+    fn test_parse_delimiter() {
+        assert_eq!(parse_delimiter("\\t"), "\t");
+        assert_eq!(parse_delimiter("\\n"), "\n");
+        assert_eq!(parse_delimiter(","), ",");
+    }
+
+    #[test]
+    fn test_is_uniform_array_empty() {
+        let arr: Vec<Value> = vec![];
+        let (uniform, _) = is_uniform_array(&arr);
+        assert!(!uniform);
+    }
+
+    #[test]
+    fn test_is_uniform_array_primitives() {
+        let arr = vec![json!(1), json!(2), json!(3)];
+        let (uniform, _) = is_uniform_array(&arr);
+        assert!(!uniform);
+    }
+
+    #[test]
+    fn test_is_uniform_array_uniform_objects() {
+        let arr = vec![json!({"id": 1, "name": "a"}), json!({"id": 2, "name": "b"})];
+        let (uniform, fields) = is_uniform_array(&arr);
+        assert!(uniform);
+        assert!(fields.contains(&"id".to_string()));
+        assert!(fields.contains(&"name".to_string()));
+    }
+
+    #[test]
+    fn test_is_uniform_array_preserves_first_seen_field_order() {
+        // "zeta" sorts after "alpha", so this only passes if `fields` reflects
+        // the objects' own key order rather than an alphabetical resort.
+        let arr = vec![json!({"zeta": 1, "alpha": "a"}), json!({"zeta": 2, "alpha": "b"})];
+        let (uniform, fields) = is_uniform_array(&arr);
+        assert!(uniform);
+        assert_eq!(fields, vec!["zeta".to_string(), "alpha".to_string()]);
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_preserves_column_order() {
+        let data = json!([
+            {"zeta": 1, "alpha": "a"},
+            {"zeta": 2, "alpha": "b"}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+        assert!(encoded.contains("{zeta\talpha}"));
+
+        let decoded = decode(&encoded).unwrap();
+        let first_row = decoded[0].as_object().unwrap();
+        let keys: Vec<&String> = first_row.keys().collect();
+        assert_eq!(keys, vec!["zeta", "alpha"]);
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_preserves_large_u64_id() {
+        let data = json!([
+            {"id": 18446744073709551615u64, "name": "a"}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+        assert!(encoded.contains("18446744073709551615"));
+
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded[0]["id"], json!(18446744073709551615u64));
+    }
+
+    #[test]
+    fn test_is_uniform_array_nested_objects() {
+        let arr = vec![json!({"id": 1, "nested": {"a": 1}})];
+        let (uniform, _) = is_uniform_array(&arr);
+        assert!(!uniform); // Contains nested object
+    }
+
+    #[test]
+    fn test_is_primitive_array() {
+        assert!(is_primitive_array(&[json!(1), json!("two"), json!(true)]));
+        assert!(!is_primitive_array(&[json!({"a": 1})]));
+        assert!(!is_primitive_array(&[json!([1, 2])]));
+    }
+
+    #[test]
+    fn test_split_row_simple() {
+        let row = split_row("a\tb\tc", "\t");
+        assert_eq!(row, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_split_row_quoted() {
+        let row = split_row("\"a\tb\"\tc", "\t");
+        assert_eq!(row, vec!["\"a\tb\"", "c"]);
+    }
+
+    #[test]
+    fn test_split_row_escaped_quote() {
+        let row = split_row("\"a\\\"b\"\tc", "\t");
+        assert_eq!(row, vec!["\"a\\\"b\"", "c"]);
+    }
+
+    #[test]
+    fn test_get_indent_depth() {
+        assert_eq!(get_indent_depth("no indent"), 0);
+        assert_eq!(get_indent_depth("  one level"), 1);
+        assert_eq!(get_indent_depth("    two levels"), 2);
+    }
+
+    // ========================================================================
+    // Edge cases
+    // ========================================================================
+
+    #[test]
+    fn test_encode_special_floats() {
+        let data = json!({
+            "nan": null,  // NaN becomes null in JSON
+            "inf": null   // Infinity becomes null in JSON
+        });
+        let encoded = encode(&data, false).unwrap();
+        assert!(encoded.contains("null"));
+    }
+
+    #[test]
+    fn test_unicode_strings() {
+        let data = json!({"text": "Hello ‰∏ñÁïå üåç"});
+        let encoded = encode(&data, true).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded["text"], "Hello ‰∏ñÁïå üåç");
+    }
+
+    #[test]
+    fn test_long_string() {
+        let long = "x".repeat(1000);
+        let data = json!({"text": long});
+        let encoded = encode(&data, true).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded["text"].as_str().unwrap().len(), 1000);
+    }
+
+    #[test]
+    fn test_deeply_nested() {
+        let data = json!({
+            "a": {
+                "b": {
+                    "c": {
+                        "d": "deep"
+                    }
+                }
+            }
+        });
+        let encoded = encode(&data, true).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded["a"]["b"]["c"]["d"], "deep");
+    }
+
+    #[test]
+    fn test_array_of_mixed_objects() {
+        let data = json!([
+            {"type": "a", "value": 1},
+            {"type": "b", "extra": "field"}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert!(decoded.is_array());
+        assert_eq!(decoded.as_array().unwrap().len(), 2);
+    }
+
+    // ========================================================================
+    // SerializeOptions tests
+    // ========================================================================
+
+    #[test]
+    fn test_encode_with_custom_indent_width() {
+        let data = json!({"outer": {"inner": "value"}});
+        let options = SerializeOptions {
+            indent: Some(4),
+            sort_keys: false,
+        };
+        let encoded = encode_with_options(&data, false, &options).unwrap();
+        assert!(encoded.contains("    inner: value"));
+    }
+
+    #[test]
+    fn test_encode_with_sort_keys() {
+        let data = json!({"zeta": 1, "alpha": 2});
+        let options = SerializeOptions {
+            indent: None,
+            sort_keys: true,
+        };
+        let encoded = encode_with_options(&data, false, &options).unwrap();
+        let alpha_pos = encoded.find("alpha").unwrap();
+        let zeta_pos = encoded.find("zeta").unwrap();
+        assert!(alpha_pos < zeta_pos);
+    }
+
+    // ========================================================================
+    // Typed serde round-trip tests
+    // ========================================================================
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+    struct Quote {
+        symbol: String,
+        price: f64,
+    }
+
+    #[test]
+    fn test_to_string_vec_of_structs_is_tabular() {
+        let data = vec![
+            Quote { symbol: "AAPL".to_string(), price: 150.0 },
+            Quote { symbol: "MSFT".to_string(), price: 300.0 },
+        ];
+        let encoded = to_string(&data, true).unwrap();
+        assert!(encoded.contains("[2]{"));
+        assert!(encoded.contains("AAPL"));
+    }
 
-    while idx < lines.len() {
-        let line = lines[idx];
-        if line.trim().is_empty() || line.trim().starts_with('#') {
-            idx += 1;
-            continue;
-        }
+    #[test]
+    fn test_from_str_round_trips_through_to_string() {
+        let data = vec![
+            Quote { symbol: "AAPL".to_string(), price: 150.0 },
+            Quote { symbol: "MSFT".to_string(), price: 300.0 },
+        ];
+        let encoded = to_string(&data, true).unwrap();
+        let decoded: Vec<Quote> = from_str(&encoded).unwrap();
+        assert_eq!(decoded, data);
+    }
 
-        let line_depth = get_indent_depth(line);
-        if line_depth < base_depth {
-            break;
+    #[test]
+    fn test_to_string_honors_serde_rename() {
+        #[derive(serde::Serialize)]
+        struct Renamed {
+            #[serde(rename = "ticker")]
+            symbol: String,
         }
+        let encoded = to_string(&Renamed { symbol: "AAPL".to_string() }, false).unwrap();
+        assert!(encoded.contains("ticker"));
+        assert!(!encoded.contains("symbol"));
+    }
 
-        let stripped = line.trim();
+    #[test]
+    fn test_from_str_missing_field_errors() {
+        let payload = "@AGON rows\n\nsymbol: AAPL";
+        let result: Result<Quote> = from_str(payload);
+        assert!(matches!(result, Err(AgonError::JsonError(_))));
+    }
 
-        // Check for array patterns first
-        if let Some(caps) = TABULAR_HEADER_RE.captures(stripped) {
-            let (nested, new_idx) = decode_tabular_array(lines, idx, line_depth, delimiter, &caps)?;
-            if let Value::Object(map) = nested {
-                for (k, v) in map {
-                    result.insert(k, v);
-                }
-            }
-            idx = new_idx;
-            continue;
-        }
+    // ========================================================================
+    // RowReader / RowWriter tests
+    // ========================================================================
 
-        if let Some(caps) = PRIMITIVE_ARRAY_RE.captures(stripped) {
-            let values_part = caps.get(3).map(|m| m.as_str()).unwrap_or("").trim();
-            if !values_part.is_empty() {
-                let (nested, new_idx) = decode_primitive_array(&caps, delimiter, idx)?;
-                if let Value::Object(map) = nested {
-                    for (k, v) in map {
-                        result.insert(k, v);
-                    }
-                }
-                idx = new_idx;
-                continue;
-            }
-        }
+    #[test]
+    fn test_row_reader_yields_one_object_per_row() {
+        let payload = "@AGON rows\n\n[2]{id\tname}\n1\tAlice\n2\tBob";
+        let mut reader = row_reader(payload.as_bytes()).unwrap();
+        assert_eq!(reader.fields(), &["id".to_string(), "name".to_string()]);
+
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(first, json!({"id": 1, "name": "Alice"}));
+        let second = reader.next().unwrap().unwrap();
+        assert_eq!(second, json!({"id": 2, "name": "Bob"}));
+        assert!(reader.next().is_none());
+    }
 
-        if let Some(caps) = LIST_ARRAY_RE.captures(stripped) {
-            let (nested, new_idx) = decode_list_array(lines, idx, line_depth, delimiter, &caps)?;
-            if let Value::Object(map) = nested {
-                for (k, v) in map {
-                    result.insert(k, v);
-                }
-            }
-            idx = new_idx;
-            continue;
-        }
+    #[test]
+    fn test_row_reader_honors_custom_delimiter() {
+        let payload = "@AGON rows\n@D=,\n\n[1]{id,name}\n1,Alice";
+        let mut reader = row_reader(payload.as_bytes()).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap(), json!({"id": 1, "name": "Alice"}));
+    }
 
-        if let Some(caps) = KEY_VALUE_RE.captures(stripped) {
-            let key = caps.get(1).map(|m| m.as_str()).unwrap_or("").trim();
-            let val_str = caps.get(2).map(|m| m.as_str()).unwrap_or("").trim();
+    #[test]
+    fn test_row_reader_rejects_named_tabular_array() {
+        let payload = "@AGON rows\n\nitems[1]{id}\n1";
+        let err = row_reader(payload.as_bytes()).unwrap_err();
+        assert!(matches!(err, AgonError::DecodingError(_)));
+    }
 
-            if !val_str.is_empty() {
-                result.insert(key.to_string(), parse_primitive(val_str));
-                idx += 1;
-            } else {
-                idx += 1;
-                if idx < lines.len() {
-                    let next_depth = get_indent_depth(lines[idx]);
-                    if next_depth > line_depth {
-                        let (nested, new_idx) = decode_value(lines, idx, next_depth, delimiter)?;
-                        result.insert(key.to_string(), nested);
-                        idx = new_idx;
-                    } else {
-                        result.insert(key.to_string(), Value::Object(Map::new()));
-                    }
-                } else {
-                    result.insert(key.to_string(), Value::Object(Map::new()));
-                }
-            }
-        } else {
-            break;
-        }
+    #[test]
+    fn test_row_reader_matches_decode_output() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2, "name": "Bob"}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+
+        let via_decode = decode(&encoded).unwrap();
+        let via_reader: Vec<Value> = row_reader(encoded.as_bytes())
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(via_decode, Value::Array(via_reader));
     }
 
-    Ok((Value::Object(result), idx))
-}
+    #[test]
+    fn test_row_writer_round_trips_through_row_reader() {
+        let fields = vec!["id".to_string(), "name".to_string()];
+        let mut writer = row_writer(Vec::new(), fields, true);
+        writer.push_row(&[json!(1), json!("Alice")]);
+        writer.push_row(&[json!(2), json!("Bob")]);
+        let bytes = writer.finish().unwrap();
+
+        let rows: Vec<Value> = row_reader(bytes.as_slice())
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                json!({"id": 1, "name": "Alice"}),
+                json!({"id": 2, "name": "Bob"}),
+            ]
+        );
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::json;
+    #[test]
+    fn test_row_writer_quotes_values_needing_it() {
+        let fields = vec!["note".to_string()];
+        let mut writer = row_writer(Vec::new(), fields, true);
+        writer.push_row(&[json!("has\ttab")]);
+        let bytes = writer.finish().unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.contains("\"has\\ttab\""));
+    }
 
     // ========================================================================
-    // Encoding tests
+    // Dotted-path flattening tests
     // ========================================================================
 
     #[test]
-    fn test_encode_simple_array() {
+    fn test_encode_flattened_emits_dotted_columns() {
         let data = json!([
-            {"id": 1, "name": "Alice"},
-            {"id": 2, "name": "Bob"}
+            {"id": 1, "user": {"name": "Alice", "addr": {"city": "Seattle"}}},
+            {"id": 2, "user": {"name": "Bob", "addr": {"city": "Austin"}}},
         ]);
-        let encoded = encode(&data, false).unwrap();
-        assert!(encoded.contains("[2]{"));
-        assert!(encoded.contains("Alice"));
+        let encoded = encode_flattened(&data, false, 3).unwrap();
+        assert!(encoded.contains("user.name"));
+        assert!(encoded.contains("user.addr.city"));
+        assert!(encoded.contains("Seattle"));
     }
 
     #[test]
-    fn test_encode_with_header() {
-        let data = json!({"name": "test"});
-        let encoded = encode(&data, true).unwrap();
-        assert!(encoded.starts_with("@AGON rows"));
+    fn test_decode_reconstructs_nested_object_from_dotted_columns() {
+        let payload = "@AGON rows\n\n[1]{id\tuser.name\tuser.addr.city}\n1\tAlice\tSeattle";
+        let decoded = decode(payload).unwrap();
+        let row = &decoded.as_array().unwrap()[0];
+        assert_eq!(row["user"]["name"], "Alice");
+        assert_eq!(row["user"]["addr"]["city"], "Seattle");
     }
 
     #[test]
-    fn test_encode_without_header() {
-        let data = json!({"name": "test"});
-        let encoded = encode(&data, false).unwrap();
-        assert!(!encoded.contains("@AGON"));
+    fn test_flatten_and_decode_round_trip_nested_records() {
+        let data = json!([
+            {"id": 1, "user": {"name": "Alice", "addr": {"city": "Seattle"}}},
+            {"id": 2, "user": {"name": "Bob", "addr": {"city": "Austin"}}},
+        ]);
+        let encoded = encode_flattened(&data, true, 3).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded, data);
     }
 
     #[test]
-    fn test_encode_primitives() {
-        let data = json!({
-            "string": "hello",
-            "number": 42,
-            "float": 3.15,
-            "bool_true": true,
-            "bool_false": false,
-            "null_val": null
-        });
-        let encoded = encode(&data, false).unwrap();
-        assert!(encoded.contains("string: hello"));
-        assert!(encoded.contains("number: 42"));
-        assert!(encoded.contains("float: 3.15"));
-        assert!(encoded.contains("bool_true: true"));
-        assert!(encoded.contains("bool_false: false"));
-        assert!(encoded.contains("null_val: null"));
+    fn test_encode_flattened_fills_missing_leaf_with_empty() {
+        let data = json!([
+            {"id": 1, "user": {"name": "Alice"}},
+            {"id": 2, "user": {"name": "Bob", "nickname": "Bobby"}},
+        ]);
+        let encoded = encode_flattened(&data, true, 2).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        let rows = decoded.as_array().unwrap();
+        assert!(rows[0].get("user").unwrap().get("nickname").is_none());
+        assert_eq!(rows[1]["user"]["nickname"], "Bobby");
     }
 
     #[test]
-    fn test_encode_empty_array() {
-        let data = json!({"items": []});
-        let encoded = encode(&data, false).unwrap();
-        assert!(encoded.contains("items[0]:"));
+    fn test_encode_flattened_respects_max_depth() {
+        let data = json!([{"a": {"b": {"c": 1}}}]);
+        // max_depth 1 only flattens one level -- "a.b" becomes a leaf
+        // whose value is the still-nested `{"c": 1}` object.
+        let encoded = encode_flattened(&data, false, 1).unwrap();
+        assert!(encoded.contains("a.b"));
+        assert!(!encoded.contains("a.b.c"));
     }
 
     #[test]
-    fn test_encode_primitive_array() {
-        let data = json!({"nums": [1, 2, 3]});
-        let encoded = encode(&data, false).unwrap();
-        assert!(encoded.contains("nums[3]:"));
+    fn test_encode_flattened_quotes_keys_containing_dots() {
+        let data = json!([{"a.b": {"c": 1}}]);
+        let encoded = encode_flattened(&data, false, 2).unwrap();
+        assert!(encoded.contains("\"a.b\".c"));
     }
 
     #[test]
-    fn test_encode_nested_object() {
-        let data = json!({
-            "outer": {
-                "inner": {
-                    "value": 42
-                }
-            }
-        });
-        let encoded = encode(&data, false).unwrap();
-        assert!(encoded.contains("outer:"));
-        assert!(encoded.contains("inner:"));
-        assert!(encoded.contains("value: 42"));
+    fn test_flatten_quoted_dotted_key_round_trips() {
+        let data = json!([{"a.b": {"c": 1}}]);
+        let encoded = encode_flattened(&data, true, 2).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_encode_flattened_falls_back_when_leaf_is_array() {
+        let data = json!([{"id": 1, "tags": [1, 2, 3]}]);
+        let encoded = encode_flattened(&data, false, 3).unwrap();
+        // No tabular header: fell back to encode()'s list format.
+        assert!(!encoded.contains("[1]{"));
+    }
+
+    #[test]
+    fn test_encode_flattened_falls_back_on_scalar_object_collision() {
+        // Record A has a scalar at "user", record B has a nested object
+        // there -- the union of columns would hold both "user" and
+        // "user.name", which can't be reassembled unambiguously.
+        let data = json!([
+            {"user": "Alice"},
+            {"user": {"name": "Bob"}},
+        ]);
+        let encoded = encode_flattened(&data, false, 2).unwrap();
+        assert!(!encoded.contains("[2]{"));
+    }
+
+    #[test]
+    fn test_has_leaf_prefix_collision_detects_prefix_pair() {
+        assert!(has_leaf_prefix_collision(&[
+            "user".to_string(),
+            "user.name".to_string()
+        ]));
+        assert!(!has_leaf_prefix_collision(&[
+            "user.name".to_string(),
+            "user.addr.city".to_string()
+        ]));
+    }
+
+    #[test]
+    fn test_split_dotted_path_respects_quoted_segment() {
+        assert_eq!(
+            split_dotted_path("\"a.b\".c"),
+            vec!["a.b".to_string(), "c".to_string()]
+        );
+        assert_eq!(split_dotted_path("plain"), vec!["plain".to_string()]);
     }
 
     // ========================================================================
-    // Decoding tests
+    // Normalize / auto-delimiter tests
     // ========================================================================
 
     #[test]
-    fn test_decode_empty_payload() {
-        let result = decode("");
-        assert!(result.is_err());
+    fn test_choose_delimiter_prefers_tab_when_safe() {
+        let data = json!({"name": "Alice"});
+        assert_eq!(choose_delimiter(&data), "\t");
     }
 
     #[test]
-    fn test_decode_invalid_header() {
-        let result = decode("invalid header");
-        assert!(result.is_err());
+    fn test_choose_delimiter_skips_candidate_found_in_string() {
+        let data = json!({"name": "has\ttab"});
+        assert_eq!(choose_delimiter(&data), ",");
     }
 
     #[test]
-    fn test_decode_header_only() {
-        let result = decode("@AGON rows\n\n").unwrap();
-        assert!(result.is_null());
+    fn test_choose_delimiter_tries_each_candidate_in_order() {
+        let data = json!({"name": "has\ttab, and comma"});
+        assert_eq!(choose_delimiter(&data), "|");
     }
 
     #[test]
-    fn test_decode_simple_object() {
-        let payload = "@AGON rows\n\nname: Alice\nage: 30";
-        let decoded = decode(payload).unwrap();
-        assert_eq!(decoded["name"], "Alice");
-        assert_eq!(decoded["age"], 30);
+    fn test_choose_delimiter_falls_back_to_default_when_all_collide() {
+        let data = json!({"name": "has\ttab, pipe| and semi;"});
+        assert_eq!(choose_delimiter(&data), DEFAULT_DELIMITER);
     }
 
     #[test]
-    fn test_decode_tabular_array() {
-        let payload = "@AGON rows\n\n[2]{id\tname}\n1\tAlice\n2\tBob";
-        let decoded = decode(payload).unwrap();
-        assert!(decoded.is_array());
-        let arr = decoded.as_array().unwrap();
-        assert_eq!(arr.len(), 2);
-        assert_eq!(arr[0]["id"], 1);
-        assert_eq!(arr[0]["name"], "Alice");
+    fn test_encode_auto_delimiter_omits_d_line_for_default() {
+        let data = json!({"name": "Alice"});
+        let encoded = encode_auto_delimiter(&data, true).unwrap();
+        assert!(!encoded.contains("@D="));
     }
 
     #[test]
-    fn test_decode_named_tabular_array() {
-        let payload = "@AGON rows\n\nusers[2]{id\tname}\n1\tAlice\n2\tBob";
-        let decoded = decode(payload).unwrap();
-        assert!(decoded.is_object());
-        let users = decoded["users"].as_array().unwrap();
-        assert_eq!(users.len(), 2);
+    fn test_encode_auto_delimiter_emits_d_line_when_overridden() {
+        let data = json!({"name": "has\ttab"});
+        let encoded = encode_auto_delimiter(&data, true).unwrap();
+        assert!(encoded.contains("@D=,"));
     }
 
     #[test]
-    fn test_decode_primitive_array() {
-        let payload = "@AGON rows\n\nnums[3]: 1\t2\t3";
-        let decoded = decode(payload).unwrap();
-        let nums = decoded["nums"].as_array().unwrap();
-        assert_eq!(nums.len(), 3);
-        assert_eq!(nums[0], 1);
-        assert_eq!(nums[1], 2);
-        assert_eq!(nums[2], 3);
+    fn test_encode_auto_delimiter_round_trips_through_decode() {
+        let data = json!([
+            {"id": 1, "note": "has\ttab"},
+            {"id": 2, "note": "plain"},
+        ]);
+        let encoded = encode_auto_delimiter(&data, true).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded, data);
     }
 
     #[test]
-    fn test_decode_custom_delimiter() {
-        let payload = "@AGON rows\n@D=\\t\n\nname: test";
-        let decoded = decode(payload).unwrap();
-        assert_eq!(decoded["name"], "test");
+    fn test_normalize_is_idempotent() {
+        let payload = "@AGON rows\n\n[2]{id\tname}\n1\tAlice\n2\tBob";
+        let once = normalize(payload).unwrap();
+        let twice = normalize(&once).unwrap();
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn test_normalize_preserves_data() {
+        let payload = "@AGON rows\n\nname: Alice\nage: 30";
+        let normalized = normalize(payload).unwrap();
+        let decoded = decode(&normalized).unwrap();
+        assert_eq!(decoded["name"], "Alice");
+        assert_eq!(decoded["age"], 30);
+    }
+
+    #[test]
+    fn test_normalize_picks_non_colliding_delimiter() {
+        let payload = "@AGON rows\n\nnote: has\ttab";
+        let normalized = normalize(payload).unwrap();
+        // `note`'s decoded value contains a real tab, so normalize should
+        // pick the next safe candidate, a comma.
+        assert!(normalized.contains("@D=,"));
     }
 
     // ========================================================================
-    // Roundtrip tests
+    // RowSchema tests
     // ========================================================================
 
+    fn quote_schema() -> RowSchema {
+        RowSchema::new()
+            .column("symbol", ColumnType::String, false)
+            .column("price", ColumnType::Float, false)
+            .column("note", ColumnType::String, true)
+    }
+
     #[test]
-    fn test_roundtrip() {
-        let data = json!({
-            "users": [
-                {"id": 1, "name": "Alice"},
-                {"id": 2, "name": "Bob"}
-            ]
-        });
-        let encoded = encode(&data, true).unwrap();
-        let decoded = decode(&encoded).unwrap();
+    fn test_check_schema_accepts_matching_rows() {
+        let data = json!([
+            {"symbol": "AAPL", "price": 150.0, "note": null},
+            {"symbol": "MSFT", "price": 300.0, "note": "watch"},
+        ]);
+        assert!(check_schema(&data, &quote_schema()).is_ok());
+    }
 
-        assert!(decoded.is_object());
-        let users = decoded.get("users").unwrap();
-        assert!(users.is_array());
-        assert_eq!(users.as_array().unwrap().len(), 2);
+    #[test]
+    fn test_check_schema_rejects_non_array() {
+        let data = json!({"symbol": "AAPL"});
+        let err = check_schema(&data, &quote_schema()).unwrap_err();
+        assert!(matches!(err, AgonError::SchemaError { .. }));
     }
 
     #[test]
-    fn test_roundtrip_nested_object() {
-        let data = json!({
-            "company": {
-                "name": "ACME",
-                "address": {
-                    "city": "Seattle"
-                }
+    fn test_check_schema_rejects_wrong_type() {
+        let data = json!([{"symbol": "AAPL", "price": "not a number", "note": null}]);
+        let err = check_schema(&data, &quote_schema()).unwrap_err();
+        match err {
+            AgonError::SchemaError { expected, found, .. } => {
+                assert_eq!(expected, "float");
+                assert_eq!(found, "string");
             }
-        });
-        let encoded = encode(&data, true).unwrap();
-        let decoded = decode(&encoded).unwrap();
-        assert_eq!(decoded["company"]["name"], "ACME");
-        assert_eq!(decoded["company"]["address"]["city"], "Seattle");
+            _ => panic!("expected SchemaError"),
+        }
     }
 
     #[test]
-    fn test_roundtrip_empty_object() {
-        let data = json!({});
-        let encoded = encode(&data, true).unwrap();
-        let decoded = decode(&encoded).unwrap();
-        assert!(
-            decoded.is_null() || (decoded.is_object() && decoded.as_object().unwrap().is_empty())
-        );
+    fn test_check_schema_rejects_null_in_non_nullable_column() {
+        let data = json!([{"symbol": "AAPL", "price": null, "note": null}]);
+        let err = check_schema(&data, &quote_schema()).unwrap_err();
+        assert!(matches!(err, AgonError::SchemaError { .. }));
     }
 
     #[test]
-    fn test_roundtrip_mixed_array() {
-        let data = json!({
-            "items": [1, "two", true, null]
-        });
-        let encoded = encode(&data, true).unwrap();
-        let decoded = decode(&encoded).unwrap();
-        let items = decoded["items"].as_array().unwrap();
-        assert_eq!(items.len(), 4);
+    fn test_check_schema_allows_null_in_nullable_column() {
+        let data = json!([{"symbol": "AAPL", "price": 150.0, "note": null}]);
+        assert!(check_schema(&data, &quote_schema()).is_ok());
     }
 
-    // ========================================================================
-    // Helper function tests
-    // ========================================================================
-
     #[test]
-    fn test_needs_quote_empty() {
-        assert!(needs_quote("", "\t"));
+    fn test_encode_with_schema_succeeds_for_valid_data() {
+        let data = json!([{"symbol": "AAPL", "price": 150.0, "note": null}]);
+        let encoded = encode_with_schema(&data, &quote_schema(), true).unwrap();
+        assert!(encoded.contains("AAPL"));
     }
 
     #[test]
-    fn test_needs_quote_whitespace() {
-        assert!(needs_quote("  padded  ", "\t"));
-        assert!(needs_quote(" leading", "\t"));
-        assert!(needs_quote("trailing ", "\t"));
+    fn test_encode_with_schema_fails_for_invalid_data() {
+        let data = json!([{"symbol": "AAPL", "price": "bad", "note": null}]);
+        let err = encode_with_schema(&data, &quote_schema(), true).unwrap_err();
+        assert!(matches!(err, AgonError::SchemaError { .. }));
     }
 
     #[test]
-    fn test_needs_quote_delimiter() {
-        assert!(needs_quote("has\ttab", "\t"));
-        assert!(needs_quote("has,comma", ","));
+    fn test_decode_with_schema_reads_valid_rows() {
+        let payload = "@AGON rows\n\n[2]{symbol\tprice\tnote}\nAAPL\t150\t\nMSFT\t300\twatch";
+        let decoded = decode_with_schema(payload, &quote_schema()).unwrap();
+        let rows = decoded.as_array().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["symbol"], "AAPL");
+        assert_eq!(rows[1]["note"], "watch");
     }
 
     #[test]
-    fn test_needs_quote_special_chars() {
-        assert!(needs_quote("has\nnewline", "\t"));
-        assert!(needs_quote("has\"quote", "\t"));
-        assert!(needs_quote("has\\backslash", "\t"));
+    fn test_decode_with_schema_rejects_header_column_mismatch() {
+        let payload = "@AGON rows\n\n[1]{symbol\tprice}\nAAPL\t150";
+        let err = decode_with_schema(payload, &quote_schema()).unwrap_err();
+        assert!(matches!(err, AgonError::SchemaError { .. }));
     }
 
     #[test]
-    fn test_needs_quote_special_prefix() {
-        assert!(needs_quote("@mention", "\t"));
-        assert!(needs_quote("#comment", "\t"));
-        assert!(needs_quote("-item", "\t"));
+    fn test_decode_with_schema_rejects_row_arity_mismatch() {
+        let payload = "@AGON rows\n\n[1]{symbol\tprice\tnote}\nAAPL\t150\twatch\textra";
+        let err = decode_with_schema(payload, &quote_schema()).unwrap_err();
+        match err {
+            AgonError::SchemaError { expected, found, .. } => {
+                assert_eq!(expected, "3 fields");
+                assert_eq!(found, "4 fields");
+            }
+            _ => panic!("expected SchemaError"),
+        }
     }
 
     #[test]
-    fn test_needs_quote_looks_like_primitive() {
-        assert!(needs_quote("true", "\t"));
-        assert!(needs_quote("false", "\t"));
-        assert!(needs_quote("null", "\t"));
-        assert!(needs_quote("42", "\t"));
-        assert!(needs_quote("3.14", "\t"));
+    fn test_decode_with_schema_rejects_type_mismatch() {
+        let payload = "@AGON rows\n\n[1]{symbol\tprice\tnote}\nAAPL\tfree\t";
+        let err = decode_with_schema(payload, &quote_schema()).unwrap_err();
+        match err {
+            AgonError::SchemaError { expected, found, .. } => {
+                assert_eq!(expected, "float");
+                assert_eq!(found, "string");
+            }
+            _ => panic!("expected SchemaError"),
+        }
     }
 
     #[test]
-    fn test_needs_quote_normal_string() {
-        assert!(!needs_quote("hello", "\t"));
-        assert!(!needs_quote("normal string", "\t"));
+    fn test_decode_with_schema_round_trips_encode_with_schema() {
+        // A missing "note" doesn't round-trip back to an explicit
+        // `null` -- the same empty-field-is-omitted behavior `decode`
+        // already has (see `decode_tabular_array`) -- so only the row with
+        // a non-null note is used here to keep the equality check honest.
+        let data = json!([{"symbol": "AAPL", "price": 150.0, "note": "watch"}]);
+        let schema = quote_schema();
+        let encoded = encode_with_schema(&data, &schema, true).unwrap();
+        let decoded = decode_with_schema(&encoded, &schema).unwrap();
+        assert_eq!(decoded, data);
     }
 
+    // ========================================================================
+    // Parser / decode_events tests
+    // ========================================================================
+
     #[test]
-    fn test_quote_string() {
-        assert_eq!(quote_string("hello"), "\"hello\"");
-        assert_eq!(quote_string("say \"hi\""), "\"say \\\"hi\\\"\"");
-        assert_eq!(quote_string("line\nbreak"), "\"line\\nbreak\"");
-        assert_eq!(quote_string("tab\there"), "\"tab\\there\"");
+    fn test_parser_yields_array_and_row_events_in_order() {
+        let payload = "@AGON rows\n\n[2]{id\tname}\n1\tAlice\n2\tBob";
+        let events: Vec<AgonEvent> = parser(payload.as_bytes()).unwrap().collect();
+        assert_eq!(
+            events,
+            vec![
+                AgonEvent::ArrayStart(2),
+                AgonEvent::ObjectStart,
+                AgonEvent::Key("id".to_string()),
+                AgonEvent::Primitive(json!(1)),
+                AgonEvent::Key("name".to_string()),
+                AgonEvent::Primitive(json!("Alice")),
+                AgonEvent::ObjectEnd,
+                AgonEvent::ObjectStart,
+                AgonEvent::Key("id".to_string()),
+                AgonEvent::Primitive(json!(2)),
+                AgonEvent::Key("name".to_string()),
+                AgonEvent::Primitive(json!("Bob")),
+                AgonEvent::ObjectEnd,
+                AgonEvent::ArrayEnd,
+            ]
+        );
     }
 
     #[test]
-    fn test_unquote_string() {
-        assert_eq!(unquote_string("\"hello\""), "hello");
-        assert_eq!(unquote_string("\"say \\\"hi\\\"\""), "say \"hi\"");
-        assert_eq!(unquote_string("\"line\\nbreak\""), "line\nbreak");
-        assert_eq!(unquote_string("unquoted"), "unquoted");
+    fn test_parser_is_exhausted_after_array_end() {
+        let payload = "@AGON rows\n\n[1]{id}\n1";
+        let mut p = parser(payload.as_bytes()).unwrap();
+        // ArrayStart, ObjectStart, Key, Primitive, ObjectEnd, ArrayEnd.
+        for _ in 0..6 {
+            assert!(p.next().is_some());
+        }
+        assert!(p.next().is_none());
+        assert!(p.next().is_none());
     }
 
     #[test]
-    fn test_parse_primitive_null() {
-        assert_eq!(parse_primitive("null"), Value::Null);
-        assert_eq!(parse_primitive("NULL"), Value::Null);
-        assert_eq!(parse_primitive(""), Value::Null);
+    fn test_parser_empty_array_yields_only_start_and_end() {
+        let payload = "@AGON rows\n\n[0]{id}";
+        let events: Vec<AgonEvent> = parser(payload.as_bytes()).unwrap().collect();
+        assert_eq!(
+            events,
+            vec![AgonEvent::ArrayStart(0), AgonEvent::ArrayEnd]
+        );
     }
 
     #[test]
-    fn test_parse_primitive_bool() {
-        assert_eq!(parse_primitive("true"), Value::Bool(true));
-        assert_eq!(parse_primitive("TRUE"), Value::Bool(true));
-        assert_eq!(parse_primitive("false"), Value::Bool(false));
-        assert_eq!(parse_primitive("FALSE"), Value::Bool(false));
+    fn test_parser_honors_custom_delimiter() {
+        let payload = "@AGON rows\n@D=,\n\n[1]{id,name}\n1,Alice";
+        let events: Vec<AgonEvent> = parser(payload.as_bytes()).unwrap().collect();
+        assert!(events.contains(&AgonEvent::Primitive(json!("Alice"))));
     }
 
     #[test]
-    fn test_parse_primitive_number() {
-        assert_eq!(parse_primitive("42"), json!(42));
-        assert_eq!(parse_primitive("-17"), json!(-17));
-        assert_eq!(parse_primitive("3.15"), json!(3.15));
-        assert_eq!(parse_primitive("1e10"), json!(1e10));
+    fn test_parser_rejects_named_tabular_array() {
+        let payload = "@AGON rows\n\nitems[1]{id}\n1";
+        let err = parser(payload.as_bytes()).unwrap_err();
+        assert!(matches!(err, AgonError::DecodingError(_)));
     }
 
     #[test]
-    fn test_parse_primitive_string() {
-        assert_eq!(parse_primitive("hello"), Value::String("hello".to_string()));
+    fn test_parser_stack_tracks_row_index_and_field_key() {
+        let mut p = parser("@AGON rows\n\n[1]{id\tname}\n1\tAlice".as_bytes()).unwrap();
+
+        assert_eq!(p.next(), Some(AgonEvent::ArrayStart(1)));
+        assert!(p.stack().is_empty());
+
+        assert_eq!(p.next(), Some(AgonEvent::ObjectStart));
+        assert_eq!(p.stack(), &[StackElement::Index(0)]);
+
+        assert_eq!(p.next(), Some(AgonEvent::Key("id".to_string())));
         assert_eq!(
-            parse_primitive("\"quoted\""),
-            Value::String("quoted".to_string())
+            p.stack(),
+            &[StackElement::Index(0), StackElement::Key("id".to_string())]
+        );
+
+        p.next(); // Primitive(1)
+        assert_eq!(p.next(), Some(AgonEvent::Key("name".to_string())));
+        assert_eq!(
+            p.stack(),
+            &[
+                StackElement::Index(0),
+                StackElement::Key("name".to_string())
+            ]
         );
+
+        p.next(); // Primitive("Alice")
+        assert_eq!(p.next(), Some(AgonEvent::ObjectEnd));
+        assert_eq!(p.stack(), &[StackElement::Index(0)]);
+
+        assert_eq!(p.next(), Some(AgonEvent::ArrayEnd));
+        assert!(p.stack().is_empty());
     }
 
     #[test]
-    fn test_parse_delimiter() {
-        assert_eq!(parse_delimiter("\\t"), "\t");
-        assert_eq!(parse_delimiter("\\n"), "\n");
-        assert_eq!(parse_delimiter(","), ",");
+    fn test_decode_events_matches_decode_for_unnamed_tabular_array() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2, "name": "Bob"}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+
+        let via_decode = decode(&encoded).unwrap();
+        let via_events = decode_events(&encoded).unwrap();
+
+        assert_eq!(via_decode, via_events);
     }
 
     #[test]
-    fn test_is_uniform_array_empty() {
-        let arr: Vec<Value> = vec![];
-        let (uniform, _) = is_uniform_array(&arr);
-        assert!(!uniform);
+    fn test_decode_events_empty_array() {
+        let payload = "@AGON rows\n\n[0]{id}";
+        assert_eq!(decode_events(payload).unwrap(), json!([]));
     }
 
     #[test]
-    fn test_is_uniform_array_primitives() {
-        let arr = vec![json!(1), json!(2), json!(3)];
-        let (uniform, _) = is_uniform_array(&arr);
-        assert!(!uniform);
+    fn test_from_reader_matches_decode_for_unnamed_tabular_array() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2, "name": "Bob"}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+
+        let via_decode = decode(&encoded).unwrap();
+        let via_reader = from_reader(encoded.as_bytes()).unwrap();
+
+        assert_eq!(via_decode, via_reader);
     }
 
     #[test]
-    fn test_is_uniform_array_uniform_objects() {
-        let arr = vec![json!({"id": 1, "name": "a"}), json!({"id": 2, "name": "b"})];
-        let (uniform, fields) = is_uniform_array(&arr);
-        assert!(uniform);
-        assert!(fields.contains(&"id".to_string()));
-        assert!(fields.contains(&"name".to_string()));
+    fn test_from_reader_rejects_named_tabular_array() {
+        let payload = "@AGON rows\n\nusers[1]{id}\n1";
+        let err = from_reader(payload.as_bytes()).unwrap_err();
+        assert!(matches!(err, AgonError::DecodingError(_)));
     }
 
+    // ========================================================================
+    // to_writer tests
+    // ========================================================================
+
     #[test]
-    fn test_is_uniform_array_nested_objects() {
-        let arr = vec![json!({"id": 1, "nested": {"a": 1}})];
-        let (uniform, _) = is_uniform_array(&arr);
-        assert!(!uniform); // Contains nested object
+    fn test_to_writer_uniform_array_round_trips_through_from_reader() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2, "name": "Bob"}
+        ]);
+        let bytes = to_writer(Vec::new(), &data, true).unwrap();
+
+        let via_reader = from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(via_reader, data);
     }
 
     #[test]
-    fn test_is_primitive_array() {
-        assert!(is_primitive_array(&[json!(1), json!("two"), json!(true)]));
-        assert!(!is_primitive_array(&[json!({"a": 1})]));
-        assert!(!is_primitive_array(&[json!([1, 2])]));
+    fn test_to_writer_non_uniform_value_falls_back_to_encode() {
+        let data = json!({"name": "test"});
+        let bytes = to_writer(Vec::new(), &data, false).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert_eq!(text, encode(&data, false).unwrap());
     }
 
+    // ========================================================================
+    // encode_streaming tests
+    // ========================================================================
+
     #[test]
-    fn test_split_row_simple() {
-        let row = split_row("a\tb\tc", "\t");
-        assert_eq!(row, vec!["a", "b", "c"]);
+    fn test_encode_streaming_matches_to_writer_across_batch_sizes() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2, "name": "Bob"},
+            {"id": 3, "name": "Carol"},
+            {"id": 4, "name": "Dave"},
+            {"id": 5, "name": "Eve"}
+        ]);
+        let expected = to_writer(Vec::new(), &data, true).unwrap();
+
+        for batch_size in [1, 2, 3, 100] {
+            let bytes = encode_streaming(Vec::new(), &data, true, batch_size).unwrap();
+            assert_eq!(bytes, expected, "batch_size = {}", batch_size);
+        }
     }
 
     #[test]
-    fn test_split_row_quoted() {
-        let row = split_row("\"a\tb\"\tc", "\t");
-        assert_eq!(row, vec!["\"a\tb\"", "c"]);
+    fn test_encode_streaming_round_trips_through_from_reader() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2, "name": "Bob"}
+        ]);
+        let bytes = encode_streaming(Vec::new(), &data, true, 1).unwrap();
+
+        let via_reader = from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(via_reader, data);
     }
 
     #[test]
-    fn test_split_row_escaped_quote() {
-        let row = split_row("\"a\\\"b\"\tc", "\t");
-        assert_eq!(row, vec!["\"a\\\"b\"", "c"]);
+    fn test_encode_streaming_non_uniform_value_falls_back_to_encode() {
+        let data = json!({"name": "test"});
+        let bytes = encode_streaming(Vec::new(), &data, false, 10).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert_eq!(text, encode(&data, false).unwrap());
     }
 
     #[test]
-    fn test_get_indent_depth() {
-        assert_eq!(get_indent_depth("no indent"), 0);
-        assert_eq!(get_indent_depth("  one level"), 1);
-        assert_eq!(get_indent_depth("    two levels"), 2);
+    fn test_encode_streaming_fills_missing_fields_with_null() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2}
+        ]);
+        let bytes = encode_streaming(Vec::new(), &data, true, 1).unwrap();
+        let via_reader = from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(
+            via_reader,
+            json!([
+                {"id": 1, "name": "Alice"},
+                {"id": 2, "name": null}
+            ])
+        );
     }
 
     // ========================================================================
-    // Edge cases
+    // Serializer tests
     // ========================================================================
 
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Row {
+        id: i32,
+        name: String,
+    }
+
+    #[derive(serde::Serialize)]
+    enum Shape {
+        Unit,
+        Newtype(i32),
+        Tuple(i32, i32),
+        Struct { x: i32, y: i32 },
+    }
+
     #[test]
-    fn test_encode_special_floats() {
-        let data = json!({
-            "nan": null,  // NaN becomes null in JSON
-            "inf": null   // Infinity becomes null in JSON
-        });
-        let encoded = encode(&data, false).unwrap();
-        assert!(encoded.contains("null"));
+    fn test_to_string_primitive() {
+        assert_eq!(to_string(&42i32, false).unwrap(), "42");
+        assert_eq!(to_string(&"hello", false).unwrap(), "hello");
+        assert_eq!(to_string(&true, false).unwrap(), "true");
     }
 
     #[test]
-    fn test_unicode_strings() {
-        let data = json!({"text": "Hello ‰∏ñÁïå üåç"});
-        let encoded = encode(&data, true).unwrap();
-        let decoded = decode(&encoded).unwrap();
-        assert_eq!(decoded["text"], "Hello ‰∏ñÁïå üåç");
+    fn test_to_string_vec_of_structs_produces_tabular_block() {
+        let data = vec![
+            Row { id: 1, name: "Alice".to_string() },
+            Row { id: 2, name: "Bob".to_string() },
+        ];
+        let encoded = to_string(&data, false).unwrap();
+        assert!(encoded.contains("[2]{"));
+        assert!(encoded.contains("Alice"));
     }
 
     #[test]
-    fn test_long_string() {
-        let long = "x".repeat(1000);
-        let data = json!({"text": long});
-        let encoded = encode(&data, true).unwrap();
-        let decoded = decode(&encoded).unwrap();
-        assert_eq!(decoded["text"].as_str().unwrap().len(), 1000);
+    fn test_serialize_bytes_uses_tagged_json() {
+        let value = serde::Serializer::serialize_bytes(Serializer, &[1, 2, 3]).unwrap();
+        assert_eq!(value, bytes_to_tagged_json(&[1, 2, 3]));
     }
 
     #[test]
-    fn test_deeply_nested() {
-        let data = json!({
-            "a": {
-                "b": {
-                    "c": {
-                        "d": "deep"
-                    }
-                }
-            }
-        });
-        let encoded = encode(&data, true).unwrap();
-        let decoded = decode(&encoded).unwrap();
-        assert_eq!(decoded["a"]["b"]["c"]["d"], "deep");
+    fn test_to_string_option_none_is_null() {
+        #[derive(serde::Serialize)]
+        struct Opt {
+            value: Option<i32>,
+        }
+        let encoded = to_string(&Opt { value: None }, false).unwrap();
+        assert!(encoded.contains("value: null"));
     }
 
     #[test]
-    fn test_array_of_mixed_objects() {
-        let data = json!([
-            {"type": "a", "value": 1},
-            {"type": "b", "extra": "field"}
-        ]);
-        let encoded = encode(&data, true).unwrap();
-        let decoded = decode(&encoded).unwrap();
-        assert!(decoded.is_array());
-        assert_eq!(decoded.as_array().unwrap().len(), 2);
+    fn test_to_string_unit_variant_is_string() {
+        let encoded = to_string(&Shape::Unit, false).unwrap();
+        assert_eq!(encoded, "Unit");
+    }
+
+    #[test]
+    fn test_to_string_newtype_variant() {
+        let encoded = to_string(&Shape::Newtype(7), false).unwrap();
+        assert!(encoded.contains("Newtype: 7"));
+    }
+
+    #[test]
+    fn test_to_string_tuple_variant() {
+        let encoded = to_string(&Shape::Tuple(1, 2), false).unwrap();
+        assert!(encoded.contains("Tuple[2]: 1\t2"));
+    }
+
+    #[test]
+    fn test_to_string_struct_variant() {
+        let encoded = to_string(&Shape::Struct { x: 1, y: 2 }, false).unwrap();
+        assert!(encoded.contains("Struct:"));
+        assert!(encoded.contains("x: 1"));
+        assert!(encoded.contains("y: 2"));
+    }
+
+    #[test]
+    fn test_to_string_round_trips_through_from_str() {
+        let data = vec![
+            Row { id: 1, name: "Alice".to_string() },
+            Row { id: 2, name: "Bob".to_string() },
+        ];
+        let encoded = to_string(&data, true).unwrap();
+        let decoded: Vec<Row> = from_str(&encoded).unwrap();
+        assert_eq!(decoded, data);
     }
 }
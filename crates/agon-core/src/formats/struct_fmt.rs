@@ -10,16 +10,55 @@
 //!
 //! - key: StructName(val1, val2, val3)
 //! ```
-
+//!
+//! The decoder below is a hand-rolled, indentation-tracking line scanner
+//! rather than a grammar compiled from a `.pest`-style spec (this checkout
+//! has no build manifest to add a parser-generator crate to). Its productions
+//! still correspond to a PEG grammar; writing them down here keeps the scanner
+//! honest about what it accepts, and is the reference a future migration to a
+//! generated parser (see `decode_key_value_field`/`decode_bare_key_field`,
+//! which already give the `field` and `bare_key_field` productions single,
+//! non-duplicated implementations) would compile against:
+//!
+//! ```text
+//! document      = header, blank_line, { struct_def, blank_line }, value
+//! header        = "@AGON struct"
+//! struct_def    = "@", ident, [ "(", ident, { ",", ident }, ")" ], ":", field_list
+//! field_list    = field_def, { ",", field_def }
+//! field_def     = ident, [ ":", ident ], [ "?" ]
+//! value         = object | array | inline_value
+//! object        = { field | bare_key_field }
+//! field         = ident, ":", [ inline_value ], [ "\n", indent, value ]
+//! bare_key_field = ident, "\n", indent, array            (* array-valued only *)
+//! array         = array_header, { list_item }
+//! array_header  = [ ident ], "[", digit, { digit }, "]", [ ":" ]
+//! list_item     = "-", ( field | inline_value )
+//! inline_value  = struct_call | primitive
+//! struct_call   = ident, "(", [ primitive, { ",", primitive } ], ")"
+//! primitive     = quoted_string | number | "true" | "false" | "null" | bare_string
+//! quoted_string = '"', { escape | any_char_except('"', '\\') }, '"'
+//! ```
+//!
+//! ## Batched writes
+//!
+//! [`encode_streaming`] writes the header, struct definitions and the
+//! top-level array's `[N]:` line immediately (the registry only needs one
+//! shape-detection pass over `data`, and the row count is already known),
+//! then formats and appends fixed-size batches of array items one at a time
+//! via rayon, bounding peak memory to one batch's formatted lines rather
+//! than the whole array's.
+
+use rayon::prelude::*;
 use regex::Regex;
 use serde_json::{Map, Value};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::sync::LazyLock;
 
-use crate::error::{AgonError, Result};
+use crate::error::{AgonError, Result, StructParseErrorKind};
+use crate::options::SerializeOptions;
 
 const HEADER: &str = "@AGON struct";
-const INDENT: &str = "  ";
 
 // Regex patterns
 static NUMBER_RE: LazyLock<Regex> =
@@ -31,16 +70,165 @@ static KEY_VALUE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^([^:]+):\s
 static ARRAY_HEADER_RE: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^(\w*)\[(\d+)\]:?").unwrap());
 
-/// Struct definition stored in registry: (fields, optional_fields, parents)
-type StructDef = (Vec<String>, Vec<String>, Vec<String>);
+/// Per-field type annotations declared in a struct definition (e.g.
+/// `"price" -> "float"`), keyed by field name. A field absent from this map
+/// keeps today's value-based type inference on decode.
+type FieldTypes = HashMap<String, String>;
+
+/// Struct definition stored in registry: (fields, optional_fields, parents, field_types)
+type StructDef = (Vec<String>, Vec<String>, Vec<String>, FieldTypes);
 type StructRegistry = HashMap<String, StructDef>;
 
-/// Struct definition with name for creation: (name, fields, optional_fields, parents)
+/// Struct definition with name for creation: (name, fields, optional_fields, parents, field_types)
 #[allow(clippy::type_complexity)]
-type StructDefWithName = (String, Vec<String>, Vec<String>, Vec<String>);
+type StructDefWithName = (String, Vec<String>, Vec<String>, Vec<String>, FieldTypes);
+
+/// A set of named struct definitions supplied by the caller, used to drive
+/// [`encode_with_schema`] instead of letting [`encode`] auto-detect shapes
+/// from the data. Definitions are parsed from the same
+/// `@Name(Parents): f1, f2?, ...` grammar the wire format itself uses (see
+/// [`parse_struct_def`]), so a schema can be written once and shared
+/// verbatim between the two parties encoding and decoding a payload —
+/// exactly how `preserves-schema` lets you compile record definitions ahead
+/// of time.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    registry: StructRegistry,
+    defs: Vec<StructDefWithName>,
+    strict: bool,
+}
+
+impl Schema {
+    /// An empty schema with no struct definitions registered.
+    pub fn new() -> Self {
+        Schema::default()
+    }
+
+    /// In strict mode, [`encode_with_schema`] fails with an
+    /// [`AgonError::EncodingError`] if the data contains a struct-eligible
+    /// object shape (primitive-only fields) that matches none of this
+    /// schema's definitions, rather than silently falling back to literal
+    /// field encoding.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Register a named struct directly, without going through the text
+    /// grammar. `field_types` carries any per-field type annotations (see
+    /// [`FieldTypes`]); pass an empty map for untyped fields.
+    pub fn register(
+        &mut self,
+        name: &str,
+        fields: &[String],
+        optional: &[String],
+        parents: &[String],
+        field_types: &FieldTypes,
+    ) -> Result<()> {
+        register_struct(&mut self.registry, name, fields, optional, parents, field_types)?;
+        self.defs.push((
+            name.to_string(),
+            fields.to_vec(),
+            optional.to_vec(),
+            parents.to_vec(),
+            field_types.clone(),
+        ));
+        Ok(())
+    }
+
+    /// Parse and register a struct definition written in the same
+    /// `@Name(Parents): f1, f2?, ...` grammar `decode` reads off the wire.
+    pub fn define(&mut self, line: &str) -> Result<()> {
+        let (name, fields, optional, parents, field_types) = parse_struct_def(line)
+            .ok_or_else(|| AgonError::InvalidFormat(format!("Invalid struct definition: {}", line)))?;
+        self.register(&name, &fields, &optional, &parents, &field_types)
+    }
+}
+
+/// Controls for the encoder's automatic struct-promotion heuristics,
+/// following RON's `options.rs` config-builder convention. The defaults
+/// match [`encode`]'s hardcoded behavior; construct with
+/// [`EncodeOptions::new`], chain setters, and pass to
+/// [`encode_with_struct_options`].
+#[derive(Debug, Clone)]
+pub struct EncodeOptions {
+    min_occurrences: usize,
+    min_fields: usize,
+    force_single_struct: bool,
+    disable_inheritance: bool,
+    serialize: SerializeOptions,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions {
+            min_occurrences: 3,
+            min_fields: 2,
+            force_single_struct: false,
+            disable_inheritance: false,
+            serialize: SerializeOptions::default(),
+        }
+    }
+}
+
+impl EncodeOptions {
+    /// Defaults matching today's hardcoded thresholds: promote a shape once
+    /// it recurs 3+ times with 2+ fields, factoring shared fields into
+    /// parent structs, at 2-space indentation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Minimum number of times a shape must repeat before it's promoted to
+    /// a `@`-struct. Default 3.
+    pub fn min_occurrences(mut self, n: usize) -> Self {
+        self.min_occurrences = n;
+        self
+    }
+
+    /// Minimum field count a shape must have before it's eligible for
+    /// promotion. Default 2.
+    pub fn min_fields(mut self, n: usize) -> Self {
+        self.min_fields = n;
+        self
+    }
+
+    /// Promote every shape that appears as a direct array element to a
+    /// struct regardless of `min_occurrences`, so a single token-budget
+    /// tuning pass can collapse a whole array of objects into one template
+    /// even when none of its elements repeat enough to clear the default
+    /// threshold on its own.
+    pub fn force_single_struct(mut self, force: bool) -> Self {
+        self.force_single_struct = force;
+        self
+    }
+
+    /// Skip parent-struct extraction, emitting every promoted shape as its
+    /// own flat definition instead of factoring fields shared by two or
+    /// more shapes into a base struct.
+    pub fn disable_inheritance(mut self, disable: bool) -> Self {
+        self.disable_inheritance = disable;
+        self
+    }
+
+    /// Spaces per nesting level in the emitted text. Default 2.
+    pub fn indent(mut self, width: usize) -> Self {
+        self.serialize.indent = Some(width);
+        self
+    }
+}
 
 /// Encode data to AGONStruct format
 pub fn encode(data: &Value, include_header: bool) -> Result<String> {
+    encode_with_options(data, include_header, &SerializeOptions::default())
+}
+
+/// Encode data to AGONStruct format with explicit serialize options
+pub fn encode_with_options(
+    data: &Value,
+    include_header: bool,
+    options: &SerializeOptions,
+) -> Result<String> {
     let mut lines = Vec::new();
 
     // Detect shapes and create struct definitions
@@ -49,8 +237,8 @@ pub fn encode(data: &Value, include_header: bool) -> Result<String> {
 
     // Build registry
     let mut registry = StructRegistry::new();
-    for (name, fields, optional, parents) in &struct_defs {
-        register_struct(&mut registry, name, fields, optional, parents)?;
+    for (name, fields, optional, parents, field_types) in &struct_defs {
+        register_struct(&mut registry, name, fields, optional, parents, field_types)?;
     }
 
     if include_header {
@@ -60,33 +248,210 @@ pub fn encode(data: &Value, include_header: bool) -> Result<String> {
 
     // Emit struct definitions
     if !struct_defs.is_empty() {
-        for (name, fields, optional, parents) in &struct_defs {
-            let fields_str: Vec<String> = fields
-                .iter()
-                .map(|f| {
-                    if optional.contains(f) {
-                        format!("{}?", f)
-                    } else {
-                        f.clone()
-                    }
-                })
-                .collect();
+        for (name, fields, optional, parents, field_types) in &struct_defs {
+            lines.push(format_struct_def_line(name, fields, optional, parents, field_types));
+        }
+        lines.push(String::new());
+    }
 
-            if parents.is_empty() {
-                lines.push(format!("@{}: {}", name, fields_str.join(", ")));
-            } else {
-                lines.push(format!(
-                    "@{}({}): {}",
-                    name,
-                    parents.join(", "),
-                    fields_str.join(", ")
-                ));
+    encode_value(data, &mut lines, 0, &registry, options);
+
+    Ok(lines.join("\n"))
+}
+
+/// Encode a top-level array to a [`Write`]r in fixed-size batches instead of
+/// building the whole document as one [`String`] through [`encode`] first.
+/// The struct registry and header lines only depend on `data`'s shapes, not
+/// its row count, so they're written once up front exactly as
+/// [`encode_with_options`] writes them; the `[N]:` array header can be
+/// written immediately too, since `data` is already a fully in-memory
+/// `&Value` and its length is known before a single item is formatted. Each
+/// batch of items is then formatted in parallel with rayon's `par_iter` via
+/// [`encode_array_item_lines`] and appended -- a straightforward append,
+/// the same as [`crate::formats::rows::encode_streaming`], since a
+/// list-item-per-row format has nowhere that needs a continuation marker
+/// the way [`crate::formats::columns`]'s per-field lines do. Any `data`
+/// that isn't an array falls back to one [`encode_with_options`] call
+/// written in a single `write_all`.
+pub fn encode_streaming<W: Write>(
+    writer: W,
+    data: &Value,
+    include_header: bool,
+    batch_size: usize,
+    options: &SerializeOptions,
+) -> Result<W> {
+    let mut writer = writer;
+
+    let Value::Array(arr) = data else {
+        let encoded = encode_with_options(data, include_header, options)?;
+        writer
+            .write_all(encoded.as_bytes())
+            .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+        return Ok(writer);
+    };
+
+    let shapes = detect_shapes(data);
+    let struct_defs = create_struct_definitions(&shapes, 3, 2);
+
+    let mut registry = StructRegistry::new();
+    for (name, fields, optional, parents, field_types) in &struct_defs {
+        register_struct(&mut registry, name, fields, optional, parents, field_types)?;
+    }
+
+    let mut header_lines = Vec::new();
+    if include_header {
+        header_lines.push(HEADER.to_string());
+        header_lines.push(String::new());
+    }
+    if !struct_defs.is_empty() {
+        for (name, fields, optional, parents, field_types) in &struct_defs {
+            header_lines.push(format_struct_def_line(name, fields, optional, parents, field_types));
+        }
+        header_lines.push(String::new());
+    }
+    header_lines.push(if arr.is_empty() {
+        "[0]:".to_string()
+    } else {
+        format!("[{}]:", arr.len())
+    });
+    writer
+        .write_all(header_lines.join("\n").as_bytes())
+        .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+
+    if arr.is_empty() {
+        return Ok(writer);
+    }
+
+    let batch_size = batch_size.max(1);
+    for chunk in arr.chunks(batch_size) {
+        let formatted: Vec<String> = chunk
+            .par_iter()
+            .flat_map(|item| encode_array_item_lines(item, 0, &registry, options))
+            .collect();
+
+        writer
+            .write_all(b"\n")
+            .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+        writer
+            .write_all(formatted.join("\n").as_bytes())
+            .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+    }
+
+    Ok(writer)
+}
+
+/// Encode data to AGONStruct format using a caller-supplied [`Schema`]
+/// instead of auto-detecting shapes from `data`. Struct names come from the
+/// schema rather than [`generate_struct_name`], so the encoded output is
+/// diff-stable across runs and payloads sharing the same pinned schema.
+pub fn encode_with_schema(data: &Value, schema: &Schema, include_header: bool) -> Result<String> {
+    encode_with_schema_and_options(data, schema, include_header, &SerializeOptions::default())
+}
+
+/// Like [`encode_with_schema`], with explicit serialize options.
+pub fn encode_with_schema_and_options(
+    data: &Value,
+    schema: &Schema,
+    include_header: bool,
+    options: &SerializeOptions,
+) -> Result<String> {
+    if schema.strict {
+        check_strict_schema(data, &schema.registry, true)?;
+    }
+
+    let mut lines = Vec::new();
+
+    if include_header {
+        lines.push(HEADER.to_string());
+        lines.push(String::new());
+    }
+
+    if !schema.defs.is_empty() {
+        for (name, fields, optional, parents, field_types) in &schema.defs {
+            lines.push(format_struct_def_line(name, fields, optional, parents, field_types));
+        }
+        lines.push(String::new());
+    }
+
+    encode_value(data, &mut lines, 0, &schema.registry, options);
+
+    Ok(lines.join("\n"))
+}
+
+/// Verify every struct-eligible object in `data` (primitive-only fields —
+/// the same shapes [`encode_object`]/[`encode_array`] try to templatize)
+/// matches a struct in `registry`, for [`Schema::strict`] mode. The root
+/// value itself is exempt: it's never struct-encoded regardless of its
+/// shape, since a struct reference needs a surrounding key or list item to
+/// attach to.
+fn check_strict_schema(data: &Value, registry: &StructRegistry, is_root: bool) -> Result<()> {
+    match data {
+        Value::Array(arr) => {
+            for item in arr {
+                check_strict_schema(item, registry, false)?;
             }
         }
+        Value::Object(obj) => {
+            if !is_root {
+                let has_nested = obj.values().any(|v| v.is_object() || v.is_array());
+                if !has_nested && !obj.is_empty() && find_matching_struct(obj, registry).is_none() {
+                    return Err(AgonError::EncodingError(format!(
+                        "strict schema: no struct definition matches fields {:?}",
+                        get_shape(obj)
+                    )));
+                }
+            }
+            for v in obj.values() {
+                check_strict_schema(v, registry, false)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Encode data to AGONStruct format, driving struct promotion with an
+/// explicit [`EncodeOptions`] instead of the hardcoded thresholds [`encode`]
+/// uses.
+pub fn encode_with_struct_options(
+    data: &Value,
+    include_header: bool,
+    options: &EncodeOptions,
+) -> Result<String> {
+    let mut lines = Vec::new();
+
+    let shapes = detect_shapes(data);
+    let force_shapes = if options.force_single_struct {
+        detect_array_shapes(data)
+    } else {
+        HashSet::new()
+    };
+    let struct_defs = create_struct_definitions_ex(
+        &shapes,
+        options.min_occurrences,
+        options.min_fields,
+        &force_shapes,
+        options.disable_inheritance,
+    );
+
+    let mut registry = StructRegistry::new();
+    for (name, fields, optional, parents, field_types) in &struct_defs {
+        register_struct(&mut registry, name, fields, optional, parents, field_types)?;
+    }
+
+    if include_header {
+        lines.push(HEADER.to_string());
+        lines.push(String::new());
+    }
+
+    if !struct_defs.is_empty() {
+        for (name, fields, optional, parents, field_types) in &struct_defs {
+            lines.push(format_struct_def_line(name, fields, optional, parents, field_types));
+        }
         lines.push(String::new());
     }
 
-    encode_value(data, &mut lines, 0, &registry);
+    encode_value(data, &mut lines, 0, &registry, &options.serialize);
 
     Ok(lines.join("\n"))
 }
@@ -94,6 +459,28 @@ pub fn encode(data: &Value, include_header: bool) -> Result<String> {
 /// Decode AGONStruct payload
 pub fn decode(payload: &str) -> Result<Value> {
     let lines: Vec<&str> = payload.lines().collect();
+    decode_lines(&lines, StructRegistry::new())
+}
+
+/// Decode an AGONStruct payload against a caller-supplied [`Schema`] instead
+/// of relying solely on the `@Name: ...` definitions inline in `payload`.
+/// `schema`'s definitions seed the registry before the payload's own (if
+/// any) are layered on top, so a payload may omit its `@`-definitions
+/// entirely once the two parties have agreed on a schema out of band — the
+/// delta-encoding use case [`Schema`] exists for: transmit the shape once,
+/// then send bodies only.
+pub fn decode_with_schema(payload: &str, schema: &Schema) -> Result<Value> {
+    let lines: Vec<&str> = payload.lines().collect();
+    decode_lines(&lines, schema.registry.clone())
+}
+
+/// Decode a single `@AGON struct` document already split into lines, seeding
+/// the struct registry with `registry` before layering in any `@Name: ...`
+/// definitions the document itself declares. Shared by [`decode`] (empty
+/// seed registry), [`decode_with_schema`] (a [`Schema`]'s registry), and
+/// [`StreamDecoder`] (one document at a time off a reader, empty seed
+/// registry), so all three see identical parsing behavior.
+fn decode_lines(lines: &[&str], mut registry: StructRegistry) -> Result<Value> {
     if lines.is_empty() {
         return Err(AgonError::DecodingError("Empty payload".to_string()));
     }
@@ -102,7 +489,7 @@ pub fn decode(payload: &str) -> Result<Value> {
 
     // Parse header
     let header_line = lines[idx].trim();
-    if !header_line.starts_with("@AGON struct") {
+    if !header_line.starts_with(HEADER) {
         return Err(AgonError::DecodingError(format!(
             "Invalid header: {}",
             header_line
@@ -111,7 +498,6 @@ pub fn decode(payload: &str) -> Result<Value> {
     idx += 1;
 
     // Parse struct definitions
-    let mut registry = StructRegistry::new();
     while idx < lines.len() {
         let line = lines[idx].trim();
         if line.is_empty() {
@@ -122,8 +508,8 @@ pub fn decode(payload: &str) -> Result<Value> {
             break;
         }
         if let Some(parsed) = parse_struct_def(line) {
-            let (name, fields, optional, parents) = parsed;
-            register_struct(&mut registry, &name, &fields, &optional, &parents)?;
+            let (name, fields, optional, parents, field_types) = parsed;
+            register_struct(&mut registry, &name, &fields, &optional, &parents, &field_types)?;
         }
         idx += 1;
     }
@@ -137,10 +523,466 @@ pub fn decode(payload: &str) -> Result<Value> {
         return Ok(Value::Null);
     }
 
-    let (result, _) = decode_value(&lines, idx, 0, &registry)?;
+    let (result, _) = decode_value(lines, idx, 0, &registry)?;
     Ok(result)
 }
 
+/// Read a sequence of concatenated `@AGON struct` documents from `reader`,
+/// yielding each as a decoded [`Value`] without buffering the whole stream.
+///
+/// Mirrors serde_json's `Deserializer::from_reader` + `StreamDeserializer`:
+/// each `@AGON struct` header line starts a new document, so the
+/// `StructRegistry` built from that document's `@Name: ...` definitions
+/// resets at the header and is discarded once the document is decoded,
+/// the same lifetime a single in-memory payload already gets from
+/// [`decode`] — just applied one document at a time as lines arrive.
+pub fn decode_reader<R: Read>(reader: R) -> StreamDecoder<R> {
+    StreamDecoder::new(reader)
+}
+
+/// Iterator over the `@AGON struct` documents in a [`Read`]er, produced by
+/// [`decode_reader`]. Each call to [`next`](Iterator::next) reads lines up to
+/// (not including) the next document's header, then decodes just that
+/// document — only one document's lines are ever held in memory at once.
+pub struct StreamDecoder<R> {
+    lines: std::io::Lines<BufReader<R>>,
+    next_header: Option<String>,
+    done: bool,
+}
+
+impl<R: Read> StreamDecoder<R> {
+    fn new(reader: R) -> Self {
+        StreamDecoder {
+            lines: BufReader::new(reader).lines(),
+            next_header: None,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Iterator for StreamDecoder<R> {
+    type Item = Result<Value>;
+
+    fn next(&mut self) -> Option<Result<Value>> {
+        if self.done {
+            return None;
+        }
+
+        let first_line = match self.next_header.take() {
+            Some(header) => header,
+            None => match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(AgonError::DecodingError(e.to_string())));
+                }
+                None => {
+                    self.done = true;
+                    return None;
+                }
+            },
+        };
+
+        let mut buffered = vec![first_line];
+        loop {
+            match self.lines.next() {
+                Some(Ok(line)) => {
+                    if line.trim().starts_with(HEADER) {
+                        self.next_header = Some(line);
+                        break;
+                    }
+                    buffered.push(line);
+                }
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(AgonError::DecodingError(e.to_string())));
+                }
+                None => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+
+        let doc_lines: Vec<&str> = buffered.iter().map(String::as_str).collect();
+        Some(decode_lines(&doc_lines, StructRegistry::new()))
+    }
+}
+
+// ============================================================================
+// Binary transfer syntax
+//
+// A compact alternative to the textual form above, sharing the same
+// shape-detection and StructRegistry logic. Struct/field names are interned
+// into a string dictionary once and referenced by index everywhere else;
+// every value carries an explicit type tag, so there's no text-format
+// quoting heuristic (`needs_quote`) to worry about. Decodes to an identical
+// `serde_json::Value` as the text form, except for numbers outside the `i64`
+// range: binary writes them as `f64` floats, the text form reads them back
+// as strings.
+// ============================================================================
+
+/// Magic bytes identifying an AGONStruct binary payload, followed by a
+/// single format-version byte.
+const BINARY_MAGIC: &[u8; 4] = b"AGSB";
+const BINARY_VERSION: u8 = 1;
+
+/// Tag-length-value primitive tags used in the binary body.
+mod tag {
+    pub const NULL: u8 = 0;
+    pub const BOOL: u8 = 1;
+    pub const INT: u8 = 2;
+    pub const FLOAT: u8 = 3;
+    pub const STRING: u8 = 4;
+    pub const ARRAY: u8 = 5;
+    pub const OBJECT: u8 = 6;
+    pub const STRUCT: u8 = 7;
+}
+
+/// Encode `data` into AGONStruct's binary transfer syntax: a magic header,
+/// an interned string dictionary, a struct-definitions table (reusing
+/// [`detect_shapes`]/[`create_struct_definitions`]), then the data itself as
+/// a single tag-length-value tree in which struct instances are written as
+/// `(struct_id, field_values...)` referencing the definitions table instead
+/// of repeating field names.
+pub fn encode_binary(data: &Value) -> Vec<u8> {
+    let shapes = detect_shapes(data);
+    let struct_defs = create_struct_definitions(&shapes, 3, 2);
+
+    let mut registry = StructRegistry::new();
+    for (name, fields, optional, parents, field_types) in &struct_defs {
+        register_struct(&mut registry, name, fields, optional, parents, field_types)
+            .expect("register_struct never fails for defs from create_struct_definitions");
+    }
+
+    let mut strings: Vec<String> = Vec::new();
+    let mut string_idx: HashMap<String, u64> = HashMap::new();
+    for (name, fields, _, parents, _) in &struct_defs {
+        intern_string(name, &mut strings, &mut string_idx);
+        for f in fields {
+            intern_string(f, &mut strings, &mut string_idx);
+        }
+        for p in parents {
+            intern_string(p, &mut strings, &mut string_idx);
+        }
+    }
+
+    let def_index_by_name: HashMap<String, u64> = struct_defs
+        .iter()
+        .enumerate()
+        .map(|(i, (name, ..))| (name.clone(), i as u64))
+        .collect();
+
+    let mut buf = Vec::new();
+    buf.extend_from_slice(BINARY_MAGIC);
+    buf.push(BINARY_VERSION);
+
+    write_varint(&mut buf, strings.len() as u64);
+    for s in &strings {
+        write_varint(&mut buf, s.len() as u64);
+        buf.extend_from_slice(s.as_bytes());
+    }
+
+    write_varint(&mut buf, struct_defs.len() as u64);
+    for (name, fields, optional, parents, _) in &struct_defs {
+        write_varint(&mut buf, *string_idx.get(name).unwrap());
+        write_varint(&mut buf, fields.len() as u64);
+        for f in fields {
+            write_varint(&mut buf, *string_idx.get(f).unwrap());
+            buf.push(if optional.contains(f) { 1 } else { 0 });
+        }
+        write_varint(&mut buf, parents.len() as u64);
+        for p in parents {
+            write_varint(&mut buf, *string_idx.get(p).unwrap());
+        }
+    }
+
+    encode_value_binary(data, &registry, &def_index_by_name, &mut buf);
+    buf
+}
+
+fn intern_string(s: &str, strings: &mut Vec<String>, string_idx: &mut HashMap<String, u64>) -> u64 {
+    if let Some(&i) = string_idx.get(s) {
+        return i;
+    }
+    let i = strings.len() as u64;
+    strings.push(s.to_string());
+    string_idx.insert(s.to_string(), i);
+    i
+}
+
+fn encode_value_binary(
+    val: &Value,
+    registry: &StructRegistry,
+    def_index_by_name: &HashMap<String, u64>,
+    buf: &mut Vec<u8>,
+) {
+    match val {
+        Value::Null => buf.push(tag::NULL),
+        Value::Bool(b) => {
+            buf.push(tag::BOOL);
+            buf.push(if *b { 1 } else { 0 });
+        }
+        Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                buf.push(tag::INT);
+                write_varint(buf, zigzag_encode(i));
+            } else {
+                let f = n.as_f64().unwrap_or(0.0);
+                buf.push(tag::FLOAT);
+                buf.extend_from_slice(&f.to_le_bytes());
+            }
+        }
+        Value::String(s) => {
+            buf.push(tag::STRING);
+            write_varint(buf, s.len() as u64);
+            buf.extend_from_slice(s.as_bytes());
+        }
+        Value::Array(arr) => {
+            buf.push(tag::ARRAY);
+            write_varint(buf, arr.len() as u64);
+            for item in arr {
+                encode_value_binary(item, registry, def_index_by_name, buf);
+            }
+        }
+        Value::Object(obj) => {
+            let has_nested = obj.values().any(|v| v.is_object() || v.is_array());
+            let matched = if has_nested {
+                None
+            } else {
+                find_matching_struct(obj, registry)
+            };
+
+            if let Some(struct_name) = matched {
+                if let (Some((fields, _, _, _)), Some(&def_idx)) = (
+                    registry.get(&struct_name),
+                    def_index_by_name.get(&struct_name),
+                ) {
+                    buf.push(tag::STRUCT);
+                    write_varint(buf, def_idx);
+                    for f in fields {
+                        let field_val = obj.get(f).cloned().unwrap_or(Value::Null);
+                        encode_value_binary(&field_val, registry, def_index_by_name, buf);
+                    }
+                    return;
+                }
+            }
+
+            buf.push(tag::OBJECT);
+            write_varint(buf, obj.len() as u64);
+            for (k, v) in obj {
+                write_varint(buf, k.len() as u64);
+                buf.extend_from_slice(k.as_bytes());
+                encode_value_binary(v, registry, def_index_by_name, buf);
+            }
+        }
+    }
+}
+
+/// Decode a payload produced by [`encode_binary`].
+pub fn decode_binary(bytes: &[u8]) -> Result<Value> {
+    if bytes.len() < 5 || &bytes[0..4] != BINARY_MAGIC {
+        return Err(AgonError::DecodingError(
+            "Missing AGONStruct binary magic header".to_string(),
+        ));
+    }
+    if bytes[4] != BINARY_VERSION {
+        return Err(AgonError::DecodingError(format!(
+            "Unsupported AGONStruct binary version: {}",
+            bytes[4]
+        )));
+    }
+
+    let mut pos = 5;
+    let string_count = read_varint(bytes, &mut pos)? as usize;
+    let mut strings = Vec::with_capacity(string_count);
+    for _ in 0..string_count {
+        let len = read_varint(bytes, &mut pos)? as usize;
+        strings.push(read_utf8(bytes, &mut pos, len)?);
+    }
+
+    let def_count = read_varint(bytes, &mut pos)? as usize;
+    let mut registry = StructRegistry::new();
+    let mut def_names = Vec::with_capacity(def_count);
+    for _ in 0..def_count {
+        let name_idx = read_varint(bytes, &mut pos)? as usize;
+        let name = get_string(&strings, name_idx)?;
+
+        let field_count = read_varint(bytes, &mut pos)? as usize;
+        let mut fields = Vec::with_capacity(field_count);
+        let mut optional = Vec::new();
+        for _ in 0..field_count {
+            let field_idx = read_varint(bytes, &mut pos)? as usize;
+            let field_name = get_string(&strings, field_idx)?;
+            let is_optional = read_byte(bytes, &mut pos)?;
+            if is_optional != 0 {
+                optional.push(field_name.clone());
+            }
+            fields.push(field_name);
+        }
+
+        let parent_count = read_varint(bytes, &mut pos)? as usize;
+        let mut parents = Vec::with_capacity(parent_count);
+        for _ in 0..parent_count {
+            let parent_idx = read_varint(bytes, &mut pos)? as usize;
+            parents.push(get_string(&strings, parent_idx)?);
+        }
+
+        register_struct(&mut registry, &name, &fields, &optional, &parents, &FieldTypes::new())?;
+        def_names.push(name);
+    }
+
+    let (value, _) = decode_value_binary(bytes, pos, &registry, &def_names)?;
+    Ok(value)
+}
+
+fn decode_value_binary(
+    bytes: &[u8],
+    mut pos: usize,
+    registry: &StructRegistry,
+    def_names: &[String],
+) -> Result<(Value, usize)> {
+    let value_tag = read_byte(bytes, &mut pos)?;
+    match value_tag {
+        tag::NULL => Ok((Value::Null, pos)),
+        tag::BOOL => {
+            let b = read_byte(bytes, &mut pos)?;
+            Ok((Value::Bool(b != 0), pos))
+        }
+        tag::INT => {
+            let zz = read_varint(bytes, &mut pos)?;
+            Ok((Value::Number(zigzag_decode(zz).into()), pos))
+        }
+        tag::FLOAT => {
+            let raw = read_bytes(bytes, &mut pos, 8)?;
+            let f = f64::from_le_bytes(raw.try_into().unwrap());
+            let n = serde_json::Number::from_f64(f).ok_or_else(|| {
+                AgonError::DecodingError("Non-finite float in binary payload".to_string())
+            })?;
+            Ok((Value::Number(n), pos))
+        }
+        tag::STRING => {
+            let len = read_varint(bytes, &mut pos)? as usize;
+            Ok((Value::String(read_utf8(bytes, &mut pos, len)?), pos))
+        }
+        tag::ARRAY => {
+            let len = read_varint(bytes, &mut pos)? as usize;
+            let mut items = Vec::with_capacity(len);
+            for _ in 0..len {
+                let (item, new_pos) = decode_value_binary(bytes, pos, registry, def_names)?;
+                items.push(item);
+                pos = new_pos;
+            }
+            Ok((Value::Array(items), pos))
+        }
+        tag::OBJECT => {
+            let len = read_varint(bytes, &mut pos)? as usize;
+            let mut obj = Map::new();
+            for _ in 0..len {
+                let key_len = read_varint(bytes, &mut pos)? as usize;
+                let key = read_utf8(bytes, &mut pos, key_len)?;
+                let (val, new_pos) = decode_value_binary(bytes, pos, registry, def_names)?;
+                obj.insert(key, val);
+                pos = new_pos;
+            }
+            Ok((Value::Object(obj), pos))
+        }
+        tag::STRUCT => {
+            let def_idx = read_varint(bytes, &mut pos)? as usize;
+            let name = def_names.get(def_idx).ok_or_else(|| {
+                AgonError::DecodingError(format!("Unknown struct index: {}", def_idx))
+            })?;
+            let (fields, _, _, _) = registry
+                .get(name)
+                .ok_or_else(|| AgonError::DecodingError(format!("Unregistered struct: {}", name)))?;
+            let mut obj = Map::new();
+            for field in fields {
+                let (val, new_pos) = decode_value_binary(bytes, pos, registry, def_names)?;
+                obj.insert(field.clone(), val);
+                pos = new_pos;
+            }
+            Ok((Value::Object(obj), pos))
+        }
+        other => Err(AgonError::DecodingError(format!(
+            "Unknown binary tag: {}",
+            other
+        ))),
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            break;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut result: u64 = 0;
+    let mut shift = 0;
+    loop {
+        let byte = read_byte(bytes, pos)?;
+        result |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        if shift >= 64 {
+            return Err(AgonError::DecodingError("Varint too long".to_string()));
+        }
+    }
+    Ok(result)
+}
+
+fn zigzag_encode(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn zigzag_decode(n: u64) -> i64 {
+    ((n >> 1) as i64) ^ -((n & 1) as i64)
+}
+
+fn read_byte(bytes: &[u8], pos: &mut usize) -> Result<u8> {
+    let b = bytes.get(*pos).copied().ok_or_else(|| {
+        AgonError::DecodingError("Unexpected end of binary payload".to_string())
+    })?;
+    *pos += 1;
+    Ok(b)
+}
+
+fn read_bytes<'a>(bytes: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+    // `len` comes straight from a decoded varint, so a crafted payload can
+    // name a length near `usize::MAX` -- add with `checked_add` instead of
+    // `+` so that case hits the same "Unexpected end" error as a merely
+    // truncated payload, rather than panicking on overflow.
+    let end = pos
+        .checked_add(len)
+        .ok_or_else(|| AgonError::DecodingError("Unexpected end of binary payload".to_string()))?;
+    let slice = bytes.get(*pos..end).ok_or_else(|| {
+        AgonError::DecodingError("Unexpected end of binary payload".to_string())
+    })?;
+    *pos = end;
+    Ok(slice)
+}
+
+fn read_utf8(bytes: &[u8], pos: &mut usize, len: usize) -> Result<String> {
+    let slice = read_bytes(bytes, pos, len)?;
+    String::from_utf8(slice.to_vec())
+        .map_err(|e| AgonError::DecodingError(format!("Invalid UTF-8 in binary payload: {}", e)))
+}
+
+fn get_string(strings: &[String], idx: usize) -> Result<String> {
+    strings
+        .get(idx)
+        .cloned()
+        .ok_or_else(|| AgonError::DecodingError(format!("Unknown string index: {}", idx)))
+}
+
 // ============================================================================
 // Shape detection
 // ============================================================================
@@ -184,24 +1026,170 @@ fn collect_shapes(data: &Value, shapes: &mut HashMap<Shape, usize>) {
     }
 }
 
+/// Shapes that appear as a direct element of a JSON array anywhere in
+/// `data`, for [`EncodeOptions::force_single_struct`] — these bypass the
+/// occurrence threshold [`create_struct_definitions`] normally enforces.
+fn detect_array_shapes(data: &Value) -> HashSet<Shape> {
+    let mut array_shapes = HashSet::new();
+    collect_array_shapes(data, &mut array_shapes, false);
+    array_shapes
+}
+
+fn collect_array_shapes(data: &Value, array_shapes: &mut HashSet<Shape>, in_array: bool) {
+    match data {
+        Value::Array(arr) => {
+            for item in arr {
+                collect_array_shapes(item, array_shapes, true);
+            }
+        }
+        Value::Object(obj) => {
+            let shape = get_shape(obj);
+            if in_array && !shape.is_empty() {
+                array_shapes.insert(shape);
+            }
+            for v in obj.values() {
+                collect_array_shapes(v, array_shapes, false);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Group frequent shapes (count >= `min_occurrences`, len >= `min_fields`)
+/// into struct definitions, factoring any field set shared by two or more
+/// distinct shapes out into a parent struct rather than repeating it in
+/// every definition — the same trick schema compilers use to factor shared
+/// record structure into base definitions.
 fn create_struct_definitions(
     shapes: &HashMap<Shape, usize>,
     min_occurrences: usize,
     min_fields: usize,
 ) -> Vec<StructDefWithName> {
-    let mut defs = Vec::new();
-    let mut used_names: std::collections::HashSet<String> = std::collections::HashSet::new();
+    create_struct_definitions_ex(shapes, min_occurrences, min_fields, &HashSet::new(), false)
+}
+
+/// Like [`create_struct_definitions`], with the two [`EncodeOptions`] knobs
+/// it doesn't take: `force_shapes` are promoted regardless of
+/// `min_occurrences` (for [`EncodeOptions::force_single_struct`]), and
+/// `disable_inheritance` skips parent-struct extraction, emitting every
+/// promoted shape as its own flat definition.
+fn create_struct_definitions_ex(
+    shapes: &HashMap<Shape, usize>,
+    min_occurrences: usize,
+    min_fields: usize,
+    force_shapes: &HashSet<Shape>,
+    disable_inheritance: bool,
+) -> Vec<StructDefWithName> {
+    let mut used_names: HashSet<String> = HashSet::new();
+
+    let mut frequent: Vec<Shape> = shapes
+        .iter()
+        .filter(|(shape, count)| {
+            (**count >= min_occurrences || force_shapes.contains(*shape)) && shape.len() >= min_fields
+        })
+        .map(|(shape, _)| shape.clone())
+        .collect();
+    // HashMap iteration order isn't deterministic; sort so the parent
+    // extraction below (and the names it generates) is stable run to run.
+    frequent.sort();
+
+    if frequent.is_empty() {
+        return Vec::new();
+    }
 
-    for (shape, count) in shapes {
-        if *count >= min_occurrences && shape.len() >= min_fields {
+    if disable_inheritance {
+        let mut defs: Vec<StructDefWithName> = Vec::new();
+        for shape in &frequent {
             let name = generate_struct_name(shape, &mut used_names);
-            defs.push((name, shape.clone(), vec![], vec![]));
+            defs.push((name, shape.clone(), vec![], vec![], FieldTypes::new()));
+        }
+        return defs;
+    }
+
+    // Candidate parents: every pairwise field-set intersection that's large
+    // enough on its own to be worth factoring out.
+    let mut candidates: HashSet<Shape> = HashSet::new();
+    for i in 0..frequent.len() {
+        for j in (i + 1)..frequent.len() {
+            let intersection = field_intersection(&frequent[i], &frequent[j]);
+            if intersection.len() >= min_fields {
+                candidates.insert(intersection);
+            }
+        }
+    }
+
+    let mut candidates: Vec<Shape> = candidates.into_iter().collect();
+    // Largest shared core first, so it gets first claim on the shapes it
+    // covers; ties broken by field content for determinism.
+    candidates.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+
+    let mut defs: Vec<StructDefWithName> = Vec::new();
+    let mut parent_fields_by_name: Vec<(String, Shape)> = Vec::new();
+    // Each frequent shape is claimed by at most one parent: the first (i.e.
+    // largest) candidate that is a subset of it and of at least one other
+    // still-unclaimed shape.
+    let mut assigned: HashMap<Shape, String> = HashMap::new();
+
+    for fields in candidates {
+        let covers: Vec<&Shape> = frequent
+            .iter()
+            .filter(|shape| !assigned.contains_key(*shape) && is_subset(&fields, shape))
+            .collect();
+        if covers.len() < 2 {
+            continue;
+        }
+        let name = generate_struct_name(&fields, &mut used_names);
+        for shape in &covers {
+            assigned.insert((*shape).clone(), name.clone());
+        }
+        defs.push((name.clone(), fields.clone(), vec![], vec![], FieldTypes::new()));
+        parent_fields_by_name.push((name, fields));
+    }
+
+    for shape in &frequent {
+        match assigned.get(shape) {
+            Some(parent_name) => {
+                let parent_fields = parent_fields_by_name
+                    .iter()
+                    .find(|(name, _)| name == parent_name)
+                    .map(|(_, fields)| fields)
+                    .expect("assigned parent must be registered above");
+                let own_fields: Vec<String> = shape
+                    .iter()
+                    .filter(|f| !parent_fields.contains(f))
+                    .cloned()
+                    .collect();
+                let name = generate_struct_name(shape, &mut used_names);
+                defs.push((
+                    name,
+                    own_fields,
+                    vec![],
+                    vec![parent_name.clone()],
+                    FieldTypes::new(),
+                ));
+            }
+            None => {
+                let name = generate_struct_name(shape, &mut used_names);
+                defs.push((name, shape.clone(), vec![], vec![], FieldTypes::new()));
+            }
         }
     }
 
     defs
 }
 
+/// Sorted fields common to both `a` and `b`.
+fn field_intersection(a: &Shape, b: &Shape) -> Shape {
+    let mut intersection: Vec<String> = a.iter().filter(|f| b.contains(f)).cloned().collect();
+    intersection.sort();
+    intersection
+}
+
+/// Whether every field in `smaller` is also present in `larger`.
+fn is_subset(smaller: &Shape, larger: &Shape) -> bool {
+    smaller.iter().all(|f| larger.contains(f))
+}
+
 /// Generate a struct name from field names
 /// Takes first letter of each field (up to 4), adds counter on collision
 fn generate_struct_name(
@@ -234,36 +1222,75 @@ fn generate_struct_name(
     name
 }
 
+/// Render one `@Name(Parents): f1, f2?, ...` struct definition line, in the
+/// same grammar [`parse_struct_def`] reads back.
+fn format_struct_def_line(
+    name: &str,
+    fields: &[String],
+    optional: &[String],
+    parents: &[String],
+    field_types: &FieldTypes,
+) -> String {
+    let fields_str: Vec<String> = fields
+        .iter()
+        .map(|f| {
+            let base = match field_types.get(f) {
+                Some(t) => format!("{}:{}", f, t),
+                None => f.clone(),
+            };
+            if optional.contains(f) {
+                format!("{}?", base)
+            } else {
+                base
+            }
+        })
+        .collect();
+
+    if parents.is_empty() {
+        format!("@{}: {}", name, fields_str.join(", "))
+    } else {
+        format!("@{}({}): {}", name, parents.join(", "), fields_str.join(", "))
+    }
+}
+
 fn register_struct(
     registry: &mut StructRegistry,
     name: &str,
     fields: &[String],
     optional: &[String],
     parents: &[String],
+    field_types: &FieldTypes,
 ) -> Result<()> {
     let mut all_fields = Vec::new();
+    let mut all_types = FieldTypes::new();
 
-    // Resolve parent fields
+    // Resolve parent fields, carrying over each parent's own declared types
     for parent_name in parents {
-        if let Some((parent_fields, _, _)) = registry.get(parent_name) {
+        if let Some((parent_fields, _, _, parent_types)) = registry.get(parent_name) {
             for f in parent_fields {
                 if !all_fields.contains(f) {
                     all_fields.push(f.clone());
+                    if let Some(t) = parent_types.get(f) {
+                        all_types.insert(f.clone(), t.clone());
+                    }
                 }
             }
         }
     }
 
-    // Add own fields
+    // Add own fields, with own declared types taking precedence
     for f in fields {
         if !all_fields.contains(f) {
             all_fields.push(f.clone());
         }
+        if let Some(t) = field_types.get(f) {
+            all_types.insert(f.clone(), t.clone());
+        }
     }
 
     registry.insert(
         name.to_string(),
-        (all_fields, optional.to_vec(), parents.to_vec()),
+        (all_fields, optional.to_vec(), parents.to_vec(), all_types),
     );
     Ok(())
 }
@@ -273,29 +1300,80 @@ fn register_struct(
 // ============================================================================
 
 fn format_primitive(val: &Value) -> String {
+    format_primitive_typed(val, None)
+}
+
+/// Like [`format_primitive`], but given the field's declared type (if any)
+/// from a struct definition. A `str`-typed field never needs the
+/// number/bool/null-lookalike quoting in [`needs_quote`] — the declared type
+/// already disambiguates it on decode — so it only quotes for the
+/// structural reasons [`needs_quote_structural`] covers, shrinking output.
+fn format_primitive_typed(val: &Value, field_type: Option<&str>) -> String {
     match val {
-        Value::Null => "null".to_string(),
-        Value::Bool(b) => if *b { "true" } else { "false" }.to_string(),
-        Value::Number(n) => n.to_string(),
         Value::String(s) => {
-            // Quote if contains special chars or could be parsed as another type
-            if needs_quote(s) {
-                format!(
-                    "\"{}\"",
-                    s.replace('\\', "\\\\")
-                        .replace('"', "\\\"")
-                        .replace('\n', "\\n")
-                )
+            let quote = if field_type == Some("str") {
+                needs_quote_structural(s)
+            } else {
+                needs_quote(s)
+            };
+            if quote {
+                format!("\"{}\"", escape_string(s))
             } else {
                 s.clone()
             }
         }
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => if *b { "true" } else { "false" }.to_string(),
+        Value::Number(n) => n.to_string(),
         _ => serde_json::to_string(val).unwrap_or_default(),
     }
 }
 
+/// Render `s`'s content for a quoted primitive, escaping exactly the set
+/// [`unescape_string`] reverses: `\ " \n \r \t \b \f` and any other C0
+/// control character as `\u00XX`. A raw newline can't survive this format's
+/// `lines`-based decoder, so a literal `\n` is always escaped rather than
+/// emitted as a physical line break.
+fn escape_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
 /// Check if a string needs quoting to preserve its type
 fn needs_quote(s: &str) -> bool {
+    if needs_quote_structural(s) {
+        return true;
+    }
+    // Boolean/null keywords
+    let lower = s.to_lowercase();
+    if lower == "true" || lower == "false" || lower == "null" {
+        return true;
+    }
+    // Looks like a number - needs quoting to preserve string type
+    if s.parse::<i64>().is_ok() || s.parse::<f64>().is_ok() {
+        return true;
+    }
+    false
+}
+
+/// Quoting needed purely for the struct grammar's syntax, independent of
+/// whether the string is ambiguous with another type. A `str`-typed field
+/// only needs this subset, since its declared type already rules out the
+/// bool/null/number-lookalike ambiguity that `needs_quote` otherwise guards.
+fn needs_quote_structural(s: &str) -> bool {
     if s.is_empty() {
         return true;
     }
@@ -309,23 +1387,19 @@ fn needs_quote(s: &str) -> bool {
         || s.contains(':')
         || s.contains('(')
         || s.contains(')')
-        || s.contains('\n')
         || s.contains('\\')
         || s.contains('"')
     {
         return true;
     }
-    // Tree chars and special prefixes
-    if s.starts_with('@') || s.starts_with('#') || s.starts_with('-') {
-        return true;
-    }
-    // Boolean/null keywords
-    let lower = s.to_lowercase();
-    if lower == "true" || lower == "false" || lower == "null" {
+    // Any control character (newline, tab, carriage return, ...) needs
+    // quoting+escaping, since a raw one would break the `lines`-based
+    // decoder or be invisible on re-read.
+    if s.chars().any(|c| (c as u32) < 0x20) {
         return true;
     }
-    // Looks like a number - needs quoting to preserve string type
-    if s.parse::<i64>().is_ok() || s.parse::<f64>().is_ok() {
+    // Tree chars and special prefixes
+    if s.starts_with('@') || s.starts_with('#') || s.starts_with('-') {
         return true;
     }
     false
@@ -345,7 +1419,7 @@ fn find_matching_struct(obj: &Map<String, Value>, registry: &StructRegistry) ->
         return None;
     }
 
-    for (name, (fields, _, _)) in registry {
+    for (name, (fields, _, _, _)) in registry {
         // Check if all required fields match
         let mut sorted_fields = fields.clone();
         sorted_fields.sort();
@@ -356,24 +1430,36 @@ fn find_matching_struct(obj: &Map<String, Value>, registry: &StructRegistry) ->
     None
 }
 
-fn encode_value(val: &Value, lines: &mut Vec<String>, depth: usize, registry: &StructRegistry) {
-    let indent = INDENT.repeat(depth);
+fn encode_value(
+    val: &Value,
+    lines: &mut Vec<String>,
+    depth: usize,
+    registry: &StructRegistry,
+    options: &SerializeOptions,
+) {
+    let indent = options.indent_unit().repeat(depth);
 
     match val {
         Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {
             lines.push(format!("{}{}", indent, format_primitive(val)));
         }
         Value::Array(arr) => {
-            encode_array(arr, lines, depth, registry);
+            encode_array(arr, lines, depth, registry, options);
         }
         Value::Object(obj) => {
-            encode_object(obj, lines, depth, registry, None);
+            encode_object(obj, lines, depth, registry, None, options);
         }
     }
 }
 
-fn encode_array(arr: &[Value], lines: &mut Vec<String>, depth: usize, registry: &StructRegistry) {
-    let indent = INDENT.repeat(depth);
+fn encode_array(
+    arr: &[Value],
+    lines: &mut Vec<String>,
+    depth: usize,
+    registry: &StructRegistry,
+    options: &SerializeOptions,
+) {
+    let indent = options.indent_unit().repeat(depth);
 
     if arr.is_empty() {
         lines.push(format!("{}[0]:", indent));
@@ -383,33 +1469,50 @@ fn encode_array(arr: &[Value], lines: &mut Vec<String>, depth: usize, registry:
     lines.push(format!("{}[{}]:", indent, arr.len()));
 
     for item in arr {
-        if let Some(obj) = item.as_object() {
-            // Only use struct template if ALL fields are primitives (struct covers everything)
-            // If object has nested objects/arrays, use list item format to preserve them
-            let has_nested = obj.values().any(|v| v.is_object() || v.is_array());
+        lines.extend(encode_array_item_lines(item, depth, registry, options));
+    }
+}
 
-            if !has_nested {
-                if let Some(struct_name) = find_matching_struct(obj, registry) {
-                    if let Some((fields, _, _)) = registry.get(&struct_name) {
-                        let values: Vec<String> = fields
-                            .iter()
-                            .map(|f| obj.get(f).map(format_primitive).unwrap_or_default())
-                            .collect();
-                        lines.push(format!(
-                            "{}  - {}({})",
-                            indent,
-                            struct_name,
-                            values.join(", ")
-                        ));
-                        continue;
-                    }
+/// The lines [`encode_array`] would push for a single array `item`, factored
+/// out so [`encode_streaming`] can format items from different batches in
+/// parallel with rayon and concatenate their lines afterward, without
+/// duplicating the struct-call-vs-list-item choice [`encode_array`] makes.
+fn encode_array_item_lines(
+    item: &Value,
+    depth: usize,
+    registry: &StructRegistry,
+    options: &SerializeOptions,
+) -> Vec<String> {
+    let indent = options.indent_unit().repeat(depth);
+    let mut lines = Vec::new();
+
+    if let Some(obj) = item.as_object() {
+        // Only use struct template if ALL fields are primitives (struct covers everything)
+        // If object has nested objects/arrays, use list item format to preserve them
+        let has_nested = obj.values().any(|v| v.is_object() || v.is_array());
+
+        if !has_nested {
+            if let Some(struct_name) = find_matching_struct(obj, registry) {
+                if let Some((fields, _, _, field_types)) = registry.get(&struct_name) {
+                    let values: Vec<String> = fields
+                        .iter()
+                        .map(|f| {
+                            obj.get(f)
+                                .map(|v| format_primitive_typed(v, field_types.get(f).map(String::as_str)))
+                                .unwrap_or_default()
+                        })
+                        .collect();
+                    lines.push(format!("{}  - {}({})", indent, struct_name, values.join(", ")));
+                    return lines;
                 }
             }
-            encode_list_item(obj, lines, depth + 1, registry);
-        } else {
-            lines.push(format!("{}  - {}", indent, format_primitive(item)));
         }
+        encode_list_item(obj, &mut lines, depth + 1, registry, options);
+    } else {
+        lines.push(format!("{}  - {}", indent, format_primitive(item)));
     }
+
+    lines
 }
 
 fn encode_list_item(
@@ -417,11 +1520,12 @@ fn encode_list_item(
     lines: &mut Vec<String>,
     depth: usize,
     registry: &StructRegistry,
+    options: &SerializeOptions,
 ) {
-    let indent = INDENT.repeat(depth);
+    let indent = options.indent_unit().repeat(depth);
     let mut first = true;
 
-    for (k, v) in obj {
+    for (k, v) in crate::options::ordered_entries(obj, options.sort_keys) {
         let prefix = if first {
             format!("{}- ", indent)
         } else {
@@ -432,10 +1536,17 @@ fn encode_list_item(
         // Check if value can use a struct
         if let Some(nested_obj) = v.as_object() {
             if let Some(struct_name) = find_matching_struct(nested_obj, registry) {
-                if let Some((fields, _, _)) = registry.get(&struct_name) {
+                if let Some((fields, _, _, field_types)) = registry.get(&struct_name) {
                     let values: Vec<String> = fields
                         .iter()
-                        .map(|f| nested_obj.get(f).map(format_primitive).unwrap_or_default())
+                        .map(|f| {
+                            nested_obj
+                                .get(f)
+                                .map(|v| {
+                                    format_primitive_typed(v, field_types.get(f).map(String::as_str))
+                                })
+                                .unwrap_or_default()
+                        })
                         .collect();
                     lines.push(format!(
                         "{}{}: {}({})",
@@ -452,11 +1563,11 @@ fn encode_list_item(
         match v {
             Value::Object(nested) => {
                 lines.push(format!("{}{}:", prefix, k));
-                encode_object(nested, lines, depth + 2, registry, None);
+                encode_object(nested, lines, depth + 2, registry, None, options);
             }
             Value::Array(arr) => {
                 lines.push(format!("{}{}:", prefix, k));
-                encode_array(arr, lines, depth + 2, registry);
+                encode_array(arr, lines, depth + 2, registry, options);
             }
             _ => {
                 lines.push(format!("{}{}: {}", prefix, k, format_primitive(v)));
@@ -471,8 +1582,9 @@ fn encode_object(
     depth: usize,
     registry: &StructRegistry,
     name: Option<&str>,
+    options: &SerializeOptions,
 ) {
-    let indent = INDENT.repeat(depth);
+    let indent = options.indent_unit().repeat(depth);
     let mut actual_depth = depth;
 
     if let Some(n) = name {
@@ -480,16 +1592,23 @@ fn encode_object(
         actual_depth += 1;
     }
 
-    let actual_indent = INDENT.repeat(actual_depth);
+    let actual_indent = options.indent_unit().repeat(actual_depth);
 
-    for (k, v) in obj {
+    for (k, v) in crate::options::ordered_entries(obj, options.sort_keys) {
         // Check if value can use a struct
         if let Some(nested_obj) = v.as_object() {
             if let Some(struct_name) = find_matching_struct(nested_obj, registry) {
-                if let Some((fields, _, _)) = registry.get(&struct_name) {
+                if let Some((fields, _, _, field_types)) = registry.get(&struct_name) {
                     let values: Vec<String> = fields
                         .iter()
-                        .map(|f| nested_obj.get(f).map(format_primitive).unwrap_or_default())
+                        .map(|f| {
+                            nested_obj
+                                .get(f)
+                                .map(|v| {
+                                    format_primitive_typed(v, field_types.get(f).map(String::as_str))
+                                })
+                                .unwrap_or_default()
+                        })
                         .collect();
                     lines.push(format!(
                         "{}{}: {}({})",
@@ -505,11 +1624,11 @@ fn encode_object(
 
         match v {
             Value::Object(nested) => {
-                encode_object(nested, lines, actual_depth, registry, Some(k));
+                encode_object(nested, lines, actual_depth, registry, Some(k), options);
             }
             Value::Array(arr) => {
                 lines.push(format!("{}{}", actual_indent, k));
-                encode_array(arr, lines, actual_depth + 1, registry);
+                encode_array(arr, lines, actual_depth + 1, registry, options);
             }
             _ => {
                 lines.push(format!("{}{}: {}", actual_indent, k, format_primitive(v)));
@@ -539,45 +1658,57 @@ fn parse_struct_def(line: &str) -> Option<StructDefWithName> {
 
     let mut fields = Vec::new();
     let mut optional = Vec::new();
+    let mut field_types = FieldTypes::new();
 
     for field in fields_str.split(',') {
         let field = field.trim();
         if field.is_empty() {
             continue;
         }
-        if let Some(name) = field.strip_suffix('?') {
-            fields.push(name.to_string());
-            optional.push(name.to_string());
-        } else {
-            fields.push(field.to_string());
+        // Strip the optional marker first so `name:type?` and `name?` both work.
+        let (field, is_optional) = match field.strip_suffix('?') {
+            Some(name) => (name, true),
+            None => (field, false),
+        };
+        // An optional `:type` annotation, e.g. `price:float`.
+        let (field_name, field_type) = match field.split_once(':') {
+            Some((name, ty)) => (name.trim(), Some(ty.trim().to_string())),
+            None => (field, None),
+        };
+
+        fields.push(field_name.to_string());
+        if is_optional {
+            optional.push(field_name.to_string());
+        }
+        if let Some(ty) = field_type {
+            field_types.insert(field_name.to_string(), ty);
         }
     }
 
-    Some((name, fields, optional, parents))
+    Some((name, fields, optional, parents, field_types))
 }
 
-fn parse_primitive(s: &str) -> Value {
+/// Parse a bare or quoted primitive found at `line`/`column` (used to
+/// position a [`StructParseErrorKind::InvalidEscape`] if a quoted string's
+/// escapes are malformed).
+pub(crate) fn parse_primitive(s: &str, line: usize, column: usize) -> Result<Value> {
     let s = s.trim();
     if s.is_empty() {
-        return Value::Null;
+        return Ok(Value::Null);
     }
 
     // Quoted string
-    if s.starts_with('"') && s.ends_with('"') {
-        let inner = &s[1..s.len() - 1];
-        return Value::String(
-            inner
-                .replace("\\n", "\n")
-                .replace("\\\"", "\"")
-                .replace("\\\\", "\\"),
-        );
+    if let Some(end) = find_string_end(s) {
+        if end == s.len() - 1 {
+            return Ok(Value::String(unescape_string(&s[1..end], line, column)?));
+        }
     }
 
     // Boolean/null
     match s.to_lowercase().as_str() {
-        "null" => return Value::Null,
-        "true" => return Value::Bool(true),
-        "false" => return Value::Bool(false),
+        "null" => return Ok(Value::Null),
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
         _ => {}
     }
 
@@ -586,15 +1717,123 @@ fn parse_primitive(s: &str) -> Value {
         if s.contains('.') || s.to_lowercase().contains('e') {
             if let Ok(f) = s.parse::<f64>() {
                 if let Some(n) = serde_json::Number::from_f64(f) {
-                    return Value::Number(n);
+                    return Ok(Value::Number(n));
                 }
             }
         } else if let Ok(i) = s.parse::<i64>() {
-            return Value::Number(i.into());
+            return Ok(Value::Number(i.into()));
+        }
+    }
+
+    Ok(Value::String(s.to_string()))
+}
+
+/// Find the byte index, within `s`, of the unescaped `"` that closes the
+/// quoted string starting at `s`'s first character. A `\X` pair (for any
+/// `X`) is always treated as one escaped unit, so an escaped `\"` never
+/// closes the string early. Returns `None` if `s` doesn't start with `"` or
+/// the quote is never closed.
+fn find_string_end(s: &str) -> Option<usize> {
+    if !s.starts_with('"') {
+        return None;
+    }
+    let mut chars = s.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '"' => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Unescape a quoted primitive's raw content (surrounding quotes already
+/// stripped), following the same grammar serde_json's `de.rs` string
+/// scanner uses: `\" \\ \/ \n \r \t \b \f`, and `\uXXXX` with UTF-16
+/// surrogate pairs combined into a single `char`. `line`/`column` position
+/// any [`StructParseErrorKind::InvalidEscape`] this produces.
+fn unescape_string(inner: &str, line: usize, column: usize) -> Result<String> {
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        let escape = chars
+            .next()
+            .ok_or_else(|| invalid_escape(line, column, "trailing backslash"))?;
+        match escape {
+            '"' => out.push('"'),
+            '\\' => out.push('\\'),
+            '/' => out.push('/'),
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            't' => out.push('\t'),
+            'b' => out.push('\u{8}'),
+            'f' => out.push('\u{c}'),
+            'u' => {
+                let high = read_hex4(&mut chars, line, column)?;
+                let code_point = if (0xD800..=0xDBFF).contains(&high) {
+                    // High surrogate: must be immediately followed by a
+                    // low-surrogate escape to combine into one scalar value.
+                    if chars.next() != Some('\\') || chars.next() != Some('u') {
+                        return Err(invalid_escape(line, column, "unpaired UTF-16 surrogate"));
+                    }
+                    let low = read_hex4(&mut chars, line, column)?;
+                    if !(0xDC00..=0xDFFF).contains(&low) {
+                        return Err(invalid_escape(line, column, "unpaired UTF-16 surrogate"));
+                    }
+                    0x10000 + (high - 0xD800) * 0x400 + (low - 0xDC00)
+                } else if (0xDC00..=0xDFFF).contains(&high) {
+                    return Err(invalid_escape(line, column, "unpaired UTF-16 surrogate"));
+                } else {
+                    high
+                };
+                out.push(
+                    char::from_u32(code_point)
+                        .ok_or_else(|| invalid_escape(line, column, "invalid \\u escape"))?,
+                );
+            }
+            other => {
+                return Err(invalid_escape(
+                    line,
+                    column,
+                    &format!("unknown escape \\{}", other),
+                ))
+            }
         }
     }
 
-    Value::String(s.to_string())
+    Ok(out)
+}
+
+/// Read exactly 4 hex digits off `chars` as a `\uXXXX` code unit.
+fn read_hex4(chars: &mut std::str::Chars<'_>, line: usize, column: usize) -> Result<u32> {
+    let mut value = 0u32;
+    for _ in 0..4 {
+        let digit = chars
+            .next()
+            .ok_or_else(|| invalid_escape(line, column, "incomplete \\u escape"))?
+            .to_digit(16)
+            .ok_or_else(|| invalid_escape(line, column, "invalid hex digit in \\u escape"))?;
+        value = value * 16 + digit;
+    }
+    Ok(value)
+}
+
+fn invalid_escape(line: usize, column: usize, message: &str) -> AgonError {
+    AgonError::StructDecodeError {
+        line,
+        column,
+        kind: StructParseErrorKind::InvalidEscape,
+        message: message.to_string(),
+    }
 }
 
 fn get_indent_depth(line: &str) -> usize {
@@ -603,34 +1842,137 @@ fn get_indent_depth(line: &str) -> usize {
     spaces / 2
 }
 
-fn parse_struct_instance(s: &str, registry: &StructRegistry) -> Option<Value> {
-    let caps = STRUCT_INST_RE.captures(s)?;
-    let name = caps.get(1)?.as_str();
+/// The 1-based (line, column) location of `lines[idx]`'s first non-space
+/// character, for attaching to a [`StructDecodeError`](AgonError::StructDecodeError).
+fn line_location(lines: &[&str], idx: usize) -> (usize, usize) {
+    let line = lines[idx];
+    let column = line.len() - line.trim_start_matches(' ').len() + 1;
+    (idx + 1, column)
+}
+
+/// Parse a positional value according to a field's declared type, bypassing
+/// value-based inference entirely so a `str` field is always a string (even
+/// if it reads as a number) and a numeric/boolean field rejects values that
+/// don't fit its declared type.
+fn coerce_typed_value(val_str: &str, field_type: &str, line: usize, column: usize) -> Result<Value> {
+    let trimmed = val_str.trim();
+    let quoted_end = find_string_end(trimmed).filter(|&end| end == trimmed.len() - 1);
+    let unquoted = match quoted_end {
+        Some(end) => &trimmed[1..end],
+        None => trimmed,
+    };
+
+    match field_type {
+        "str" => Ok(Value::String(if quoted_end.is_some() {
+            unescape_string(unquoted, line, column)?
+        } else {
+            unquoted.to_string()
+        })),
+        "int" => unquoted.parse::<i64>().map(|i| Value::Number(i.into())).map_err(|_| {
+            AgonError::DecodingError(format!(
+                "Field declared `int` has a non-integer value: {}",
+                val_str
+            ))
+        }),
+        "float" => unquoted
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| {
+                AgonError::DecodingError(format!(
+                    "Field declared `float` has a non-numeric value: {}",
+                    val_str
+                ))
+            }),
+        "bool" => match unquoted.to_lowercase().as_str() {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => Err(AgonError::DecodingError(format!(
+                "Field declared `bool` has a non-boolean value: {}",
+                val_str
+            ))),
+        },
+        // Unrecognized type name: fall back to today's value-based inference.
+        _ => parse_primitive(val_str, line, column),
+    }
+}
+
+fn parse_struct_instance(
+    s: &str,
+    registry: &StructRegistry,
+    line: usize,
+    column: usize,
+) -> Result<Option<Value>> {
+    let caps = match STRUCT_INST_RE.captures(s) {
+        Some(caps) => caps,
+        None => return Ok(None),
+    };
+    let name = match caps.get(1) {
+        Some(m) => m.as_str(),
+        None => return Ok(None),
+    };
+
+    // Find the closing paren. Without one this isn't a struct call at all
+    // (e.g. a bare identifier that happens to contain an opening paren).
+    let start = match s.find('(') {
+        Some(i) => i + 1,
+        None => return Ok(None),
+    };
+    let end = match s.rfind(')') {
+        Some(i) => i,
+        None => return Ok(None),
+    };
 
-    let (fields, _, _) = registry.get(name)?;
+    let (fields, optional, _, field_types) = registry.get(name).ok_or_else(|| {
+        AgonError::StructDecodeError {
+            line,
+            column,
+            kind: StructParseErrorKind::UnknownStruct,
+            message: format!("`{}` has no registered struct definition", name),
+        }
+    })?;
 
-    // Find the closing paren
-    let start = s.find('(')? + 1;
-    let end = s.rfind(')')?;
     let values_str = &s[start..end];
 
     // Split values (respecting nested parens and quotes)
     let values = split_struct_values(values_str);
 
+    let required = fields.len() - optional.len();
+    if values.len() < required || values.len() > fields.len() {
+        return Err(AgonError::StructDecodeError {
+            line,
+            column,
+            kind: StructParseErrorKind::BadStructArity,
+            message: format!(
+                "`{}` takes {} argument{} ({} required), got {}",
+                name,
+                fields.len(),
+                if fields.len() == 1 { "" } else { "s" },
+                required,
+                values.len()
+            ),
+        });
+    }
+
     let mut obj = Map::new();
     for (i, field) in fields.iter().enumerate() {
         if let Some(val_str) = values.get(i) {
             // Recursively parse struct instances
-            let val = if let Some(nested) = parse_struct_instance(val_str.trim(), registry) {
+            let val = if let Some(nested) =
+                parse_struct_instance(val_str.trim(), registry, line, column)?
+            {
                 nested
+            } else if let Some(field_type) = field_types.get(field) {
+                coerce_typed_value(val_str, field_type, line, column)?
             } else {
-                parse_primitive(val_str)
+                parse_primitive(val_str, line, column)?
             };
             obj.insert(field.clone(), val);
         }
     }
 
-    Some(Value::Object(obj))
+    Ok(Some(Value::Object(obj)))
 }
 
 fn split_struct_values(s: &str) -> Vec<String> {
@@ -638,8 +1980,18 @@ fn split_struct_values(s: &str) -> Vec<String> {
     let mut current = String::new();
     let mut paren_depth = 0;
     let mut in_quote = false;
-
-    for c in s.chars() {
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        // An escaped character inside a quoted value never toggles quote
+        // state or counts as a delimiter, so `"a, b\"c"` stays one value.
+        if in_quote && c == '\\' {
+            current.push(c);
+            if let Some(escaped) = chars.next() {
+                current.push(escaped);
+            }
+            continue;
+        }
         match c {
             '"' if !in_quote => in_quote = true,
             '"' if in_quote => in_quote = false,
@@ -711,10 +2063,11 @@ fn decode_value(
     }
 
     // Single value
-    let val = if let Some(struct_val) = parse_struct_instance(stripped, registry) {
+    let (line_no, column) = line_location(lines, idx);
+    let val = if let Some(struct_val) = parse_struct_instance(stripped, registry, line_no, column)? {
         struct_val
     } else {
-        parse_primitive(stripped)
+        parse_primitive(stripped, line_no, column)?
     };
 
     Ok((val, idx + 1))
@@ -755,26 +2108,36 @@ fn decode_array(
         let stripped = line.trim();
         if let Some(item_str) = stripped.strip_prefix("- ") {
             let content = item_str.trim();
+            let (line_no, column) = line_location(lines, idx);
             // Check struct instance FIRST (struct values may contain ':' which matches KEY_VALUE_RE)
-            if let Some(struct_val) = parse_struct_instance(content, registry) {
+            if let Some(struct_val) = parse_struct_instance(content, registry, line_no, column)? {
                 result.push(struct_val);
                 idx += 1;
             } else if is_quoted_string(content) {
                 // If this is a quoted string list item, treat it as a primitive.
                 // This avoids ambiguity with inline object syntax when the string
                 // contains ':' (e.g. "keyword match: foo").
-                result.push(parse_primitive(content));
+                result.push(parse_primitive(content, line_no, column)?);
                 idx += 1;
             } else if KEY_VALUE_RE.is_match(content) {
                 let (obj, new_idx) = decode_list_item(lines, idx, base_depth, registry)?;
                 result.push(obj);
                 idx = new_idx;
             } else {
-                result.push(parse_primitive(item_str));
+                result.push(parse_primitive(item_str, line_no, column)?);
                 idx += 1;
             }
         } else {
-            break;
+            let (line_no, column) = line_location(lines, idx);
+            return Err(AgonError::StructDecodeError {
+                line: line_no,
+                column,
+                kind: StructParseErrorKind::UnexpectedIndent,
+                message: format!(
+                    "expected a `- ` list item at indent level {}, found: {}",
+                    base_depth, stripped
+                ),
+            });
         }
     }
 
@@ -813,20 +2176,21 @@ fn decode_array_from_items(
         let stripped = line.trim();
         if let Some(item_str) = stripped.strip_prefix("- ") {
             let content = item_str.trim();
+            let (line_no, column) = line_location(lines, idx);
             // Check struct instance first
-            if let Some(struct_val) = parse_struct_instance(content, registry) {
+            if let Some(struct_val) = parse_struct_instance(content, registry, line_no, column)? {
                 result.push(struct_val);
                 idx += 1;
             } else if is_quoted_string(content) {
                 // Quoted strings should be treated as primitives, not key-value pairs
-                result.push(parse_primitive(content));
+                result.push(parse_primitive(content, line_no, column)?);
                 idx += 1;
             } else if KEY_VALUE_RE.is_match(content) {
                 let (obj, new_idx) = decode_list_item(lines, idx, base_depth, registry)?;
                 result.push(obj);
                 idx = new_idx;
             } else {
-                result.push(parse_primitive(item_str));
+                result.push(parse_primitive(item_str, line_no, column)?);
                 idx += 1;
             }
         } else {
@@ -839,7 +2203,87 @@ fn decode_array_from_items(
 
 /// Check if a string is a quoted string (starts and ends with double quotes)
 fn is_quoted_string(s: &str) -> bool {
-    s.len() >= 2 && s.starts_with('"') && s.ends_with('"')
+    find_string_end(s).is_some_and(|end| end == s.len() - 1)
+}
+
+/// Grammar rule `field = ident, ":", [ inline_value ], [ "\n", indent, value ]`
+/// — decode a `key: value` line into `map[key]`. An empty `value` means the
+/// field's value lives in an indented block on the following lines, which is
+/// only consumed if it's indented past `nested_threshold`; otherwise the
+/// field decodes to an empty object. Shared by [`decode_object`] and
+/// [`decode_list_item`], which previously duplicated this logic inline.
+/// Returns the line index to resume scanning from.
+fn decode_key_value_field(
+    map: &mut Map<String, Value>,
+    caps: &regex::Captures<'_>,
+    lines: &[&str],
+    idx: usize,
+    nested_threshold: usize,
+    registry: &StructRegistry,
+) -> Result<usize> {
+    let key = caps.get(1).map(|m| m.as_str()).unwrap_or("").trim();
+    let val_str = caps.get(2).map(|m| m.as_str()).unwrap_or("").trim();
+    let mut idx = idx;
+
+    if !val_str.is_empty() {
+        let (line_no, column) = line_location(lines, idx);
+        let val = if let Some(struct_val) = parse_struct_instance(val_str, registry, line_no, column)? {
+            struct_val
+        } else {
+            parse_primitive(val_str, line_no, column)?
+        };
+        map.insert(key.to_string(), val);
+        idx += 1;
+    } else {
+        idx += 1;
+        if idx < lines.len() {
+            let next_depth = get_indent_depth(lines[idx]);
+            if next_depth > nested_threshold {
+                let (nested, new_idx) = decode_value(lines, idx, next_depth, registry)?;
+                map.insert(key.to_string(), nested);
+                idx = new_idx;
+            } else {
+                // Empty object - no nested content
+                map.insert(key.to_string(), Value::Object(Map::new()));
+            }
+        } else {
+            map.insert(key.to_string(), Value::Object(Map::new()));
+        }
+    }
+    Ok(idx)
+}
+
+/// Grammar rule `bare_key_field = ident, "\n", indent, array` — decode a bare
+/// key (no `:`) that's only valid when followed by an `array_header` line;
+/// any other following content leaves the key set to `null`. Shared by
+/// [`decode_object`] and [`decode_list_item`].
+fn decode_bare_key_field(
+    map: &mut Map<String, Value>,
+    key: &str,
+    lines: &[&str],
+    idx: usize,
+    field_depth: usize,
+    registry: &StructRegistry,
+) -> Result<usize> {
+    let mut idx = idx + 1;
+
+    while idx < lines.len() && lines[idx].trim().is_empty() {
+        idx += 1;
+    }
+
+    if idx < lines.len() {
+        let next_line = lines[idx].trim();
+        if ARRAY_HEADER_RE.is_match(next_line) {
+            let (arr, new_idx) = decode_array(lines, idx, field_depth, registry)?;
+            map.insert(key.to_string(), arr);
+            idx = new_idx;
+        } else {
+            map.insert(key.to_string(), Value::Null);
+        }
+    } else {
+        map.insert(key.to_string(), Value::Null);
+    }
+    Ok(idx)
 }
 
 fn decode_list_item(
@@ -857,33 +2301,7 @@ fn decode_list_item(
     let mut idx = idx;
 
     if let Some(caps) = KEY_VALUE_RE.captures(first_content) {
-        let key = caps.get(1).map(|m| m.as_str()).unwrap_or("").trim();
-        let val_str = caps.get(2).map(|m| m.as_str()).unwrap_or("").trim();
-
-        if !val_str.is_empty() {
-            let val = if let Some(struct_val) = parse_struct_instance(val_str, registry) {
-                struct_val
-            } else {
-                parse_primitive(val_str)
-            };
-            obj.insert(key.to_string(), val);
-            idx += 1;
-        } else {
-            idx += 1;
-            if idx < lines.len() {
-                let next_depth = get_indent_depth(lines[idx]);
-                if next_depth > item_depth + 1 {
-                    let (nested, new_idx) = decode_value(lines, idx, next_depth, registry)?;
-                    obj.insert(key.to_string(), nested);
-                    idx = new_idx;
-                } else {
-                    // Empty object - no nested content
-                    obj.insert(key.to_string(), Value::Object(Map::new()));
-                }
-            } else {
-                obj.insert(key.to_string(), Value::Object(Map::new()));
-            }
-        }
+        idx = decode_key_value_field(&mut obj, &caps, lines, idx, item_depth + 1, registry)?;
     } else {
         idx += 1;
     }
@@ -909,57 +2327,9 @@ fn decode_list_item(
         }
 
         if let Some(caps) = KEY_VALUE_RE.captures(stripped) {
-            let key = caps.get(1).map(|m| m.as_str()).unwrap_or("").trim();
-            let val_str = caps.get(2).map(|m| m.as_str()).unwrap_or("").trim();
-
-            if !val_str.is_empty() {
-                let val = if let Some(struct_val) = parse_struct_instance(val_str, registry) {
-                    struct_val
-                } else {
-                    parse_primitive(val_str)
-                };
-                obj.insert(key.to_string(), val);
-                idx += 1;
-            } else {
-                idx += 1;
-                if idx < lines.len() {
-                    let next_depth = get_indent_depth(lines[idx]);
-                    if next_depth > line_depth {
-                        let (nested, new_idx) = decode_value(lines, idx, next_depth, registry)?;
-                        obj.insert(key.to_string(), nested);
-                        idx = new_idx;
-                    } else {
-                        // Empty object - no nested content
-                        obj.insert(key.to_string(), Value::Object(Map::new()));
-                    }
-                } else {
-                    obj.insert(key.to_string(), Value::Object(Map::new()));
-                }
-            }
+            idx = decode_key_value_field(&mut obj, &caps, lines, idx, line_depth, registry)?;
         } else if is_bare_identifier(stripped) {
-            // Bare key (no colon) - check if next line is an array
-            let key = stripped.to_string();
-            idx += 1;
-
-            // Skip blank lines
-            while idx < lines.len() && lines[idx].trim().is_empty() {
-                idx += 1;
-            }
-
-            if idx < lines.len() {
-                let next_line = lines[idx].trim();
-                // Check if next line starts an array
-                if ARRAY_HEADER_RE.is_match(next_line) {
-                    let (arr, new_idx) = decode_array(lines, idx, line_depth, registry)?;
-                    obj.insert(key, arr);
-                    idx = new_idx;
-                } else {
-                    // Not an array, treat key as having null/empty value
-                    obj.insert(key, Value::Null);
-                }
-            } else {
-                obj.insert(key, Value::Null);
-            }
+            idx = decode_bare_key_field(&mut obj, stripped, lines, idx, line_depth, registry)?;
         } else {
             idx += 1;
         }
@@ -993,59 +2363,17 @@ fn decode_object(
         let stripped = line.trim();
 
         if let Some(caps) = KEY_VALUE_RE.captures(stripped) {
-            let key = caps.get(1).map(|m| m.as_str()).unwrap_or("").trim();
-            let val_str = caps.get(2).map(|m| m.as_str()).unwrap_or("").trim();
-
-            if !val_str.is_empty() {
-                let val = if let Some(struct_val) = parse_struct_instance(val_str, registry) {
-                    struct_val
-                } else {
-                    parse_primitive(val_str)
-                };
-                result.insert(key.to_string(), val);
-                idx += 1;
-            } else {
-                idx += 1;
-                if idx < lines.len() {
-                    let next_depth = get_indent_depth(lines[idx]);
-                    if next_depth > line_depth {
-                        let (nested, new_idx) = decode_value(lines, idx, next_depth, registry)?;
-                        result.insert(key.to_string(), nested);
-                        idx = new_idx;
-                    } else {
-                        result.insert(key.to_string(), Value::Object(Map::new()));
-                    }
-                } else {
-                    // End of file - still insert empty object
-                    result.insert(key.to_string(), Value::Object(Map::new()));
-                }
-            }
+            idx = decode_key_value_field(&mut result, &caps, lines, idx, line_depth, registry)?;
         } else if is_bare_identifier(stripped) {
-            // Bare key (no colon) - check if next line is an array
-            let key = stripped.to_string();
-            idx += 1;
-
-            // Skip blank lines
-            while idx < lines.len() && lines[idx].trim().is_empty() {
-                idx += 1;
-            }
-
-            if idx < lines.len() {
-                let next_line = lines[idx].trim();
-                // Check if next line starts an array
-                if ARRAY_HEADER_RE.is_match(next_line) {
-                    let (arr, new_idx) = decode_array(lines, idx, line_depth, registry)?;
-                    result.insert(key, arr);
-                    idx = new_idx;
-                } else {
-                    // Not an array, treat key as having null/empty value
-                    result.insert(key, Value::Null);
-                }
-            } else {
-                result.insert(key, Value::Null);
-            }
+            idx = decode_bare_key_field(&mut result, stripped, lines, idx, line_depth, registry)?;
         } else {
-            break;
+            let (line_no, column) = line_location(lines, idx);
+            return Err(AgonError::StructDecodeError {
+                line: line_no,
+                column,
+                kind: StructParseErrorKind::UnexpectedIndent,
+                message: format!("expected a `key: value` line at indent level {}, found: {}", base_depth, stripped),
+            });
         }
     }
 
@@ -1114,38 +2442,250 @@ mod tests {
     }
 
     #[test]
-    fn test_encode_primitives() {
+    fn test_encode_primitives() {
+        let data = json!({
+            "string": "hello",
+            "number": 42,
+            "bool_true": true,
+            "null_val": null
+        });
+        let encoded = encode(&data, false).unwrap();
+        assert!(encoded.contains("string: hello"));
+        assert!(encoded.contains("number: 42"));
+        assert!(encoded.contains("bool_true: true"));
+        assert!(encoded.contains("null_val: null"));
+    }
+
+    #[test]
+    fn test_encode_repeated_shapes_creates_struct() {
+        // Three occurrences of same shape should create a struct
+        let data = json!({
+            "price": {"fmt": "100.00", "raw": 100.0},
+            "change": {"fmt": "+5.00", "raw": 5.0},
+            "volume": {"fmt": "1M", "raw": 1000000}
+        });
+        let encoded = encode(&data, false).unwrap();
+        // Should have struct definition
+        assert!(encoded.contains("@") && encoded.contains(":"));
+    }
+
+    #[test]
+    fn test_encode_empty_array() {
+        let data = json!({"items": []});
+        let encoded = encode(&data, false).unwrap();
+        assert!(encoded.contains("[0]:"));
+    }
+
+    // ========================================================================
+    // Schema tests
+    // ========================================================================
+
+    #[test]
+    fn test_encode_with_schema_uses_stable_name_instead_of_generated_one() {
+        let mut schema = Schema::new();
+        schema
+            .define("@Quote: fmt, raw")
+            .expect("valid struct definition");
+
+        // Only a single occurrence, so auto-detection (which requires 3)
+        // would never template this shape at all.
+        let data = json!({"price": {"fmt": "100.00", "raw": 100.0}});
+        let encoded = encode_with_schema(&data, &schema, true).unwrap();
+        assert!(encoded.contains("@Quote: fmt, raw"));
+        assert!(encoded.contains("price: Quote(\"100.00\", 100.0)"));
+    }
+
+    #[test]
+    fn test_encode_with_schema_register_matches_define() {
+        let mut by_register = Schema::new();
+        by_register
+            .register(
+                "Quote",
+                &["fmt".to_string(), "raw".to_string()],
+                &[],
+                &[],
+                &FieldTypes::new(),
+            )
+            .unwrap();
+        let mut by_define = Schema::new();
+        by_define.define("@Quote: fmt, raw").unwrap();
+
+        let data = json!({"price": {"fmt": "100.00", "raw": 100.0}});
+        assert_eq!(
+            encode_with_schema(&data, &by_register, false).unwrap(),
+            encode_with_schema(&data, &by_define, false).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_define_rejects_malformed_struct_definition() {
+        let mut schema = Schema::new();
+        let err = schema.define("not a struct def").unwrap_err();
+        assert!(matches!(err, AgonError::InvalidFormat(_)));
+    }
+
+    #[test]
+    fn test_encode_with_schema_strict_mode_errors_on_unmatched_shape() {
+        let mut schema = Schema::new();
+        schema.define("@Quote: fmt, raw").unwrap();
+        let schema = schema.strict(true);
+
+        let data = json!({"price": {"fmt": "100.00", "raw": 100.0, "extra": true}});
+        let err = encode_with_schema(&data, &schema, true).unwrap_err();
+        assert!(matches!(err, AgonError::EncodingError(_)));
+    }
+
+    #[test]
+    fn test_encode_with_schema_strict_mode_accepts_matching_shape() {
+        let mut schema = Schema::new();
+        schema.define("@Quote: fmt, raw").unwrap();
+        let schema = schema.strict(true);
+
+        let data = json!({"price": {"fmt": "100.00", "raw": 100.0}});
+        assert!(encode_with_schema(&data, &schema, true).is_ok());
+    }
+
+    #[test]
+    fn test_encode_with_schema_strict_mode_does_not_flag_root_object() {
+        // The root object is never struct-encoded (there's no key for it to
+        // attach to), so strict mode must not reject it just because its own
+        // fields match no struct.
+        let mut schema = Schema::new();
+        schema.define("@Quote: fmt, raw").unwrap();
+        let schema = schema.strict(true);
+
+        let data = json!({"fmt": "100.00", "raw": 100.0, "extra": true});
+        assert!(encode_with_schema(&data, &schema, false).is_ok());
+    }
+
+    #[test]
+    fn test_encode_with_schema_honors_parent_and_optional_fields() {
+        let mut schema = Schema::new();
+        schema.define("@Base: id, name").unwrap();
+        schema.define("@Item(Base): price?").unwrap();
+
+        let data = json!({
+            "thing": {"id": "1", "name": "widget", "price": "9.99"}
+        });
+        let encoded = encode_with_schema(&data, &schema, true).unwrap();
+        assert!(encoded.contains("@Item(Base): price?"));
+        // Untyped fields still quote number-lookalike strings to preserve type on decode.
+        assert!(encoded.contains("thing: Item(\"1\", widget, \"9.99\")"));
+    }
+
+    #[test]
+    fn test_decode_with_schema_reads_payload_with_no_inline_definitions() {
+        let mut schema = Schema::new();
+        schema.define("@Quote: fmt, raw").unwrap();
+
+        // No `@Quote: ...` line at all: the schema is the only source of the
+        // struct's shape, as when it was agreed on out of band.
+        let payload = "@AGON struct\n\nprice: Quote(\"100.00\", 100.0)";
+        let decoded = decode_with_schema(payload, &schema).unwrap();
+        assert_eq!(
+            decoded,
+            json!({"price": {"fmt": "100.00", "raw": 100.0}})
+        );
+    }
+
+    #[test]
+    fn test_decode_with_schema_roundtrips_encode_with_schema_output() {
+        let mut schema = Schema::new();
+        schema.define("@Quote: fmt, raw").unwrap();
+
+        let data = json!({"price": {"fmt": "100.00", "raw": 100.0}});
+        let encoded = encode_with_schema(&data, &schema, true).unwrap();
+        assert_eq!(decode_with_schema(&encoded, &schema).unwrap(), data);
+    }
+
+    #[test]
+    fn test_decode_with_schema_inline_definition_overrides_schema() {
+        let mut schema = Schema::new();
+        schema.define("@Quote: fmt, raw").unwrap();
+
+        // The payload's own definition (a different field order) wins over
+        // the pre-registered one for this document.
+        let payload = "@AGON struct\n\n@Quote: raw, fmt\n\nprice: Quote(100.0, \"100.00\")";
+        let decoded = decode_with_schema(payload, &schema).unwrap();
+        assert_eq!(
+            decoded,
+            json!({"price": {"fmt": "100.00", "raw": 100.0}})
+        );
+    }
+
+    // ========================================================================
+    // EncodeOptions tests
+    // ========================================================================
+
+    #[test]
+    fn test_encode_with_struct_options_defaults_match_encode() {
+        let data = json!({
+            "items": [
+                {"id": "1", "name": "a"},
+                {"id": "2", "name": "b"},
+                {"id": "3", "name": "c"},
+            ]
+        });
+        let default_options = encode(&data, true).unwrap();
+        let explicit_options =
+            encode_with_struct_options(&data, true, &EncodeOptions::new()).unwrap();
+        assert_eq!(default_options, explicit_options);
+    }
+
+    #[test]
+    fn test_encode_with_struct_options_lower_min_occurrences_promotes_sooner() {
+        let data = json!({
+            "items": [
+                {"id": "1", "name": "a"},
+                {"id": "2", "name": "b"},
+            ]
+        });
+        // Only 2 occurrences: below the default threshold of 3.
+        let default_encoded = encode(&data, true).unwrap();
+        assert!(!default_encoded.contains("IN:"));
+
+        let options = EncodeOptions::new().min_occurrences(2);
+        let encoded = encode_with_struct_options(&data, true, &options).unwrap();
+        assert!(encoded.contains("IN:"));
+    }
+
+    #[test]
+    fn test_encode_with_struct_options_force_single_struct_ignores_threshold() {
         let data = json!({
-            "string": "hello",
-            "number": 42,
-            "bool_true": true,
-            "null_val": null
+            "items": [
+                {"id": "1", "name": "a"},
+            ]
         });
-        let encoded = encode(&data, false).unwrap();
-        assert!(encoded.contains("string: hello"));
-        assert!(encoded.contains("number: 42"));
-        assert!(encoded.contains("bool_true: true"));
-        assert!(encoded.contains("null_val: null"));
+        let options = EncodeOptions::new().force_single_struct(true);
+        let encoded = encode_with_struct_options(&data, true, &options).unwrap();
+        assert!(encoded.contains("IN:"));
+        assert!(encoded.contains("IN(\"1\", a)"));
     }
 
     #[test]
-    fn test_encode_repeated_shapes_creates_struct() {
-        // Three occurrences of same shape should create a struct
+    fn test_encode_with_struct_options_disable_inheritance_skips_parent_factoring() {
         let data = json!({
-            "price": {"fmt": "100.00", "raw": 100.0},
-            "change": {"fmt": "+5.00", "raw": 5.0},
-            "volume": {"fmt": "1M", "raw": 1000000}
+            "a": {"id": "1", "name": "x", "extra": "p"},
+            "b": {"id": "2", "name": "y", "extra": "q"},
+            "c": {"id": "3", "name": "z", "extra": "r"},
+            "d": {"id": "4", "name": "w"},
+            "e": {"id": "5", "name": "v"},
+            "f": {"id": "6", "name": "u"},
         });
-        let encoded = encode(&data, false).unwrap();
-        // Should have struct definition
-        assert!(encoded.contains("@") && encoded.contains(":"));
+        let default_encoded = encode(&data, true).unwrap();
+        assert!(default_encoded.lines().any(|l| l.starts_with('@') && l.contains('(')));
+
+        let options = EncodeOptions::new().disable_inheritance(true);
+        let encoded = encode_with_struct_options(&data, true, &options).unwrap();
+        assert!(!encoded.lines().any(|l| l.starts_with('@') && l.contains('(')));
     }
 
     #[test]
-    fn test_encode_empty_array() {
-        let data = json!({"items": []});
-        let encoded = encode(&data, false).unwrap();
-        assert!(encoded.contains("[0]:"));
+    fn test_encode_with_struct_options_indent_overrides_width() {
+        let data = json!({"nested": {"x": {"deep": 1}}});
+        let options = EncodeOptions::new().indent(4);
+        let encoded = encode_with_struct_options(&data, true, &options).unwrap();
+        assert!(encoded.contains("\n    x:"));
     }
 
     // ========================================================================
@@ -1262,6 +2802,59 @@ mod tests {
         assert!(decoded["quote"].is_object());
     }
 
+    // ========================================================================
+    // Streaming decode tests
+    // ========================================================================
+
+    #[test]
+    fn test_decode_reader_yields_one_document() {
+        let payload = "@AGON struct\n\n@FR: fmt, raw\n\nprice: FR(\"100.00\", 100.0)";
+        let docs: Vec<Value> = decode_reader(payload.as_bytes())
+            .collect::<Result<Vec<Value>>>()
+            .unwrap();
+        assert_eq!(docs.len(), 1);
+        assert_eq!(docs[0]["price"]["fmt"], "100.00");
+    }
+
+    #[test]
+    fn test_decode_reader_yields_each_concatenated_document() {
+        let stream = "@AGON struct\n\n@FR: fmt, raw\n\nprice: FR(\"1\", 1)\n\
+                       @AGON struct\n\n@FR: fmt, raw\n\nprice: FR(\"2\", 2)\n";
+        let docs: Vec<Value> = decode_reader(stream.as_bytes())
+            .collect::<Result<Vec<Value>>>()
+            .unwrap();
+        assert_eq!(docs.len(), 2);
+        assert_eq!(docs[0]["price"]["fmt"], "1");
+        assert_eq!(docs[1]["price"]["fmt"], "2");
+    }
+
+    #[test]
+    fn test_decode_reader_registry_does_not_leak_across_documents() {
+        // Document two defines no `@FR` struct of its own, so if the
+        // registry from document one leaked through, `FR(...)` there would
+        // wrongly resolve instead of surfacing an UnknownStruct error.
+        let stream = "@AGON struct\n\n@FR: fmt, raw\n\nprice: FR(\"1\", 1)\n\
+                       @AGON struct\n\nprice: FR(\"2\", 2)\n";
+        let mut docs = decode_reader(stream.as_bytes());
+        assert!(docs.next().unwrap().is_ok());
+        let err = docs.next().unwrap().unwrap_err();
+        assert!(matches!(
+            err,
+            AgonError::StructDecodeError {
+                kind: StructParseErrorKind::UnknownStruct,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_decode_reader_empty_input_yields_no_documents() {
+        let docs: Vec<Value> = decode_reader(&b""[..])
+            .collect::<Result<Vec<Value>>>()
+            .unwrap();
+        assert!(docs.is_empty());
+    }
+
     // ========================================================================
     // Parse struct definition tests
     // ========================================================================
@@ -1269,17 +2862,18 @@ mod tests {
     #[test]
     fn test_parse_struct_def() {
         let line = "@FR: fmt, raw";
-        let (name, fields, optional, parents) = parse_struct_def(line).unwrap();
+        let (name, fields, optional, parents, field_types) = parse_struct_def(line).unwrap();
         assert_eq!(name, "FR");
         assert_eq!(fields, vec!["fmt", "raw"]);
         assert!(optional.is_empty());
         assert!(parents.is_empty());
+        assert!(field_types.is_empty());
     }
 
     #[test]
     fn test_parse_struct_def_with_optional() {
         let line = "@Quote: symbol, price, volume?";
-        let (name, fields, optional, _) = parse_struct_def(line).unwrap();
+        let (name, fields, optional, _, _) = parse_struct_def(line).unwrap();
         assert_eq!(name, "Quote");
         assert_eq!(fields, vec!["symbol", "price", "volume"]);
         assert_eq!(optional, vec!["volume"]);
@@ -1288,12 +2882,283 @@ mod tests {
     #[test]
     fn test_parse_struct_def_with_parent() {
         let line = "@FRC(FR): currency";
-        let (name, fields, _, parents) = parse_struct_def(line).unwrap();
+        let (name, fields, _, parents, _) = parse_struct_def(line).unwrap();
         assert_eq!(name, "FRC");
         assert_eq!(fields, vec!["currency"]);
         assert_eq!(parents, vec!["FR"]);
     }
 
+    #[test]
+    fn test_parse_struct_def_with_types() {
+        let line = "@Product: name:str, price:float, count:int, active:bool, tags:str?";
+        let (name, fields, optional, _, field_types) = parse_struct_def(line).unwrap();
+        assert_eq!(name, "Product");
+        assert_eq!(fields, vec!["name", "price", "count", "active", "tags"]);
+        assert_eq!(optional, vec!["tags"]);
+        assert_eq!(field_types.get("name").map(String::as_str), Some("str"));
+        assert_eq!(field_types.get("price").map(String::as_str), Some("float"));
+        assert_eq!(field_types.get("count").map(String::as_str), Some("int"));
+        assert_eq!(field_types.get("active").map(String::as_str), Some("bool"));
+        assert_eq!(field_types.get("tags").map(String::as_str), Some("str"));
+    }
+
+    // ========================================================================
+    // Typed struct fields
+    // ========================================================================
+
+    #[test]
+    fn test_decode_typed_str_field_keeps_number_lookalike_unquoted() {
+        let payload = "@AGON struct\n\n@Product: name:str, price:float\n\nitem: Product(42, 9.99)";
+        let value = decode(payload).unwrap();
+        assert_eq!(value["item"]["name"], json!("42"));
+        assert_eq!(value["item"]["price"], json!(9.99));
+    }
+
+    #[test]
+    fn test_decode_typed_int_field_rejects_fractional_value() {
+        let payload = "@AGON struct\n\n@Product: count:int\n\nitem: Product(3.5)";
+        let err = decode(payload).unwrap_err();
+        assert!(matches!(err, AgonError::DecodingError(_)));
+    }
+
+    #[test]
+    fn test_decode_typed_float_field_rejects_non_numeric_value() {
+        let payload = "@AGON struct\n\n@Product: price:float\n\nitem: Product(abc)";
+        let err = decode(payload).unwrap_err();
+        assert!(matches!(err, AgonError::DecodingError(_)));
+    }
+
+    #[test]
+    fn test_decode_typed_bool_field_rejects_non_boolean_value() {
+        let payload = "@AGON struct\n\n@Product: active:bool\n\nitem: Product(yes)";
+        let err = decode(payload).unwrap_err();
+        assert!(matches!(err, AgonError::DecodingError(_)));
+    }
+
+    #[test]
+    fn test_decode_untyped_field_keeps_inference_behavior() {
+        // Untyped fields are unaffected: a quoted number-lookalike still
+        // decodes to a string, same as before typed annotations existed.
+        let payload = "@AGON struct\n\n@FR: fmt, raw\n\nitem: FR(\"42\", 42)";
+        let value = decode(payload).unwrap();
+        assert_eq!(value["item"]["fmt"], json!("42"));
+        assert_eq!(value["item"]["raw"], json!(42));
+    }
+
+    // ========================================================================
+    // Positioned decode errors
+    // ========================================================================
+
+    #[test]
+    fn test_decode_unknown_struct_reports_position() {
+        let payload = "@AGON struct\n\n@FR: fmt, raw\n\nprice: Quote(\"100.00\", 100.0)";
+        let err = decode(payload).unwrap_err();
+        match err {
+            AgonError::StructDecodeError { line, kind, .. } => {
+                assert_eq!(line, 5);
+                assert_eq!(kind, StructParseErrorKind::UnknownStruct);
+            }
+            other => panic!("expected StructDecodeError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_struct_too_few_args_reports_position() {
+        let payload = "@AGON struct\n\n@FR: fmt, raw\n\nprice: FR(\"100.00\")";
+        let err = decode(payload).unwrap_err();
+        match err {
+            AgonError::StructDecodeError { line, kind, .. } => {
+                assert_eq!(line, 5);
+                assert_eq!(kind, StructParseErrorKind::BadStructArity);
+            }
+            other => panic!("expected StructDecodeError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_struct_too_many_args_reports_position() {
+        let payload = "@AGON struct\n\n@FR: fmt, raw\n\nprice: FR(\"100.00\", 100.0, \"extra\")";
+        let err = decode(payload).unwrap_err();
+        assert!(matches!(
+            err,
+            AgonError::StructDecodeError {
+                kind: StructParseErrorKind::BadStructArity,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_decode_struct_optional_arg_omitted_is_not_arity_error() {
+        // Already covered by test_decode_optional_field_omitted, but make the
+        // boundary explicit: the minimum required count is still accepted.
+        let payload = "@AGON struct\n\n@Quote: symbol, price, volume?\n\nstock: Quote(AAPL, 150.0)";
+        assert!(decode(payload).is_ok());
+    }
+
+    #[test]
+    fn test_decode_unexpected_indent_in_array_reports_position() {
+        let payload = "@AGON struct\n\n@FR: fmt, raw\n\n[2]:\n  - FR(\"1\", 1)\n  garbage";
+        let err = decode(payload).unwrap_err();
+        match err {
+            AgonError::StructDecodeError { line, kind, .. } => {
+                assert_eq!(line, 7);
+                assert_eq!(kind, StructParseErrorKind::UnexpectedIndent);
+            }
+            other => panic!("expected StructDecodeError, got {:?}", other),
+        }
+    }
+
+    // ========================================================================
+    // Escape sequences in quoted strings
+    // ========================================================================
+
+    #[test]
+    fn test_roundtrip_string_with_newline_and_quote() {
+        let data = json!({"note": "line one\nline \"two\"\tend"});
+        let encoded = encode(&data, true).unwrap();
+        // The physical payload is line-based, so the `note` value itself
+        // must stay on a single line: the embedded newline is escaped
+        // rather than emitted as a raw line break.
+        let note_line = encoded
+            .lines()
+            .find(|l| l.starts_with("note:"))
+            .expect("note line");
+        assert!(note_line.contains("\\n"));
+        assert_eq!(encoded.lines().count(), 3);
+
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_decode_all_named_escapes() {
+        let payload = r#"@AGON struct
+
+value: "a\"b\\c\/d\ne\rf\tg\bh\fi""#;
+        let decoded = decode(payload).unwrap();
+        assert_eq!(decoded["value"], json!("a\"b\\c/d\ne\rf\tg\u{8}h\u{c}i"));
+    }
+
+    #[test]
+    fn test_decode_unicode_escape() {
+        let payload = "@AGON struct\n\nvalue: \"caf\\u00e9\"";
+        let decoded = decode(payload).unwrap();
+        assert_eq!(decoded["value"], json!("caf\u{e9}"));
+    }
+
+    #[test]
+    fn test_decode_surrogate_pair_escape() {
+        // U+1F600 GRINNING FACE, encoded as a UTF-16 surrogate pair.
+        let payload = "@AGON struct\n\nvalue: \"\\ud83d\\ude00\"";
+        let decoded = decode(payload).unwrap();
+        assert_eq!(decoded["value"], json!("\u{1f600}"));
+    }
+
+    #[test]
+    fn test_decode_lone_high_surrogate_reports_position() {
+        let payload = "@AGON struct\n\nvalue: \"\\ud800\"";
+        let err = decode(payload).unwrap_err();
+        match err {
+            AgonError::StructDecodeError { line, kind, .. } => {
+                assert_eq!(line, 3);
+                assert_eq!(kind, StructParseErrorKind::InvalidEscape);
+            }
+            other => panic!("expected StructDecodeError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_incomplete_unicode_escape_reports_position() {
+        let payload = "@AGON struct\n\nvalue: \"\\u12\"";
+        let err = decode(payload).unwrap_err();
+        assert!(matches!(
+            err,
+            AgonError::StructDecodeError {
+                kind: StructParseErrorKind::InvalidEscape,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_decode_unknown_escape_reports_position() {
+        let payload = "@AGON struct\n\nvalue: \"\\q\"";
+        let err = decode(payload).unwrap_err();
+        assert!(matches!(
+            err,
+            AgonError::StructDecodeError {
+                kind: StructParseErrorKind::InvalidEscape,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_quoted_list_item_with_escaped_quote_stays_one_item() {
+        let payload = "@AGON struct\n\n[1]:\n  - \"has \\\"quotes\\\" inside\"";
+        let decoded = decode(payload).unwrap();
+        assert_eq!(decoded, json!(["has \"quotes\" inside"]));
+    }
+
+    #[test]
+    fn test_struct_instance_with_escaped_quote_in_value() {
+        let payload = "@AGON struct\n\n@Quote: text\n\nitem: Quote(\"a \\\"b\\\" c\")";
+        let decoded = decode(payload).unwrap();
+        assert_eq!(decoded["item"]["text"], json!("a \"b\" c"));
+    }
+
+    #[test]
+    fn test_encode_typed_str_field_skips_number_lookalike_quoting() {
+        let mut registry = StructRegistry::new();
+        let mut types = FieldTypes::new();
+        types.insert("code".to_string(), "str".to_string());
+        register_struct(
+            &mut registry,
+            "Item",
+            &["code".to_string()],
+            &[],
+            &[],
+            &types,
+        )
+        .unwrap();
+
+        let obj = json!({"code": "42"}).as_object().unwrap().clone();
+        let (fields, _, _, field_types) = registry.get("Item").unwrap();
+        let formatted: Vec<String> = fields
+            .iter()
+            .map(|f| format_primitive_typed(obj.get(f).unwrap(), field_types.get(f).map(String::as_str)))
+            .collect();
+        assert_eq!(formatted, vec!["42".to_string()]);
+    }
+
+    #[test]
+    fn test_coerce_typed_value_str_always_string() {
+        assert_eq!(coerce_typed_value("42", "str", 1, 1).unwrap(), json!("42"));
+        assert_eq!(coerce_typed_value("true", "str", 1, 1).unwrap(), json!("true"));
+    }
+
+    #[test]
+    fn test_coerce_typed_value_int_accepts_whole_number() {
+        assert_eq!(coerce_typed_value("42", "int", 1, 1).unwrap(), json!(42));
+    }
+
+    #[test]
+    fn test_struct_def_typed_field_roundtrip_through_text_format() {
+        // A struct def carrying a `str` type annotation, decoded then
+        // re-encoded, keeps the declared type and decodes identically.
+        let payload = "@AGON struct\n\n@Code: value:str\n\nitem: Code(007)";
+        let decoded = decode(payload).unwrap();
+        assert_eq!(decoded["item"]["value"], json!("007"));
+
+        let mut schema = Schema::new();
+        schema.define("@Code: value:str").unwrap();
+        let encoded = encode_with_schema(&decoded, &schema, true).unwrap();
+        assert!(encoded.contains("@Code: value:str"));
+        assert!(encoded.contains("item: Code(007)"));
+        assert_eq!(decode(&encoded).unwrap(), decoded);
+    }
+
     // ========================================================================
     // Helper function tests
     // ========================================================================
@@ -1376,6 +3241,229 @@ mod tests {
         assert_eq!(shapes.get(&shape), Some(&3));
     }
 
+    #[test]
+    fn test_create_struct_definitions_factors_shared_parent() {
+        // Two frequent shapes sharing a 2-field core should factor that core
+        // out into a parent, with each shape becoming a child that only
+        // lists its own extra fields.
+        let mut shapes = HashMap::new();
+        shapes.insert(
+            vec!["fmt".to_string(), "raw".to_string()],
+            3,
+        );
+        shapes.insert(
+            vec!["currency".to_string(), "fmt".to_string(), "raw".to_string()],
+            3,
+        );
+
+        let defs = create_struct_definitions(&shapes, 3, 2);
+
+        let parent = defs
+            .iter()
+            .find(|(_, fields, _, parents, _)| parents.is_empty() && fields.len() == 2)
+            .expect("expected a parent struct with the shared 2-field core");
+        let parent_name = &parent.0;
+
+        let child = defs
+            .iter()
+            .find(|(_, _, _, parents, _)| parents == &vec![parent_name.clone()])
+            .expect("expected a child struct referencing the parent");
+        assert_eq!(child.1, vec!["currency".to_string()]);
+    }
+
+    #[test]
+    fn test_create_struct_definitions_pure_alias_child() {
+        // A shape identical to the shared core becomes a child with zero
+        // extra fields of its own, still referencing the parent by name.
+        let mut shapes = HashMap::new();
+        shapes.insert(vec!["fmt".to_string(), "raw".to_string()], 3);
+        shapes.insert(
+            vec!["currency".to_string(), "fmt".to_string(), "raw".to_string()],
+            3,
+        );
+
+        let defs = create_struct_definitions(&shapes, 3, 2);
+        let alias_child = defs
+            .iter()
+            .find(|(_, fields, _, parents, _)| fields.is_empty() && !parents.is_empty())
+            .expect("expected a pure-alias child with no fields of its own");
+        assert_eq!(alias_child.3.len(), 1);
+    }
+
+    #[test]
+    fn test_create_struct_definitions_no_shared_core_stays_flat() {
+        let mut shapes = HashMap::new();
+        shapes.insert(vec!["a".to_string(), "b".to_string()], 3);
+        shapes.insert(vec!["x".to_string(), "y".to_string()], 3);
+
+        let defs = create_struct_definitions(&shapes, 3, 2);
+        assert_eq!(defs.len(), 2);
+        assert!(defs.iter().all(|(_, _, _, parents, _)| parents.is_empty()));
+    }
+
+    #[test]
+    fn test_create_struct_definitions_below_min_occurrences_ignored() {
+        let mut shapes = HashMap::new();
+        shapes.insert(vec!["a".to_string(), "b".to_string()], 1);
+        let defs = create_struct_definitions(&shapes, 3, 2);
+        assert!(defs.is_empty());
+    }
+
+    #[test]
+    fn test_roundtrip_auto_factored_parent_struct() {
+        let data = json!([
+            {"fmt": "1", "raw": 1},
+            {"fmt": "2", "raw": 2},
+            {"fmt": "3", "raw": 3},
+            {"fmt": "4", "raw": 4, "currency": "USD"},
+            {"fmt": "5", "raw": 5, "currency": "USD"},
+            {"fmt": "6", "raw": 6, "currency": "USD"}
+        ]);
+        let encoded = encode(&data, true).unwrap();
+        // A parent struct should have been factored out and referenced via
+        // the `@Child(Parent): ...` syntax.
+        assert!(encoded.contains('('));
+
+        let decoded = decode(&encoded).unwrap();
+        let arr = decoded.as_array().unwrap();
+        assert_eq!(arr[0]["fmt"], "1");
+        assert_eq!(arr[0]["raw"], 1);
+        assert!(arr[0].get("currency").is_none());
+        assert_eq!(arr[3]["fmt"], "4");
+        assert_eq!(arr[3]["currency"], "USD");
+    }
+
+    #[test]
+    fn test_register_struct_dedupes_fields_shared_by_two_parents_first_wins() {
+        let mut registry = StructRegistry::new();
+        registry.insert(
+            "A".to_string(),
+            (vec!["shared".to_string()], vec![], vec![], FieldTypes::new()),
+        );
+        registry.insert(
+            "B".to_string(),
+            (vec!["shared".to_string()], vec![], vec![], FieldTypes::new()),
+        );
+
+        register_struct(
+            &mut registry,
+            "Child",
+            &["extra".to_string()],
+            &[],
+            &["A".to_string(), "B".to_string()],
+            &FieldTypes::new(),
+        )
+        .unwrap();
+
+        let (fields, _, _, _) = registry.get("Child").unwrap();
+        // "shared" is only listed once even though both parents contribute it.
+        assert_eq!(
+            fields.iter().filter(|f| *f == "shared").count(),
+            1
+        );
+        assert_eq!(fields, &vec!["shared".to_string(), "extra".to_string()]);
+    }
+
+    #[test]
+    fn test_binary_roundtrip_primitives_and_collections() {
+        let data = serde_json::json!({
+            "a": null,
+            "b": true,
+            "c": -42,
+            "d": 3.5,
+            "e": "hello",
+            "f": [1, 2, 3],
+            "g": {"nested": "object"}
+        });
+        let bytes = encode_binary(&data);
+        let decoded = decode_binary(&bytes).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_binary_roundtrip_struct_templated_objects() {
+        let data = serde_json::json!([
+            {"id": 1, "name": "alice"},
+            {"id": 2, "name": "bob"},
+            {"id": 3, "name": "carol"}
+        ]);
+        let bytes = encode_binary(&data);
+        let decoded = decode_binary(&bytes).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_binary_roundtrip_inherited_parent_struct() {
+        // Shares a 2-field core ("id", "name") with one row adding an extra
+        // field, which should trigger the same parent-factoring logic used
+        // by the text format's `create_struct_definitions`.
+        let data = serde_json::json!([
+            {"id": 1, "name": "alice", "role": "admin"},
+            {"id": 2, "name": "bob", "role": "admin"},
+            {"id": 3, "name": "carol", "role": "admin"},
+            {"id": 4, "name": "dave"},
+            {"id": 5, "name": "erin"},
+            {"id": 6, "name": "frank"}
+        ]);
+        let bytes = encode_binary(&data);
+        let decoded = decode_binary(&bytes).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_binary_and_text_decode_to_identical_value() {
+        let data = serde_json::json!([
+            {"id": 1, "name": "alice"},
+            {"id": 2, "name": "bob"},
+            {"id": 3, "name": "carol"}
+        ]);
+        let binary_decoded = decode_binary(&encode_binary(&data)).unwrap();
+        let text_decoded = decode(&encode(&data, true).unwrap()).unwrap();
+        assert_eq!(binary_decoded, text_decoded);
+    }
+
+    #[test]
+    fn test_decode_binary_rejects_bad_magic() {
+        let err = decode_binary(&[0, 0, 0, 0, 0]).unwrap_err();
+        assert!(matches!(err, AgonError::DecodingError(_)));
+    }
+
+    #[test]
+    fn test_decode_binary_rejects_unsupported_version() {
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.push(99);
+        let err = decode_binary(&bytes).unwrap_err();
+        assert!(matches!(err, AgonError::DecodingError(_)));
+    }
+
+    #[test]
+    fn test_decode_binary_rejects_string_len_near_usize_max_without_overflow_panic() {
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.push(BINARY_VERSION);
+        write_varint(&mut bytes, 1); // string_count
+        write_varint(&mut bytes, u64::MAX); // a crafted, unsatisfiable string length
+        let err = decode_binary(&bytes).unwrap_err();
+        assert!(matches!(err, AgonError::DecodingError(_)));
+    }
+
+    #[test]
+    fn test_varint_roundtrip() {
+        for value in [0u64, 1, 127, 128, 300, u64::MAX] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, value);
+            let mut pos = 0;
+            assert_eq!(read_varint(&buf, &mut pos).unwrap(), value);
+            assert_eq!(pos, buf.len());
+        }
+    }
+
+    #[test]
+    fn test_zigzag_roundtrip() {
+        for value in [0i64, 1, -1, 42, -42, i64::MAX, i64::MIN] {
+            assert_eq!(zigzag_decode(zigzag_encode(value)), value);
+        }
+    }
+
     #[test]
     fn test_generate_struct_name() {
         let mut used = std::collections::HashSet::new();
@@ -1397,7 +3485,7 @@ mod tests {
         let mut registry = StructRegistry::new();
         registry.insert(
             "FR".to_string(),
-            (vec!["fmt".to_string(), "raw".to_string()], vec![], vec![]),
+            (vec!["fmt".to_string(), "raw".to_string()], vec![], vec![], FieldTypes::new()),
         );
 
         let obj = json!({"fmt": "100", "raw": 100})
@@ -1413,7 +3501,7 @@ mod tests {
         let mut registry = StructRegistry::new();
         registry.insert(
             "FR".to_string(),
-            (vec!["fmt".to_string(), "raw".to_string()], vec![], vec![]),
+            (vec!["fmt".to_string(), "raw".to_string()], vec![], vec![], FieldTypes::new()),
         );
 
         let obj = json!({"x": 1, "y": 2}).as_object().unwrap().clone();
@@ -1426,7 +3514,7 @@ mod tests {
         let mut registry = StructRegistry::new();
         registry.insert(
             "FR".to_string(),
-            (vec!["fmt".to_string(), "raw".to_string()], vec![], vec![]),
+            (vec!["fmt".to_string(), "raw".to_string()], vec![], vec![], FieldTypes::new()),
         );
 
         // Object with nested value - should not match struct
@@ -1501,4 +3589,86 @@ mod tests {
         let decoded = decode(&encoded).unwrap();
         assert!(decoded["level1"]["level2"].is_object());
     }
+
+    // ========================================================================
+    // SerializeOptions tests
+    // ========================================================================
+
+    #[test]
+    fn test_encode_with_custom_indent_width() {
+        let data = json!({"outer": {"inner": "value"}});
+        let options = SerializeOptions {
+            indent: Some(4),
+            sort_keys: false,
+        };
+        let encoded = encode_with_options(&data, false, &options).unwrap();
+        assert!(encoded.contains("    inner: value"));
+    }
+
+    #[test]
+    fn test_encode_with_sort_keys() {
+        let data = json!({"zeta": 1, "alpha": 2});
+        let options = SerializeOptions {
+            indent: None,
+            sort_keys: true,
+        };
+        let encoded = encode_with_options(&data, false, &options).unwrap();
+        let alpha_pos = encoded.find("alpha").unwrap();
+        let zeta_pos = encoded.find("zeta").unwrap();
+        assert!(alpha_pos < zeta_pos);
+    }
+
+    // ========================================================================
+    // encode_streaming tests
+    // ========================================================================
+
+    #[test]
+    fn test_encode_streaming_matches_encode_across_batch_sizes() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2, "name": "Bob"},
+            {"id": 3, "name": "Carol"},
+            {"id": 4, "name": "Dave"}
+        ]);
+        let expected = encode(&data, true).unwrap();
+        let options = SerializeOptions::default();
+
+        for batch_size in [1, 2, 3, 100] {
+            let bytes = encode_streaming(Vec::new(), &data, true, batch_size, &options).unwrap();
+            let text = String::from_utf8(bytes).unwrap();
+            assert_eq!(text, expected, "batch_size = {}", batch_size);
+        }
+    }
+
+    #[test]
+    fn test_encode_streaming_round_trips_through_decode() {
+        let data = json!([
+            {"id": 1, "name": "Alice"},
+            {"id": 2, "name": "Bob"},
+            {"id": 3, "name": "Carol"}
+        ]);
+        let options = SerializeOptions::default();
+        let bytes = encode_streaming(Vec::new(), &data, true, 2, &options).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        let decoded = decode(&text).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_encode_streaming_empty_array() {
+        let data = json!([]);
+        let options = SerializeOptions::default();
+        let bytes = encode_streaming(Vec::new(), &data, true, 10, &options).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert_eq!(text, encode(&data, true).unwrap());
+    }
+
+    #[test]
+    fn test_encode_streaming_non_array_falls_back_to_encode_with_options() {
+        let data = json!({"name": "test"});
+        let options = SerializeOptions::default();
+        let bytes = encode_streaming(Vec::new(), &data, true, 10, &options).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert_eq!(text, encode(&data, true).unwrap());
+    }
 }
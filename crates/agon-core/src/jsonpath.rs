@@ -0,0 +1,645 @@
+//! JSONPath queries directly over encoded AGON payloads
+//!
+//! [`select`] decodes a payload (sniffing its `@AGON <format>` header the
+//! same way [`crate::formats::decode_auto`] does) and evaluates a JSONPath
+//! expression against the result, supporting the core grammar: root `$`,
+//! child `.name` and `['name']`, wildcard `*`, recursive descent `..`,
+//! array index `[n]` and `[-n]`, slices `[start:end:step]`, and filter
+//! predicates `[?(@.field op value)]` with `==`, `!=`, `<`, `<=`, `>`, `>=`.
+//!
+//! ```text
+//! $.users[2].name                  // third user's name
+//! $.users[*].name                  // every user's name
+//! $..id                            // every "id" field at any depth
+//! $.users[?(@.age>30)].name        // names of users over 30
+//! ```
+//!
+//! A query of the shape `$.<name>[?(@.field op value)]` (optionally
+//! followed by one more `.field` step) is AGON-columns-specific fast: since
+//! a columnar array already stores each field as its own `├`/`└` line, the
+//! filter and projection fields are the only columns
+//! [`columns::decode_projected`] needs to parse -- every other column in
+//! the table is skipped rather than reconstructed into objects just to be
+//! thrown away.
+
+use serde_json::Value;
+
+use crate::error::{AgonError, Result};
+use crate::formats::{self, columns, struct_fmt::parse_primitive};
+
+/// One step of a tokenized JSONPath expression.
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Child(String),
+    Wildcard,
+    RecursiveDescent,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, i64),
+    Filter(FilterExpr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FilterExpr {
+    field: String,
+    op: CmpOp,
+    value: Value,
+}
+
+impl FilterExpr {
+    /// Filters only ever match objects carrying the filtered field; any
+    /// other node (including an object missing the field) fails.
+    fn matches(&self, node: &Value) -> bool {
+        let Value::Object(obj) = node else {
+            return false;
+        };
+        let Some(actual) = obj.get(&self.field) else {
+            return false;
+        };
+        match self.op {
+            CmpOp::Eq => actual == &self.value,
+            CmpOp::Ne => actual != &self.value,
+            CmpOp::Lt | CmpOp::Le | CmpOp::Gt | CmpOp::Ge => {
+                let (Some(a), Some(b)) = (actual.as_f64(), self.value.as_f64()) else {
+                    return false;
+                };
+                match self.op {
+                    CmpOp::Lt => a < b,
+                    CmpOp::Le => a <= b,
+                    CmpOp::Gt => a > b,
+                    CmpOp::Ge => a >= b,
+                    CmpOp::Eq | CmpOp::Ne => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+/// Evaluate `path` (a JSONPath expression starting with `$`) against the
+/// decoded form of `payload`, returning every matched value in document
+/// order. Returns an empty vector (not an error) when a step navigates to
+/// a missing field, an out-of-range index, or a filter matches nothing --
+/// only malformed JSONPath syntax is an error.
+pub fn select(payload: &str, path: &str) -> Result<Vec<Value>> {
+    let steps = tokenize(path)?;
+    let root = decode_root(payload, &steps)?;
+    Ok(walk(&steps, vec![root]))
+}
+
+/// Decode `payload` to the `Value` the path's steps should walk, taking the
+/// columnar fast path described in the module docs when it applies.
+fn decode_root(payload: &str, steps: &[Step]) -> Result<Value> {
+    if let Some(fields) = fast_path_fields(steps)
+        && is_columns_payload(payload)
+    {
+        return columns::decode_projected(payload, &fields);
+    }
+    formats::decode_auto(payload, None)
+}
+
+fn is_columns_payload(payload: &str) -> bool {
+    payload
+        .lines()
+        .next()
+        .is_some_and(|line| line.trim().starts_with("@AGON columns"))
+}
+
+/// Recognizes `<name>[?(@.field op value)]`, optionally followed by one
+/// more `.field` projection step, and returns the column names that query
+/// actually reads -- the filter field, plus the projection field if
+/// present.
+fn fast_path_fields(steps: &[Step]) -> Option<Vec<&str>> {
+    let Step::Child(_name) = steps.first()? else {
+        return None;
+    };
+    let Step::Filter(expr) = steps.get(1)? else {
+        return None;
+    };
+    let mut fields = vec![expr.field.as_str()];
+    match steps.get(2..) {
+        Some([]) => {}
+        Some([Step::Child(proj)]) => fields.push(proj.as_str()),
+        _ => return None,
+    }
+    Some(fields)
+}
+
+/// Tokenize a JSONPath expression into its steps, following the root `$`.
+fn tokenize(path: &str) -> Result<Vec<Step>> {
+    let trimmed = path.trim();
+    let Some(rest) = trimmed.strip_prefix('$') else {
+        return Err(AgonError::InvalidFormat(format!(
+            "JSONPath must start with '$': {}",
+            path
+        )));
+    };
+
+    let chars: Vec<char> = rest.chars().collect();
+    let mut steps = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '.' => {
+                if chars.get(i + 1) == Some(&'.') {
+                    steps.push(Step::RecursiveDescent);
+                    i += 2;
+                    continue;
+                }
+                i += 1;
+                if chars.get(i) == Some(&'*') {
+                    steps.push(Step::Wildcard);
+                    i += 1;
+                    continue;
+                }
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                if start == i {
+                    return Err(AgonError::InvalidFormat(format!(
+                        "Expected a field name after '.' in JSONPath: {}",
+                        path
+                    )));
+                }
+                steps.push(Step::Child(chars[start..i].iter().collect()));
+            }
+            '[' => {
+                let close = find_matching_bracket(&chars, i, path)?;
+                let inner: String = chars[i + 1..close].iter().collect();
+                steps.push(parse_bracket(&inner, path)?);
+                i = close + 1;
+            }
+            other => {
+                return Err(AgonError::InvalidFormat(format!(
+                    "Unexpected character '{}' in JSONPath: {}",
+                    other, path
+                )));
+            }
+        }
+    }
+    Ok(steps)
+}
+
+/// Find the `]` closing the `[` at `open`, skipping over any `'...'` or
+/// `"..."` quoted span so a bracketed key or filter literal may itself
+/// contain `]`.
+fn find_matching_bracket(chars: &[char], open: usize, path: &str) -> Result<usize> {
+    let mut j = open + 1;
+    let mut quote: Option<char> = None;
+    while j < chars.len() {
+        let c = chars[j];
+        if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            }
+        } else if c == '\'' || c == '"' {
+            quote = Some(c);
+        } else if c == ']' {
+            return Ok(j);
+        }
+        j += 1;
+    }
+    Err(AgonError::InvalidFormat(format!(
+        "Unterminated '[' in JSONPath: {}",
+        path
+    )))
+}
+
+/// Parse the contents of a `[...]` step: `*`, a quoted field name, a
+/// `?(...)` filter, a `start:end:step` slice, or a bare (possibly
+/// negative) index.
+fn parse_bracket(inner: &str, path: &str) -> Result<Step> {
+    let inner = inner.trim();
+    if inner == "*" {
+        return Ok(Step::Wildcard);
+    }
+    if let Some(expr) = inner.strip_prefix("?(").and_then(|s| s.strip_suffix(')')) {
+        return Ok(Step::Filter(parse_filter(expr, path)?));
+    }
+    if inner.len() >= 2
+        && ((inner.starts_with('\'') && inner.ends_with('\''))
+            || (inner.starts_with('"') && inner.ends_with('"')))
+    {
+        return Ok(Step::Child(inner[1..inner.len() - 1].to_string()));
+    }
+    if inner.contains(':') {
+        return parse_slice(inner, path);
+    }
+    inner.parse::<i64>().map(Step::Index).map_err(|_| {
+        AgonError::InvalidFormat(format!(
+            "Invalid bracket step '[{}]' in JSONPath: {}",
+            inner, path
+        ))
+    })
+}
+
+fn parse_slice(inner: &str, path: &str) -> Result<Step> {
+    let parts: Vec<&str> = inner.split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        return Err(AgonError::InvalidFormat(format!(
+            "Invalid slice '[{}]' in JSONPath: {}",
+            inner, path
+        )));
+    }
+    let parse_bound = |s: &str| -> Result<Option<i64>> {
+        let s = s.trim();
+        if s.is_empty() {
+            Ok(None)
+        } else {
+            s.parse::<i64>().map(Some).map_err(|_| {
+                AgonError::InvalidFormat(format!(
+                    "Invalid slice bound '{}' in JSONPath: {}",
+                    s, path
+                ))
+            })
+        }
+    };
+    let start = parse_bound(parts[0])?;
+    let end = parse_bound(parts[1])?;
+    let step = match parts.get(2) {
+        Some(s) => parse_bound(s)?.unwrap_or(1),
+        None => 1,
+    };
+    if step == 0 {
+        return Err(AgonError::InvalidFormat(format!(
+            "Slice step cannot be zero in JSONPath: {}",
+            path
+        )));
+    }
+    Ok(Step::Slice(start, end, step))
+}
+
+const COMPARISON_OPS: &[(&str, CmpOp)] = &[
+    ("==", CmpOp::Eq),
+    ("!=", CmpOp::Ne),
+    ("<=", CmpOp::Le),
+    (">=", CmpOp::Ge),
+    ("<", CmpOp::Lt),
+    (">", CmpOp::Gt),
+];
+
+/// Parse a `@.field op value` filter body. `value` is interpreted via
+/// [`parse_primitive`], the same leaf-value parser AGONStruct and the
+/// `/`-selector predicates in [`crate::path`] already use.
+fn parse_filter(expr: &str, path: &str) -> Result<FilterExpr> {
+    let expr = expr.trim();
+    for (token, op) in COMPARISON_OPS {
+        if let Some(idx) = expr.find(token) {
+            let field = expr[..idx].trim().strip_prefix("@.").ok_or_else(|| {
+                AgonError::InvalidFormat(format!(
+                    "Filter must reference a field via '@.': {}",
+                    path
+                ))
+            })?;
+            if field.is_empty() {
+                return Err(AgonError::InvalidFormat(format!(
+                    "Filter clause missing field name: {}",
+                    path
+                )));
+            }
+            let value_str = expr[idx + token.len()..].trim();
+            let value = parse_primitive(value_str, 1, 1)?;
+            return Ok(FilterExpr {
+                field: field.to_string(),
+                op: *op,
+                value,
+            });
+        }
+    }
+    Err(AgonError::InvalidFormat(format!(
+        "Filter predicate missing a comparison operator: {}",
+        path
+    )))
+}
+
+/// Walk `current` through every step, in order.
+fn walk(steps: &[Step], current: Vec<Value>) -> Vec<Value> {
+    let mut current = current;
+    for step in steps {
+        current = apply_step(step, current);
+    }
+    current
+}
+
+fn apply_step(step: &Step, current: Vec<Value>) -> Vec<Value> {
+    match step {
+        Step::Child(name) => current
+            .into_iter()
+            .filter_map(|node| match node {
+                Value::Object(mut obj) => obj.remove(name),
+                _ => None,
+            })
+            .collect(),
+        Step::Wildcard => current.into_iter().flat_map(children).collect(),
+        Step::RecursiveDescent => current.into_iter().flat_map(descendants_or_self).collect(),
+        Step::Index(index) => current
+            .into_iter()
+            .filter_map(|node| index_into(node, *index))
+            .collect(),
+        Step::Slice(start, end, step) => current
+            .into_iter()
+            .flat_map(|node| slice_array(node, *start, *end, *step))
+            .collect(),
+        Step::Filter(expr) => current.into_iter().flat_map(|node| filter_node(node, expr)).collect(),
+    }
+}
+
+/// The immediate children of an array (its elements) or object (its
+/// values); any other node has none.
+fn children(node: Value) -> Vec<Value> {
+    match node {
+        Value::Array(arr) => arr,
+        Value::Object(obj) => obj.into_values().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// `node` itself, plus every descendant, in pre-order. JSON is acyclic, so
+/// a plain recursion suffices -- no cycle guard needed.
+fn descendants_or_self(node: Value) -> Vec<Value> {
+    let child_nodes: Vec<Value> = match &node {
+        Value::Array(arr) => arr.clone(),
+        Value::Object(obj) => obj.values().cloned().collect(),
+        _ => Vec::new(),
+    };
+    let mut result = vec![node];
+    for child in child_nodes {
+        result.extend(descendants_or_self(child));
+    }
+    result
+}
+
+/// Index into an array, resolving a negative index from the end the way
+/// Python and JSONPath both do.
+fn index_into(node: Value, index: i64) -> Option<Value> {
+    let Value::Array(arr) = node else {
+        return None;
+    };
+    let len = arr.len() as i64;
+    let real_index = if index < 0 { len + index } else { index };
+    if real_index < 0 || real_index >= len {
+        return None;
+    }
+    arr.into_iter().nth(real_index as usize)
+}
+
+/// Slice an array with Python-style `start:end:step` semantics, including
+/// negative bounds and a negative step for reverse iteration.
+fn slice_array(node: Value, start: Option<i64>, end: Option<i64>, step: i64) -> Vec<Value> {
+    let Value::Array(arr) = node else {
+        return Vec::new();
+    };
+    let len = arr.len() as i64;
+    let normalize = |v: i64| -> i64 {
+        let v = if v < 0 { len + v } else { v };
+        v.clamp(0, len)
+    };
+
+    let mut result = Vec::new();
+    if step > 0 {
+        let start = start.map(normalize).unwrap_or(0);
+        let end = end.map(normalize).unwrap_or(len);
+        let mut i = start;
+        while i < end {
+            if let Some(v) = arr.get(i as usize) {
+                result.push(v.clone());
+            }
+            i += step;
+        }
+    } else {
+        let start = start.map(normalize).unwrap_or(len - 1).min(len - 1);
+        let end = end.map(normalize);
+        let mut i = start;
+        while i >= 0 && end.is_none_or(|end| i > end) {
+            if let Some(v) = arr.get(i as usize) {
+                result.push(v.clone());
+            }
+            i += step;
+        }
+    }
+    result
+}
+
+/// A filter applies to the elements of an array; against any other node,
+/// the node itself is the lone candidate.
+fn filter_node(node: Value, expr: &FilterExpr) -> Vec<Value> {
+    match node {
+        Value::Array(arr) => arr.into_iter().filter(|item| expr.matches(item)).collect(),
+        other => {
+            if expr.matches(&other) {
+                vec![other]
+            } else {
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::columns;
+    use serde_json::json;
+
+    fn columns_payload(data: &Value) -> String {
+        columns::encode(data, true).unwrap()
+    }
+
+    #[test]
+    fn test_select_requires_dollar_root() {
+        let err = select("{}", "users").unwrap_err();
+        assert!(matches!(err, AgonError::InvalidFormat(_)));
+    }
+
+    #[test]
+    fn test_select_root_alone_returns_whole_document() {
+        let payload = columns_payload(&json!({"a": 1}));
+        let result = select(&payload, "$").unwrap();
+        assert_eq!(result, vec![json!({"a": 1})]);
+    }
+
+    #[test]
+    fn test_select_dot_child() {
+        let payload = columns_payload(&json!({"a": {"b": 42}}));
+        let result = select(&payload, "$.a.b").unwrap();
+        assert_eq!(result, vec![json!(42)]);
+    }
+
+    #[test]
+    fn test_select_bracket_child() {
+        let payload = columns_payload(&json!({"a": {"b": 42}}));
+        let result = select(&payload, "$['a']['b']").unwrap();
+        assert_eq!(result, vec![json!(42)]);
+    }
+
+    #[test]
+    fn test_select_missing_field_yields_empty() {
+        let payload = columns_payload(&json!({"a": 1}));
+        assert!(select(&payload, "$.missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_select_wildcard_over_object() {
+        let payload = columns_payload(&json!({"a": 1, "b": 2}));
+        let mut result = select(&payload, "$.*").unwrap();
+        result.sort_by_key(|v| v.as_i64().unwrap());
+        assert_eq!(result, vec![json!(1), json!(2)]);
+    }
+
+    #[test]
+    fn test_select_wildcard_projection_over_array() {
+        let data = json!({"users": [{"name": "Alice"}, {"name": "Bob"}]});
+        let payload = columns_payload(&data);
+        let result = select(&payload, "$.users[*].name").unwrap();
+        assert_eq!(result, vec![json!("Alice"), json!("Bob")]);
+    }
+
+    #[test]
+    fn test_select_recursive_descent_finds_every_matching_field() {
+        let data = json!({"id": 1, "child": {"id": 2}});
+        let payload = columns_payload(&data);
+        let mut result = select(&payload, "$..id").unwrap();
+        result.sort_by_key(|v| v.as_i64().unwrap());
+        assert_eq!(result, vec![json!(1), json!(2)]);
+    }
+
+    #[test]
+    fn test_select_array_index() {
+        let data = json!({"items": ["a", "b", "c"]});
+        let payload = columns_payload(&data);
+        let result = select(&payload, "$.items[1]").unwrap();
+        assert_eq!(result, vec![json!("b")]);
+    }
+
+    #[test]
+    fn test_select_negative_array_index() {
+        let data = json!({"items": ["a", "b", "c"]});
+        let payload = columns_payload(&data);
+        let result = select(&payload, "$.items[-1]").unwrap();
+        assert_eq!(result, vec![json!("c")]);
+    }
+
+    #[test]
+    fn test_select_out_of_range_index_yields_empty() {
+        let data = json!({"items": [1, 2]});
+        let payload = columns_payload(&data);
+        assert!(select(&payload, "$.items[5]").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_select_slice_start_end() {
+        let data = json!({"items": [0, 1, 2, 3, 4]});
+        let payload = columns_payload(&data);
+        let result = select(&payload, "$.items[1:3]").unwrap();
+        assert_eq!(result, vec![json!(1), json!(2)]);
+    }
+
+    #[test]
+    fn test_select_slice_with_step() {
+        let data = json!({"items": [0, 1, 2, 3, 4]});
+        let payload = columns_payload(&data);
+        let result = select(&payload, "$.items[0:5:2]").unwrap();
+        assert_eq!(result, vec![json!(0), json!(2), json!(4)]);
+    }
+
+    #[test]
+    fn test_select_slice_open_bounds() {
+        let data = json!({"items": [0, 1, 2, 3]});
+        let payload = columns_payload(&data);
+        assert_eq!(
+            select(&payload, "$.items[:2]").unwrap(),
+            vec![json!(0), json!(1)]
+        );
+        assert_eq!(
+            select(&payload, "$.items[2:]").unwrap(),
+            vec![json!(2), json!(3)]
+        );
+    }
+
+    #[test]
+    fn test_select_filter_gt() {
+        let data = json!({"users": [{"age": 25}, {"age": 35}]});
+        let payload = columns_payload(&data);
+        let result = select(&payload, "$.users[?(@.age>30)]").unwrap();
+        assert_eq!(result, vec![json!({"age": 35})]);
+    }
+
+    #[test]
+    fn test_select_filter_then_projection() {
+        let data = json!({
+            "users": [
+                {"age": 25, "name": "Alice"},
+                {"age": 35, "name": "Bob"}
+            ]
+        });
+        let payload = columns_payload(&data);
+        let result = select(&payload, "$.users[?(@.age>30)].name").unwrap();
+        assert_eq!(result, vec![json!("Bob")]);
+    }
+
+    #[test]
+    fn test_select_filter_eq_string() {
+        let data = json!({"users": [{"kind": "admin"}, {"kind": "user"}]});
+        let payload = columns_payload(&data);
+        let result = select(&payload, "$.users[?(@.kind=='admin')]").unwrap();
+        assert_eq!(result, vec![json!({"kind": "admin"})]);
+    }
+
+    #[test]
+    fn test_select_filter_ne() {
+        let data = json!({"users": [{"kind": "admin"}, {"kind": "user"}]});
+        let payload = columns_payload(&data);
+        let result = select(&payload, "$.users[?(@.kind!='admin')]").unwrap();
+        assert_eq!(result, vec![json!({"kind": "user"})]);
+    }
+
+    #[test]
+    fn test_select_malformed_path_errors() {
+        let err = select("{}", "$.[").unwrap_err();
+        assert!(matches!(err, AgonError::InvalidFormat(_)));
+    }
+
+    #[test]
+    fn test_select_filter_missing_operator_errors() {
+        let data = json!({"users": []});
+        let payload = columns_payload(&data);
+        let err = select(&payload, "$.users[?(@.age)]").unwrap_err();
+        assert!(matches!(err, AgonError::InvalidFormat(_)));
+    }
+
+    #[test]
+    fn test_fast_path_only_decodes_filter_and_projection_columns() {
+        // The `extra` column is wide enough to dominate the payload if it
+        // were decoded -- the fast path should skip it entirely and still
+        // answer the query correctly.
+        let data = json!({
+            "users": [
+                {"age": 25, "name": "Alice", "extra": "x".repeat(1000)},
+                {"age": 35, "name": "Bob", "extra": "y".repeat(1000)}
+            ]
+        });
+        let payload = columns_payload(&data);
+        let fields = fast_path_fields(&tokenize("$.users[?(@.age>30)].name").unwrap()).unwrap();
+        assert_eq!(fields, vec!["age", "name"]);
+        let result = select(&payload, "$.users[?(@.age>30)].name").unwrap();
+        assert_eq!(result, vec![json!("Bob")]);
+    }
+
+    #[test]
+    fn test_select_on_rows_format_still_works() {
+        use crate::formats::rows;
+        let data = json!([{"id": 1}, {"id": 2}]);
+        let payload = rows::encode(&data, true).unwrap();
+        let result = select(&payload, "$[*].id").unwrap();
+        assert_eq!(result, vec![json!(1), json!(2)]);
+    }
+}
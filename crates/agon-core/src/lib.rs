@@ -7,17 +7,30 @@
 
 use pyo3::exceptions::PyNotImplementedError;
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList};
+use pyo3::types::{PyBytes, PyDict, PyList};
 use std::collections::HashMap;
 
+mod cbor;
+mod de;
 mod error;
 mod formats;
+mod jsonpath;
+mod options;
+mod path;
+mod ser;
 mod types;
 mod utils;
 
+pub use de::from_str;
 pub use error::AgonError;
-pub use formats::{columns, rows, struct_fmt};
+pub use formats::{
+    columns, encode_all_parallel_with_tokenizer, encode_auto_parallel_with_tokenizer, rows,
+    struct_fmt,
+};
+pub use options::SerializeOptions;
+pub use ser::to_string;
 pub use types::JsonValue;
+pub use utils::{HeuristicTokenCounter, NamedTokenizer, TokenCounter};
 
 // ============================================================================
 // AGONFormat - Abstract base class
@@ -71,7 +84,10 @@ impl AGONFormat {
     ///
     /// Args:
     ///     data: List of objects to project
-    ///     keep_paths: List of field paths to keep (supports dotted paths like "user.name")
+    ///     keep_paths: List of field paths to keep. Supports dotted paths
+    ///         (`"user.name"`), a `*` segment matching every key at that
+    ///         level (`"user.*"`), and a trailing `[*]`/`[n]` array
+    ///         selector on a key (`"users[*].name"`, `"items[0].price"`)
     ///
     /// Returns:
     ///     Projected data with only the specified fields
@@ -102,13 +118,61 @@ impl AGONFormat {
     }
 }
 
-/// Recursive keep tree: None means "keep whole value", Some(map) means "keep these subfields"
+/// Recursive keep tree for `project_data`'s path grammar.
+///
+/// `children` holds literal-key segments; `wildcard` holds the `*` segment,
+/// which matches every key at that level. Each matched child is a
+/// [`KeepChild`] describing what to do with the value found there.
 #[derive(Default)]
 struct KeepTree {
-    children: HashMap<String, Option<Box<KeepTree>>>,
+    children: HashMap<String, KeepChild>,
+    wildcard: Option<Box<KeepChild>>,
+}
+
+/// What to do with the value a `KeepTree` segment matched.
+enum KeepChild {
+    /// Keep the whole value as-is.
+    Leaf,
+    /// The value is an object (or a homogeneous list of objects, kept for
+    /// backward compatibility); project it with this subtree.
+    Tree(Box<KeepTree>),
+    /// The value is a list; `[*]` or `[n]` selects which elements survive,
+    /// and `inner` (if any) further projects each selected element.
+    Array(ArraySelector, Option<Box<KeepTree>>),
 }
 
-// Helper: Build keep tree from dotted paths
+/// A `[*]` or `[n]` array selector attached to a path segment.
+#[derive(Clone, Copy)]
+enum ArraySelector {
+    All,
+    Index(usize),
+}
+
+/// Split a single dotted-path segment into its literal key and an optional
+/// trailing `[*]`/`[n]` array selector, e.g. `"users[*]"` -> `("users",
+/// Some(All))`, `"items[0]"` -> `("items", Some(Index(0)))`, `"name"` ->
+/// `("name", None)`.
+fn parse_path_segment(segment: &str) -> (&str, Option<ArraySelector>) {
+    if let Some(bracket_start) = segment.find('[') {
+        if let Some(inside) = segment
+            .strip_suffix(']')
+            .and_then(|s| s.get(bracket_start + 1..))
+        {
+            let selector = if inside == "*" {
+                Some(ArraySelector::All)
+            } else {
+                inside.parse::<usize>().ok().map(ArraySelector::Index)
+            };
+            if selector.is_some() {
+                return (&segment[..bracket_start], selector);
+            }
+        }
+    }
+    (segment, None)
+}
+
+// Helper: Build keep tree from dotted paths (segments may be a literal key,
+// `*`, or a literal key with a trailing `[*]`/`[n]` array selector).
 fn build_keep_tree(keep_paths: &[String]) -> KeepTree {
     let mut tree = KeepTree::default();
 
@@ -122,34 +186,58 @@ fn build_keep_tree(keep_paths: &[String]) -> KeepTree {
             continue;
         }
 
-        // Walk the path and build nested structure
-        let mut cur = &mut tree;
-        for (i, part) in parts.iter().enumerate() {
-            let is_last = i == parts.len() - 1;
-            let key = part.to_string();
+        insert_path(&mut tree, &parts);
+    }
 
-            if is_last {
-                // Leaf: set to None if not already a subtree
-                cur.children.entry(key).or_insert(None);
-            } else {
-                // Intermediate: ensure subtree exists
-                let entry = cur
-                    .children
-                    .entry(key)
-                    .or_insert_with(|| Some(Box::new(KeepTree::default())));
-                if let Some(subtree) = entry {
-                    cur = subtree.as_mut();
-                } else {
-                    // Was None (keep whole), upgrade to subtree
-                    let new_subtree = Box::new(KeepTree::default());
-                    *entry = Some(new_subtree);
-                    cur = entry.as_mut().unwrap().as_mut();
+    tree
+}
+
+fn insert_path(tree: &mut KeepTree, parts: &[&str]) {
+    let (segment, rest) = match parts.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    if *segment == "*" {
+        let child = tree
+            .wildcard
+            .get_or_insert_with(|| Box::new(KeepChild::Leaf));
+        insert_into_child(child, rest);
+        return;
+    }
+
+    let (key, selector) = parse_path_segment(segment);
+    let child = tree
+        .children
+        .entry(key.to_string())
+        .or_insert(KeepChild::Leaf);
+
+    match selector {
+        Some(sel) => {
+            if !matches!(child, KeepChild::Array(_, _)) {
+                *child = KeepChild::Array(sel, None);
+            }
+            if let KeepChild::Array(_, inner) = child {
+                if !rest.is_empty() {
+                    let subtree = inner.get_or_insert_with(|| Box::new(KeepTree::default()));
+                    insert_path(subtree, rest);
                 }
             }
         }
+        None => insert_into_child(child, rest),
     }
+}
 
-    tree
+fn insert_into_child(child: &mut KeepChild, rest: &[&str]) {
+    if rest.is_empty() {
+        return;
+    }
+    if !matches!(child, KeepChild::Tree(_)) {
+        *child = KeepChild::Tree(Box::new(KeepTree::default()));
+    }
+    if let KeepChild::Tree(subtree) = child {
+        insert_path(subtree, rest);
+    }
 }
 
 // Helper: Project a single object recursively
@@ -160,57 +248,110 @@ fn project_obj(
 ) -> PyResult<Py<PyDict>> {
     let out = PyDict::new(py);
 
-    for (key, sub_keep) in &keep_tree.children {
+    for (key, child) in &keep_tree.children {
         if let Ok(Some(value)) = obj.get_item(key) {
-            match sub_keep {
-                None => {
-                    // Leaf: keep the whole value
-                    out.set_item(key, &value)?;
-                }
-                Some(sub_tree) => {
-                    // Need to project nested structure
-                    if value.is_none() {
-                        out.set_item(key, &value)?;
-                    } else if value.is_instance_of::<PyDict>() {
-                        let nested_dict = value
+            let projected = project_value(py, &value, child)?;
+            out.set_item(key, projected)?;
+        }
+    }
+
+    if let Some(wildcard_child) = &keep_tree.wildcard {
+        for (key, value) in obj.iter() {
+            let key_str: String = key.extract()?;
+            if keep_tree.children.contains_key(&key_str) {
+                continue; // explicit literal segment already took this key
+            }
+            let projected = project_value(py, &value, wildcard_child)?;
+            out.set_item(key_str, projected)?;
+        }
+    }
+
+    Ok(out.unbind())
+}
+
+// Helper: Apply a single KeepChild to the value it matched
+fn project_value(
+    py: Python<'_>,
+    value: &Bound<'_, PyAny>,
+    child: &KeepChild,
+) -> PyResult<Py<PyAny>> {
+    match child {
+        KeepChild::Leaf => Ok(value.clone().unbind()),
+        KeepChild::Tree(sub_tree) => {
+            if value.is_none() {
+                Ok(value.clone().unbind())
+            } else if value.is_instance_of::<PyDict>() {
+                let nested_dict = value
+                    .cast::<PyDict>()
+                    .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+                Ok(project_obj(py, nested_dict, sub_tree)?.into_any())
+            } else if value.is_instance_of::<PyList>() {
+                let nested_list = value
+                    .cast::<PyList>()
+                    .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+
+                // Check if list is empty or all items are dicts
+                let all_dicts = nested_list
+                    .iter()
+                    .all(|item| item.is_instance_of::<PyDict>());
+
+                if nested_list.is_empty() || all_dicts {
+                    let projected_list = PyList::empty(py);
+                    for item in nested_list.iter() {
+                        let item_dict = item
                             .cast::<PyDict>()
                             .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
-                        let projected = project_obj(py, nested_dict, sub_tree)?;
-                        out.set_item(key, projected)?;
-                    } else if value.is_instance_of::<PyList>() {
-                        let nested_list = value
-                            .cast::<PyList>()
-                            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
-
-                        // Check if list is empty or all items are dicts
-                        let all_dicts = nested_list
-                            .iter()
-                            .all(|item| item.is_instance_of::<PyDict>());
-
-                        if nested_list.is_empty() || all_dicts {
-                            let projected_list = PyList::empty(py);
-                            for item in nested_list.iter() {
-                                let item_dict = item.cast::<PyDict>().map_err(|e| {
-                                    pyo3::exceptions::PyValueError::new_err(e.to_string())
-                                })?;
-                                let projected = project_obj(py, item_dict, sub_tree)?;
-                                projected_list.append(projected)?;
-                            }
-                            out.set_item(key, projected_list)?;
-                        } else {
-                            // Mixed list or not all dicts: keep as-is
-                            out.set_item(key, &value)?;
-                        }
+                        let projected = project_obj(py, item_dict, sub_tree)?;
+                        projected_list.append(projected)?;
+                    }
+                    Ok(projected_list.into_any().unbind())
+                } else {
+                    // Mixed list or not all dicts: keep as-is
+                    Ok(value.clone().unbind())
+                }
+            } else {
+                // Not a dict or list: keep as-is
+                Ok(value.clone().unbind())
+            }
+        }
+        KeepChild::Array(selector, inner) => {
+            if !value.is_instance_of::<PyList>() {
+                // Selector doesn't apply to this shape; keep as-is.
+                return Ok(value.clone().unbind());
+            }
+            let list = value
+                .cast::<PyList>()
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+
+            let indices: Vec<usize> = match selector {
+                ArraySelector::All => (0..list.len()).collect(),
+                ArraySelector::Index(n) => {
+                    if *n < list.len() {
+                        vec![*n]
                     } else {
-                        // Not a dict or list: keep as-is
-                        out.set_item(key, &value)?;
+                        vec![]
                     }
                 }
+            };
+
+            let projected_list = PyList::empty(py);
+            for i in indices {
+                let item = list.get_item(i)?;
+                let projected = match inner {
+                    None => item.unbind(),
+                    Some(sub_tree) if item.is_instance_of::<PyDict>() => {
+                        let item_dict = item
+                            .cast::<PyDict>()
+                            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+                        project_obj(py, item_dict, sub_tree)?.into_any()
+                    }
+                    Some(_) => item.unbind(),
+                };
+                projected_list.append(projected)?;
             }
+            Ok(projected_list.into_any().unbind())
         }
     }
-
-    Ok(out.unbind())
 }
 
 // ============================================================================
@@ -229,16 +370,46 @@ impl AGONRows {
     }
 
     #[staticmethod]
-    #[pyo3(signature = (data, include_header = false))]
-    fn encode(data: &Bound<'_, PyAny>, include_header: bool) -> PyResult<String> {
-        let value = types::py_to_json(data)?;
-        rows::encode(&value, include_header).map_err(|e| e.into())
+    #[pyo3(signature = (data, include_header = false, indent = None, sort_keys = false, default = None, allow_nan = false, preserve_types = false))]
+    fn encode(
+        data: &Bound<'_, PyAny>,
+        include_header: bool,
+        indent: Option<usize>,
+        sort_keys: bool,
+        default: Option<Bound<'_, PyAny>>,
+        allow_nan: bool,
+        preserve_types: bool,
+    ) -> PyResult<String> {
+        let nan_mode = if allow_nan {
+            types::NanMode::Sentinel
+        } else {
+            types::NanMode::Strict
+        };
+        let options = SerializeOptions { indent, sort_keys };
+        if preserve_types {
+            let (value, tags) =
+                types::py_to_json_with_type_tags(data, default.as_ref(), nan_mode)?;
+            let text = rows::encode_with_options(&value, include_header, &options)?;
+            return Ok(if include_header {
+                formats::insert_type_tags_line(&text, &tags)
+            } else {
+                text
+            });
+        }
+        let value = types::py_to_json_with_options(data, default.as_ref(), nan_mode)?;
+        rows::encode_with_options(&value, include_header, &options).map_err(|e| e.into())
     }
 
     #[staticmethod]
-    fn decode(py: Python<'_>, payload: &str) -> PyResult<Py<PyAny>> {
-        let value = rows::decode(payload)?;
-        types::json_to_py(py, &value)
+    #[pyo3(signature = (payload, rehydrate = false))]
+    fn decode(py: Python<'_>, payload: &str, rehydrate: bool) -> PyResult<Py<PyAny>> {
+        let (tags, payload) = formats::extract_type_tags(payload);
+        let value = rows::decode(&payload)?;
+        if tags.is_empty() {
+            types::json_to_py_with_options(py, &value, rehydrate)
+        } else {
+            types::json_to_py_with_type_tags(py, &value, rehydrate, &tags)
+        }
     }
 
     #[staticmethod]
@@ -267,16 +438,46 @@ impl AGONColumns {
     }
 
     #[staticmethod]
-    #[pyo3(signature = (data, include_header = false))]
-    fn encode(data: &Bound<'_, PyAny>, include_header: bool) -> PyResult<String> {
-        let value = types::py_to_json(data)?;
-        columns::encode(&value, include_header).map_err(|e| e.into())
+    #[pyo3(signature = (data, include_header = false, indent = None, sort_keys = false, default = None, allow_nan = false, preserve_types = false))]
+    fn encode(
+        data: &Bound<'_, PyAny>,
+        include_header: bool,
+        indent: Option<usize>,
+        sort_keys: bool,
+        default: Option<Bound<'_, PyAny>>,
+        allow_nan: bool,
+        preserve_types: bool,
+    ) -> PyResult<String> {
+        let nan_mode = if allow_nan {
+            types::NanMode::Sentinel
+        } else {
+            types::NanMode::Strict
+        };
+        let options = SerializeOptions { indent, sort_keys };
+        if preserve_types {
+            let (value, tags) =
+                types::py_to_json_with_type_tags(data, default.as_ref(), nan_mode)?;
+            let text = columns::encode_with_options(&value, include_header, &options)?;
+            return Ok(if include_header {
+                formats::insert_type_tags_line(&text, &tags)
+            } else {
+                text
+            });
+        }
+        let value = types::py_to_json_with_options(data, default.as_ref(), nan_mode)?;
+        columns::encode_with_options(&value, include_header, &options).map_err(|e| e.into())
     }
 
     #[staticmethod]
-    fn decode(py: Python<'_>, payload: &str) -> PyResult<Py<PyAny>> {
-        let value = columns::decode(payload)?;
-        types::json_to_py(py, &value)
+    #[pyo3(signature = (payload, rehydrate = false))]
+    fn decode(py: Python<'_>, payload: &str, rehydrate: bool) -> PyResult<Py<PyAny>> {
+        let (tags, payload) = formats::extract_type_tags(payload);
+        let value = columns::decode(&payload)?;
+        if tags.is_empty() {
+            types::json_to_py_with_options(py, &value, rehydrate)
+        } else {
+            types::json_to_py_with_type_tags(py, &value, rehydrate, &tags)
+        }
     }
 
     #[staticmethod]
@@ -305,16 +506,46 @@ impl AGONStruct {
     }
 
     #[staticmethod]
-    #[pyo3(signature = (data, include_header = false))]
-    fn encode(data: &Bound<'_, PyAny>, include_header: bool) -> PyResult<String> {
-        let value = types::py_to_json(data)?;
-        struct_fmt::encode(&value, include_header).map_err(|e| e.into())
+    #[pyo3(signature = (data, include_header = false, indent = None, sort_keys = false, default = None, allow_nan = false, preserve_types = false))]
+    fn encode(
+        data: &Bound<'_, PyAny>,
+        include_header: bool,
+        indent: Option<usize>,
+        sort_keys: bool,
+        default: Option<Bound<'_, PyAny>>,
+        allow_nan: bool,
+        preserve_types: bool,
+    ) -> PyResult<String> {
+        let nan_mode = if allow_nan {
+            types::NanMode::Sentinel
+        } else {
+            types::NanMode::Strict
+        };
+        let options = SerializeOptions { indent, sort_keys };
+        if preserve_types {
+            let (value, tags) =
+                types::py_to_json_with_type_tags(data, default.as_ref(), nan_mode)?;
+            let text = struct_fmt::encode_with_options(&value, include_header, &options)?;
+            return Ok(if include_header {
+                formats::insert_type_tags_line(&text, &tags)
+            } else {
+                text
+            });
+        }
+        let value = types::py_to_json_with_options(data, default.as_ref(), nan_mode)?;
+        struct_fmt::encode_with_options(&value, include_header, &options).map_err(|e| e.into())
     }
 
     #[staticmethod]
-    fn decode(py: Python<'_>, payload: &str) -> PyResult<Py<PyAny>> {
-        let value = struct_fmt::decode(payload)?;
-        types::json_to_py(py, &value)
+    #[pyo3(signature = (payload, rehydrate = false))]
+    fn decode(py: Python<'_>, payload: &str, rehydrate: bool) -> PyResult<Py<PyAny>> {
+        let (tags, payload) = formats::extract_type_tags(payload);
+        let value = struct_fmt::decode(&payload)?;
+        if tags.is_empty() {
+            types::json_to_py_with_options(py, &value, rehydrate)
+        } else {
+            types::json_to_py_with_type_tags(py, &value, rehydrate, &tags)
+        }
     }
 
     #[staticmethod]
@@ -343,16 +574,25 @@ struct EncodingResult {
     header: String,
     #[pyo3(get)]
     token_estimate: usize,
+    #[pyo3(get)]
+    dropped_fields: Vec<String>,
+    /// `rows`/`columns`/`struct`, ranked most-likely-to-win first, as the
+    /// statistics-driven predictor scored them before deciding which formats
+    /// were worth fully encoding. Empty for results from `encode_all_parallel`,
+    /// which always fully encodes every format.
+    #[pyo3(get)]
+    predicted_formats: Vec<String>,
 }
 
 #[pymethods]
 impl EncodingResult {
     fn __repr__(&self) -> String {
         format!(
-            "EncodingResult(format={:?}, len={}, tokens={})",
+            "EncodingResult(format={:?}, len={}, tokens={}, dropped_fields={:?})",
             self.format,
             self.text.len(),
-            self.token_estimate
+            self.token_estimate,
+            self.dropped_fields
         )
     }
 }
@@ -361,21 +601,48 @@ impl EncodingResult {
 // Module-level functions
 // ============================================================================
 
+/// Encode data with the smallest-token format, optionally fit to a hard
+/// token budget.
+///
+/// If the best encoding still exceeds `max_tokens`, the highest-cost
+/// top-level fields are dropped one at a time and the data is re-encoded
+/// until it fits (or no droppable fields remain). `keep_paths` lists
+/// top-level field names that must never be dropped. The returned
+/// `EncodingResult.dropped_fields` records what was omitted.
+///
+/// `encoding` selects the tokenizer used to measure token counts and thus to
+/// pick the best format: either the name of a built-in tiktoken encoding
+/// (`"o200k_base"`, `"cl100k_base"`, ...) or a path to a HuggingFace
+/// `tokenizers` JSON file. Defaults to `"o200k_base"`.
 #[pyfunction]
-#[pyo3(signature = (data, force = false, min_savings = 0.10, encoding = None))]
+#[pyo3(signature = (data, force = false, min_savings = 0.10, encoding = None, max_tokens = None, keep_paths = None))]
 fn encode_auto_parallel(
+    py: Python<'_>,
     data: &Bound<'_, PyAny>,
     force: bool,
     min_savings: f64,
     encoding: Option<&str>,
+    max_tokens: Option<usize>,
+    keep_paths: Option<Vec<String>>,
 ) -> PyResult<EncodingResult> {
+    let encoding = encoding.unwrap_or(formats::DEFAULT_ENCODING);
     let value = types::py_to_json(data)?;
-    let result = formats::encode_auto_parallel(&value, force, min_savings, encoding)?;
+    let keep_paths = keep_paths.unwrap_or_default();
+    let result = py.allow_threads(|| {
+        formats::encode_auto_parallel(&value, force, min_savings, max_tokens, &keep_paths, encoding)
+    })?;
+    let predicted_formats = result
+        .stats
+        .as_ref()
+        .map(|s| s.predicted_formats.clone())
+        .unwrap_or_default();
     Ok(EncodingResult {
         format: result.format,
         text: result.text,
         header: result.header,
         token_estimate: result.token_estimate,
+        dropped_fields: result.dropped_fields,
+        predicted_formats,
     })
 }
 
@@ -387,10 +654,59 @@ fn count_tokens(text: &str, encoding: &str) -> PyResult<usize> {
         .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
 }
 
+/// Convert a Python object to CBOR bytes.
+///
+/// Byte strings have no JSON equivalent, so a CBOR byte string decoded from
+/// `data` is represented as `{"__bytes__": {"base64": "..."}}`; conversely an
+/// object using that exact shape is re-encoded as a real CBOR byte string.
 #[pyfunction]
-fn encode_all_parallel(data: &Bound<'_, PyAny>) -> PyResult<Vec<EncodingResult>> {
+fn json_to_cbor(py: Python<'_>, data: &Bound<'_, PyAny>) -> PyResult<Py<PyBytes>> {
     let value = types::py_to_json(data)?;
-    let results = formats::encode_all_parallel(&value)?;
+    let bytes = cbor::json_to_cbor(&value).map_err(|e| e.into())?;
+    Ok(PyBytes::new(py, &bytes).unbind())
+}
+
+/// Convert CBOR bytes to a Python object, using the `{"__bytes__": {"base64": "..."}}`
+/// escape convention for any byte strings found in the payload.
+#[pyfunction]
+#[pyo3(signature = (data, rehydrate = false))]
+fn cbor_to_json(py: Python<'_>, data: &[u8], rehydrate: bool) -> PyResult<Py<PyAny>> {
+    let value = cbor::cbor_to_json(data).map_err(|e| e.into())?;
+    types::json_to_py_with_options(py, &value, rehydrate)
+}
+
+/// Decode an AGON payload without knowing its format ahead of time.
+///
+/// Sniffs the leading `@AGON <format>` header line and dispatches to the
+/// matching format's decoder. If the payload has no header, `default_format`
+/// (one of `"rows"`, `"columns"`, `"struct"`) is used instead.
+#[pyfunction]
+#[pyo3(signature = (payload, default_format = None, rehydrate = false))]
+fn decode_auto(
+    py: Python<'_>,
+    payload: &str,
+    default_format: Option<&str>,
+    rehydrate: bool,
+) -> PyResult<Py<PyAny>> {
+    let value = formats::decode_auto(payload, default_format)?;
+    types::json_to_py_with_options(py, &value, rehydrate)
+}
+
+/// Encode data with every format in parallel and return all of their results.
+///
+/// `encoding` selects the tokenizer used for each result's `token_estimate`:
+/// either the name of a built-in tiktoken encoding or a path to a
+/// HuggingFace `tokenizers` JSON file. Defaults to `"o200k_base"`.
+#[pyfunction]
+#[pyo3(signature = (data, encoding = None))]
+fn encode_all_parallel(
+    py: Python<'_>,
+    data: &Bound<'_, PyAny>,
+    encoding: Option<&str>,
+) -> PyResult<Vec<EncodingResult>> {
+    let encoding = encoding.unwrap_or(formats::DEFAULT_ENCODING);
+    let value = types::py_to_json(data)?;
+    let results = py.allow_threads(|| formats::encode_all_parallel(&value, encoding))?;
     Ok(results
         .into_iter()
         .map(|r| EncodingResult {
@@ -398,6 +714,8 @@ fn encode_all_parallel(data: &Bound<'_, PyAny>) -> PyResult<Vec<EncodingResult>>
             text: r.text,
             header: r.header,
             token_estimate: r.token_estimate,
+            dropped_fields: r.dropped_fields,
+            predicted_formats: Vec::new(),
         })
         .collect())
 }
@@ -416,6 +734,9 @@ fn agon_core(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(encode_auto_parallel, m)?)?;
     m.add_function(wrap_pyfunction!(encode_all_parallel, m)?)?;
     m.add_function(wrap_pyfunction!(count_tokens, m)?)?;
+    m.add_function(wrap_pyfunction!(json_to_cbor, m)?)?;
+    m.add_function(wrap_pyfunction!(cbor_to_json, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_auto, m)?)?;
     Ok(())
 }
 
@@ -432,7 +753,7 @@ mod tests {
         let paths = vec!["name".to_string()];
         let tree = build_keep_tree(&paths);
         assert!(tree.children.contains_key("name"));
-        assert!(tree.children.get("name").unwrap().is_none()); // Leaf node
+        assert!(matches!(tree.children.get("name").unwrap(), KeepChild::Leaf));
     }
 
     #[test]
@@ -450,9 +771,10 @@ mod tests {
         let paths = vec!["user.name".to_string()];
         let tree = build_keep_tree(&paths);
         assert!(tree.children.contains_key("user"));
-        let user_subtree = tree.children.get("user").unwrap();
-        assert!(user_subtree.is_some());
-        let user = user_subtree.as_ref().unwrap();
+        let user = match tree.children.get("user").unwrap() {
+            KeepChild::Tree(t) => t,
+            _ => panic!("expected a Tree child"),
+        };
         assert!(user.children.contains_key("name"));
     }
 
@@ -461,11 +783,16 @@ mod tests {
         let paths = vec!["a.b.c.d".to_string()];
         let tree = build_keep_tree(&paths);
 
-        let a = tree.children.get("a").unwrap().as_ref().unwrap();
-        let b = a.children.get("b").unwrap().as_ref().unwrap();
-        let c = b.children.get("c").unwrap().as_ref().unwrap();
+        let unwrap_tree = |child: &KeepChild| match child {
+            KeepChild::Tree(t) => t.as_ref(),
+            _ => panic!("expected a Tree child"),
+        };
+
+        let a = unwrap_tree(tree.children.get("a").unwrap());
+        let b = unwrap_tree(a.children.get("b").unwrap());
+        let c = unwrap_tree(b.children.get("c").unwrap());
         assert!(c.children.contains_key("d"));
-        assert!(c.children.get("d").unwrap().is_none()); // Leaf
+        assert!(matches!(c.children.get("d").unwrap(), KeepChild::Leaf));
     }
 
     #[test]
@@ -479,14 +806,64 @@ mod tests {
 
         // Top-level "id"
         assert!(tree.children.contains_key("id"));
-        assert!(tree.children.get("id").unwrap().is_none());
+        assert!(matches!(tree.children.get("id").unwrap(), KeepChild::Leaf));
 
         // Nested "user.name" and "user.email"
-        let user = tree.children.get("user").unwrap().as_ref().unwrap();
+        let user = match tree.children.get("user").unwrap() {
+            KeepChild::Tree(t) => t,
+            _ => panic!("expected a Tree child"),
+        };
         assert!(user.children.contains_key("name"));
         assert!(user.children.contains_key("email"));
     }
 
+    #[test]
+    fn test_build_keep_tree_wildcard() {
+        let paths = vec!["*.name".to_string()];
+        let tree = build_keep_tree(&paths);
+        assert!(tree.children.is_empty());
+        let wildcard = match tree.wildcard.as_deref().unwrap() {
+            KeepChild::Tree(t) => t,
+            _ => panic!("expected a Tree child"),
+        };
+        assert!(wildcard.children.contains_key("name"));
+    }
+
+    #[test]
+    fn test_build_keep_tree_array_all_selector() {
+        let paths = vec!["users[*].name".to_string()];
+        let tree = build_keep_tree(&paths);
+        let (selector, inner) = match tree.children.get("users").unwrap() {
+            KeepChild::Array(sel, inner) => (sel, inner),
+            _ => panic!("expected an Array child"),
+        };
+        assert!(matches!(selector, ArraySelector::All));
+        assert!(inner.as_ref().unwrap().children.contains_key("name"));
+    }
+
+    #[test]
+    fn test_build_keep_tree_array_index_selector() {
+        let paths = vec!["items[0].price".to_string()];
+        let tree = build_keep_tree(&paths);
+        let (selector, inner) = match tree.children.get("items").unwrap() {
+            KeepChild::Array(sel, inner) => (sel, inner),
+            _ => panic!("expected an Array child"),
+        };
+        assert!(matches!(selector, ArraySelector::Index(0)));
+        assert!(inner.as_ref().unwrap().children.contains_key("price"));
+    }
+
+    #[test]
+    fn test_build_keep_tree_array_selector_without_subpath_is_leaf_array() {
+        let paths = vec!["items[*]".to_string()];
+        let tree = build_keep_tree(&paths);
+        let inner = match tree.children.get("items").unwrap() {
+            KeepChild::Array(ArraySelector::All, inner) => inner,
+            _ => panic!("expected an all-selector Array child"),
+        };
+        assert!(inner.is_none());
+    }
+
     #[test]
     fn test_build_keep_tree_empty_paths() {
         let paths: Vec<String> = vec![];
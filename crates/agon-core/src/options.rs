@@ -0,0 +1,69 @@
+//! Shared encoding options for the AGON formats
+
+/// Options controlling how a value is rendered to AGON text.
+///
+/// `indent` overrides the number of spaces used per nesting level (the
+/// formats default to 2). `sort_keys` emits object keys in sorted order
+/// instead of their natural (insertion) order, which is useful for diffing,
+/// snapshot tests, and content hashing.
+///
+/// "Natural (insertion) order" itself depends on `serde_json::Map`'s
+/// `preserve_order` Cargo feature being enabled — without it, `Map` is
+/// `BTreeMap`-backed and iterates sorted regardless of what was inserted
+/// first.
+#[derive(Debug, Clone, Copy)]
+pub struct SerializeOptions {
+    pub indent: Option<usize>,
+    pub sort_keys: bool,
+}
+
+impl Default for SerializeOptions {
+    fn default() -> Self {
+        SerializeOptions {
+            indent: None,
+            sort_keys: false,
+        }
+    }
+}
+
+impl SerializeOptions {
+    /// The indent unit (a run of spaces) to use for one nesting level.
+    pub fn indent_unit(&self) -> String {
+        " ".repeat(self.indent.unwrap_or(2))
+    }
+}
+
+/// Iterate an object's entries, sorted by key when `sort_keys` is set,
+/// otherwise in the map's natural (insertion) order.
+pub fn ordered_entries<'a>(
+    obj: &'a serde_json::Map<String, serde_json::Value>,
+    sort_keys: bool,
+) -> Vec<(&'a String, &'a serde_json::Value)> {
+    let mut entries: Vec<(&String, &serde_json::Value)> = obj.iter().collect();
+    if sort_keys {
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_options() {
+        let opts = SerializeOptions::default();
+        assert_eq!(opts.indent, None);
+        assert!(!opts.sort_keys);
+        assert_eq!(opts.indent_unit(), "  ");
+    }
+
+    #[test]
+    fn test_custom_indent_unit() {
+        let opts = SerializeOptions {
+            indent: Some(4),
+            sort_keys: false,
+        };
+        assert_eq!(opts.indent_unit(), "    ");
+    }
+}
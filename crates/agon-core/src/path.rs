@@ -0,0 +1,295 @@
+//! Path/selector queries over decoded AGON values
+//!
+//! Once a payload is decoded to a `serde_json::Value`, [`select`] lets
+//! callers pull out subtrees with a compact `/`-separated selector, modeled
+//! on Preserves' path selectors: each step navigates to a child (by field
+//! name, array index, `*` for all immediate children, or `**` for every
+//! descendant) and may carry a `{...}` predicate that filters the nodes the
+//! step produced.
+//!
+//! ```text
+//! items/*/{.price}        // every item that has a "price" field
+//! items/*/{.kind = user}  // every item whose "kind" field equals "user"
+//! **/{.id & .active}      // any descendant with both an id and active field
+//! ```
+
+use serde_json::Value;
+
+use crate::error::{AgonError, Result};
+use crate::formats::struct_fmt::parse_primitive;
+
+/// A predicate attached to a selector step, filtering the nodes the step's
+/// navigation produced down to the ones matching a field condition.
+#[derive(Debug, Clone, PartialEq)]
+enum Predicate {
+    Exists(String),
+    Eq(String, Value),
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+}
+
+impl Predicate {
+    /// Predicates only ever match against objects; any other node fails.
+    fn matches(&self, node: &Value) -> bool {
+        let Value::Object(obj) = node else {
+            return false;
+        };
+        match self {
+            Predicate::Exists(key) => obj.contains_key(key),
+            Predicate::Eq(key, expected) => obj.get(key) == Some(expected),
+            Predicate::And(a, b) => a.matches(node) && b.matches(node),
+            Predicate::Or(a, b) => a.matches(node) || b.matches(node),
+        }
+    }
+}
+
+/// Select every node in `root` reachable by `selector`, a `/`-separated
+/// sequence of steps. Returns an empty vector (not an error) when a step
+/// navigates to a missing field or an out-of-range array index — only
+/// malformed selector syntax is an error.
+pub fn select<'a>(root: &'a Value, selector: &str) -> Result<Vec<&'a Value>> {
+    let mut current: Vec<&'a Value> = vec![root];
+
+    for raw_step in selector.split('/') {
+        if raw_step.is_empty() {
+            continue;
+        }
+        let (base, predicate) = split_step(raw_step)?;
+        current = apply_step(&current, base);
+        if let Some(predicate) = &predicate {
+            let predicate = parse_predicate(predicate)?;
+            current.retain(|node| predicate.matches(node));
+        }
+    }
+
+    Ok(current)
+}
+
+/// Split a raw step into its navigation base and optional `{...}` predicate.
+fn split_step(raw_step: &str) -> Result<(&str, Option<String>)> {
+    let Some(brace_idx) = raw_step.find('{') else {
+        return Ok((raw_step, None));
+    };
+    if !raw_step.ends_with('}') {
+        return Err(AgonError::InvalidFormat(format!(
+            "Unterminated predicate in selector step: {}",
+            raw_step
+        )));
+    }
+    let base = &raw_step[..brace_idx];
+    let predicate = &raw_step[brace_idx + 1..raw_step.len() - 1];
+    Ok((base, Some(predicate.to_string())))
+}
+
+/// Navigate one step of `current` by `base`: `*` for all immediate
+/// children, `**` for self-plus-every-descendant, `[n]` for an array index,
+/// or a literal object field name.
+fn apply_step<'a>(current: &[&'a Value], base: &str) -> Vec<&'a Value> {
+    match base {
+        "" => current.to_vec(),
+        "*" => current.iter().flat_map(|node| children(node)).collect(),
+        "**" => current
+            .iter()
+            .flat_map(|node| descendants_or_self(node))
+            .collect(),
+        _ => {
+            if let Some(index) = parse_index(base) {
+                current
+                    .iter()
+                    .filter_map(|node| node.as_array().and_then(|arr| arr.get(index)))
+                    .collect()
+            } else {
+                current
+                    .iter()
+                    .filter_map(|node| node.as_object().and_then(|obj| obj.get(base)))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// The immediate children of an array (its elements) or object (its
+/// values); any other node has none.
+fn children(node: &Value) -> Vec<&Value> {
+    match node {
+        Value::Array(arr) => arr.iter().collect(),
+        Value::Object(obj) => obj.values().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// `node` itself, plus every descendant, in pre-order. JSON is acyclic, so
+/// a plain recursion suffices — no cycle guard needed.
+fn descendants_or_self(node: &Value) -> Vec<&Value> {
+    let mut result = vec![node];
+    for child in children(node) {
+        result.extend(descendants_or_self(child));
+    }
+    result
+}
+
+/// Parse a `[n]` array-index step, if `base` is one.
+fn parse_index(base: &str) -> Option<usize> {
+    let digits = base.strip_prefix('[')?.strip_suffix(']')?;
+    digits.parse().ok()
+}
+
+/// Parse a predicate expression: `|` (loosest, "or") over clauses split on
+/// `&` (tighter, "and"), each leaf a `.key` existence check or `.key = value`
+/// equality check.
+fn parse_predicate(expr: &str) -> Result<Predicate> {
+    let mut or_clauses = expr.split('|');
+    let first = or_clauses.next().unwrap_or("");
+    let mut predicate = parse_and_clause(first)?;
+    for clause in or_clauses {
+        predicate = Predicate::Or(Box::new(predicate), Box::new(parse_and_clause(clause)?));
+    }
+    Ok(predicate)
+}
+
+fn parse_and_clause(expr: &str) -> Result<Predicate> {
+    let mut and_clauses = expr.split('&');
+    let first = and_clauses.next().unwrap_or("");
+    let mut predicate = parse_predicate_clause(first)?;
+    for clause in and_clauses {
+        predicate = Predicate::And(Box::new(predicate), Box::new(parse_predicate_clause(clause)?));
+    }
+    Ok(predicate)
+}
+
+/// Parse one leaf predicate clause: `.key` (existence) or `.key = value`
+/// (equality, with `value` interpreted via [`parse_primitive`]).
+fn parse_predicate_clause(clause: &str) -> Result<Predicate> {
+    let clause = clause.trim();
+    let rest = clause.strip_prefix('.').ok_or_else(|| {
+        AgonError::InvalidFormat(format!("Predicate clause must start with '.': {}", clause))
+    })?;
+    if rest.is_empty() {
+        return Err(AgonError::InvalidFormat(
+            "Empty predicate clause".to_string(),
+        ));
+    }
+
+    match rest.split_once('=') {
+        Some((key, value)) => {
+            let key = key.trim().to_string();
+            if key.is_empty() {
+                return Err(AgonError::InvalidFormat(format!(
+                    "Predicate clause missing key: {}",
+                    clause
+                )));
+            }
+            Ok(Predicate::Eq(key, parse_primitive(value.trim(), 1, 1)?))
+        }
+        None => Ok(Predicate::Exists(rest.trim().to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_select_literal_field_path() {
+        let data = json!({"a": {"b": {"c": 42}}});
+        let result = select(&data, "a/b/c").unwrap();
+        assert_eq!(result, vec![&json!(42)]);
+    }
+
+    #[test]
+    fn test_select_missing_field_yields_empty() {
+        let data = json!({"a": 1});
+        let result = select(&data, "missing").unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn test_select_out_of_range_index_yields_empty() {
+        let data = json!({"items": [1, 2]});
+        let result = select(&data, "items/[5]").unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn test_select_array_index() {
+        let data = json!({"items": ["a", "b", "c"]});
+        let result = select(&data, "items/[1]").unwrap();
+        assert_eq!(result, vec![&json!("b")]);
+    }
+
+    #[test]
+    fn test_select_wildcard_children() {
+        let data = json!({"items": [{"id": 1}, {"id": 2}]});
+        let result = select(&data, "items/*").unwrap();
+        assert_eq!(result, vec![&json!({"id": 1}), &json!({"id": 2})]);
+    }
+
+    #[test]
+    fn test_select_recursive_descent_includes_self() {
+        let data = json!({"a": {"b": 1}});
+        let result = select(&data, "**").unwrap();
+        assert_eq!(result.len(), 3); // root, "a" object, and 1
+    }
+
+    #[test]
+    fn test_select_exists_predicate_filters_children() {
+        let data = json!({"items": [{"id": 1}, {"id": 2, "active": true}]});
+        let result = select(&data, "items/*/{.active}").unwrap();
+        assert_eq!(result, vec![&json!({"id": 2, "active": true})]);
+    }
+
+    #[test]
+    fn test_select_eq_predicate_filters_children() {
+        let data = json!({"items": [{"kind": "user"}, {"kind": "admin"}]});
+        let result = select(&data, "items/*/{.kind = admin}").unwrap();
+        assert_eq!(result, vec![&json!({"kind": "admin"})]);
+    }
+
+    #[test]
+    fn test_select_and_predicate_requires_both() {
+        let data = json!({"items": [{"id": 1}, {"id": 2, "active": true}]});
+        let result = select(&data, "items/*/{.id & .active}").unwrap();
+        assert_eq!(result, vec![&json!({"id": 2, "active": true})]);
+    }
+
+    #[test]
+    fn test_select_or_predicate_requires_either() {
+        let data = json!({"items": [{"a": 1}, {"b": 1}, {"c": 1}]});
+        let result = select(&data, "items/*/{.a | .b}").unwrap();
+        assert_eq!(result, vec![&json!({"a": 1}), &json!({"b": 1})]);
+    }
+
+    #[test]
+    fn test_select_predicate_only_step_filters_current_nodes() {
+        let data = json!({"a": 1});
+        let result = select(&data, "{.a}").unwrap();
+        assert_eq!(result, vec![&json!({"a": 1})]);
+    }
+
+    #[test]
+    fn test_select_predicate_against_non_object_never_matches() {
+        let data = json!({"items": [1, 2, 3]});
+        let result = select(&data, "items/*/{.id}").unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn test_split_step_unterminated_brace_errors() {
+        let err = select(&json!({}), "a/{.b").unwrap_err();
+        assert!(matches!(err, AgonError::InvalidFormat(_)));
+    }
+
+    #[test]
+    fn test_predicate_clause_missing_dot_prefix_errors() {
+        let err = select(&json!({"a": 1}), "{a}").unwrap_err();
+        assert!(matches!(err, AgonError::InvalidFormat(_)));
+    }
+
+    #[test]
+    fn test_predicate_eq_value_uses_parse_primitive() {
+        let data = json!({"items": [{"count": 3}, {"count": 4}]});
+        let result = select(&data, "items/*/{.count = 3}").unwrap();
+        assert_eq!(result, vec![&json!({"count": 3})]);
+    }
+}
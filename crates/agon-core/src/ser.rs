@@ -0,0 +1,477 @@
+//! A `serde::Serializer` that drives AGON struct encoding directly from a
+//! Rust value, the way RON's `ser` module drives RON text straight from a
+//! `Serialize` implementor rather than through an intermediate document
+//! type.
+//!
+//! Serializing still builds a `serde_json::Value` under the hood, since
+//! that's the representation `struct_fmt::encode_with_schema` understands
+//! -- but unlike calling `serde_json::to_value` and handing the result to
+//! `encode` (which has to *guess* struct shapes via frequency-based
+//! [`detect_shapes`](crate::formats::struct_fmt)), this serializer sees
+//! every `serialize_struct`/`serialize_struct_variant` call as it happens
+//! and registers an `@Name: fields` definition for it directly from the
+//! Rust type's own name and field list. A `#[derive(Serialize)] struct
+//! Quote { .. }` therefore maps onto `@Quote` by construction, not by
+//! guesswork, and round-trips even when it only appears once in the data.
+
+use std::collections::HashSet;
+
+use serde::ser::{
+    Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+    SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+};
+use serde_json::{Map, Value};
+
+use crate::error::{AgonError, Result};
+use crate::struct_fmt::{self, Schema};
+use crate::types::bytes_to_tagged_json;
+
+/// Serialize `value` to AGON struct text, registering a named struct
+/// definition for every Rust struct/struct-variant type it contains.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    let mut builder = SchemaBuilder::default();
+    let json = value.serialize(Serializer {
+        schema: &mut builder,
+    })?;
+    struct_fmt::encode_with_schema(&json, &builder.schema, true)
+}
+
+/// The `Schema` being assembled as serialization discovers struct shapes,
+/// plus the set of type names already registered so a type serialized more
+/// than once (e.g. inside a `Vec<Quote>`) only contributes one definition.
+#[derive(Default)]
+struct SchemaBuilder {
+    schema: Schema,
+    seen: HashSet<String>,
+}
+
+impl SchemaBuilder {
+    fn define_once(&mut self, name: &str, fields: &[String]) -> Result<()> {
+        if self.seen.contains(name) {
+            return Ok(());
+        }
+        self.schema.define(&format!("@{}: {}", name, fields.join(", ")))?;
+        self.seen.insert(name.to_string());
+        Ok(())
+    }
+}
+
+struct Serializer<'a> {
+    schema: &'a mut SchemaBuilder,
+}
+
+impl<'a> serde::Serializer for Serializer<'a> {
+    type Ok = Value;
+    type Error = AgonError;
+
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = SeqSerializer<'a>;
+    type SerializeTupleStruct = SeqSerializer<'a>;
+    type SerializeTupleVariant = TupleVariantSerializer<'a>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = StructSerializer<'a>;
+    type SerializeStructVariant = StructSerializer<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i16(self, v: i16) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i32(self, v: i32) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i64(self, v: i64) -> Result<Value> {
+        Ok(Value::Number(v.into()))
+    }
+    fn serialize_i128(self, v: i128) -> Result<Value> {
+        serde_json::Number::from_i128(v)
+            .map(Value::Number)
+            .ok_or_else(|| AgonError::EncodingError(format!("i128 out of range: {}", v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value> {
+        self.serialize_u64(v as u64)
+    }
+    fn serialize_u16(self, v: u16) -> Result<Value> {
+        self.serialize_u64(v as u64)
+    }
+    fn serialize_u32(self, v: u32) -> Result<Value> {
+        self.serialize_u64(v as u64)
+    }
+    fn serialize_u64(self, v: u64) -> Result<Value> {
+        Ok(Value::Number(v.into()))
+    }
+    fn serialize_u128(self, v: u128) -> Result<Value> {
+        serde_json::Number::from_u128(v)
+            .map(Value::Number)
+            .ok_or_else(|| AgonError::EncodingError(format!("u128 out of range: {}", v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value> {
+        self.serialize_f64(v as f64)
+    }
+    fn serialize_f64(self, v: f64) -> Result<Value> {
+        serde_json::Number::from_f64(v)
+            .map(Value::Number)
+            .ok_or_else(|| AgonError::EncodingError(format!("non-finite float: {}", v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value> {
+        Ok(Value::String(v.to_string()))
+    }
+    fn serialize_str(self, v: &str) -> Result<Value> {
+        Ok(Value::String(v.to_string()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value> {
+        Ok(bytes_to_tagged_json(v))
+    }
+
+    fn serialize_none(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
+        Ok(Value::Null)
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        Ok(Value::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        let mut obj = Map::new();
+        obj.insert(variant.to_string(), value.serialize(Serializer { schema: self.schema })?);
+        Ok(Value::Object(obj))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer<'a>> {
+        Ok(SeqSerializer {
+            schema: self.schema,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer<'a>> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer<'a>> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<TupleVariantSerializer<'a>> {
+        Ok(TupleVariantSerializer {
+            variant,
+            items: SeqSerializer {
+                schema: self.schema,
+                items: Vec::with_capacity(len),
+            },
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer<'a>> {
+        Ok(MapSerializer {
+            schema: self.schema,
+            map: Map::new(),
+            pending_key: None,
+        })
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<StructSerializer<'a>> {
+        Ok(StructSerializer {
+            schema: self.schema,
+            name,
+            variant: None,
+            fields: Vec::with_capacity(len),
+            map: Map::new(),
+        })
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<StructSerializer<'a>> {
+        Ok(StructSerializer {
+            schema: self.schema,
+            name: variant,
+            variant: Some(variant),
+            fields: Vec::with_capacity(len),
+            map: Map::new(),
+        })
+    }
+}
+
+struct SeqSerializer<'a> {
+    schema: &'a mut SchemaBuilder,
+    items: Vec<Value>,
+}
+
+impl<'a> SerializeSeq for SeqSerializer<'a> {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.items
+            .push(value.serialize(Serializer { schema: &mut *self.schema })?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value> {
+        Ok(Value::Array(self.items))
+    }
+}
+
+impl<'a> SerializeTuple for SeqSerializer<'a> {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Value> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl<'a> SerializeTupleStruct for SeqSerializer<'a> {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Value> {
+        SerializeSeq::end(self)
+    }
+}
+
+struct TupleVariantSerializer<'a> {
+    variant: &'static str,
+    items: SeqSerializer<'a>,
+}
+
+impl<'a> SerializeTupleVariant for TupleVariantSerializer<'a> {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        SerializeSeq::serialize_element(&mut self.items, value)
+    }
+    fn end(self) -> Result<Value> {
+        let mut obj = Map::new();
+        obj.insert(self.variant.to_string(), SerializeSeq::end(self.items)?);
+        Ok(Value::Object(obj))
+    }
+}
+
+struct MapSerializer<'a> {
+    schema: &'a mut SchemaBuilder,
+    map: Map<String, Value>,
+    pending_key: Option<String>,
+}
+
+/// Converts a serialized map key into the `String` AGON object keys require.
+fn value_to_map_key(value: Value) -> Result<String> {
+    match value {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        other => Err(AgonError::EncodingError(format!(
+            "map keys must serialize to a string, number, or bool, got {}",
+            other
+        ))),
+    }
+}
+
+impl<'a> SerializeMap for MapSerializer<'a> {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        let key = key.serialize(Serializer { schema: &mut *self.schema })?;
+        self.pending_key = Some(value_to_map_key(key)?);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .pending_key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        let value = value.serialize(Serializer { schema: &mut *self.schema })?;
+        self.map.insert(key, value);
+        Ok(())
+    }
+    fn end(self) -> Result<Value> {
+        Ok(Value::Object(self.map))
+    }
+}
+
+struct StructSerializer<'a> {
+    schema: &'a mut SchemaBuilder,
+    name: &'static str,
+    variant: Option<&'static str>,
+    fields: Vec<String>,
+    map: Map<String, Value>,
+}
+
+impl<'a> SerializeStruct for StructSerializer<'a> {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        let value = value.serialize(Serializer { schema: &mut *self.schema })?;
+        self.fields.push(key.to_string());
+        self.map.insert(key.to_string(), value);
+        Ok(())
+    }
+    fn end(self) -> Result<Value> {
+        self.schema.define_once(self.name, &self.fields)?;
+        let obj = Value::Object(self.map);
+        match self.variant {
+            Some(variant) => {
+                let mut wrapper = Map::new();
+                wrapper.insert(variant.to_string(), obj);
+                Ok(Value::Object(wrapper))
+            }
+            None => Ok(obj),
+        }
+    }
+}
+
+impl<'a> SerializeStructVariant for StructSerializer<'a> {
+    type Ok = Value;
+    type Error = AgonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        SerializeStruct::serialize_field(self, key, value)
+    }
+    fn end(self) -> Result<Value> {
+        SerializeStruct::end(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct Quote {
+        fmt: String,
+        raw: f64,
+    }
+
+    #[derive(Serialize)]
+    enum Shape {
+        Unit,
+        Newtype(i32),
+        Tuple(i32, i32),
+        Struct { x: i32, y: i32 },
+    }
+
+    #[test]
+    fn test_to_string_primitive() {
+        assert_eq!(to_string(&42i32).unwrap(), "42");
+        assert_eq!(to_string(&"hello").unwrap(), "hello");
+        assert_eq!(to_string(&true).unwrap(), "true");
+    }
+
+    #[test]
+    fn test_to_string_registers_struct_by_type_name() {
+        let data = vec![
+            Quote { fmt: "1.00".to_string(), raw: 1.0 },
+        ];
+        let encoded = to_string(&data).unwrap();
+        assert!(encoded.contains("@Quote: fmt, raw"));
+    }
+
+    #[test]
+    fn test_to_string_dedupes_repeated_struct_definition() {
+        let data = vec![
+            Quote { fmt: "1.00".to_string(), raw: 1.0 },
+            Quote { fmt: "2.00".to_string(), raw: 2.0 },
+        ];
+        let encoded = to_string(&data).unwrap();
+        assert_eq!(encoded.matches("@Quote:").count(), 1);
+    }
+
+    #[test]
+    fn test_to_string_option_none_is_null() {
+        #[derive(Serialize)]
+        struct Opt {
+            value: Option<i32>,
+        }
+        let encoded = to_string(&Opt { value: None }).unwrap();
+        assert!(encoded.contains("value: null"));
+    }
+
+    #[test]
+    fn test_to_string_unit_variant_is_string() {
+        let encoded = to_string(&Shape::Unit).unwrap();
+        assert_eq!(encoded, "Unit");
+    }
+
+    #[test]
+    fn test_to_string_newtype_variant() {
+        let encoded = to_string(&Shape::Newtype(7)).unwrap();
+        assert!(encoded.contains("Newtype: 7"));
+    }
+
+    #[test]
+    fn test_to_string_tuple_variant() {
+        let encoded = to_string(&Shape::Tuple(1, 2)).unwrap();
+        assert!(encoded.contains("Tuple:"));
+    }
+
+    #[test]
+    fn test_to_string_struct_variant() {
+        let encoded = to_string(&Shape::Struct { x: 1, y: 2 }).unwrap();
+        assert!(encoded.contains("Struct:"));
+        assert!(encoded.contains("x: 1"));
+        assert!(encoded.contains("y: 2"));
+    }
+}
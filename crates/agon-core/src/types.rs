@@ -1,16 +1,133 @@
 //! Type definitions and Python/JSON conversion utilities
+//!
+//! Note: relies on serde_json's `arbitrary_precision` feature so that
+//! integers wider than i64/u64 round-trip without losing digits.
 
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
 use pyo3::prelude::*;
-use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString};
+use pyo3::types::{
+    PyBool, PyByteArray, PyBytes, PyDict, PyFloat, PyFrozenSet, PyInt, PyList, PySet, PyString,
+    PyTuple,
+};
 use serde_json::Value as SerdeValue;
+use std::collections::HashMap;
 
 use crate::error::{AgonError, Result};
 
 /// Our JSON value type (re-export of serde_json::Value for convenience)
 pub type JsonValue = SerdeValue;
 
-/// Convert a Python object to a JSON Value
+/// The reserved key used to escape a byte string (from Python `bytes`, or a
+/// CBOR byte string — see the `cbor` module) through JSON, since JSON has no
+/// native byte-string type.
+pub const BYTES_TAG: &str = "__bytes__";
+
+/// Wrap raw bytes in the `{"__bytes__": {"base64": "..."}}` escape.
+pub fn bytes_to_tagged_json(bytes: &[u8]) -> JsonValue {
+    let mut inner = serde_json::Map::new();
+    inner.insert("base64".to_string(), JsonValue::String(BASE64.encode(bytes)));
+    let mut obj = serde_json::Map::new();
+    obj.insert(BYTES_TAG.to_string(), JsonValue::Object(inner));
+    JsonValue::Object(obj)
+}
+
+/// Recognize the `{"__bytes__": {"base64": "..."}}` escape. Returns `Ok(None)`
+/// for a plain object, `Ok(Some(bytes))` for a valid escape, and an error for
+/// a reserved-looking key (`__foo__`) that isn't the one we know how to decode.
+pub fn decode_bytes_escape(obj: &serde_json::Map<String, JsonValue>) -> Result<Option<Vec<u8>>> {
+    if obj.len() != 1 {
+        return Ok(None);
+    }
+    let (key, val) = obj.iter().next().unwrap();
+    if !(key.starts_with("__") && key.ends_with("__")) {
+        return Ok(None);
+    }
+    if key != BYTES_TAG {
+        return Err(AgonError::UnknownTaggedKey(key.clone()));
+    }
+
+    let base64_str = val
+        .as_object()
+        .and_then(|inner| inner.get("base64"))
+        .and_then(|b| b.as_str())
+        .ok_or_else(|| AgonError::CborError(format!("Malformed {} escape", BYTES_TAG)))?;
+    let bytes = BASE64
+        .decode(base64_str)
+        .map_err(|e| AgonError::CborError(e.to_string()))?;
+    Ok(Some(bytes))
+}
+
+/// How many times in a row the `default` callback may be invoked while
+/// converting a single value, guarding against a callback that keeps
+/// returning another object it can't itself represent.
+const MAX_DEFAULT_DEPTH: usize = 1000;
+
+/// The sentinel strings used to round-trip non-finite floats through JSON
+/// in [`NanMode::Sentinel`] mode.
+const NAN_TOKEN: &str = "NaN";
+const INFINITY_TOKEN: &str = "Infinity";
+const NEG_INFINITY_TOKEN: &str = "-Infinity";
+
+/// How to handle a non-finite float (`NaN`, `Infinity`, `-Infinity`), which
+/// JSON has no native representation for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NanMode {
+    /// Raise `AgonError::InvalidData` (like `json.dumps(allow_nan=False)`).
+    Strict,
+    /// Encode as a sentinel string (`"NaN"`, `"Infinity"`, `"-Infinity"`)
+    /// that [`json_to_py_with_options`] can rehydrate back to the original
+    /// float when `rehydrate` is set.
+    Sentinel,
+}
+
+impl Default for NanMode {
+    fn default() -> Self {
+        NanMode::Strict
+    }
+}
+
+/// Convert a Python object to a JSON Value.
+///
+/// Any object that isn't natively representable raises
+/// `AgonError::InvalidData`, as does a non-finite float. Use
+/// [`py_to_json_with_default`] or [`py_to_json_with_options`] for more
+/// permissive behavior.
 pub fn py_to_json(obj: &Bound<'_, PyAny>) -> Result<JsonValue> {
+    py_to_json_with_default(obj, None)
+}
+
+/// Convert a Python object to a JSON Value, invoking `default` for any
+/// object that isn't natively representable.
+///
+/// `default` is called with the unconvertible object and must return a
+/// JSON-serializable Python object, which is then itself recursively
+/// converted. If `default` is `None`, or it keeps returning objects it can't
+/// represent for more than [`MAX_DEFAULT_DEPTH`] rounds, conversion fails
+/// with `AgonError::InvalidData`.
+pub fn py_to_json_with_default(
+    obj: &Bound<'_, PyAny>,
+    default: Option<&Bound<'_, PyAny>>,
+) -> Result<JsonValue> {
+    py_to_json_with_options(obj, default, NanMode::default())
+}
+
+/// Convert a Python object to a JSON Value, with full control over the
+/// `default` fallback and non-finite float handling.
+pub fn py_to_json_with_options(
+    obj: &Bound<'_, PyAny>,
+    default: Option<&Bound<'_, PyAny>>,
+    nan_mode: NanMode,
+) -> Result<JsonValue> {
+    py_to_json_inner(obj, default, nan_mode, 0)
+}
+
+fn py_to_json_inner(
+    obj: &Bound<'_, PyAny>,
+    default: Option<&Bound<'_, PyAny>>,
+    nan_mode: NanMode,
+    depth: usize,
+) -> Result<JsonValue> {
     if obj.is_none() {
         return Ok(JsonValue::Null);
     }
@@ -24,13 +141,15 @@ pub fn py_to_json(obj: &Bound<'_, PyAny>) -> Result<JsonValue> {
         if let Ok(n) = obj.extract::<i64>() {
             return Ok(JsonValue::Number(n.into()));
         }
-        // Try as float if i64 doesn't work (large numbers)
-        if let Ok(f) = obj.extract::<f64>() {
-            if let Some(n) = serde_json::Number::from_f64(f) {
-                return Ok(JsonValue::Number(n));
-            }
+        if let Ok(n) = obj.extract::<u64>() {
+            return Ok(JsonValue::Number(n.into()));
         }
-        return Err(AgonError::InvalidData("Integer too large".to_string()));
+        // Too big for i64/u64: preserve every digit via the arbitrary-precision
+        // Number rather than demoting to a lossy f64.
+        let digits = obj.str()?.to_string();
+        return Ok(JsonValue::Number(serde_json::Number::from_string_unchecked(
+            digits,
+        )));
     }
 
     if obj.is_instance_of::<PyFloat>() {
@@ -38,19 +157,74 @@ pub fn py_to_json(obj: &Bound<'_, PyAny>) -> Result<JsonValue> {
         if let Some(n) = serde_json::Number::from_f64(val) {
             return Ok(JsonValue::Number(n));
         }
-        // Handle NaN/Infinity as null (JSON doesn't support them)
-        return Ok(JsonValue::Null);
+        // Non-finite (NaN, Infinity, -Infinity): JSON has no native
+        // representation for these, so defer to the configured mode.
+        return match nan_mode {
+            NanMode::Strict => Err(AgonError::InvalidData(format!(
+                "Cannot convert non-finite float {} to JSON in strict mode",
+                val
+            ))),
+            NanMode::Sentinel => {
+                let token = if val.is_nan() {
+                    NAN_TOKEN
+                } else if val > 0.0 {
+                    INFINITY_TOKEN
+                } else {
+                    NEG_INFINITY_TOKEN
+                };
+                Ok(JsonValue::String(token.to_string()))
+            }
+        };
     }
 
     if obj.is_instance_of::<PyString>() {
         return Ok(JsonValue::String(obj.extract::<String>()?));
     }
 
-    if obj.is_instance_of::<PyList>() {
-        let list = obj
-            .cast::<PyList>()
+    if obj.is_instance_of::<PyBytes>() {
+        let bytes = obj
+            .cast::<PyBytes>()
+            .map_err(|e| AgonError::InvalidData(e.to_string()))?;
+        return Ok(bytes_to_tagged_json(bytes.as_bytes()));
+    }
+
+    if obj.is_instance_of::<PyByteArray>() {
+        let bytearray = obj
+            .cast::<PyByteArray>()
             .map_err(|e| AgonError::InvalidData(e.to_string()))?;
-        let arr: Result<Vec<JsonValue>> = list.iter().map(|item| py_to_json(&item)).collect();
+        return Ok(bytes_to_tagged_json(&bytearray.to_vec()));
+    }
+
+    if obj.is_instance_of::<PyList>()
+        || obj.is_instance_of::<PyTuple>()
+        || obj.is_instance_of::<PySet>()
+        || obj.is_instance_of::<PyFrozenSet>()
+    {
+        let items: Vec<Bound<'_, PyAny>> = if obj.is_instance_of::<PyTuple>() {
+            obj.cast::<PyTuple>()
+                .map_err(|e| AgonError::InvalidData(e.to_string()))?
+                .iter()
+                .collect()
+        } else if obj.is_instance_of::<PySet>() {
+            obj.cast::<PySet>()
+                .map_err(|e| AgonError::InvalidData(e.to_string()))?
+                .iter()
+                .collect()
+        } else if obj.is_instance_of::<PyFrozenSet>() {
+            obj.cast::<PyFrozenSet>()
+                .map_err(|e| AgonError::InvalidData(e.to_string()))?
+                .iter()
+                .collect()
+        } else {
+            obj.cast::<PyList>()
+                .map_err(|e| AgonError::InvalidData(e.to_string()))?
+                .iter()
+                .collect()
+        };
+        let arr: Result<Vec<JsonValue>> = items
+            .iter()
+            .map(|item| py_to_json_inner(item, default, nan_mode, depth))
+            .collect();
         return Ok(JsonValue::Array(arr?));
     }
 
@@ -63,14 +237,28 @@ pub fn py_to_json(obj: &Bound<'_, PyAny>) -> Result<JsonValue> {
             let key_str = key
                 .extract::<String>()
                 .map_err(|_| AgonError::InvalidData("Dict keys must be strings".to_string()))?;
-            map.insert(key_str, py_to_json(&value)?);
+            map.insert(key_str, py_to_json_inner(&value, default, nan_mode, depth)?);
         }
         return Ok(JsonValue::Object(map));
     }
 
-    // Try to convert via str() as fallback
-    if let Ok(s) = obj.str() {
-        return Ok(JsonValue::String(s.to_string()));
+    // decimal.Decimal: preserve every digit via the arbitrary-precision Number,
+    // the same trick used above for integers wider than i64/u64. Checked after
+    // the built-in types above since it requires importing the `decimal` module.
+    let decimal_cls = decimal_class(obj.py())?;
+    if obj.is_instance(&decimal_cls)? {
+        let digits = obj.str()?.to_string();
+        return Ok(JsonValue::Number(serde_json::Number::from_string_unchecked(
+            digits,
+        )));
+    }
+
+    // datetime.date/time/datetime: `datetime` is a subclass of `date`, so
+    // checking `date` covers both.
+    let (date_cls, time_cls) = datetime_classes(obj.py())?;
+    if obj.is_instance(&date_cls)? || obj.is_instance(&time_cls)? {
+        let iso = obj.call_method0("isoformat")?.extract::<String>()?;
+        return Ok(JsonValue::String(iso));
     }
 
     let type_name = obj
@@ -78,38 +266,313 @@ pub fn py_to_json(obj: &Bound<'_, PyAny>) -> Result<JsonValue> {
         .name()
         .map(|n| n.to_string())
         .unwrap_or_else(|_| "unknown".to_string());
+
+    if let Some(default_fn) = default {
+        if depth >= MAX_DEFAULT_DEPTH {
+            return Err(AgonError::InvalidData(format!(
+                "default callback recursion limit ({}) exceeded for {}",
+                MAX_DEFAULT_DEPTH, type_name
+            )));
+        }
+        let replaced = default_fn
+            .call1((obj,))
+            .map_err(|e| AgonError::InvalidData(e.to_string()))?;
+        return py_to_json_inner(&replaced, default, nan_mode, depth + 1);
+    }
+
     Err(AgonError::InvalidData(format!(
         "Cannot convert {} to JSON",
         type_name
     )))
 }
 
-/// Convert a JSON Value to a Python object
+/// Convert a Python object to JSON, also returning a type-tag map (see
+/// [`collect_type_tags`]) describing which top-level fields (or, for a
+/// top-level list of dicts, which columns) need a tag to round-trip
+/// precisely: `int`/`float`/`decimal.Decimal` all serialize as JSON numbers,
+/// `tuple`/`list` both serialize as JSON arrays, and `datetime`/`date`/`time`
+/// all serialize as ISO-8601 strings. Consulted by the text formats'
+/// `preserve_types` option.
+pub fn py_to_json_with_type_tags(
+    obj: &Bound<'_, PyAny>,
+    default: Option<&Bound<'_, PyAny>>,
+    nan_mode: NanMode,
+) -> Result<(JsonValue, HashMap<String, String>)> {
+    let value = py_to_json_with_options(obj, default, nan_mode)?;
+    let tags = collect_type_tags(obj)?;
+    Ok((value, tags))
+}
+
+/// Collect a flat field-name -> type-tag map for `obj`, which must be a dict
+/// or a list/tuple of dicts (the shapes AGON's tabular formats encode). Only
+/// fields whose type JSON can't tell apart from a more common one get a tag
+/// (see [`infer_type_tag`]); everything else round-trips fine without one.
+/// For a list of dicts, the first non-`None` value seen for a given key wins.
+pub fn collect_type_tags(obj: &Bound<'_, PyAny>) -> Result<HashMap<String, String>> {
+    let mut tags = HashMap::new();
+    if obj.is_instance_of::<PyDict>() {
+        let dict = obj
+            .cast::<PyDict>()
+            .map_err(|e| AgonError::InvalidData(e.to_string()))?;
+        collect_tags_from_dict(&dict, &mut tags)?;
+    } else if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
+        let items: Vec<Bound<'_, PyAny>> = if obj.is_instance_of::<PyTuple>() {
+            obj.cast::<PyTuple>()
+                .map_err(|e| AgonError::InvalidData(e.to_string()))?
+                .iter()
+                .collect()
+        } else {
+            obj.cast::<PyList>()
+                .map_err(|e| AgonError::InvalidData(e.to_string()))?
+                .iter()
+                .collect()
+        };
+        for item in &items {
+            if item.is_instance_of::<PyDict>() {
+                let dict = item
+                    .cast::<PyDict>()
+                    .map_err(|e| AgonError::InvalidData(e.to_string()))?;
+                collect_tags_from_dict(&dict, &mut tags)?;
+            }
+        }
+    }
+    Ok(tags)
+}
+
+fn collect_tags_from_dict(
+    dict: &Bound<'_, PyDict>,
+    tags: &mut HashMap<String, String>,
+) -> Result<()> {
+    for (key, value) in dict.iter() {
+        let Ok(key_str) = key.extract::<String>() else {
+            continue;
+        };
+        if tags.contains_key(&key_str) || value.is_none() {
+            continue;
+        }
+        if let Some(tag) = infer_type_tag(&value)? {
+            tags.insert(key_str, tag.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Identify the Python type of `obj` when it's one JSON can't distinguish
+/// from a more common type on its own. Returns `None` for types that
+/// round-trip unambiguously already (`bool`, `str`, `bytes`, `list`, `dict`,
+/// `None`).
+fn infer_type_tag(obj: &Bound<'_, PyAny>) -> Result<Option<&'static str>> {
+    // Check bool before int (bool is subclass of int in Python).
+    if obj.is_instance_of::<PyBool>() {
+        return Ok(None);
+    }
+    if obj.is_instance_of::<PyInt>() {
+        return Ok(Some("int"));
+    }
+    if obj.is_instance_of::<PyFloat>() {
+        return Ok(Some("float"));
+    }
+    if obj.is_instance_of::<PyTuple>() {
+        return Ok(Some("tuple"));
+    }
+    let decimal_cls = decimal_class(obj.py())?;
+    if obj.is_instance(&decimal_cls)? {
+        return Ok(Some("decimal"));
+    }
+    // datetime.datetime is a subclass of datetime.date, so it must be checked
+    // first or every datetime would be tagged "date".
+    let datetime_mod = PyModule::import(obj.py(), "datetime")?;
+    if obj.is_instance(&datetime_mod.getattr("datetime")?)? {
+        return Ok(Some("datetime"));
+    }
+    if obj.is_instance(&datetime_mod.getattr("date")?)? {
+        return Ok(Some("date"));
+    }
+    if obj.is_instance(&datetime_mod.getattr("time")?)? {
+        return Ok(Some("time"));
+    }
+    Ok(None)
+}
+
+/// Coerce `value` (already converted to a plain Python object) back to the
+/// precise type named by `tag`, as recorded by [`infer_type_tag`]. Falls back
+/// to returning `value` unchanged if it doesn't have the shape `tag` expects.
+fn apply_type_tag(py: Python<'_>, value: &Bound<'_, PyAny>, tag: &str) -> PyResult<Py<PyAny>> {
+    if value.is_none() {
+        return Ok(value.clone().unbind());
+    }
+    match tag {
+        "int" => PyModule::import(py, "builtins")?
+            .call_method1("int", (value,))
+            .map(Bound::unbind),
+        "float" => PyModule::import(py, "builtins")?
+            .call_method1("float", (value,))
+            .map(Bound::unbind),
+        "decimal" => decimal_class(py)?
+            .call1((value.str()?,))
+            .map(Bound::unbind),
+        "tuple" => match value.cast::<PyList>() {
+            Ok(list) => Ok(PyTuple::new(py, list.iter())?.unbind().into_any()),
+            Err(_) => Ok(value.clone().unbind()),
+        },
+        "datetime" | "date" | "time" => {
+            let Ok(s) = value.extract::<String>() else {
+                return Ok(value.clone().unbind());
+            };
+            let cls = PyModule::import(py, "datetime")?.getattr(tag)?;
+            match cls.call_method1("fromisoformat", (s,)) {
+                Ok(parsed) => Ok(parsed.unbind()),
+                Err(_) => Ok(value.clone().unbind()),
+            }
+        }
+        _ => Ok(value.clone().unbind()),
+    }
+}
+
+/// Like [`json_to_py_with_options`], but consults a type-tag map (see
+/// [`collect_type_tags`]) to precisely reconstruct fields whose type JSON
+/// can't tell apart from a more common one: `int`/`float`/`decimal.Decimal`,
+/// `tuple`/`list`, and `datetime`/`date`/`time`. `tags` applies to the
+/// top-level dict's fields, or, for a top-level list, to every dict in it.
+pub fn json_to_py_with_type_tags(
+    py: Python<'_>,
+    value: &JsonValue,
+    rehydrate: bool,
+    tags: &HashMap<String, String>,
+) -> PyResult<Py<PyAny>> {
+    let converted = json_to_py_with_options(py, value, rehydrate)?;
+    if tags.is_empty() {
+        return Ok(converted);
+    }
+    let bound = converted.bind(py);
+    if let Ok(dict) = bound.cast::<PyDict>() {
+        retag_dict(py, dict, tags)?;
+    } else if let Ok(list) = bound.cast::<PyList>() {
+        for row in list.iter() {
+            if let Ok(dict) = row.cast::<PyDict>() {
+                retag_dict(py, &dict, tags)?;
+            }
+        }
+    }
+    Ok(converted)
+}
+
+fn retag_dict(py: Python<'_>, dict: &Bound<'_, PyDict>, tags: &HashMap<String, String>) -> PyResult<()> {
+    for (key, tag) in tags {
+        if let Some(item) = dict.get_item(key)? {
+            dict.set_item(key, apply_type_tag(py, &item, tag)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Look up `decimal.Decimal`.
+fn decimal_class(py: Python<'_>) -> PyResult<Bound<'_, PyAny>> {
+    PyModule::import(py, "decimal")?.getattr("Decimal")
+}
+
+/// Look up `(datetime.date, datetime.time)`. `datetime.datetime` is a
+/// subclass of `date`, so checking against `date` also matches it.
+fn datetime_classes(py: Python<'_>) -> PyResult<(Bound<'_, PyAny>, Bound<'_, PyAny>)> {
+    let datetime_mod = PyModule::import(py, "datetime")?;
+    Ok((datetime_mod.getattr("date")?, datetime_mod.getattr("time")?))
+}
+
+/// Try to parse `s` back into a `datetime`/`date`/`time`, in that order of
+/// specificity. Returns `None` if `s` isn't a valid ISO-8601 value for any of
+/// them, in which case the caller should keep it as a plain string.
+///
+/// Note: this is a heuristic — any string that happens to look like an
+/// ISO-8601 timestamp will be rehydrated, even if it started out as a plain
+/// string field. Only opt into this via `rehydrate=True` on `decode()`.
+fn try_rehydrate_timestamp(py: Python<'_>, s: &str) -> PyResult<Option<Py<PyAny>>> {
+    let datetime_mod = PyModule::import(py, "datetime")?;
+    for cls_name in ["datetime", "date", "time"] {
+        let cls = datetime_mod.getattr(cls_name)?;
+        if let Ok(value) = cls.call_method1("fromisoformat", (s,)) {
+            return Ok(Some(value.unbind()));
+        }
+    }
+    Ok(None)
+}
+
+/// Recognize a `NanMode::Sentinel` token (`"NaN"`, `"Infinity"`,
+/// `"-Infinity"`) and return the float it represents.
+fn rehydrate_nan_token(s: &str) -> Option<f64> {
+    match s {
+        NAN_TOKEN => Some(f64::NAN),
+        INFINITY_TOKEN => Some(f64::INFINITY),
+        NEG_INFINITY_TOKEN => Some(f64::NEG_INFINITY),
+        _ => None,
+    }
+}
+
+/// Convert a JSON Value to a Python object.
+///
+/// Tagged byte strings and ISO-8601 timestamps are left as plain JSON types
+/// (a base64-tagged object, a string). Use [`json_to_py_with_options`] to
+/// rehydrate those back into `bytes` and `datetime` instances.
 pub fn json_to_py(py: Python<'_>, value: &JsonValue) -> PyResult<Py<PyAny>> {
+    json_to_py_with_options(py, value, false)
+}
+
+/// Convert a JSON Value to a Python object.
+///
+/// When `rehydrate` is `true`, a `{"__bytes__": {"base64": "..."}}` escape is
+/// restored to `bytes`, a `NanMode::Sentinel` token (`"NaN"`, `"Infinity"`,
+/// `"-Infinity"`) is restored to the corresponding float, and any other
+/// string that parses as an ISO-8601 timestamp is restored to a
+/// `datetime`/`date`/`time` instance.
+pub fn json_to_py_with_options(
+    py: Python<'_>,
+    value: &JsonValue,
+    rehydrate: bool,
+) -> PyResult<Py<PyAny>> {
     match value {
         JsonValue::Null => Ok(py.None()),
         JsonValue::Bool(b) => Ok(b.into_pyobject(py)?.to_owned().unbind().into_any()),
         JsonValue::Number(n) => {
             if let Some(i) = n.as_i64() {
                 Ok(i.into_pyobject(py)?.to_owned().unbind().into_any())
+            } else if let Some(u) = n.as_u64() {
+                Ok(u.into_pyobject(py)?.to_owned().unbind().into_any())
             } else if let Some(f) = n.as_f64() {
                 Ok(f.into_pyobject(py)?.to_owned().unbind().into_any())
             } else {
-                Ok(n.to_string().into_pyobject(py)?.unbind().into_any())
+                // Arbitrary-precision integer beyond i64/u64/f64: rebuild the
+                // Python int from its decimal digits instead of returning a str.
+                let builtins = PyModule::import(py, "builtins")?;
+                let int_obj = builtins.call_method1("int", (n.to_string(),))?;
+                Ok(int_obj.unbind())
             }
         }
-        JsonValue::String(s) => Ok(s.into_pyobject(py)?.unbind().into_any()),
+        JsonValue::String(s) => {
+            if rehydrate {
+                if let Some(f) = rehydrate_nan_token(s) {
+                    return Ok(f.into_pyobject(py)?.to_owned().unbind().into_any());
+                }
+                if let Some(dt) = try_rehydrate_timestamp(py, s)? {
+                    return Ok(dt);
+                }
+            }
+            Ok(s.into_pyobject(py)?.unbind().into_any())
+        }
         JsonValue::Array(arr) => {
             let list = PyList::empty(py);
             for item in arr {
-                list.append(json_to_py(py, item)?)?;
+                list.append(json_to_py_with_options(py, item, rehydrate)?)?;
             }
             Ok(list.unbind().into_any())
         }
         JsonValue::Object(map) => {
+            if rehydrate {
+                if let Some(bytes) = decode_bytes_escape(map).map_err(PyErr::from)? {
+                    return Ok(PyBytes::new(py, &bytes).unbind().into_any());
+                }
+            }
             let dict = PyDict::new(py);
             for (key, val) in map {
-                dict.set_item(key, json_to_py(py, val)?)?;
+                dict.set_item(key, json_to_py_with_options(py, val, rehydrate)?)?;
             }
             Ok(dict.unbind().into_any())
         }
@@ -127,4 +590,40 @@ mod tests {
         let back = serde_json::to_string(&value).unwrap();
         assert!(back.contains("name"));
     }
+
+    #[test]
+    fn test_bytes_tagged_json_roundtrip() {
+        let bytes = vec![0u8, 1, 2, 255];
+        let tagged = bytes_to_tagged_json(&bytes);
+        let obj = tagged.as_object().unwrap();
+        let decoded = decode_bytes_escape(obj).unwrap();
+        assert_eq!(decoded, Some(bytes));
+    }
+
+    #[test]
+    fn test_decode_bytes_escape_ignores_plain_object() {
+        let value = serde_json::json!({"a": 1, "b": 2});
+        let decoded = decode_bytes_escape(value.as_object().unwrap()).unwrap();
+        assert_eq!(decoded, None);
+    }
+
+    #[test]
+    fn test_decode_bytes_escape_rejects_unknown_tagged_key() {
+        let value = serde_json::json!({"__weird__": {"base64": "AQID"}});
+        let err = decode_bytes_escape(value.as_object().unwrap()).unwrap_err();
+        assert!(matches!(err, AgonError::UnknownTaggedKey(_)));
+    }
+
+    #[test]
+    fn test_nan_mode_default_is_strict() {
+        assert_eq!(NanMode::default(), NanMode::Strict);
+    }
+
+    #[test]
+    fn test_rehydrate_nan_tokens() {
+        assert!(rehydrate_nan_token("NaN").unwrap().is_nan());
+        assert_eq!(rehydrate_nan_token("Infinity"), Some(f64::INFINITY));
+        assert_eq!(rehydrate_nan_token("-Infinity"), Some(f64::NEG_INFINITY));
+        assert_eq!(rehydrate_nan_token("not a sentinel"), None);
+    }
 }
@@ -1,17 +1,118 @@
 //! Shared utilities for AGON encoding
 
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::{LazyLock, RwLock};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
 use tiktoken_rs::CoreBPE;
+use tokenizers::Tokenizer;
 
 use crate::error::{AgonError, Result};
 
-/// Cached tokenizer instances by encoding name
-static TOKENIZERS: LazyLock<RwLock<HashMap<String, CoreBPE>>> =
+/// Tiktoken encodings built into `tiktoken_rs`. Anything else passed as an
+/// `encoding` is treated as a path to a HuggingFace `tokenizers` JSON file.
+const BUILTIN_TIKTOKEN_ENCODINGS: &[&str] = &[
+    "o200k_base",
+    "o200k_harmony",
+    "cl100k_base",
+    "p50k_base",
+    "p50k_edit",
+    "r50k_base",
+];
+
+/// A loaded tokenizer, either one of tiktoken's built-in BPE encodings or a
+/// HuggingFace `tokenizers` tokenizer loaded from a JSON file. This lets
+/// callers count tokens the way the model that will actually consume the
+/// output does, rather than assuming an OpenAI tiktoken encoding.
+#[derive(Clone)]
+enum TokenizerBackend {
+    Tiktoken(CoreBPE),
+    HuggingFace(Tokenizer),
+}
+
+impl TokenizerBackend {
+    fn count(&self, text: &str) -> Result<usize> {
+        match self {
+            TokenizerBackend::Tiktoken(bpe) => Ok(bpe.encode_ordinary(text).len()),
+            TokenizerBackend::HuggingFace(tokenizer) => tokenizer
+                .encode(text, false)
+                .map(|encoding| encoding.len())
+                .map_err(|e| AgonError::EncodingError(e.to_string())),
+        }
+    }
+
+    /// Encode `text`, keep only its first `max_tokens` token ids, and decode
+    /// those back to a string. Returns `text` unchanged if it's already
+    /// within budget.
+    fn truncate(&self, text: &str, max_tokens: usize) -> Result<String> {
+        match self {
+            TokenizerBackend::Tiktoken(bpe) => {
+                let ids = bpe.encode_ordinary(text);
+                if ids.len() <= max_tokens {
+                    return Ok(text.to_string());
+                }
+                bpe.decode(ids[..max_tokens].to_vec())
+                    .map_err(|e| AgonError::EncodingError(e.to_string()))
+            }
+            TokenizerBackend::HuggingFace(tokenizer) => {
+                let encoding = tokenizer
+                    .encode(text, false)
+                    .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+                let ids = encoding.get_ids();
+                if ids.len() <= max_tokens {
+                    return Ok(text.to_string());
+                }
+                tokenizer
+                    .decode(&ids[..max_tokens], true)
+                    .map_err(|e| AgonError::EncodingError(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Whether `encoding` names one of tiktoken's built-in BPE encodings, as
+/// opposed to a path to a HuggingFace `tokenizers` JSON file.
+fn is_builtin_tiktoken_encoding(encoding: &str) -> bool {
+    BUILTIN_TIKTOKEN_ENCODINGS.contains(&encoding)
+}
+
+/// Model-name prefixes to their tiktoken encoding, so callers can pass a
+/// model id (`"gpt-4o-2024-08-06"`) instead of memorizing which encoding it
+/// uses. Matched longest-prefix-first, so a versioned suffix still resolves
+/// to its family's encoding.
+const MODEL_ENCODING_PREFIXES: &[(&str, &str)] = &[
+    ("gpt-4o", "o200k_base"),
+    ("o1", "o200k_base"),
+    ("o3", "o200k_base"),
+    ("gpt-4", "cl100k_base"),
+    ("gpt-3.5-turbo", "cl100k_base"),
+    ("text-embedding-3", "cl100k_base"),
+    ("text-davinci", "p50k_base"),
+    ("code-davinci", "p50k_base"),
+    ("code-cushman", "p50k_base"),
+    ("gpt2", "r50k_base"),
+];
+
+/// Resolve a model id (e.g. `"gpt-4o-2024-08-06"`) to the tiktoken encoding
+/// it uses, matching by longest known prefix so versioned/dated suffixes
+/// still resolve. Returns `AgonError::InvalidFormat` for unrecognized models.
+pub fn encoding_for_model(model: &str) -> Result<&'static str> {
+    MODEL_ENCODING_PREFIXES
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, encoding)| *encoding)
+        .ok_or_else(|| AgonError::InvalidFormat(format!("Unknown model: {}", model)))
+}
+
+/// Cached tokenizer instances, keyed by encoding name or tokenizer file path
+static TOKENIZERS: LazyLock<RwLock<HashMap<String, TokenizerBackend>>> =
     LazyLock::new(|| RwLock::new(HashMap::new()));
 
-/// Get or create a tokenizer for the given encoding
-fn get_tokenizer(encoding: &str) -> Result<CoreBPE> {
+/// Get or create a tokenizer for the given encoding name or tokenizer file path
+fn get_tokenizer(encoding: &str) -> Result<TokenizerBackend> {
     // Check cache first
     {
         let cache = TOKENIZERS.read().unwrap();
@@ -20,22 +121,28 @@ fn get_tokenizer(encoding: &str) -> Result<CoreBPE> {
         }
     }
 
-    // Create new tokenizer
-    let tokenizer = match encoding {
-        "o200k_base" => tiktoken_rs::o200k_base(),
-        "o200k_harmony" => tiktoken_rs::o200k_harmony(),
-        "cl100k_base" => tiktoken_rs::cl100k_base(),
-        "p50k_base" => tiktoken_rs::p50k_base(),
-        "p50k_edit" => tiktoken_rs::p50k_edit(),
-        "r50k_base" => tiktoken_rs::r50k_base(),
-        _ => {
-            return Err(AgonError::InvalidFormat(format!(
-                "Unknown encoding: {}",
-                encoding
-            )));
+    let tokenizer = if is_builtin_tiktoken_encoding(encoding) {
+        let bpe = match encoding {
+            "o200k_base" => tiktoken_rs::o200k_base(),
+            "o200k_harmony" => tiktoken_rs::o200k_harmony(),
+            "cl100k_base" => tiktoken_rs::cl100k_base(),
+            "p50k_base" => tiktoken_rs::p50k_base(),
+            "p50k_edit" => tiktoken_rs::p50k_edit(),
+            "r50k_base" => tiktoken_rs::r50k_base(),
+            _ => unreachable!("checked by is_builtin_tiktoken_encoding"),
         }
-    }
-    .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+        .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+        TokenizerBackend::Tiktoken(bpe)
+    } else {
+        let tokenizer = Tokenizer::from_file(encoding).map_err(|e| {
+            AgonError::InvalidFormat(format!(
+                "Unknown encoding: {} (not a built-in tiktoken encoding, and not a \
+                 loadable tokenizers file: {})",
+                encoding, e
+            ))
+        })?;
+        TokenizerBackend::HuggingFace(tokenizer)
+    };
 
     // Cache it
     {
@@ -46,11 +153,263 @@ fn get_tokenizer(encoding: &str) -> Result<CoreBPE> {
     Ok(tokenizer)
 }
 
-/// Count tokens using the specified tiktoken encoding
+/// Parse a `.tiktoken` rank file: each line is a base64-encoded token's raw
+/// bytes, followed by whitespace and its integer rank.
+fn parse_rank_file(rank_file: &Path) -> Result<HashMap<Vec<u8>, usize>> {
+    let contents = std::fs::read_to_string(rank_file).map_err(|e| {
+        AgonError::InvalidFormat(format!(
+            "Cannot read rank file {}: {}",
+            rank_file.display(),
+            e
+        ))
+    })?;
+    let mut encoder = HashMap::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (token_b64, rank_str) = line.split_once(char::is_whitespace).ok_or_else(|| {
+            AgonError::InvalidFormat(format!("Malformed rank file line: {}", line))
+        })?;
+        let token = BASE64
+            .decode(token_b64)
+            .map_err(|e| AgonError::InvalidFormat(format!("Bad base64 in rank file: {}", e)))?;
+        let rank: usize = rank_str
+            .trim()
+            .parse()
+            .map_err(|e| AgonError::InvalidFormat(format!("Bad rank in rank file: {}", e)))?;
+        encoder.insert(token, rank);
+    }
+    Ok(encoder)
+}
+
+/// Register a custom token encoding built from a `.tiktoken`-style rank
+/// file (e.g. `arcade100k.tiktoken` plus its special tokens), so models that
+/// ship their own BPE vocab beyond the six built-in encodings can still be
+/// counted. `pattern` is the regex used to pre-split text before BPE
+/// merging -- the same role each built-in encoding hard-codes its own
+/// pattern for. Once registered, `count_tokens`/`get_tokenizer` find `name`
+/// transparently, the same as any built-in encoding.
+pub fn register_encoding(
+    name: &str,
+    rank_file: &Path,
+    special_tokens: HashMap<String, usize>,
+    pattern: &str,
+) -> Result<()> {
+    let encoder = parse_rank_file(rank_file)?;
+    let bpe = CoreBPE::new(encoder, special_tokens, pattern)
+        .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+    let mut cache = TOKENIZERS.write().unwrap();
+    cache.insert(name.to_string(), TokenizerBackend::Tiktoken(bpe));
+    Ok(())
+}
+
+/// Tokenizers loaded by [`count_tokens_hf`], cached separately from
+/// [`TOKENIZERS`] since they're keyed by filesystem path rather than an
+/// encoding/model name and don't share tiktoken's `TokenizerBackend` shape.
+#[cfg(feature = "hf-tokenizer")]
+static HF_TOKENIZERS: LazyLock<RwLock<HashMap<String, Tokenizer>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Count tokens the way a HuggingFace `tokenizer.json`-configured model
+/// would (BERT/Llama/Qwen WordPiece or SentencePiece, rather than a tiktoken
+/// BPE encoding). When `add_special_tokens` is true, the count includes the
+/// framing tokens that config adds (`[CLS]`/`[SEP]`, `<|endoftext|>`, ...),
+/// matching what the model server actually sees; when false it counts only
+/// the text's own tokens. Loaded tokenizers are cached by `tokenizer_path`.
+///
+/// Gated behind the `hf-tokenizer` Cargo feature, since it pulls in the full
+/// `tokenizers` WordPiece/SentencePiece machinery that tiktoken-only callers
+/// don't need.
+#[cfg(feature = "hf-tokenizer")]
+pub fn count_tokens_hf(text: &str, tokenizer_path: &Path, add_special_tokens: bool) -> Result<usize> {
+    let path_key = tokenizer_path.to_string_lossy().to_string();
+
+    {
+        let cache = HF_TOKENIZERS.read().unwrap();
+        if let Some(tokenizer) = cache.get(&path_key) {
+            return tokenizer
+                .encode(text, add_special_tokens)
+                .map(|encoding| encoding.len())
+                .map_err(|e| AgonError::EncodingError(e.to_string()));
+        }
+    }
+
+    let tokenizer = Tokenizer::from_file(tokenizer_path)
+        .map_err(|e| AgonError::EncodingError(format!("Cannot load tokenizer: {}", e)))?;
+    let count = tokenizer
+        .encode(text, add_special_tokens)
+        .map(|encoding| encoding.len())
+        .map_err(|e| AgonError::EncodingError(e.to_string()))?;
+
+    let mut cache = HF_TOKENIZERS.write().unwrap();
+    cache.insert(path_key, tokenizer);
+
+    Ok(count)
+}
+
+/// Count tokens using the specified encoding. `encoding` is either the name
+/// of a built-in tiktoken encoding (`"o200k_base"`, `"cl100k_base"`, ...) or
+/// a path to a HuggingFace `tokenizers` JSON file.
 /// Note: This is expensive (~1ms per 10KB). Use only when exact count is needed.
 pub fn count_tokens(text: &str, encoding: &str) -> Result<usize> {
     let tokenizer = get_tokenizer(encoding)?;
-    Ok(tokenizer.encode_ordinary(text).len())
+    tokenizer.count(text)
+}
+
+/// Count tokens for `text` the way `model` would see it, resolving `model`
+/// to its tiktoken encoding via [`encoding_for_model`] first.
+pub fn count_tokens_for_model(text: &str, model: &str) -> Result<usize> {
+    count_tokens(text, encoding_for_model(model)?)
+}
+
+/// A pluggable token counter. [`count_tokens`]'s `encoding: &str` parameter
+/// stays the primary way callers across the PyO3/Python boundary pick a
+/// tokenizer -- a string is what can actually cross that boundary -- but a
+/// Rust embedder of this crate may already hold a tokenizer instance (a test
+/// double, or a vocabulary `get_tokenizer` has no built-in name or file path
+/// for) and want to pass it directly instead of registering it under a name
+/// first. [`crate::formats::encode_all_parallel_with_tokenizer`] and
+/// [`crate::formats::encode_auto_parallel_with_tokenizer`] take one of these.
+///
+/// (Named `TokenCounter` rather than `Tokenizer` to avoid colliding with
+/// [`tokenizers::Tokenizer`], already imported into this module.)
+pub trait TokenCounter: Send + Sync {
+    fn count(&self, text: &str) -> usize;
+}
+
+/// [`TokenCounter`] backed by one of [`count_tokens`]'s named encodings --
+/// a built-in tiktoken vocabulary, a custom one registered via
+/// [`register_encoding`], or a HuggingFace tokenizer file path. Resolves and
+/// caches the underlying [`TokenizerBackend`] once at construction (the same
+/// lookup [`get_tokenizer`] does), so [`TokenCounter::count`] itself can stay
+/// infallible afterward.
+pub struct NamedTokenizer {
+    backend: TokenizerBackend,
+}
+
+impl NamedTokenizer {
+    /// Built-in `o200k_base` (GPT-4o/o1/o3 family) -- see [`DEFAULT_ENCODING`](crate::formats::DEFAULT_ENCODING).
+    pub fn o200k_base() -> Self {
+        Self::new("o200k_base").expect("o200k_base is a built-in tiktoken encoding")
+    }
+
+    /// Built-in `cl100k_base` (GPT-4/GPT-3.5-turbo family).
+    pub fn cl100k_base() -> Self {
+        Self::new("cl100k_base").expect("cl100k_base is a built-in tiktoken encoding")
+    }
+
+    /// Resolve `encoding` (a built-in tiktoken name, a name registered via
+    /// [`register_encoding`], or a HuggingFace tokenizer file path) the same
+    /// way [`count_tokens`] does.
+    pub fn new(encoding: &str) -> Result<Self> {
+        Ok(NamedTokenizer {
+            backend: get_tokenizer(encoding)?,
+        })
+    }
+}
+
+impl TokenCounter for NamedTokenizer {
+    fn count(&self, text: &str) -> usize {
+        // The backend was already resolved successfully in `new`; a
+        // HuggingFace encode call can still fail on malformed input, in
+        // which case this trait's infallible signature has no error to
+        // report up -- treat it the same as "nothing to count".
+        self.backend.count(text).unwrap_or(0)
+    }
+}
+
+/// [`TokenCounter`] that approximates token count from character count
+/// alone (`text.chars().count() / 4`, rounded up -- the rule of thumb that
+/// one token is roughly four characters of English text), without loading
+/// any vocabulary at all. Useful where an exact count isn't worth the BPE
+/// cost, or as a placeholder before a real [`NamedTokenizer`] is wired up.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicTokenCounter;
+
+impl TokenCounter for HeuristicTokenCounter {
+    fn count(&self, text: &str) -> usize {
+        text.chars().count().div_ceil(4)
+    }
+}
+
+/// One message in a chat conversation, as sent to an OpenAI-style chat
+/// completions API.
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+    pub name: Option<String>,
+}
+
+/// Per-message token overhead OpenAI's chat models add on top of the
+/// role/content text itself, and the priming overhead for the assistant's
+/// reply. Reproduces the formula from OpenAI's own token-counting cookbook
+/// for the cl100k_base/o200k_base chat model families.
+const CHAT_TOKENS_PER_MESSAGE: usize = 3;
+const CHAT_TOKENS_PER_NAME: usize = 1;
+const CHAT_TOKENS_PRIMING: usize = 3;
+
+/// Count the tokens a full chat conversation will cost against `model`'s
+/// context window: each message's role + content, plus the fixed
+/// per-message framing overhead (and an extra token when it carries a
+/// `name`), plus the fixed priming overhead for the assistant's reply.
+/// Counting only the flat message text (as a bare [`count_tokens`] call
+/// would) underestimates the real request by this framing.
+pub fn count_chat_tokens(messages: &[ChatMessage], model: &str) -> Result<usize> {
+    let encoding = encoding_for_model(model)?;
+    let mut total = CHAT_TOKENS_PRIMING;
+    for message in messages {
+        total += CHAT_TOKENS_PER_MESSAGE;
+        total += count_tokens(&message.role, encoding)?;
+        total += count_tokens(&message.content, encoding)?;
+        if let Some(name) = &message.name {
+            total += CHAT_TOKENS_PER_NAME;
+            total += count_tokens(name, encoding)?;
+        }
+    }
+    Ok(total)
+}
+
+/// Truncate `text` to at most `max_tokens` tokens under `encoding`, reusing
+/// the same cached tokenizer `count_tokens` would use. Encodes `text` once;
+/// if it already fits, it's returned unchanged, otherwise only the first
+/// `max_tokens` token ids are decoded back to a string. Lets callers enforce
+/// a hard context-window limit without writing their own encode/slice/decode
+/// dance.
+pub fn truncate_to_tokens(text: &str, encoding: &str, max_tokens: usize) -> Result<String> {
+    let tokenizer = get_tokenizer(encoding)?;
+    tokenizer.truncate(text, max_tokens)
+}
+
+/// Whether `text` encodes to at most `max_tokens` tokens under `encoding`.
+pub fn fits_in_budget(text: &str, encoding: &str, max_tokens: usize) -> Result<bool> {
+    Ok(count_tokens(text, encoding)? <= max_tokens)
+}
+
+/// Approximate `text`'s token count without running any BPE encoding, for
+/// budgeting/UI paths where `count_tokens`'s ~1ms-per-10KB cost isn't worth
+/// paying. Walks `text` by `char` and accumulates a weighted length: ASCII
+/// characters (letters, digits, punctuation, whitespace) contribute ~0.25
+/// tokens each (~4 chars per token, the common rule of thumb for GPT-family
+/// tokenizers), while non-ASCII scalar values (CJK, emoji, ...) contribute a
+/// full token each, since they usually don't share a byte-pair with their
+/// neighbors. The running weight is rounded up to the nearest whole token.
+///
+/// This is O(n) with no allocation and no tokenizer lock contention, at the
+/// cost of accuracy: callers needing an exact count still call
+/// [`count_tokens`].
+pub fn estimate_token_length(text: &str) -> usize {
+    let mut weight = 0.0_f64;
+    for c in text.chars() {
+        if c.is_ascii() {
+            weight += 0.25;
+        } else {
+            weight += 1.0;
+        }
+    }
+    weight.ceil() as usize
 }
 
 #[cfg(test)]
@@ -71,4 +430,311 @@ mod tests {
     fn test_count_tokens_invalid_encoding() {
         assert!(count_tokens("hello", "invalid_encoding").is_err());
     }
+
+    #[test]
+    fn test_is_builtin_tiktoken_encoding() {
+        assert!(is_builtin_tiktoken_encoding("o200k_base"));
+        assert!(is_builtin_tiktoken_encoding("cl100k_base"));
+        assert!(!is_builtin_tiktoken_encoding("/path/to/tokenizer.json"));
+    }
+
+    #[test]
+    fn test_estimate_token_length_empty() {
+        assert_eq!(estimate_token_length(""), 0);
+    }
+
+    #[test]
+    fn test_estimate_token_length_ascii_matches_four_chars_per_token() {
+        // 16 ASCII chars * 0.25 = 4.0, no rounding needed.
+        assert_eq!(estimate_token_length("0123456789abcdef"), 4);
+    }
+
+    #[test]
+    fn test_estimate_token_length_non_ascii_counts_one_token_each() {
+        assert_eq!(estimate_token_length("你好"), 2);
+    }
+
+    /// Within a tolerance band of the real `o200k_base` count, for a mostly
+    /// English sample.
+    #[test]
+    fn test_estimate_token_length_within_tolerance_for_english() {
+        let text = "The quick brown fox jumps over the lazy dog, again and again.";
+        let estimate = estimate_token_length(text);
+        let actual = count_tokens(text, "o200k_base").unwrap();
+        let diff = estimate.abs_diff(actual);
+        assert!(
+            diff as f64 <= actual as f64 * 0.5,
+            "estimate {} too far from actual {}",
+            estimate,
+            actual
+        );
+    }
+
+    /// Within a tolerance band of the real `o200k_base` count, for a mixed
+    /// English/CJK sample.
+    #[test]
+    fn test_estimate_token_length_within_tolerance_for_mixed_cjk() {
+        let text = "Hello world, 你好世界, this mixes English and 中文 together.";
+        let estimate = estimate_token_length(text);
+        let actual = count_tokens(text, "o200k_base").unwrap();
+        let diff = estimate.abs_diff(actual);
+        assert!(
+            diff as f64 <= actual as f64 * 0.5,
+            "estimate {} too far from actual {}",
+            estimate,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_truncate_to_tokens_returns_unchanged_when_within_budget() {
+        let text = "short text";
+        let count = count_tokens(text, "o200k_base").unwrap();
+        let truncated = truncate_to_tokens(text, "o200k_base", count).unwrap();
+        assert_eq!(truncated, text);
+    }
+
+    #[test]
+    fn test_truncate_to_tokens_exact_boundary_is_unchanged() {
+        let text = "The quick brown fox jumps over the lazy dog, again and again.";
+        let count = count_tokens(text, "o200k_base").unwrap();
+        // Exactly at the boundary: neither truncated nor re-grown.
+        let truncated = truncate_to_tokens(text, "o200k_base", count).unwrap();
+        assert_eq!(count_tokens(&truncated, "o200k_base").unwrap(), count);
+    }
+
+    #[test]
+    fn test_truncate_to_tokens_shrinks_when_over_budget() {
+        let text = "The quick brown fox jumps over the lazy dog, again and again.";
+        let count = count_tokens(text, "o200k_base").unwrap();
+        assert!(count > 1);
+        let truncated = truncate_to_tokens(text, "o200k_base", 1).unwrap();
+        assert!(count_tokens(&truncated, "o200k_base").unwrap() <= 1);
+        assert!(truncated.len() < text.len());
+    }
+
+    #[test]
+    fn test_truncate_to_tokens_multi_byte_chars_does_not_panic() {
+        // CJK and emoji: a token boundary can land mid-grapheme, decode must
+        // still produce a valid String rather than panicking.
+        let text = "你好世界🎉🎊こんにちは世界안녕하세요세계";
+        for budget in 0..=8 {
+            let truncated = truncate_to_tokens(text, "o200k_base", budget).unwrap();
+            assert!(count_tokens(&truncated, "o200k_base").unwrap() <= budget);
+        }
+    }
+
+    #[test]
+    fn test_fits_in_budget_true_and_false() {
+        let text = "hello world";
+        let count = count_tokens(text, "o200k_base").unwrap();
+        assert!(fits_in_budget(text, "o200k_base", count).unwrap());
+        assert!(!fits_in_budget(text, "o200k_base", count - 1).unwrap());
+    }
+
+    #[test]
+    fn test_encoding_for_model_exact_names() {
+        assert_eq!(encoding_for_model("gpt-4o").unwrap(), "o200k_base");
+        assert_eq!(encoding_for_model("gpt-3.5-turbo").unwrap(), "cl100k_base");
+        assert_eq!(encoding_for_model("gpt2").unwrap(), "r50k_base");
+        assert_eq!(
+            encoding_for_model("text-embedding-3-small").unwrap(),
+            "cl100k_base"
+        );
+    }
+
+    #[test]
+    fn test_encoding_for_model_dated_suffix_resolves_by_longest_prefix() {
+        assert_eq!(
+            encoding_for_model("gpt-4o-2024-08-06").unwrap(),
+            "o200k_base"
+        );
+        assert_eq!(encoding_for_model("o1-preview").unwrap(), "o200k_base");
+        assert_eq!(
+            encoding_for_model("gpt-4-0125-preview").unwrap(),
+            "cl100k_base"
+        );
+        // "gpt-4o" is a longer, more specific prefix than "gpt-4", so a
+        // gpt-4o-family model must not fall through to cl100k_base.
+        assert_eq!(
+            encoding_for_model("gpt-4o-mini-2024-07-18").unwrap(),
+            "o200k_base"
+        );
+    }
+
+    #[test]
+    fn test_encoding_for_model_unknown_model_errors() {
+        let err = encoding_for_model("not-a-real-model").unwrap_err();
+        assert!(matches!(err, AgonError::InvalidFormat(_)));
+    }
+
+    #[test]
+    fn test_count_tokens_for_model() {
+        let text = "hello world";
+        assert_eq!(
+            count_tokens_for_model(text, "gpt-4o-2024-08-06").unwrap(),
+            count_tokens(text, "o200k_base").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_count_tokens_for_model_unknown_model_errors() {
+        assert!(count_tokens_for_model("hello", "not-a-real-model").is_err());
+    }
+
+    #[test]
+    fn test_count_chat_tokens_empty_conversation_is_priming_only() {
+        assert_eq!(count_chat_tokens(&[], "gpt-4o").unwrap(), CHAT_TOKENS_PRIMING);
+    }
+
+    #[test]
+    fn test_count_chat_tokens_matches_documented_formula() {
+        let messages = vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: "You are a helpful assistant.".to_string(),
+                name: None,
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: "Hello!".to_string(),
+                name: None,
+            },
+        ];
+        let encoding = encoding_for_model("gpt-4o").unwrap();
+        let expected = CHAT_TOKENS_PRIMING
+            + messages
+                .iter()
+                .map(|m| {
+                    CHAT_TOKENS_PER_MESSAGE
+                        + count_tokens(&m.role, encoding).unwrap()
+                        + count_tokens(&m.content, encoding).unwrap()
+                })
+                .sum::<usize>();
+        assert_eq!(count_chat_tokens(&messages, "gpt-4o").unwrap(), expected);
+    }
+
+    #[test]
+    fn test_count_chat_tokens_name_field_adds_overhead() {
+        let encoding = encoding_for_model("gpt-4o").unwrap();
+        let without_name = vec![ChatMessage {
+            role: "user".to_string(),
+            content: "hi".to_string(),
+            name: None,
+        }];
+        let with_name = vec![ChatMessage {
+            role: "user".to_string(),
+            content: "hi".to_string(),
+            name: Some("alice".to_string()),
+        }];
+        let diff = count_chat_tokens(&with_name, "gpt-4o").unwrap()
+            - count_chat_tokens(&without_name, "gpt-4o").unwrap();
+        assert_eq!(
+            diff,
+            CHAT_TOKENS_PER_NAME + count_tokens("alice", encoding).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_register_encoding_counts_tokens_against_synthetic_rank_file() {
+        // Three single-byte "tokens": 'a', 'b', 'c', ranked 0, 1, 2.
+        let mut rank_file = std::env::temp_dir();
+        rank_file.push("agon_test_synthetic.tiktoken");
+        let contents = format!(
+            "{} 0\n{} 1\n{} 2\n",
+            BASE64.encode(b"a"),
+            BASE64.encode(b"b"),
+            BASE64.encode(b"c"),
+        );
+        std::fs::write(&rank_file, contents).unwrap();
+
+        register_encoding(
+            "agon-test-synthetic",
+            &rank_file,
+            HashMap::new(),
+            r"[abc]|.",
+        )
+        .unwrap();
+
+        assert_eq!(count_tokens("abc", "agon-test-synthetic").unwrap(), 3);
+
+        std::fs::remove_file(&rank_file).ok();
+    }
+
+    #[test]
+    fn test_register_encoding_bad_rank_file_errors() {
+        let err = register_encoding(
+            "agon-test-missing",
+            Path::new("/nonexistent/path/to.tiktoken"),
+            HashMap::new(),
+            r".",
+        )
+        .unwrap_err();
+        assert!(matches!(err, AgonError::InvalidFormat(_)));
+    }
+
+    #[cfg(feature = "hf-tokenizer")]
+    #[test]
+    fn test_count_tokens_hf_counts_against_bundled_config() {
+        let mut config_path = std::env::temp_dir();
+        config_path.push("agon_test_wordlevel_tokenizer.json");
+        let config = r#"{
+            "version": "1.0",
+            "truncation": null,
+            "padding": null,
+            "added_tokens": [
+                {"id": 0, "content": "[UNK]", "single_word": false, "lstrip": false, "rstrip": false, "normalized": false, "special": true},
+                {"id": 1, "content": "[CLS]", "single_word": false, "lstrip": false, "rstrip": false, "normalized": false, "special": true},
+                {"id": 2, "content": "[SEP]", "single_word": false, "lstrip": false, "rstrip": false, "normalized": false, "special": true}
+            ],
+            "normalizer": null,
+            "pre_tokenizer": {"type": "Whitespace"},
+            "post_processor": {
+                "type": "TemplateProcessing",
+                "single": [
+                    {"SpecialToken": {"id": "[CLS]", "type_id": 0}},
+                    {"Sequence": {"id": "A", "type_id": 0}},
+                    {"SpecialToken": {"id": "[SEP]", "type_id": 0}}
+                ],
+                "pair": [],
+                "special_tokens": {
+                    "[CLS]": {"id": "[CLS]", "ids": [1], "tokens": ["[CLS]"]},
+                    "[SEP]": {"id": "[SEP]", "ids": [2], "tokens": ["[SEP]"]}
+                }
+            },
+            "decoder": null,
+            "model": {
+                "type": "WordLevel",
+                "vocab": {"[UNK]": 0, "[CLS]": 1, "[SEP]": 2, "hello": 3, "world": 4},
+                "unk_token": "[UNK]"
+            }
+        }"#;
+        std::fs::write(&config_path, config).unwrap();
+
+        let without_special = count_tokens_hf("hello world", &config_path, false).unwrap();
+        assert_eq!(without_special, 2);
+
+        let with_special = count_tokens_hf("hello world", &config_path, true).unwrap();
+        assert_eq!(with_special, 4); // [CLS] hello world [SEP]
+
+        std::fs::remove_file(&config_path).ok();
+    }
+
+    #[cfg(feature = "hf-tokenizer")]
+    #[test]
+    fn test_count_tokens_hf_missing_file_errors() {
+        let err = count_tokens_hf("hello", Path::new("/nonexistent/tokenizer.json"), false)
+            .unwrap_err();
+        assert!(matches!(err, AgonError::EncodingError(_)));
+    }
+
+    #[test]
+    fn test_count_chat_tokens_unknown_model_errors() {
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: "hi".to_string(),
+            name: None,
+        }];
+        assert!(count_chat_tokens(&messages, "not-a-real-model").is_err());
+    }
 }